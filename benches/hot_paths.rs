@@ -0,0 +1,164 @@
+#![allow(clippy::needless_return)]
+// The hot paths, to see what changes to the locking cost: cargo bench
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::rt::System;
+use actix_web::test::{self, TestRequest};
+use actix_web::{web, HttpServer};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures_util::future::join_all;
+use serde_json::json;
+use std::sync::mpsc;
+
+use rest::{app, etag, Config, State, Task};
+
+// tasks the listings page through
+const TASKS: usize = 1000;
+// requests in flight at once in the mixed benchmark, every fourth a write
+const IN_FLIGHT: usize = 32;
+
+async fn token<S, B>(app: &S) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, TestRequest::post().uri("/v1/tokens").to_request()).await;
+    let body = test::read_body(response).await;
+    return String::from_utf8(body.to_vec()).unwrap();
+}
+
+// creates a task with a fresh token and deletes it again, so the
+// collection stays the size it was
+async fn create_and_delete<S, B>(app: &S)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(app).await))
+        .set_json(json!({ "text": "Water the plants" }))
+        .to_request();
+    let response = test::call_service(app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = String::from(response.headers().get("Location").unwrap().to_str().unwrap());
+    test::call_service(app, TestRequest::delete().uri(&location).to_request()).await;
+}
+
+fn listings(c: &mut Criterion) {
+    let system = System::new();
+    let app = system.block_on(async {
+        let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::default())))).await;
+        for n in 0..TASKS {
+            let request = TestRequest::post().uri("/v1/tasks")
+                .insert_header(("Post-Token", token(&app).await))
+                .set_json(json!({ "text": format!("Task {}", n), "tags": ["bench"] }))
+                .to_request();
+            test::call_service(&app, request).await;
+        }
+        return app;
+    });
+
+    let mut group = c.benchmark_group("list");
+    for (page, per_page) in [(1, 20), (TASKS / 20, 20), (1, 100)] {
+        let uri = format!("/v1/tasks?page={}&per_page={}", page, per_page);
+        group.throughput(Throughput::Elements(per_page as u64));
+        group.bench_with_input(BenchmarkId::new(format!("page_{}", page), per_page), &uri, |b, uri| {
+            b.iter(|| system.block_on(async {
+                let response = test::call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+                assert_eq!(response.status(), StatusCode::OK);
+                return test::read_body(response).await;
+            }));
+        });
+    }
+    group.finish();
+}
+
+fn etags(c: &mut Criterion) {
+    let mut group = c.benchmark_group("etag");
+    for words in [10, 1000] {
+        let text = vec!["lorem"; words].join(" ");
+        let mut task: Task = serde_json::from_value(json!({ "text": text, "tags": ["bench"] })).unwrap();
+        group.bench_function(BenchmarkId::new("refresh", words), |b| {
+            b.iter(|| etag::refresh(&mut task).unwrap());
+        });
+    }
+    group.finish();
+}
+
+fn tokens(c: &mut Criterion) {
+    let system = System::new();
+    let app = system.block_on(async {
+        return test::init_service(app(web::Data::new(State::with_sample_data(Config::default())))).await;
+    });
+
+    let mut group = c.benchmark_group("token");
+    // issued tokens pile up until the sweep, as they do when clients drop them
+    group.bench_function("issue", |b| {
+        b.iter(|| system.block_on(token(&app)));
+    });
+    group.bench_function("issue_and_spend", |b| {
+        b.iter(|| system.block_on(create_and_delete(&app)));
+    });
+    group.finish();
+}
+
+// a server with four workers on its own thread, for requests that really
+// run at once
+fn serve() -> String {
+    let (sender, address) = mpsc::channel();
+    std::thread::spawn(move || {
+        System::new().block_on(async move {
+            let state = web::Data::new(State::with_sample_data(Config::default()));
+            let server = HttpServer::new(move || app(state.clone()))
+                .workers(4)
+                .bind(("127.0.0.1", 0))
+                .unwrap();
+            sender.send(server.addrs()[0]).unwrap();
+            server.run().await.unwrap();
+        });
+    });
+    return format!("http://{}", address.recv().unwrap());
+}
+
+async fn write(client: &reqwest::Client, url: &str) {
+    let token = client.post(format!("{}/v1/tokens", url)).send().await.unwrap().text().await.unwrap();
+    let response = client.post(format!("{}/v1/tasks", url))
+        .header("Post-Token", token)
+        .json(&json!({ "text": "Water the plants" }))
+        .send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let location = response.headers()["Location"].to_str().unwrap().to_owned();
+    client.delete(format!("{}{}", url, location)).send().await.unwrap();
+}
+
+async fn read(client: &reqwest::Client, url: &str, id: usize) {
+    let response = client.get(format!("{}/v1/tasks/{}", url, id)).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    response.bytes().await.unwrap();
+}
+
+fn mixed(c: &mut Criterion) {
+    let url = serve();
+    let system = System::new();
+    let client = reqwest::Client::new();
+
+    let mut group = c.benchmark_group("mixed");
+    group.throughput(Throughput::Elements(IN_FLIGHT as u64));
+    group.bench_function("read_write", |b| {
+        b.iter(|| system.block_on(join_all((0..IN_FLIGHT).map(|n| {
+            let (client, url) = (&client, url.as_str());
+            async move {
+                match n % 4 {
+                    0   => write(client, url).await,
+                    _   => read(client, url, n % 9 + 1).await,
+                }
+            }
+        }))));
+    });
+    group.finish();
+}
+
+criterion_group!(benches, listings, etags, tokens, mixed);
+criterion_main!(benches);
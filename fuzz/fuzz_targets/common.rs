@@ -0,0 +1,24 @@
+// Sends one request to a fresh server over the sample data, so every input
+// meets the same state. A panicking handler is answered with a 500 by the
+// server, which is turned back into a panic for the fuzzer to report.
+use actix_web::rt::System;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+
+use rest::{app, Config, State};
+
+thread_local! {
+    static SYSTEM: actix_web::rt::SystemRunner = System::new();
+}
+
+pub fn send(request: TestRequest) {
+    SYSTEM.with(|system| system.block_on(async {
+        let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::default())))).await;
+        let token = test::call_service(&app, TestRequest::post().uri("/v1/tokens").to_request()).await;
+        let token = test::read_body(token).await;
+        let request = request.insert_header(("Post-Token", token.to_vec())).to_request();
+        let uri = request.uri().clone();
+        let response = test::call_service(&app, request).await;
+        assert!(!response.status().is_server_error(), "{} answered {}", uri, response.status());
+    }));
+}
@@ -0,0 +1,25 @@
+#![no_main]
+// the importers with any body: iCalendar, Todoist CSV and JSON, Markdown
+// checklists, zips of Markdown journals and takeouts
+use actix_web::test::TestRequest;
+use libfuzzer_sys::fuzz_target;
+
+mod common;
+
+const ROUTES: [(&str, &str); 5] = [
+    ("/v1/import/ics",      "text/calendar"),
+    ("/v1/import/todoist",  "text/csv"),
+    ("/v1/import/markdown", "text/markdown"),
+    ("/v1/import/journals", "application/zip"),
+    ("/v1/import",          "application/json"),
+];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((choice, body)) = data.split_first() else {
+        return;
+    };
+    let (uri, content_type) = ROUTES[*choice as usize % ROUTES.len()];
+    common::send(TestRequest::post().uri(uri)
+        .insert_header(("Content-Type", content_type))
+        .set_payload(body.to_vec()));
+});
@@ -0,0 +1,17 @@
+#![no_main]
+// POST /task_merger and /journal_merger with any body
+use actix_web::test::TestRequest;
+use libfuzzer_sys::fuzz_target;
+
+mod common;
+
+const ROUTES: [&str; 2] = ["/v1/task_merger", "/v1/journal_merger"];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((choice, body)) = data.split_first() else {
+        return;
+    };
+    common::send(TestRequest::post().uri(ROUTES[*choice as usize % ROUTES.len()])
+        .insert_header(("Content-Type", "application/json"))
+        .set_payload(body.to_vec()));
+});
@@ -0,0 +1,19 @@
+#![no_main]
+// PATCH /tasks/{id} with any body, in any of the encodings it is decoded from
+use actix_web::test::TestRequest;
+use libfuzzer_sys::fuzz_target;
+
+mod common;
+
+const ENCODINGS: [&str; 3] = ["application/json", "application/msgpack", "application/cbor"];
+
+fuzz_target!(|data: &[u8]| {
+    let Some((choice, body)) = data.split_first() else {
+        return;
+    };
+    // the sample tasks are at version 1
+    common::send(TestRequest::patch().uri("/v1/tasks/1")
+        .insert_header(("Content-Type", ENCODINGS[*choice as usize % ENCODINGS.len()]))
+        .insert_header(("If-Match", "1"))
+        .set_payload(body.to_vec()));
+});
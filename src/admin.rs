@@ -0,0 +1,115 @@
+// Operator endpoints under /admin, for requests carrying
+// "Authorization: Bearer <JOURNAL_ADMIN_TOKEN>"
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::{analytics, audit, flags, maintenance, reload, replica, scheduler, seed};
+use crate::state::State;
+
+pub(crate) fn require_admin(state: &State, request: &HttpRequest) -> Result<(), HttpResponse> {
+    let admin_token = match &state.config.admin_token {
+        Some(admin_token)   => admin_token,
+        None                => return Err(HttpResponse::Forbidden().body("The admin API is disabled")),
+    };
+    let credential = request.headers().get("Authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if credential != Some(admin_token.as_str()) {
+        return Err(HttpResponse::Unauthorized()
+            .append_header(("WWW-Authenticate", "Bearer"))
+            .body("Bad admin credential"));
+    }
+    return Ok(());
+}
+
+#[derive(Serialize)]
+struct TokenView {
+    token:          String,
+    created_at:     DateTime<Utc>,
+    age_seconds:    u64,
+}
+
+// the write tokens that are still usable, oldest first
+async fn list_tokens(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    let now = Utc::now();
+    let tokens: Vec<TokenView> = state.active_tokens().into_iter()
+        .map(|(token, age)| TokenView {
+            token,
+            created_at:     now - age,
+            age_seconds:    age.as_secs(),
+        })
+        .collect();
+    return HttpResponse::Ok().json(tokens);
+}
+
+async fn revoke_token(path: web::Path<String>, state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    if !state.revoke_token(&path.into_inner()) {
+        return HttpResponse::NotFound().body("No such token");
+    }
+    return HttpResponse::Ok().body("Revoked");
+}
+
+#[derive(Serialize)]
+struct Revoked {
+    revoked: usize,
+}
+
+async fn revoke_tokens(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    return HttpResponse::Ok().json(Revoked { revoked: state.revoke_tokens() });
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+            web::resource("/admin/tokens")
+            .route(web::get().to(list_tokens))
+            .route(web::delete().to(revoke_tokens))
+        )
+        .service(
+            web::resource("/admin/tokens/{token}")
+            .route(web::delete().to(revoke_token))
+        )
+        .service(
+            web::resource("/admin/jobs")
+            .route(web::get().to(scheduler::list))
+        )
+        .service(
+            web::resource("/admin/audit")
+            .route(web::get().to(audit::show))
+        )
+        .service(
+            web::resource("/admin/usage")
+            .route(web::get().to(analytics::show))
+        )
+        .service(
+            web::resource("/admin/maintenance")
+            .route(web::get().to(maintenance::show))
+            .route(web::post().to(maintenance::toggle))
+        )
+        .service(
+            web::resource("/admin/replication")
+            .route(web::get().to(replica::show))
+        )
+        .service(
+            web::resource("/admin/seed")
+            .route(web::post().to(seed::create))
+        )
+        .service(
+            web::resource("/admin/flags")
+            .route(web::get().to(flags::show))
+            .route(web::patch().to(flags::update))
+        )
+        .service(
+            web::resource("/admin/reload")
+            .route(web::post().to(reload::update))
+        );
+}
@@ -0,0 +1,168 @@
+// Who uses the API how much: every request is counted under its client,
+// with the bytes it sent and got and whether it failed, in buckets of a
+// minute kept for a day. GET /admin/usage adds them up over the last
+// minute, hour or day, busiest client first.
+use actix_web::body::{BodySize, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::CONTENT_LENGTH;
+use actix_web::middleware::Next;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::ops::AddAssign;
+use std::sync::Mutex;
+
+use crate::admin::require_admin;
+use crate::state::State;
+
+// a day of minutes
+const KEPT: i64 = 24 * 60;
+// how much of a credential names its client, so the listing leaks none
+const PREFIX: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+struct Counts {
+    requests:   u64,
+    bytes_in:   u64,
+    bytes_out:  u64,
+    // answered with 4xx or 5xx
+    errors:     u64,
+}
+
+impl AddAssign for Counts {
+    fn add_assign(&mut self, other: Counts) {
+        self.requests += other.requests;
+        self.bytes_in += other.bytes_in;
+        self.bytes_out += other.bytes_out;
+        self.errors += other.errors;
+    }
+}
+
+// the counts of every client, per minute since the epoch, oldest first
+#[derive(Default)]
+pub(crate) struct Analytics {
+    clients: Mutex<HashMap<String, VecDeque<(i64, Counts)>>>,
+}
+
+impl Analytics {
+    fn count(&self, client: String, counts: Counts) {
+        let minute = Utc::now().timestamp() / 60;
+        let mut clients = self.clients.lock().unwrap();
+        let minutes = clients.entry(client).or_default();
+        match minutes.back_mut() {
+            Some((last, total)) if *last == minute  => *total += counts,
+            _                                       => minutes.push_back((minute, counts)),
+        }
+        while minutes.front().is_some_and(|(oldest, _)| *oldest <= minute - KEPT) {
+            minutes.pop_front();
+        }
+    }
+
+    // the clients seen in the last `minutes`, busiest first
+    fn totals(&self, minutes: i64) -> Vec<(String, Counts)> {
+        let now = Utc::now().timestamp() / 60;
+        let since = now - minutes;
+        let mut clients = self.clients.lock().unwrap();
+        // those not seen for a day have no counts left
+        clients.retain(|_, counted| counted.back().is_some_and(|(last, _)| *last > now - KEPT));
+        let mut totals: Vec<(String, Counts)> = clients.iter()
+            .filter_map(|(client, counted)| {
+                let mut total = Counts::default();
+                for (_, counts) in counted.iter().filter(|(minute, _)| *minute > since) {
+                    total += *counts;
+                }
+                return (total.requests > 0).then(|| (client.clone(), total));
+            })
+            .collect();
+        totals.sort_by(|(a, a_counts), (b, b_counts)| b_counts.requests.cmp(&a_counts.requests).then(a.cmp(b)));
+        return totals;
+    }
+}
+
+fn prefix(credential: &str) -> &str {
+    return credential.char_indices().nth(PREFIX).map_or(credential, |(end, _)| &credential[..end]);
+}
+
+// the workspace key or bearer credential the request shows, its address
+// without one; write tokens are used once, so they name nobody
+pub(crate) fn client(request: &HttpRequest) -> String {
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+    if let Some(key) = header("Workspace-Key") {
+        return format!("key:{}", prefix(key));
+    }
+    if let Some(bearer) = header("Authorization").and_then(|value| value.strip_prefix("Bearer ")) {
+        return format!("bearer:{}", prefix(bearer));
+    }
+    return format!("address:{}", request.peer_addr().map_or(String::from("unknown"), |address| address.ip().to_string()));
+}
+
+pub async fn record(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let state = match request.app_data::<web::Data<State>>().cloned() {
+        Some(state) => state,
+        None        => return next.call(request).await,
+    };
+    let client = client(request.request());
+    let bytes_in = request.headers().get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok()?.parse().ok())
+        .unwrap_or(0);
+    let response = next.call(request).await;
+    let (bytes_out, failed) = match &response {
+        Ok(response)    => {
+            let size = match response.response().body().size() {
+                BodySize::Sized(size)   => size,
+                _                       => 0,
+            };
+            (size, response.status().is_client_error() || response.status().is_server_error())
+        }
+        Err(_)          => (0, true),
+    };
+    state.analytics.count(client, Counts { requests: 1, bytes_in, bytes_out, errors: u64::from(failed) });
+    return response;
+}
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+enum Window {
+    Minute,
+    #[default]
+    Hour,
+    Day,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UsageQuery {
+    #[serde(default)]
+    window: Window,
+}
+
+#[derive(Serialize)]
+struct ClientUsage {
+    client:     String,
+    #[serde(flatten)]
+    counts:     Counts,
+    // of the requests
+    error_rate: f64,
+}
+
+pub(crate) async fn show(query: web::Query<UsageQuery>, state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    let minutes = match query.window {
+        Window::Minute  => 1,
+        Window::Hour    => 60,
+        Window::Day     => KEPT,
+    };
+    let usage: Vec<ClientUsage> = state.analytics.totals(minutes).into_iter()
+        .map(|(client, counts)| ClientUsage {
+            client,
+            counts,
+            error_rate: counts.errors as f64 / counts.requests as f64,
+        })
+        .collect();
+    return HttpResponse::Ok().json(usage);
+}
@@ -0,0 +1,694 @@
+// Files attached to journals. They are uploaded in chunks so large ones
+// survive flaky connections: an upload is started with the length of the
+// file, chunks are appended at the offset the server has reached, and once
+// complete the upload is finalized into an attachment. The content is kept
+// once per space by its SHA-256, however many attachments share it, in the
+// blob store; chunks wait in a temporary file until then, everything else
+// is kept here, and with JOURNAL_EVENT_LOG the
+// attachments of the server's own space are logged along with its entries.
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::SeekFrom;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use sha256::digest;
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+
+use crate::auth::{random_key, response_token};
+use crate::blobs::BlobStore;
+use crate::eventlog::{EventLog, Projection};
+use crate::handlers::IdPath;
+use crate::models::Journal;
+use crate::quota::{self, Usage};
+use crate::ranges;
+use crate::state::State;
+use crate::store::{Collection, Entries, Observer};
+use crate::workspace::{Level, Space};
+
+// the largest file accepted, and the largest chunk of one
+pub(crate) const MAX_LENGTH: usize = 64 * 1024 * 1024;
+pub(crate) const CHUNK_LIMIT: usize = 8 * 1024 * 1024;
+
+// uploads not continued for this long are dropped
+const UPLOAD_TIMEOUT: Duration = Duration::hours(24);
+
+// what attachments are logged as in the event log
+pub(crate) const KIND: &str = "attachment";
+
+// the blobs of the server's own space are kept under this, so they are
+// found again after a restart
+pub(crate) const SERVER_NAMESPACE: &str = "server";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct Attachment {
+    pub(crate) journal:         usize,
+    pub(crate) name:            String,
+    pub(crate) content_type:    String,
+    // of the blob, which never changes, so it also makes a stable ETag
+    pub(crate) sha256:          String,
+    // of the blob as well, so the blobs can be counted again from the
+    // attachments alone
+    pub(crate) length:          usize,
+    pub(crate) created_at:      DateTime<Utc>,
+}
+
+struct Blob {
+    length:         usize,
+    // the attachments using it, it is deleted with the last one
+    references:     usize,
+}
+
+struct Upload {
+    journal:        usize,
+    name:           String,
+    content_type:   String,
+    // of the whole file, as announced when the upload started
+    length:         usize,
+    // checked on finalizing when announced
+    sha256:         Option<String>,
+    // the bytes in the file so far
+    received:       usize,
+    file:           Arc<Spool>,
+    updated_at:     DateTime<Utc>,
+}
+
+// The temporary file the chunks of an upload are written to, removed with
+// the upload. Locked while a chunk is written, so the chunks of one upload
+// are written one after the other.
+struct Spool {
+    path:   PathBuf,
+    file:   tokio::sync::Mutex<tokio::fs::File>,
+}
+
+impl Spool {
+    async fn create(token: &str) -> std::io::Result<Spool> {
+        let dir = std::env::temp_dir().join(format!("journal-uploads-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await?;
+        let path = dir.join(token);
+        let file = tokio::fs::File::create(&path).await?;
+        return Ok(Spool { path, file: tokio::sync::Mutex::new(file) });
+    }
+
+    async fn read(&self, length: usize) -> std::io::Result<Vec<u8>> {
+        let mut data = tokio::fs::read(&self.path).await?;
+        data.truncate(length);
+        return Ok(data);
+    }
+}
+
+// writes the chunk at the offset, over whatever a failed write left there
+async fn write_chunk(file: &mut tokio::fs::File, offset: usize, chunk: &[u8]) -> std::io::Result<()> {
+    file.seek(SeekFrom::Start(offset as u64)).await?;
+    file.write_all(chunk).await?;
+    return file.flush().await;
+}
+
+impl Drop for Spool {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+struct Files {
+    next_id:        usize,
+    attachments:    HashMap<usize, Attachment>,
+    blobs:          HashMap<String, Blob>,
+    // by their unguessable token, which is all a chunk needs
+    uploads:        HashMap<String, Upload>,
+    // told about every blob that comes or goes
+    usage:          Arc<Usage>,
+    // where attachments and detachments are logged, if anywhere
+    log:            Option<Arc<EventLog>>,
+}
+
+impl Files {
+    // counts a reference to a blob that is already in the store
+    fn reference(&mut self, sha256: &str, length: usize) {
+        let usage = &self.usage;
+        self.blobs.entry(String::from(sha256))
+            .or_insert_with(|| {
+                usage.attached(length);
+                Blob { length, references: 0 }
+            })
+            .references += 1;
+    }
+
+    // stores the attachment, the blob it refers to being in the store
+    fn attach(&mut self, attachment: Attachment) -> usize {
+        self.reference(&attachment.sha256, attachment.length);
+        let id = self.next_id;
+        self.next_id += 1;
+        if let Some(log) = &self.log {
+            log.attached(id, Some(&attachment));
+        }
+        self.attachments.insert(id, attachment);
+        return id;
+    }
+
+    // the SHA-256 of the blob to delete when this was its last attachment
+    fn detach(&mut self, id: usize) -> Option<String> {
+        let attachment = self.attachments.remove(&id)?;
+        if let Some(log) = &self.log {
+            log.attached(id, None);
+        }
+        let blob = self.blobs.get_mut(&attachment.sha256)?;
+        blob.references -= 1;
+        if blob.references > 0 {
+            return None;
+        }
+        let blob_length = blob.length;
+        self.blobs.remove(&attachment.sha256);
+        self.usage.detached(blob_length);
+        return Some(attachment.sha256);
+    }
+}
+
+// the blobs of a removed workspace no longer count
+impl Drop for Files {
+    fn drop(&mut self) {
+        self.usage.detached(self.blobs.values().map(|blob| blob.length).sum());
+    }
+}
+
+// the attachments and unfinished uploads of a space, shared by every
+// request to it
+#[derive(Clone)]
+pub(crate) struct Attachments {
+    files:      Arc<Mutex<Files>>,
+    store:      Arc<dyn BlobStore>,
+    // held while blobs are put or deleted, so a blob is never deleted while
+    // another attachment of the same content is being stored
+    writes:     Arc<tokio::sync::Mutex<()>>,
+    // what the keys of the space's blobs start with, one per space so that
+    // spaces never share blobs they count separately
+    namespace:  String,
+}
+
+impl Attachments {
+    // follows the journals, so the files of removed ones go with them
+    pub(crate) fn watch(
+        journals:   &Collection<Journal>,
+        store:      Arc<dyn BlobStore>,
+        usage:      Arc<Usage>,
+        namespace:  String,
+    ) -> Attachments {
+        let files = Files {
+            next_id:        0,
+            attachments:    HashMap::new(),
+            blobs:          HashMap::new(),
+            uploads:        HashMap::new(),
+            usage,
+            log:            None,
+        };
+        let attachments = Attachments {
+            files:      Arc::new(Mutex::new(files)),
+            store,
+            writes:     Arc::default(),
+            namespace,
+        };
+        journals.observe(Arc::new(attachments.clone()));
+        return attachments;
+    }
+
+    // takes over the attachments the event log holds, and logs every
+    // attachment and detachment there from now on
+    pub(crate) fn restore(&self, logged: Projection<Attachment>, log: Arc<EventLog>) {
+        let mut files = self.files.lock().unwrap();
+        for (id, attachment) in logged.entries {
+            files.reference(&attachment.sha256, attachment.length);
+            files.attachments.insert(id, attachment);
+        }
+        files.next_id = logged.next_id;
+        files.log = Some(log);
+    }
+
+    // the attachments as the event log records them, and the next id; none
+    // is attached or detached until `then` returns
+    pub(crate) fn recorded<R>(&self, then: impl FnOnce(BTreeMap<usize, Value>, usize) -> R) -> R {
+        let files = self.files.lock().unwrap();
+        let recorded = files.attachments.iter()
+            .filter_map(|(id, attachment)| Some((*id, serde_json::to_value(attachment).ok()?)))
+            .collect();
+        return then(recorded, files.next_id);
+    }
+
+    // every attachment, ascending by id, for takeouts
+    pub(crate) fn listed(&self) -> Vec<Attachment> {
+        let files = self.files.lock().unwrap();
+        let mut ids: Vec<&usize> = files.attachments.keys().collect();
+        ids.sort();
+        return ids.into_iter().map(|id| files.attachments[id].clone()).collect();
+    }
+
+    // attaches the attachments of a takeout whose content the space has and
+    // answers with the SHA-256 of the others, which have to be uploaded
+    // again; a dry run only answers
+    pub(crate) fn import(&self, imported: Vec<Attachment>, dry_run: bool) -> Vec<String> {
+        let mut files = self.files.lock().unwrap();
+        let mut missing = Vec::new();
+        for mut attachment in imported {
+            attachment.sha256 = attachment.sha256.to_ascii_lowercase();
+            let Some(length) = files.blobs.get(&attachment.sha256).map(|blob| blob.length) else {
+                missing.push(attachment.sha256);
+                continue;
+            };
+            if !dry_run {
+                attachment.length = length;
+                files.attach(attachment);
+            }
+        }
+        return missing;
+    }
+
+    fn key(&self, sha256: &str) -> String {
+        return format!("{}/{}", self.namespace, sha256);
+    }
+
+    // deletes blobs no attachment uses anymore, unless one was attached
+    // again in the meantime
+    async fn delete(&self, freed: Vec<String>) {
+        let _writes = self.writes.lock().await;
+        for sha256 in freed {
+            if self.files.lock().unwrap().blobs.contains_key(&sha256) {
+                continue;
+            }
+            if let Err(error) = self.store.delete(&self.key(&sha256)).await {
+                println!("Blob {} was not deleted: {}", sha256, error);
+            }
+        }
+    }
+}
+
+// the writer cannot wait for the store, so freed blobs are deleted right after
+impl Observer<Journal> for Attachments {
+    fn changed(&self, entries: &Entries<Journal>, ids: &BTreeSet<usize>) {
+        let removed = |journal: &usize| ids.contains(journal) && entries.get(journal).is_none();
+        let mut files = self.files.lock().unwrap();
+        let orphans: Vec<usize> = files.attachments.iter()
+            .filter(|(_, attachment)| removed(&attachment.journal))
+            .map(|(id, _)| *id)
+            .collect();
+        let freed: Vec<String> = orphans.into_iter().filter_map(|id| files.detach(id)).collect();
+        files.uploads.retain(|_, upload| !removed(&upload.journal));
+        if !freed.is_empty() {
+            let attachments = self.clone();
+            tokio::spawn(async move { attachments.delete(freed).await });
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AttachmentPath {
+    id:         usize,
+    attachment: usize,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct UploadPath {
+    id:     usize,
+    upload: String,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NewUpload {
+    name:           String,
+    #[serde(default = "default_content_type")]
+    content_type:   String,
+    length:         usize,
+    sha256:         Option<String>,
+}
+
+// a blob the space already has, attached without uploading it again
+#[derive(Deserialize)]
+pub(crate) struct KnownBlob {
+    name:           String,
+    #[serde(default = "default_content_type")]
+    content_type:   String,
+    sha256:         String,
+}
+
+fn default_content_type() -> String {
+    return String::from("application/octet-stream");
+}
+
+#[derive(Serialize)]
+struct UploadView<'a> {
+    upload:         &'a str,
+    name:           &'a str,
+    content_type:   &'a str,
+    length:         usize,
+    // where the next chunk goes, the bytes received so far
+    offset:         usize,
+}
+
+#[derive(Serialize)]
+struct AttachmentView<'a> {
+    id:             usize,
+    name:           &'a str,
+    content_type:   &'a str,
+    length:         usize,
+    sha256:         &'a str,
+    created_at:     DateTime<Utc>,
+}
+
+fn attachment_view(id: usize, attachment: &Attachment) -> AttachmentView<'_> {
+    return AttachmentView {
+        id,
+        name:           &attachment.name,
+        content_type:   &attachment.content_type,
+        length:         attachment.length,
+        sha256:         &attachment.sha256,
+        created_at:     attachment.created_at,
+    };
+}
+
+fn upload_view<'a>(token: &'a str, upload: &'a Upload) -> UploadView<'a> {
+    return UploadView {
+        upload:         token,
+        name:           &upload.name,
+        content_type:   &upload.content_type,
+        length:         upload.length,
+        offset:         upload.received,
+    };
+}
+
+pub(crate) async fn start_upload(
+    path: web::Path<IdPath>,
+    json: web::Json<NewUpload>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    let id = path.id;
+    if let Err(rejection) = space.allow::<Journal>(Some(id), Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    if space.journals.get(&id).is_none() {
+        return HttpResponse::NotFound().body("Not found");
+    }
+    let info = json.into_inner();
+    if info.name.trim().is_empty() {
+        return HttpResponse::BadRequest().body("Attachments need a name");
+    }
+    if info.length > MAX_LENGTH {
+        return HttpResponse::PayloadTooLarge().body(format!("Attachments are at most {} bytes", MAX_LENGTH));
+    }
+    if let Err(rejection) = quota::admit_attachment(&state, info.length) {
+        return rejection.into();
+    }
+    let token = random_key();
+    let file = match Spool::create(&token).await {
+        Ok(file)    => Arc::new(file),
+        Err(error)  => {
+            println!("Upload {} was not started: {}", token, error);
+            return HttpResponse::InternalServerError().body("Error starting the upload");
+        }
+    };
+    let now = Utc::now();
+    let mut files = space.attachments.files.lock().unwrap();
+    files.uploads.retain(|_, upload| now - upload.updated_at < UPLOAD_TIMEOUT);
+    let upload = Upload {
+        journal:        id,
+        name:           info.name,
+        content_type:   info.content_type,
+        length:         info.length,
+        sha256:         info.sha256.map(|sha256| sha256.to_ascii_lowercase()),
+        received:       0,
+        file,
+        updated_at:     now,
+    };
+    let response = HttpResponse::Created()
+        .append_header(("Location", format!("{}/journals/{}/uploads/{}", space.root(&request), id, token)))
+        .append_header(("Upload-Offset", "0"))
+        .json(upload_view(&token, &upload));
+    files.uploads.insert(token, upload);
+    return response;
+}
+
+// how far an upload got, to resume it after a broken connection
+pub(crate) async fn show_upload(path: web::Path<UploadPath>, space: Space) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(Some(path.id), Level::Write) {
+        return rejection.into();
+    }
+    let files = space.attachments.files.lock().unwrap();
+    match files.uploads.get(&path.upload).filter(|upload| upload.journal == path.id) {
+        Some(upload)    => HttpResponse::Ok()
+            .append_header(("Upload-Offset", upload.received.to_string()))
+            .json(upload_view(&path.upload, upload)),
+        None            => HttpResponse::NotFound().body("No such upload"),
+    }
+}
+
+// appends the body at the Upload-Offset header, which has to be where the
+// upload is, so a chunk sent twice is never stored twice
+pub(crate) async fn append_chunk(
+    path: web::Path<UploadPath>,
+    chunk: Bytes,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(Some(path.id), Level::Write) {
+        return rejection.into();
+    }
+    let offset = request.headers().get("Upload-Offset")
+        .and_then(|offset| offset.to_str().ok())
+        .and_then(|offset| offset.parse::<usize>().ok());
+    let Some(offset) = offset else {
+        return HttpResponse::BadRequest().body("Upload-Offset is missing");
+    };
+    let file = {
+        let files = space.attachments.files.lock().unwrap();
+        let Some(upload) = files.uploads.get(&path.upload).filter(|upload| upload.journal == path.id) else {
+            return HttpResponse::NotFound().body("No such upload");
+        };
+        upload.file.clone()
+    };
+    // checked again with the file locked, another chunk may have been
+    // written meanwhile; the upload only moves on once this one is written
+    let mut writing = file.file.lock().await;
+    {
+        let files = space.attachments.files.lock().unwrap();
+        let Some(upload) = files.uploads.get(&path.upload) else {
+            return HttpResponse::NotFound().body("No such upload");
+        };
+        if offset != upload.received {
+            return HttpResponse::Conflict()
+                .append_header(("Upload-Offset", upload.received.to_string()))
+                .body(format!("The upload is at offset {}", upload.received));
+        }
+        if upload.received + chunk.len() > upload.length {
+            return HttpResponse::PayloadTooLarge().body(format!("The upload is {} bytes long", upload.length));
+        }
+    }
+    if let Err(error) = write_chunk(&mut writing, offset, &chunk).await {
+        println!("Chunk of upload {} was not written: {}", path.upload, error);
+        return HttpResponse::InternalServerError().body("Error storing the chunk");
+    }
+    let mut files = space.attachments.files.lock().unwrap();
+    let Some(upload) = files.uploads.get_mut(&path.upload) else {
+        return HttpResponse::NotFound().body("No such upload");
+    };
+    upload.received = offset + chunk.len();
+    upload.updated_at = Utc::now();
+    return HttpResponse::NoContent()
+        .append_header(("Upload-Offset", upload.received.to_string()))
+        .finish();
+}
+
+// turns a complete upload into an attachment of its journal
+pub(crate) async fn finalize(
+    path: web::Path<UploadPath>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(Some(path.id), Level::Write) {
+        return rejection.into();
+    }
+    let _writes = space.attachments.writes.lock().await;
+    // every byte up to the offset is written, chunks only move it once they are
+    let (file, length) = {
+        let files = space.attachments.files.lock().unwrap();
+        match files.uploads.get(&path.upload).filter(|upload| upload.journal == path.id) {
+            None => return HttpResponse::NotFound().body("No such upload"),
+            Some(upload) if upload.received < upload.length => {
+                return HttpResponse::Conflict()
+                    .append_header(("Upload-Offset", upload.received.to_string()))
+                    .body(format!("{} of {} bytes received", upload.received, upload.length));
+            }
+            Some(upload) => (upload.file.clone(), upload.length),
+        }
+    };
+    let data = match file.read(length).await {
+        Ok(data)    => Bytes::from(data),
+        Err(error)  => {
+            println!("Upload {} was not read: {}", path.upload, error);
+            return HttpResponse::InternalServerError().body("Error storing the attachment");
+        }
+    };
+    let sha256 = digest(&data[..]);
+    let (upload, known) = {
+        let mut files = space.attachments.files.lock().unwrap();
+        // a complete upload with the wrong content cannot be continued, it goes
+        let Some(upload) = files.uploads.remove(&path.upload) else {
+            return HttpResponse::NotFound().body("No such upload");
+        };
+        let known = files.blobs.contains_key(&sha256);
+        (upload, known)
+    };
+    if upload.sha256.as_ref().is_some_and(|announced| *announced != sha256) {
+        return HttpResponse::UnprocessableEntity().body(format!("The upload has the SHA-256 {}, not the one announced", sha256));
+    }
+    if !known {
+        // checked again, other uploads may have been finalized meanwhile;
+        // kept, so finalizing can be tried once there is room
+        if let Err(rejection) = quota::admit_attachment(&state, length) {
+            space.attachments.files.lock().unwrap().uploads.insert(path.into_inner().upload, upload);
+            return rejection.into();
+        }
+        if let Err(error) = space.attachments.store.put(&space.attachments.key(&sha256), data).await {
+            println!("Blob {} was not stored: {}", sha256, error);
+            // kept, so finalizing can be tried again
+            space.attachments.files.lock().unwrap().uploads.insert(path.into_inner().upload, upload);
+            return HttpResponse::InternalServerError().body("Error storing the attachment");
+        }
+    }
+    let attachment = Attachment {
+        journal:        upload.journal,
+        name:           upload.name,
+        content_type:   upload.content_type,
+        sha256,
+        length,
+        created_at:     Utc::now(),
+    };
+    let mut files = space.attachments.files.lock().unwrap();
+    let id = files.attach(attachment);
+    return created(&files, id, &space, &request);
+}
+
+fn created(files: &Files, id: usize, space: &Space, request: &HttpRequest) -> HttpResponse {
+    let attachment = &files.attachments[&id];
+    return HttpResponse::Created()
+        .append_header(("Location", format!("{}/journals/{}/attachments/{}", space.root(request), attachment.journal, id)))
+        .json(attachment_view(id, attachment));
+}
+
+// attaches content the space already has by its SHA-256, 404 tells the
+// client to upload it
+pub(crate) async fn attach_known(
+    path: web::Path<IdPath>,
+    json: web::Json<KnownBlob>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    let id = path.id;
+    if let Err(rejection) = space.allow::<Journal>(Some(id), Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    if space.journals.get(&id).is_none() {
+        return HttpResponse::NotFound().body("Not found");
+    }
+    let info = json.into_inner();
+    if info.name.trim().is_empty() {
+        return HttpResponse::BadRequest().body("Attachments need a name");
+    }
+    let sha256 = info.sha256.to_ascii_lowercase();
+    let mut files = space.attachments.files.lock().unwrap();
+    let Some(length) = files.blobs.get(&sha256).map(|blob| blob.length) else {
+        return HttpResponse::NotFound().body("Unknown content, upload it");
+    };
+    let attachment = Attachment {
+        journal:        id,
+        name:           info.name,
+        content_type:   info.content_type,
+        sha256,
+        length,
+        created_at:     Utc::now(),
+    };
+    let attachment = files.attach(attachment);
+    return created(&files, attachment, &space, &request);
+}
+
+pub(crate) async fn abort_upload(path: web::Path<UploadPath>, space: Space) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(Some(path.id), Level::Write) {
+        return rejection.into();
+    }
+    let mut files = space.attachments.files.lock().unwrap();
+    if files.uploads.get(&path.upload).is_none_or(|upload| upload.journal != path.id) {
+        return HttpResponse::NotFound().body("No such upload");
+    }
+    files.uploads.remove(&path.upload);
+    return HttpResponse::Ok().body("Removed");
+}
+
+// the attachments of a journal, oldest first
+pub(crate) async fn list(path: web::Path<IdPath>, space: Space) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(Some(path.id), Level::Read) {
+        return rejection.into();
+    }
+    let files = space.attachments.files.lock().unwrap();
+    let mut attachments: Vec<AttachmentView<'_>> = files.attachments.iter()
+        .filter(|(_, attachment)| attachment.journal == path.id)
+        .map(|(id, attachment)| attachment_view(*id, attachment))
+        .collect();
+    attachments.sort_by_key(|attachment| attachment.id);
+    return HttpResponse::Ok().json(attachments);
+}
+
+// the file itself, in byte ranges if asked for
+pub(crate) async fn download(
+    path: web::Path<AttachmentPath>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(Some(path.id), Level::Read) {
+        return rejection.into();
+    }
+    let (content_type, name, sha256) = {
+        let files = space.attachments.files.lock().unwrap();
+        match files.attachments.get(&path.attachment).filter(|attachment| attachment.journal == path.id) {
+            Some(attachment)    => (attachment.content_type.clone(), attachment.name.clone(), attachment.sha256.clone()),
+            None                => return HttpResponse::NotFound().body("Not found"),
+        }
+    };
+    let data = match space.attachments.store.get(&space.attachments.key(&sha256)).await {
+        Ok(Some(data))  => data,
+        Ok(None)        => return HttpResponse::NotFound().body("Not found"),
+        Err(error)      => {
+            println!("Blob {} was not read: {}", sha256, error);
+            return HttpResponse::InternalServerError().body("Error reading the attachment");
+        }
+    };
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(content_type)
+        .append_header(("Content-Disposition", format!("attachment; filename={:?}", name)))
+        .append_header(("ETag", sha256.as_str()));
+    return ranges::respond(&request, response, &sha256, data);
+}
+
+pub(crate) async fn remove(path: web::Path<AttachmentPath>, space: Space) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(Some(path.id), Level::Write) {
+        return rejection.into();
+    }
+    let freed = {
+        let mut files = space.attachments.files.lock().unwrap();
+        if files.attachments.get(&path.attachment).is_none_or(|attachment| attachment.journal != path.id) {
+            return HttpResponse::NotFound().body("Not found");
+        }
+        files.detach(path.attachment)
+    };
+    space.attachments.delete(freed.into_iter().collect()).await;
+    return HttpResponse::Ok().body("Removed");
+}
@@ -0,0 +1,66 @@
+// The audit log: the last writes of every space, as the event bus published
+// them, newest first at GET /admin/audit (?kind= for one kind of entry,
+// ?limit= for how many, 100 by default). Kept in memory like the rest.
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::admin::require_admin;
+use crate::events::Bus;
+use crate::notify::Event;
+use crate::state::State;
+
+// events kept, the oldest are dropped
+const KEPT: usize = 1000;
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Default)]
+pub(crate) struct Audit {
+    events: Mutex<VecDeque<Event>>,
+}
+
+impl Audit {
+    // records every event published from now on
+    pub(crate) fn watch(bus: &Bus) -> Arc<Audit> {
+        let audit = Arc::new(Audit::default());
+        let log = audit.clone();
+        bus.consume("audit log", move |event| log.record(event));
+        return audit;
+    }
+
+    fn record(&self, event: Event) {
+        let mut events = self.events.lock().unwrap();
+        if events.len() >= KEPT {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AuditQuery {
+    kind:   Option<String>,
+    limit:  Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    // "task.created"
+    event:      String,
+    #[serde(flatten)]
+    details:    &'a Event,
+}
+
+pub(crate) async fn show(query: web::Query<AuditQuery>, state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    let events = state.audit.events.lock().unwrap();
+    let records: Vec<Record<'_>> = events.iter().rev()
+        .filter(|event| query.kind.as_deref().is_none_or(|kind| event.kind == kind))
+        .take(query.limit.unwrap_or(DEFAULT_LIMIT))
+        .map(|event| Record { event: event.name(), details: event })
+        .collect();
+    return HttpResponse::Ok().json(records);
+}
@@ -0,0 +1,121 @@
+// One-shot write tokens: handed out by POST /tokens and consumed by every
+// request that changes something
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use std::time::{Duration, SystemTime};
+
+use crate::state::State;
+
+const TOKEN_LENGTH: usize = 32;
+pub(crate) const DEFAULT_TOKEN_TTL: Duration = Duration::from_secs(60 * 3);
+pub(crate) const TOKEN_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// one-shot write token, keyed by its value in State::tokens
+#[derive(PartialEq)]
+pub(crate) struct Token {
+    timestamp:  SystemTime,
+}
+
+// unguessable, e.g. for tokens and workspace keys
+pub(crate) fn random_key() -> String {
+    return thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect();
+}
+
+impl State {
+    fn gen_token(&self) -> String {
+        let str_value = random_key();
+        let token = Token{
+            timestamp: SystemTime::now(),
+        };
+        self.tokens.lock().unwrap().insert(str_value.clone(), token);
+        return str_value;
+    }
+
+    pub(crate) fn consume_token(&self, token: &str) -> bool {
+        let mut tokens = self.tokens.lock().unwrap();
+        match tokens.remove(token) {
+            Some(rmv)   => return rmv.timestamp >= (SystemTime::now() - self.token_ttl()),
+            None        => return false,
+        }
+    }
+
+    // 3 minutes for a token to become invalid unless JOURNAL_TOKEN_TTL says
+    // otherwise
+    fn token_ttl(&self) -> Duration {
+        return self.settings.read().unwrap().token_ttl;
+    }
+
+    // returns how many were removed
+    fn sweep_tokens(&self) -> usize {
+        let oldest_valid = SystemTime::now() - self.token_ttl();
+        let mut tokens = self.tokens.lock().unwrap();
+        let before = tokens.len();
+        tokens.retain(|_, token| token.timestamp >= oldest_valid);
+        return before - tokens.len();
+    }
+
+    // the tokens that can still be used and how old they are, oldest first
+    pub(crate) fn active_tokens(&self) -> Vec<(String, Duration)> {
+        let now = SystemTime::now();
+        let ttl = self.token_ttl();
+        let mut active: Vec<(String, Duration)> = self.tokens.lock().unwrap().iter()
+            .filter_map(|(key, token)| Some((key.clone(), now.duration_since(token.timestamp).ok()?)))
+            .filter(|(_, age)| *age <= ttl)
+            .collect();
+        active.sort_by_key(|(_, age)| std::cmp::Reverse(*age));
+        return active;
+    }
+
+    pub(crate) fn revoke_token(&self, token: &str) -> bool {
+        return self.tokens.lock().unwrap().remove(token).is_some();
+    }
+
+    // returns how many were revoked
+    pub(crate) fn revoke_tokens(&self) -> usize {
+        let mut tokens = self.tokens.lock().unwrap();
+        let revoked = tokens.len();
+        tokens.clear();
+        return revoked;
+    }
+}
+
+// removes expired tokens, a job of the scheduler
+pub(crate) async fn sweep(state: web::Data<State>) -> Result<(), String> {
+    let removed = state.sweep_tokens();
+    if removed > 0 {
+        println!("Removed {} expired tokens", removed);
+    }
+    return Ok(());
+}
+
+pub(crate) async fn gen_token(state: web::Data<State>) -> impl Responder {
+    let token = state.gen_token();
+    println!("Generated token: {}", token);
+    HttpResponse::Created()
+        .body(token)
+}
+
+pub(crate) fn response_token(
+    state: &web::Data<State>,
+    request: &HttpRequest
+) -> Result<(), HttpResponse> {
+    let bad_request = |reason| Err(HttpResponse::BadRequest().body(String::from(reason)));
+    let token_val = match request.headers().get("Post-Token") {
+        Some(token) => token,
+        None        => return bad_request("Missing token"),
+    };
+    let token = match token_val.to_str() {
+        Ok(str) => str,
+        Err(_)  => return bad_request("Error during token retrieval"),
+    };
+    let is_allowed = state.consume_token(token);
+    if !is_allowed {
+        return bad_request("Bad token");
+    }
+    return Ok(());
+}
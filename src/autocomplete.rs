@@ -0,0 +1,69 @@
+// Typeahead: the tags or titles in use that start with what was typed so
+// far, most used first
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::models::Resource;
+use crate::store::Entries;
+use crate::workspace::Space;
+
+const DEFAULT_LIMIT: usize = 10;
+const MAX_LIMIT: usize = 50;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+enum Completing {
+    #[default]
+    Tags,
+    // of journals, and the text of tasks
+    Titles,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AutocompleteQuery {
+    // case-insensitive, everything matches when empty
+    #[serde(default)]
+    q:      String,
+    #[serde(rename = "type", default)]
+    kind:   Completing,
+    limit:  Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Completion {
+    value:  String,
+    // journals and tasks using it
+    count:  usize,
+}
+
+fn count<T: Resource>(space: &Space, entries: &Entries<T>, query: &AutocompleteQuery, counts: &mut HashMap<String, usize>) {
+    let prefix = query.q.to_lowercase();
+    for id in space.visible(entries) {
+        let Some(resource) = entries.get(&id) else { continue };
+        let values = match query.kind {
+            Completing::Tags    => resource.tags().to_vec(),
+            Completing::Titles  => vec![String::from(resource.summary())],
+        };
+        for value in values {
+            if value.to_lowercase().starts_with(&prefix) {
+                *counts.entry(value).or_default() += 1;
+            }
+        }
+    }
+}
+
+pub(crate) async fn complete(
+    query: web::Query<AutocompleteQuery>,
+    space: Space,
+) -> impl Responder {
+    let mut counts = HashMap::new();
+    count(&space, &space.journals, &query, &mut counts);
+    count(&space, &space.tasks, &query, &mut counts);
+    let mut completions: Vec<Completion> = counts.into_iter()
+        .map(|(value, count)| Completion { value, count })
+        .collect();
+    completions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+    completions.truncate(query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT));
+    return HttpResponse::Ok().json(completions);
+}
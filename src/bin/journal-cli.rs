@@ -0,0 +1,209 @@
+#![allow(clippy::needless_return)]
+// Terminal client for the journal server, built on rest::client
+use chrono::{DateTime, Datelike, Timelike, Utc};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{exit, Command};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use rest::client::{ClientError, JournalClient};
+
+const DEFAULT_SERVER: &str = "http://127.0.0.1:8080";
+
+const USAGE: &str = "\
+Usage: journal-cli [--server <url>] <command>
+
+Commands:
+  task add <text>                   create a task
+  task show <id>                    print a task
+  task done <id>                    mark a task as done
+  journal new [--title <title>] [--edit]
+                                    create a journal from stdin, or from $EDITOR with --edit;
+                                    without --title the first line is the title
+  journal show <id>                 print a journal
+  journal import <dir>              create a journal from every .md file in the directory, at any
+                                    depth, dated by the file; a first line heading is the title
+  export                            print the takeout JSON of everything
+
+The server defaults to $JOURNAL_SERVER, then to http://127.0.0.1:8080.";
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    exit(2);
+}
+
+fn parse_id(argument: Option<&String>) -> usize {
+    match argument.map(|id| id.trim_start_matches('#').parse()) {
+        Some(Ok(id))    => id,
+        _               => usage(),
+    }
+}
+
+// the text the user wrote in $EDITOR, starting from an empty file
+fn edit() -> std::io::Result<String> {
+    let path = std::env::temp_dir().join(format!("journal-cli-{}.md", std::process::id()));
+    std::fs::write(&path, "")?;
+    // may carry arguments, e.g. "code --wait"
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| String::from("vi"));
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program).args(words).arg(&path).status()?;
+    let text = std::fs::read_to_string(&path);
+    let _ = std::fs::remove_file(&path);
+    if !status.success() {
+        return Err(std::io::Error::other(format!("{} exited with {}", editor, status)));
+    }
+    return text;
+}
+
+async fn journal_new(client: &JournalClient, arguments: &[String]) -> Result<(), ClientError> {
+    let mut title = None;
+    let mut use_editor = false;
+    let mut arguments = arguments.iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--title"   => title = Some(arguments.next().unwrap_or_else(|| usage()).clone()),
+            "--edit"    => use_editor = true,
+            _           => usage(),
+        }
+    }
+
+    let text = if use_editor {
+        edit()
+    } else {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text).map(|_| text)
+    };
+    let text = match text {
+        Ok(text) => text,
+        Err(err) => {
+            eprintln!("Could not read the journal: {}", err);
+            exit(1);
+        }
+    };
+    let (title, data) = match title {
+        Some(title) => (title, text.trim()),
+        None        => {
+            let (title, data) = text.trim().split_once('\n').unwrap_or((text.trim(), ""));
+            (String::from(title.trim_start_matches('#').trim()), data.trim())
+        }
+    };
+    if title.is_empty() {
+        eprintln!("Empty journal, nothing created");
+        exit(1);
+    }
+
+    let journal = client.create_journal(&title, data).await?;
+    println!("Created journal #{} {}", journal.id, journal.resource.title);
+    return Ok(());
+}
+
+// the .md files under dir, with their paths relative to it and their
+// modification times
+fn zip_directory(dir: &Path) -> std::io::Result<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let mut directories = vec![PathBuf::from(dir)];
+    while let Some(directory) = directories.pop() {
+        for entry in std::fs::read_dir(&directory)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                directories.push(path);
+                continue;
+            }
+            if path.extension().is_none_or(|extension| !extension.eq_ignore_ascii_case("md")) {
+                continue;
+            }
+            let modified: DateTime<Utc> = std::fs::metadata(&path)?.modified()?.into();
+            let mut options = SimpleFileOptions::default();
+            // zips cannot hold times before 1980
+            let time = zip::DateTime::from_date_and_time(
+                modified.year() as u16, modified.month() as u8, modified.day() as u8,
+                modified.hour() as u8, modified.minute() as u8, modified.second() as u8,
+            );
+            if let Ok(time) = time {
+                options = options.last_modified_time(time);
+            }
+            let name = path.strip_prefix(dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+            zip.start_file(name, options)?;
+            zip.write_all(&std::fs::read(&path)?)?;
+        }
+    }
+    return Ok(zip.finish()?.into_inner());
+}
+
+async fn journal_import(client: &JournalClient, dir: Option<&String>) -> Result<(), ClientError> {
+    let dir = dir.unwrap_or_else(|| usage());
+    let archive = match zip_directory(Path::new(dir)) {
+        Ok(archive) => archive,
+        Err(err)    => {
+            eprintln!("Could not read {}: {}", dir, err);
+            exit(1);
+        }
+    };
+    let report: serde_json::Value = serde_json::from_str(&client.import_journals(archive).await?).unwrap_or_default();
+    for journal in report["created"].as_array().into_iter().flatten() {
+        println!("Created journal #{} {} from {}", journal["id"], journal["title"].as_str().unwrap_or(""), journal["file"].as_str().unwrap_or(""));
+    }
+    for file in report["skipped"].as_array().into_iter().flatten() {
+        println!("Skipped {}: {}", file["file"].as_str().unwrap_or(""), file["reason"].as_str().unwrap_or(""));
+    }
+    return Ok(());
+}
+
+async fn run(client: &JournalClient, arguments: &[String]) -> Result<(), ClientError> {
+    let words: Vec<&str> = arguments.iter().take(2).map(String::as_str).collect();
+    match words.as_slice() {
+        ["task", "add"] => {
+            let text = arguments[2..].join(" ");
+            if text.is_empty() {
+                usage();
+            }
+            let task = client.create_task(&text).await?;
+            println!("Added task #{} {}", task.id, task.resource.text);
+        }
+        ["task", "show"] => {
+            let task = client.get_task(parse_id(arguments.get(2))).await?;
+            let mark = if task.resource.done { "x" } else { " " };
+            println!("[{}] #{} {}", mark, task.id, task.resource.text);
+        }
+        ["task", "done"] => {
+            let mut task = client.get_task(parse_id(arguments.get(2))).await?;
+            task.resource.mark_done(true);
+            let task = client.update_task(&task).await?;
+            println!("Completed task #{} {}", task.id, task.resource.text);
+        }
+        ["journal", "new"] => journal_new(client, &arguments[2..]).await?,
+        ["journal", "import"] => journal_import(client, arguments.get(2)).await?,
+        ["journal", "show"] => {
+            let journal = client.get_journal(parse_id(arguments.get(2))).await?;
+            println!("# {}\n\n{}", journal.resource.title, journal.resource.data);
+        }
+        ["export", ..] => {
+            let takeout = client.export().await?;
+            // a closed pipe, e.g. into head, is not worth a panic
+            let _ = writeln!(std::io::stdout(), "{}", takeout);
+        }
+        _ => usage(),
+    }
+    return Ok(());
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut arguments: Vec<String> = std::env::args().skip(1).collect();
+    let mut server = std::env::var("JOURNAL_SERVER").unwrap_or_else(|_| String::from(DEFAULT_SERVER));
+    if arguments.first().map(String::as_str) == Some("--server") {
+        if arguments.len() < 2 {
+            usage();
+        }
+        server = arguments.remove(1);
+        arguments.remove(0);
+    }
+
+    let client = JournalClient::new(&server);
+    if let Err(err) = run(&client, &arguments).await {
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,248 @@
+// Where the content of attachments lives: in memory by default, in a
+// directory with JOURNAL_BLOB_DIR, or in an S3-compatible bucket with
+// JOURNAL_S3_BUCKET. Blobs are written once under their key and never
+// changed, only deleted.
+use actix_web::web::Bytes;
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+pub(crate) trait BlobStore: Send + Sync {
+    fn put(&self, key: &str, data: Bytes) -> BoxFuture<'_, io::Result<()>>;
+    // None when there is no blob under the key
+    fn get(&self, key: &str) -> BoxFuture<'_, io::Result<Option<Bytes>>>;
+    // deleting a missing blob is no error
+    fn delete(&self, key: &str) -> BoxFuture<'_, io::Result<()>>;
+}
+
+// which store to use, read from the environment at startup
+#[derive(Debug, Clone, Default)]
+pub(crate) enum BlobConfig {
+    #[default]
+    Memory,
+    Disk(PathBuf),
+    S3(S3Config),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct S3Config {
+    // e.g. "https://s3.eu-central-1.amazonaws.com" or "http://localhost:9000",
+    // addressed path-style, so any S3-compatible server works
+    endpoint:   String,
+    bucket:     String,
+    // put before every key, e.g. "journal/"
+    prefix:     String,
+    region:     String,
+    access_key: String,
+    secret_key: String,
+}
+
+impl BlobConfig {
+    pub(crate) fn from_env() -> BlobConfig {
+        let var = |name: &str| std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+        if let Some(bucket) = var("JOURNAL_S3_BUCKET") {
+            let region = var("JOURNAL_S3_REGION").unwrap_or_else(|| String::from("us-east-1"));
+            let credentials = var("JOURNAL_S3_ACCESS_KEY").zip(var("JOURNAL_S3_SECRET_KEY"));
+            let Some((access_key, secret_key)) = credentials else {
+                println!("Ignoring JOURNAL_S3_BUCKET, JOURNAL_S3_ACCESS_KEY and JOURNAL_S3_SECRET_KEY are missing");
+                return BlobConfig::Memory;
+            };
+            return BlobConfig::S3(S3Config {
+                endpoint:   var("JOURNAL_S3_ENDPOINT")
+                    .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region))
+                    .trim_end_matches('/')
+                    .to_string(),
+                bucket,
+                prefix:     var("JOURNAL_S3_PREFIX").unwrap_or_default(),
+                region,
+                access_key,
+                secret_key,
+            });
+        }
+        return match var("JOURNAL_BLOB_DIR") {
+            Some(dir)   => BlobConfig::Disk(PathBuf::from(dir)),
+            None        => BlobConfig::Memory,
+        };
+    }
+
+    pub(crate) fn open(&self) -> Arc<dyn BlobStore> {
+        return match self {
+            BlobConfig::Memory      => Arc::new(Memory::default()),
+            BlobConfig::Disk(root)  => Arc::new(Disk { root: root.clone() }),
+            BlobConfig::S3(config)  => Arc::new(S3 { config: config.clone(), client: reqwest::Client::new() }),
+        };
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Memory(Mutex<HashMap<String, Bytes>>);
+
+impl BlobStore for Memory {
+    fn put(&self, key: &str, data: Bytes) -> BoxFuture<'_, io::Result<()>> {
+        self.0.lock().unwrap().insert(String::from(key), data);
+        return Box::pin(async { Ok(()) });
+    }
+
+    fn get(&self, key: &str) -> BoxFuture<'_, io::Result<Option<Bytes>>> {
+        let blob = self.0.lock().unwrap().get(key).cloned();
+        return Box::pin(async { Ok(blob) });
+    }
+
+    fn delete(&self, key: &str) -> BoxFuture<'_, io::Result<()>> {
+        self.0.lock().unwrap().remove(key);
+        return Box::pin(async { Ok(()) });
+    }
+}
+
+// one file per blob below the root, the key being its relative path
+pub(crate) struct Disk {
+    root: PathBuf,
+}
+
+impl BlobStore for Disk {
+    fn put(&self, key: &str, data: Bytes) -> BoxFuture<'_, io::Result<()>> {
+        let path = self.root.join(key);
+        return Box::pin(async move {
+            if let Some(parent) = path.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            // renamed into place, so a blob is never seen half-written
+            let partial = path.with_extension("partial");
+            tokio::fs::write(&partial, &data).await?;
+            return tokio::fs::rename(&partial, &path).await;
+        });
+    }
+
+    fn get(&self, key: &str) -> BoxFuture<'_, io::Result<Option<Bytes>>> {
+        let path = self.root.join(key);
+        return Box::pin(async move {
+            match tokio::fs::read(&path).await {
+                Ok(data)                                            => Ok(Some(data.into())),
+                Err(error) if error.kind() == io::ErrorKind::NotFound   => Ok(None),
+                Err(error)                                          => Err(error),
+            }
+        });
+    }
+
+    fn delete(&self, key: &str) -> BoxFuture<'_, io::Result<()>> {
+        let path = self.root.join(key);
+        return Box::pin(async move {
+            match tokio::fs::remove_file(&path).await {
+                Err(error) if error.kind() != io::ErrorKind::NotFound   => Err(error),
+                _                                                   => Ok(()),
+            }
+        });
+    }
+}
+
+// an S3-compatible bucket, with requests signed by AWS Signature Version 4
+pub(crate) struct S3 {
+    config: S3Config,
+    client: reqwest::Client,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    return bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+fn hmac(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    return mac.finalize().into_bytes().to_vec();
+}
+
+// every byte but the unreserved ones and '/' percent-encoded, as the
+// canonical URI of a signature wants it
+fn uri_encode(path: &str) -> String {
+    return path.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => String::from(byte as char),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+}
+
+impl S3 {
+    async fn send(&self, method: reqwest::Method, key: &str, body: Bytes) -> io::Result<reqwest::Response> {
+        let config = &self.config;
+        let path = uri_encode(&format!("/{}/{}{}", config.bucket, config.prefix, key));
+        let url = format!("{}{}", config.endpoint, path);
+        let host = config.endpoint.split_once("://").map_or(config.endpoint.as_str(), |(_, host)| host);
+
+        let now = Utc::now();
+        let (date, timestamp) = (now.format("%Y%m%d").to_string(), now.format("%Y%m%dT%H%M%SZ").to_string());
+        let payload_hash = hex(&Sha256::digest(&body));
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, host, payload_hash, timestamp, signed_headers, payload_hash,
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, config.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            timestamp, scope, hex(&Sha256::digest(canonical_request.as_bytes())),
+        );
+        let key = [date.as_str(), config.region.as_str(), "s3", "aws4_request"].iter()
+            .fold(format!("AWS4{}", config.secret_key).into_bytes(), |key, part| hmac(&key, part));
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            config.access_key, scope, signed_headers, hex(&hmac(&key, &string_to_sign)),
+        );
+
+        return self.client.request(method, url)
+            .header("x-amz-date", timestamp)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("Authorization", authorization)
+            .body(body)
+            .send()
+            .await
+            .map_err(io::Error::other);
+    }
+}
+
+fn failed(response: &reqwest::Response) -> io::Error {
+    return io::Error::other(format!("S3 answered {}", response.status()));
+}
+
+impl BlobStore for S3 {
+    fn put(&self, key: &str, data: Bytes) -> BoxFuture<'_, io::Result<()>> {
+        let key = String::from(key);
+        return Box::pin(async move {
+            let response = self.send(reqwest::Method::PUT, &key, data).await?;
+            if !response.status().is_success() {
+                return Err(failed(&response));
+            }
+            return Ok(());
+        });
+    }
+
+    fn get(&self, key: &str) -> BoxFuture<'_, io::Result<Option<Bytes>>> {
+        let key = String::from(key);
+        return Box::pin(async move {
+            let response = self.send(reqwest::Method::GET, &key, Bytes::new()).await?;
+            match response.status() {
+                reqwest::StatusCode::NOT_FOUND              => return Ok(None),
+                status if !status.is_success()              => return Err(failed(&response)),
+                _                                           => {}
+            }
+            return response.bytes().await.map(Some).map_err(io::Error::other);
+        });
+    }
+
+    fn delete(&self, key: &str) -> BoxFuture<'_, io::Result<()>> {
+        let key = String::from(key);
+        return Box::pin(async move {
+            let response = self.send(reqwest::Method::DELETE, &key, Bytes::new()).await?;
+            // S3 answers 204 for missing keys as well
+            if !response.status().is_success() && response.status() != reqwest::StatusCode::NOT_FOUND {
+                return Err(failed(&response));
+            }
+            return Ok(());
+        });
+    }
+}
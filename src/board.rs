@@ -0,0 +1,92 @@
+// Kanban view: the tasks of a space in a column per status, each column
+// ordered and cut on its own, so a board is filled by a single request
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::models::{Resource, Status, Task, WithId};
+use crate::workspace::Space;
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+enum Order {
+    // the manual order of POST /tasks/{id}/move
+    #[default]
+    Position,
+    // newest first
+    Created,
+    Updated,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct BoardQuery {
+    // of every column
+    limit:  Option<usize>,
+    // "done:5,cancelled:0" overrides limit for those columns
+    limits: Option<String>,
+    #[serde(default)]
+    order:  Order,
+}
+
+#[derive(Serialize)]
+struct Column<'a> {
+    status: Status,
+    // in the column, shown or not
+    total:  usize,
+    tasks:  Vec<WithId<'a, Task>>,
+}
+
+#[derive(Serialize)]
+struct Board<'a> {
+    columns: Vec<Column<'a>>,
+}
+
+fn parse_limits(limits: &str) -> Option<HashMap<Status, usize>> {
+    let mut parsed = HashMap::new();
+    for limit in limits.split(',').map(str::trim).filter(|limit| !limit.is_empty()) {
+        let (status, limit) = limit.split_once(':')?;
+        parsed.insert(Status::parse(status.trim())?, limit.trim().parse::<usize>().ok()?.min(MAX_LIMIT));
+    }
+    return Some(parsed);
+}
+
+pub(crate) async fn board(
+    query: web::Query<BoardQuery>,
+    space: Space,
+) -> impl Responder {
+    let limits = match query.limits.as_deref().map(parse_limits) {
+        Some(Some(limits))  => limits,
+        Some(None)          => return HttpResponse::BadRequest().body("Expected limits=status:n,..."),
+        None                => HashMap::new(),
+    };
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    // archived tasks, e.g. merge sources, are off the board
+    let tasks: Vec<(usize, Task)> = space.visible(&space.tasks).into_iter()
+        .filter_map(|id| Some((id, space.tasks.get(&id)?.clone())))
+        .filter(|(_, task)| !task.archived)
+        .collect();
+    let mut columns = Vec::new();
+    for status in Status::ALL {
+        let mut column: Vec<&(usize, Task)> = tasks.iter()
+            .filter(|(_, task)| task.status() == Some(status))
+            .collect();
+        match query.order {
+            Order::Position => column.sort_by_key(|(id, task)| (task.position, *id)),
+            Order::Created  => column.sort_by(|a, b| b.1.created_at.cmp(&a.1.created_at).then(a.0.cmp(&b.0))),
+            Order::Updated  => column.sort_by(|a, b| b.1.updated_at.cmp(&a.1.updated_at).then(a.0.cmp(&b.0))),
+        }
+        columns.push(Column {
+            status,
+            total:  column.len(),
+            tasks:  column.into_iter()
+                .take(limits.get(&status).copied().unwrap_or(limit))
+                .map(|(id, task)| WithId { id: *id, resource: task })
+                .collect(),
+        });
+    }
+    return HttpResponse::Ok().json(Board { columns });
+}
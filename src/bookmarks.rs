@@ -0,0 +1,131 @@
+// Bookmarks: pages to read later. They are plain resources with the generic
+// handlers; journals link to them through a bookmark's `journals`, and with
+// JOURNAL_FETCH_TITLES a bookmark saved without a title gets the one of its
+// page, fetched in the background so saving stays quick. Only pages on
+// public addresses are fetched (see outbound), so bookmarks cannot reach
+// services inside the network unless JOURNAL_FETCH_PRIVATE allows it.
+use actix_web::{web, HttpResponse, Responder};
+use chrono::Utc;
+use reqwest::Url;
+use std::time::Duration;
+
+use crate::etag;
+use crate::handlers::IdPath;
+use crate::models::{Bookmark, Journal, Resource, WithId};
+use crate::notify::{Action, Event};
+use crate::outbound::{self, fetchable};
+use crate::state::State;
+use crate::workspace::{Level, Space};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+// the title is at the start, the rest of a page is not read
+const MAX_PAGE_BYTES: usize = 256 * 1024;
+
+// the bookmarks linking to the journal, by id
+pub(crate) async fn of_journal(
+    path: web::Path<IdPath>,
+    space: Space,
+) -> impl Responder {
+    let id = path.id;
+    let allowed = space.allow::<Journal>(Some(id), Level::Read).and_then(|_| space.allow::<Bookmark>(None, Level::Read));
+    if let Err(rejection) = allowed {
+        return rejection.into();
+    }
+    if !space.visible(&space.journals).contains(&id) {
+        return HttpResponse::NotFound().body("Not found");
+    }
+    let linked: Vec<(usize, Bookmark)> = space.visible(&space.bookmarks).into_iter()
+        .filter_map(|bookmark| Some((bookmark, space.bookmarks.get(&bookmark)?.clone())))
+        .filter(|(_, bookmark)| bookmark.journals.contains(&id))
+        .collect();
+    let linked: Vec<WithId<'_, Bookmark>> = linked.iter()
+        .map(|(id, bookmark)| WithId { id: *id, resource: bookmark })
+        .collect();
+    return HttpResponse::Ok().json(linked);
+}
+
+// the text of the page's <title>, its whitespace collapsed and the usual
+// entities decoded
+fn title(html: &str) -> Option<String> {
+    // the same length as the page, so its offsets hold there
+    let lower = html.to_ascii_lowercase();
+    let open = lower.find("<title")?;
+    let start = open + lower[open..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = html[start..end].split_whitespace().collect::<Vec<_>>().join(" ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&");
+    return Some(title).filter(|title| !title.is_empty());
+}
+
+async fn page_title(client: &reqwest::Client, url: &str, private: bool) -> Result<Option<String>, String> {
+    let url = Url::parse(url).map_err(|err| err.to_string())?;
+    fetchable(&url, private)?;
+    let page = read_page(client, url).await.map_err(|err| err.to_string())?;
+    return Ok(title(&String::from_utf8_lossy(&page)));
+}
+
+// the start of the page, up to MAX_PAGE_BYTES
+async fn read_page(client: &reqwest::Client, url: Url) -> Result<Vec<u8>, reqwest::Error> {
+    let mut response = client.get(url).header("Accept", "text/html").send().await?.error_for_status()?;
+    let mut page = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        page.extend_from_slice(&chunk);
+        if page.len() >= MAX_PAGE_BYTES {
+            break;
+        }
+    }
+    return Ok(page);
+}
+
+// gives the bookmark the title of its page, unless it got one meanwhile
+async fn fetch_title(client: reqwest::Client, state: web::Data<State>, workspace: Option<usize>, id: usize) {
+    let Some(space) = Space::all(&state).into_iter().find(|space| space.workspace() == workspace) else {
+        return;
+    };
+    let url = match space.bookmarks.get(&id) {
+        Some(bookmark) if bookmark.title.is_none()  => bookmark.url.clone(),
+        _                                           => return,
+    };
+    let title = match page_title(&client, &url, state.config.fetch_private).await {
+        Ok(Some(title)) => title,
+        Ok(None)        => return println!("{} has no title", url),
+        Err(err)        => return println!("Fetching the title of {} failed: {}", url, err),
+    };
+    let stored = space.bookmarks.change(move |bookmarks| {
+        let Some(mut bookmark) = bookmarks.get_mut(&id) else {
+            return;
+        };
+        if bookmark.title.is_some() || bookmark.url != url {
+            return;
+        }
+        bookmark.title = Some(title);
+        bookmark.updated_at = Utc::now();
+        if etag::refresh(&mut *bookmark).is_err() {
+            return;
+        }
+        let event = Event::of(Action::Updated, id, &*bookmark);
+        drop(bookmark);
+        bookmarks.emit(event);
+    }).await;
+    if let Err(failed) = stored {
+        println!("Storing the title of bookmark {} failed: {}", id, failed);
+    }
+}
+
+// fetches the titles of the bookmarks created from now on without one
+pub(crate) fn fetch_titles(state: &web::Data<State>) {
+    let client = match outbound::client(state.config.fetch_private, FETCH_TIMEOUT) {
+        Ok(client)  => client,
+        Err(err)    => return println!("Not fetching bookmark titles: {}", err),
+    };
+    let fetching = state.clone();
+    state.bus.consume("bookmark titles", move |event| {
+        if event.kind == Bookmark::KIND && event.action == Action::Created {
+            tokio::spawn(fetch_title(client.clone(), fetching.clone(), event.workspace, event.id));
+        }
+    });
+}
@@ -0,0 +1,126 @@
+// Changes to many tasks at once, applied in one change so no request sees
+// them half done, with a result per task
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::response_token;
+use crate::{dryrun, etag};
+use crate::models::{validate_tags, Task};
+use crate::notify::{Action, Event};
+use crate::state::State;
+use crate::workspace::{Level, Space};
+
+#[derive(Deserialize)]
+pub(crate) struct BulkTag {
+    ids:    Vec<usize>,
+    #[serde(default)]
+    add:    Vec<String>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Tagged {
+    id:         usize,
+    // 200, or 404 for ids without a task
+    status:     u16,
+    changed:    bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags:       Option<Vec<String>>,
+    // none on a dry run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag:       Option<String>,
+}
+
+// the tags of a task once those removed are gone and those added are there
+fn retag(tags: &[String], info: &BulkTag) -> Vec<String> {
+    let mut tags: Vec<String> = tags.iter().filter(|tag| !info.remove.contains(tag)).cloned().collect();
+    for tag in &info.add {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+    return tags;
+}
+
+// adds and removes tags on every task, tasks that had them all already are
+// left as they are
+pub(crate) async fn tag_tasks(
+    json: web::Json<BulkTag>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Task>(None, Level::Write) {
+        return rejection.into();
+    }
+    let dry_run = dryrun::wanted(&request);
+    if !dry_run {
+        if let Err(resp) = response_token(&state, &request) {
+            return resp;
+        }
+    }
+    let info = json.into_inner();
+    if info.ids.is_empty() || (info.add.is_empty() && info.remove.is_empty()) {
+        return HttpResponse::BadRequest().body("Nothing to tag");
+    }
+    let mut unique = info.ids.clone();
+    unique.sort();
+    unique.dedup();
+    if unique.len() != info.ids.len() {
+        return HttpResponse::BadRequest().body("Duplicate ids");
+    }
+    if let Err(reason) = validate_tags(&info.add) {
+        return HttpResponse::BadRequest().body(reason);
+    }
+    if let Some(tag) = info.add.iter().find(|tag| info.remove.contains(tag)) {
+        return HttpResponse::BadRequest().body(format!("tag {:?} is both added and removed", tag));
+    }
+    if dry_run {
+        let results: Vec<Tagged> = info.ids.iter().map(|id| match space.tasks.get(id) {
+            Some(task)  => {
+                let tags = retag(&task.tags, &info);
+                Tagged { id: *id, status: StatusCode::OK.as_u16(), changed: tags != task.tags, tags: Some(tags), etag: None }
+            }
+            None        => Tagged { id: *id, status: StatusCode::NOT_FOUND.as_u16(), changed: false, tags: None, etag: None },
+        }).collect();
+        return HttpResponse::Ok().json(results);
+    }
+
+    let results = space.tasks.change(move |tasks| {
+        let now = Utc::now();
+        let mut results = Vec::new();
+        for &id in &info.ids {
+            let Some(mut task) = tasks.get_mut(&id) else {
+                results.push(Tagged { id, status: StatusCode::NOT_FOUND.as_u16(), changed: false, tags: None, etag: None });
+                continue;
+            };
+            let tags = retag(&task.tags, &info);
+            let changed = tags != task.tags;
+            if changed {
+                task.tags = tags;
+                task.updated_at = now;
+                let _ = etag::refresh(&mut *task);
+            }
+            results.push(Tagged {
+                id,
+                status:     StatusCode::OK.as_u16(),
+                changed,
+                tags:       Some(task.tags.clone()),
+                etag:       Some(task.etag.clone()),
+            });
+            let event = changed.then(|| Event::of(Action::Updated, id, &*task));
+            drop(task);
+            if let Some(event) = event {
+                tasks.emit(event);
+            }
+        }
+        results
+    }).await;
+    return match results {
+        Ok(results) => HttpResponse::Ok().json(results),
+        Err(failed) => failed.into(),
+    };
+}
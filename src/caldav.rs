@@ -0,0 +1,345 @@
+// Minimal CalDAV surface exposing tasks as VTODO items under /caldav/tasks/
+use actix_web::http::{Method, StatusCode};
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use quick_xml::events::Event as XmlEvent;
+use quick_xml::Reader;
+
+use crate::ical::{self, Property};
+use crate::imports;
+use crate::etag::calculate_hash;
+use crate::handlers::store_put;
+use crate::models::{Etagged, Resource, Status, Task};
+use crate::state::State;
+use crate::workspace::Space;
+
+const COLLECTION: &str = "/caldav/tasks/";
+
+fn task_href(id: usize) -> String {
+    format!("{}{}.ics", COLLECTION, id)
+}
+
+// DAV entity tags are quoted, the REST ones are not
+fn dav_etag(task: &Task) -> String {
+    format!("\"{}\"", task.get_etag())
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn task_ics(id: usize, task: &Task) -> String {
+    // iCalendar has no blocked, such tasks still need action
+    let status = match task.status() {
+        Some(Status::InProgress)    => "IN-PROCESS",
+        Some(Status::Done)          => "COMPLETED",
+        Some(Status::Cancelled)     => "CANCELLED",
+        _                           => "NEEDS-ACTION",
+    };
+    let mut lines = vec![
+        String::from("BEGIN:VTODO"),
+        format!("UID:task-{}@rest-journal", id),
+        format!("DTSTAMP:{}", ical::format_timestamp(&task.updated_at)),
+        format!("CREATED:{}", ical::format_timestamp(&task.created_at)),
+        format!("LAST-MODIFIED:{}", ical::format_timestamp(&task.updated_at)),
+        format!("SUMMARY:{}", ical::escape_text(&task.text)),
+        format!("STATUS:{}", status),
+    ];
+    if let Some(completed_at) = &task.completed_at {
+        lines.push(format!("COMPLETED:{}", ical::format_timestamp(completed_at)));
+    }
+    if let Some(due) = &task.due {
+        lines.push(format!("DUE:{}", ical::format_timestamp(due)));
+    }
+    lines.push(String::from("END:VTODO"));
+    ical::write_calendar(&lines)
+}
+
+fn dav_response(href: &str, props: &str) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:propstat><d:prop>{}</d:prop>\
+         <d:status>HTTP/1.1 200 OK</d:status></d:propstat></d:response>",
+        xml_escape(href), props
+    )
+}
+
+fn dav_not_found(href: &str) -> String {
+    format!(
+        "<d:response><d:href>{}</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>",
+        xml_escape(href)
+    )
+}
+
+fn multistatus(responses: Vec<String>) -> HttpResponse {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+         <d:multistatus xmlns:d=\"DAV:\" xmlns:c=\"urn:ietf:params:xml:ns:caldav\" \
+         xmlns:cs=\"http://calendarserver.org/ns/\">{}</d:multistatus>",
+        responses.concat()
+    );
+    HttpResponse::build(StatusCode::MULTI_STATUS)
+        .content_type("application/xml; charset=utf-8")
+        .body(body)
+}
+
+fn task_props(task: &Task) -> String {
+    format!(
+        "<d:getetag>{}</d:getetag>\
+         <d:getcontenttype>text/calendar; charset=utf-8; component=VTODO</d:getcontenttype>\
+         <d:resourcetype/>",
+        xml_escape(&dav_etag(task))
+    )
+}
+
+// the tasks a listing shows, expired ones left out
+fn listed(space: &Space) -> Vec<(usize, Task)> {
+    return space.visible(&space.tasks).into_iter()
+        .filter_map(|id| Some((id, space.tasks.get(&id)?.clone())))
+        .collect();
+}
+
+// changes whenever any listed task changes, or one is no longer listed
+fn collection_ctag(space: &Space) -> String {
+    let mut etags: Vec<String> = listed(space).iter()
+        .map(|(id, task)| format!("{}:{}", id, task.get_etag()))
+        .collect();
+    etags.sort();
+    calculate_hash(etags.join(","))
+}
+
+fn collection_props(space: &Space) -> String {
+    let ctag = collection_ctag(space);
+    format!(
+        "<d:resourcetype><d:collection/><c:calendar/></d:resourcetype>\
+         <d:displayname>Tasks</d:displayname>\
+         <c:supported-calendar-component-set><c:comp name=\"VTODO\"/></c:supported-calendar-component-set>\
+         <cs:getctag>{}</cs:getctag>\
+         <d:getetag>\"{}\"</d:getetag>",
+        ctag, ctag
+    )
+}
+
+fn depth(request: &HttpRequest) -> u8 {
+    match request.headers().get("Depth").and_then(|depth| depth.to_str().ok()) {
+        Some("0") => 0,
+        _ => 1,
+    }
+}
+
+fn method(name: &str) -> Method {
+    Method::from_bytes(name.as_bytes()).unwrap()
+}
+
+async fn options() -> impl Responder {
+    HttpResponse::Ok()
+        .append_header(("DAV", "1, 3, calendar-access"))
+        .append_header(("Allow", "OPTIONS, PROPFIND, REPORT, GET, PUT, DELETE"))
+        .finish()
+}
+
+// discovery: the root acts as principal and calendar home
+async fn propfind_root() -> impl Responder {
+    let props = "<d:resourcetype><d:collection/></d:resourcetype>\
+        <d:current-user-principal><d:href>/caldav/</d:href></d:current-user-principal>\
+        <c:calendar-home-set><d:href>/caldav/</d:href></c:calendar-home-set>\
+        <d:displayname>rest-journal</d:displayname>";
+    multistatus(vec![dav_response("/caldav/", props)])
+}
+
+async fn propfind_collection(
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    let mut responses = vec![dav_response(COLLECTION, &collection_props(&space))];
+    if depth(&request) > 0 {
+        for (id, task) in listed(&space) {
+            responses.push(dav_response(&task_href(id), &task_props(&task)));
+        }
+    }
+    multistatus(responses)
+}
+
+async fn propfind_task(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+) -> impl Responder {
+    let id = path.into_inner();
+    match state.tasks.get(&id) {
+        Some(task)  => multistatus(vec![dav_response(&task_href(id), &task_props(&task))]),
+        None        => HttpResponse::NotFound().body("Not found"),
+    }
+}
+
+// (is the report a calendar-multiget, requested hrefs)
+fn parse_report(body: &str) -> Result<(bool, Vec<String>), quick_xml::Error> {
+    let mut reader = Reader::from_str(body);
+    let mut is_multiget = false;
+    let mut in_href = false;
+    let mut hrefs = Vec::new();
+    loop {
+        match reader.read_event()? {
+            XmlEvent::Start(element) => {
+                let name = element.local_name();
+                is_multiget |= name.as_ref() == b"calendar-multiget";
+                in_href = name.as_ref() == b"href";
+            }
+            XmlEvent::Text(text) if in_href => {
+                hrefs.push(String::from(text.unescape()?.trim()));
+            }
+            XmlEvent::End(_) => in_href = false,
+            XmlEvent::Eof => break,
+            _ => (),
+        }
+    }
+    return Ok((is_multiget, hrefs));
+}
+
+fn href_to_id(href: &str) -> Option<usize> {
+    // clients may send absolute URLs
+    let path = href.rsplit(COLLECTION).next()?;
+    path.strip_suffix(".ics")?.parse().ok()
+}
+
+async fn report(
+    body: String,
+    space: Space,
+) -> impl Responder {
+    let (is_multiget, hrefs) = match parse_report(&body) {
+        Ok(report)  => report,
+        Err(_)      => return HttpResponse::BadRequest().body("Broken xml"),
+    };
+    let item = |id: usize, task: &Task| {
+        let props = format!(
+            "{}<c:calendar-data>{}</c:calendar-data>",
+            task_props(task), xml_escape(&task_ics(id, task))
+        );
+        dav_response(&task_href(id), &props)
+    };
+
+    // calendar-query filters are not evaluated, every item is a VTODO anyway
+    let responses = if is_multiget {
+        hrefs.iter()
+            .map(|href| match href_to_id(href).and_then(|id| Some((id, space.tasks.get(&id)?))) {
+                Some((id, task))    => item(id, &task),
+                None                => dav_not_found(href),
+            })
+            .collect()
+    } else {
+        listed(&space).iter().map(|(id, task)| item(*id, task)).collect()
+    };
+    multistatus(responses)
+}
+
+async fn get_task(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+) -> impl Responder {
+    let id = path.into_inner();
+    match state.tasks.get(&id) {
+        Some(task) => HttpResponse::Ok()
+            .content_type("text/calendar; charset=utf-8")
+            .append_header(("ETag", dav_etag(&task)))
+            .body(task_ics(id, &task)),
+        None => HttpResponse::NotFound().body("Not found"),
+    }
+}
+
+fn apply_vtodo(task: &mut Task, vtodo: &[Property]) {
+    if let Some(summary) = ical::find(vtodo, "SUMMARY") {
+        task.text = ical::unescape_text(&summary.value);
+    }
+    task.due = ical::find(vtodo, "DUE").and_then(|due| ical::parse_timestamp(&due.value));
+    if let Some(status) = ical::find(vtodo, "STATUS") {
+        match status.value.to_ascii_uppercase().as_str() {
+            "IN-PROCESS"    => task.set_status(Status::InProgress),
+            "COMPLETED"     => task.set_status(Status::Done),
+            "CANCELLED"     => task.set_status(Status::Cancelled),
+            // what blocked tasks are sent as, so they stay blocked
+            _ if task.status() == Some(Status::Blocked) => {}
+            _               => task.set_status(Status::Todo),
+        }
+    } else if ical::find(vtodo, "COMPLETED").is_some() {
+        task.mark_done(true);
+    }
+}
+
+// Updates need the task's ETag in If-Match ("*" for whatever it is now),
+// new tasks If-None-Match: *, so no client overwrites a change it has not
+// seen (RFC 4791 5.3.2)
+async fn put_task(
+    path: web::Path<usize>,
+    body: String,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    let id = path.into_inner();
+    let vtodo = match ical::components(&body, "VTODO").into_iter().next() {
+        Some(vtodo) => vtodo,
+        None        => return HttpResponse::BadRequest().body("Missing VTODO"),
+    };
+
+    let header = |name| request.headers().get(name).map(|value| value.to_str().unwrap_or(""));
+    let if_match = header("If-Match");
+    let create = header("If-None-Match").is_some_and(|if_none_match| if_none_match.trim() == "*");
+    let existing = state.tasks.get(&id).map(|task| task.clone());
+    let (mut task, if_match) = match (existing, if_match) {
+        (Some(_), _) if create  => return HttpResponse::PreconditionFailed().body("The task exists"),
+        (Some(task), Some(if_match)) => {
+            let if_match = match if_match.trim() {
+                "*"         => task.get_etag(),
+                if_match    => String::from(if_match.trim_matches('"')),
+            };
+            (task, Some(if_match))
+        }
+        (Some(_), None)         => return HttpResponse::PreconditionRequired().body("If-Match is missing"),
+        (None, Some(_))         => return HttpResponse::PreconditionFailed().body("Not found"),
+        (None, None) if create  => (imports::task(String::new(), Status::Todo, None, Vec::new()), None),
+        (None, None)            => return HttpResponse::PreconditionRequired().body("If-None-Match: * is missing"),
+    };
+
+    let created = if_match.is_none();
+    apply_vtodo(&mut task, &vtodo);
+    match store_put(&state, &Space::server(&state), id, if_match, task).await {
+        Ok(etag) => {
+            let mut response = if created { HttpResponse::Created() } else { HttpResponse::NoContent() };
+            response.append_header(("ETag", format!("\"{}\"", etag))).finish()
+        }
+        Err(rejection) => rejection.into(),
+    }
+}
+
+async fn delete_task(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+) -> impl Responder {
+    let id = path.into_inner();
+    match state.tasks.rm_resource(id, &state.hooks).await {
+        Ok(Some(_))     => HttpResponse::NoContent().finish(),
+        Ok(None)        => HttpResponse::NotFound().body("Not found"),
+        Err(rejection)  => rejection.into(),
+    }
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+            web::resource(["/caldav", "/caldav/"])
+            .route(web::method(Method::OPTIONS).to(options))
+            .route(web::method(method("PROPFIND")).to(propfind_root))
+        )
+        .service(
+            web::resource(["/caldav/tasks", COLLECTION])
+            .route(web::method(Method::OPTIONS).to(options))
+            .route(web::method(method("PROPFIND")).to(propfind_collection))
+            .route(web::method(method("REPORT")).to(report))
+        )
+        .service(
+            web::resource("/caldav/tasks/{id:\\d+}.ics")
+            .route(web::method(Method::OPTIONS).to(options))
+            .route(web::method(method("PROPFIND")).to(propfind_task))
+            .route(web::get().to(get_task))
+            .route(web::put().to(put_task))
+            .route(web::delete().to(delete_task))
+        );
+}
@@ -0,0 +1,207 @@
+// A typed client for the /v1 REST API. Every write fetches its own one-shot
+// token, and resources come back with the ETag that updating them requires.
+use chrono::{DateTime, Utc};
+use reqwest::{header, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt;
+
+use crate::models::{Journal, Task, Timestamped};
+
+#[derive(Debug)]
+pub enum ClientError {
+    // the server could not be reached or sent something unreadable
+    Request(reqwest::Error),
+    // the server answered with an error, e.g. 412 for a stale ETag
+    Status(StatusCode, String),
+    // a successful answer without a header it always carries
+    MissingHeader(&'static str),
+}
+
+impl ClientError {
+    // someone else changed the resource since it was fetched
+    pub fn is_stale(&self) -> bool {
+        matches!(self, ClientError::Status(StatusCode::PRECONDITION_FAILED, _))
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Request(err)           => write!(f, "request failed: {}", err),
+            ClientError::Status(status, body)   => write!(f, "{}: {}", status, body),
+            ClientError::MissingHeader(name)    => write!(f, "response without {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+impl From<reqwest::Error> for ClientError {
+    fn from(err: reqwest::Error) -> ClientError {
+        ClientError::Request(err)
+    }
+}
+
+// a resource as fetched, with what is needed to write it back
+#[derive(Debug, Clone)]
+pub struct Tagged<T> {
+    pub id:         usize,
+    pub etag:       String,
+    pub resource:   T,
+}
+
+// the models skip the server-managed timestamps when deserializing, so they
+// are read next to them
+#[derive(Deserialize)]
+struct Stored<T> {
+    #[serde(flatten)]
+    resource:   T,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+pub struct JournalClient {
+    http:   reqwest::Client,
+    // e.g. "http://127.0.0.1:8080", without a trailing slash
+    base:   String,
+}
+
+impl JournalClient {
+    pub fn new(base_url: &str) -> JournalClient {
+        JournalClient {
+            http: reqwest::Client::new(),
+            base: String::from(base_url.trim_end_matches('/')),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v1{}", self.base, path)
+    }
+
+    pub async fn create_task(&self, text: &str) -> Result<Tagged<Task>, ClientError> {
+        let task = json!({ "text": text, "done": false });
+        return self.create("/tasks", &task).await;
+    }
+
+    pub async fn get_task(&self, id: usize) -> Result<Tagged<Task>, ClientError> {
+        return self.get(&format!("/tasks/{}", id), id).await;
+    }
+
+    // fails with a stale ClientError when the task changed since it was fetched
+    pub async fn update_task(&self, task: &Tagged<Task>) -> Result<Tagged<Task>, ClientError> {
+        return self.update(&format!("/tasks/{}", task.id), task).await;
+    }
+
+    pub async fn delete_task(&self, id: usize) -> Result<(), ClientError> {
+        return self.delete(&format!("/tasks/{}", id)).await;
+    }
+
+    // merges the tasks in the given order and deletes them, returns the merge
+    pub async fn merge_tasks(&self, ids: &[usize]) -> Result<Tagged<Task>, ClientError> {
+        return self.create("/task_merger", &json!({ "ids": ids })).await;
+    }
+
+    pub async fn create_journal(&self, title: &str, data: &str) -> Result<Tagged<Journal>, ClientError> {
+        let journal = json!({ "title": title, "data": data });
+        return self.create("/journals", &journal).await;
+    }
+
+    pub async fn get_journal(&self, id: usize) -> Result<Tagged<Journal>, ClientError> {
+        return self.get(&format!("/journals/{}", id), id).await;
+    }
+
+    // fails with a stale ClientError when the journal changed since it was fetched
+    pub async fn update_journal(&self, journal: &Tagged<Journal>) -> Result<Tagged<Journal>, ClientError> {
+        return self.update(&format!("/journals/{}", journal.id), journal).await;
+    }
+
+    pub async fn delete_journal(&self, id: usize) -> Result<(), ClientError> {
+        return self.delete(&format!("/journals/{}", id)).await;
+    }
+
+    // the takeout of the server's entries, as the JSON GET /export sends
+    pub async fn export(&self) -> Result<String, ClientError> {
+        let response = checked(self.http.get(self.url("/export")).send().await?).await?;
+        return Ok(response.text().await?);
+    }
+
+    // a zip of Markdown files as journals, returns the JSON report of
+    // POST /import/journals
+    pub async fn import_journals(&self, archive: Vec<u8>) -> Result<String, ClientError> {
+        let request = self.http.post(self.url("/import/journals"))
+            .header("Post-Token", self.token().await?)
+            .header(header::CONTENT_TYPE, "application/zip")
+            .body(archive);
+        let response = checked(request.send().await?).await?;
+        return Ok(response.text().await?);
+    }
+
+    async fn token(&self) -> Result<String, ClientError> {
+        let response = checked(self.http.post(self.url("/tokens")).send().await?).await?;
+        return Ok(response.text().await?);
+    }
+
+    // sends a token-protected POST and fetches what it created
+    async fn create<T, B>(&self, path: &str, body: &B) -> Result<Tagged<T>, ClientError>
+    where T: DeserializeOwned + Timestamped, B: Serialize {
+        let request = self.http.post(self.url(path))
+            .header("Post-Token", self.token().await?)
+            .json(body);
+        let response = checked(request.send().await?).await?;
+        let location = header_value(&response, "Location")?;
+        let id = location.rsplit('/').next()
+            .and_then(|id| id.parse().ok())
+            .ok_or(ClientError::MissingHeader("Location"))?;
+        return self.get(&location.replacen("/v1", "", 1), id).await;
+    }
+
+    async fn get<T>(&self, path: &str, id: usize) -> Result<Tagged<T>, ClientError>
+    where T: DeserializeOwned + Timestamped {
+        let response = checked(self.http.get(self.url(path)).send().await?).await?;
+        let etag = header_value(&response, "ETag")?;
+        let stored: Stored<T> = response.json().await?;
+        let mut resource = stored.resource;
+        resource.set_timestamps(stored.created_at, stored.updated_at);
+        return Ok(Tagged { id, etag, resource });
+    }
+
+    async fn update<T>(&self, path: &str, tagged: &Tagged<T>) -> Result<Tagged<T>, ClientError>
+    where T: Serialize + DeserializeOwned + Timestamped {
+        let request = self.http.put(self.url(path))
+            .header(header::IF_MATCH, &tagged.etag)
+            .json(&tagged.resource);
+        checked(request.send().await?).await?;
+        return self.get(path, tagged.id).await;
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), ClientError> {
+        checked(self.http.delete(self.url(path)).send().await?).await?;
+        return Ok(());
+    }
+}
+
+#[derive(Deserialize)]
+struct Problem {
+    detail: Option<String>,
+}
+
+// the response if it is a success, its status and the problem's detail (or
+// the body) as the error otherwise
+async fn checked(response: Response) -> Result<Response, ClientError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let body = response.text().await.unwrap_or_default();
+    let detail = serde_json::from_str::<Problem>(&body).ok().and_then(|problem| problem.detail);
+    return Err(ClientError::Status(status, detail.unwrap_or(body)));
+}
+
+fn header_value(response: &Response, name: &'static str) -> Result<String, ClientError> {
+    return response.headers().get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from)
+        .ok_or(ClientError::MissingHeader(name));
+}
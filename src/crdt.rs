@@ -0,0 +1,581 @@
+// Offline sync through CRDTs: devices edit their copies while offline and
+// POST /sync/merge merges every edit into the stored entries, in any order,
+// without losing any. Task fields and journal titles, tags and ratings are
+// last-writer-wins registers, the text of journals a sequence of characters
+// (RGA), so edits of different devices to the same text are all kept.
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::auth::response_token;
+use crate::etag;
+use crate::flags;
+use crate::hooks::Hooks;
+use crate::imports;
+use crate::models::{Etagged, Journal, Resource, Status, Task, Timestamped, Transitions};
+use crate::notify::{Action, Event};
+use crate::quota;
+use crate::state::{store_resource, State};
+use crate::store::{Collection, Failed, Writer};
+use crate::workspace::{Level, Space};
+
+// what the server's own edits, made outside of syncs, are stamped with
+const SERVER: &str = "server";
+
+// A Lamport timestamp: the counter, then the device that made the edit to
+// tell equal counters apart. Devices stamp every edit with a counter above
+// the clock of the last merge and every counter they have used since.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Stamp(u64, String);
+
+impl Stamp {
+    fn server(counter: u64) -> Stamp {
+        return Stamp(counter, String::from(SERVER));
+    }
+}
+
+// a field, as last written
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub(crate) struct Register {
+    value:  Value,
+    at:     Stamp,
+}
+
+// one character of a text, kept as a tombstone once deleted so edits made
+// after it can still find their place
+#[derive(Serialize, Clone)]
+struct Element {
+    id:         Stamp,
+    char:       char,
+    deleted:    bool,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum TextOp {
+    // after the element `after`, or at the start without it
+    Insert { id: Stamp, after: Option<Stamp>, char: char },
+    Delete(Stamp),
+}
+
+// The merged state of one entry, which devices replace their copy with
+#[derive(Serialize, Clone, Default)]
+struct Replica {
+    // the highest counter in it, devices continue above it
+    clock:  u64,
+    fields: BTreeMap<String, Register>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    text:   Vec<Element>,
+    // of the entry as last merged, edits made since came from outside of syncs
+    #[serde(skip)]
+    etag:   String,
+}
+
+impl Replica {
+    // an entry's first replica: everything as the server's, at counter 0
+    // for the fields and 1, 2, ... for the characters
+    fn of<T: Replicated>(resource: &T) -> Replica {
+        let mut replica = Replica::default();
+        for name in T::FIELDS {
+            replica.fields.insert(String::from(*name), Register { value: resource.field(name), at: Stamp::server(0) });
+        }
+        if let Some(text) = resource.text() {
+            replica.insert_text(None, text);
+        }
+        replica.etag = resource.get_etag();
+        return replica;
+    }
+
+    fn visible(&self) -> String {
+        return self.text.iter().filter(|element| !element.deleted).map(|element| element.char).collect();
+    }
+
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        return self.clock;
+    }
+
+    // chains `text` after the element `after`, as the server
+    fn insert_text(&mut self, mut after: Option<Stamp>, text: &str) {
+        for char in text.chars() {
+            let id = Stamp::server(self.tick());
+            // new ids are above every other one, they cannot fail
+            let _ = self.insert(id.clone(), after.as_ref(), char);
+            after = Some(id);
+        }
+    }
+
+    // takes in the edits made to the entry since the last merge as edits of
+    // the server, stamped above everything before
+    fn rebase<T: Replicated>(&mut self, resource: &T) {
+        if self.etag == resource.get_etag() {
+            return;
+        }
+        for name in T::FIELDS {
+            let value = resource.field(name);
+            if self.fields.get(*name).is_none_or(|register| register.value != value) {
+                let at = Stamp::server(self.tick());
+                self.fields.insert(String::from(*name), Register { value, at });
+            }
+        }
+        if let Some(text) = resource.text() {
+            self.rebase_text(text);
+        }
+        self.etag = resource.get_etag();
+    }
+
+    // replaces what changed between the common start and end of the texts
+    fn rebase_text(&mut self, text: &str) {
+        let visible: Vec<usize> = (0..self.text.len()).filter(|index| !self.text[*index].deleted).collect();
+        let old: Vec<char> = visible.iter().map(|index| self.text[*index].char).collect();
+        let new: Vec<char> = text.chars().collect();
+        let prefix = old.iter().zip(&new).take_while(|(old, new)| old == new).count();
+        let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(old, new)| old == new).count();
+        for index in &visible[prefix..old.len() - suffix] {
+            self.text[*index].deleted = true;
+        }
+        let after = prefix.checked_sub(1).map(|last| self.text[visible[last]].id.clone());
+        let inserted: String = new[prefix..new.len() - suffix].iter().collect();
+        self.insert_text(after, &inserted);
+    }
+
+    // RGA: right after `after`, behind the elements inserted there with a
+    // higher stamp, which puts concurrent inserts in the same order everywhere
+    fn insert(&mut self, id: Stamp, after: Option<&Stamp>, char: char) -> Result<(), String> {
+        // sent before
+        if self.text.iter().any(|element| element.id == id) {
+            return Ok(());
+        }
+        let mut index = match after {
+            None        => 0,
+            Some(after) => {
+                if id.0 <= after.0 {
+                    return Err(format!("insert {:?} has to be stamped above the element it follows", id));
+                }
+                match self.text.iter().position(|element| element.id == *after) {
+                    Some(position)  => position + 1,
+                    None            => return Err(format!("no element {:?} to insert after", after)),
+                }
+            }
+        };
+        while index < self.text.len() && self.text[index].id > id {
+            index += 1;
+        }
+        self.clock = self.clock.max(id.0);
+        self.text.insert(index, Element { id, char, deleted: false });
+        return Ok(());
+    }
+
+    fn apply(&mut self, op: TextOp) -> Result<(), String> {
+        match op {
+            TextOp::Insert { id, after, char } => return self.insert(id, after.as_ref(), char),
+            TextOp::Delete(id) => {
+                let Some(element) = self.text.iter_mut().find(|element| element.id == id) else {
+                    return Err(format!("no element {:?} to delete", id));
+                };
+                element.deleted = true;
+                return Ok(());
+            }
+        }
+    }
+
+    // the later write wins
+    fn assign(&mut self, name: String, register: Register) {
+        self.clock = self.clock.max(register.at.0);
+        if self.fields.get(&name).is_none_or(|current| register.at > current.at) {
+            self.fields.insert(name, register);
+        }
+    }
+}
+
+// The replicas of the journals or tasks of a space, with the entries created
+// offline by their key
+#[derive(Default)]
+pub(crate) struct Replicas {
+    entries:    HashMap<usize, Replica>,
+    keys:       HashMap<String, usize>,
+}
+
+// of every space, shared by every request to it
+#[derive(Clone, Default)]
+pub(crate) struct Replication {
+    journals:   Arc<Mutex<Replicas>>,
+    tasks:      Arc<Mutex<Replicas>>,
+}
+
+impl Replication {
+    fn of<T: Resource>(&self) -> &Mutex<Replicas> {
+        if T::KIND == Journal::KIND {
+            return &self.journals;
+        }
+        return &self.tasks;
+    }
+}
+
+// the resources that can be merged
+pub(crate) trait Replicated: Resource + Etagged + Timestamped + Serialize + Clone + Send + Sync + 'static {
+    // the last-writer-wins fields
+    const FIELDS: &'static [&'static str];
+    // what entries created offline start from
+    fn blank() -> Self;
+    fn field(&self, name: &str) -> Value;
+    fn set_field(&mut self, name: &str, value: Value) -> Result<(), String>;
+    // the text merged character by character, for resources that have one
+    fn text(&self) -> Option<&str> {
+        return None;
+    }
+    fn set_text(&mut self, _text: String) {}
+}
+
+fn read<V: DeserializeOwned>(name: &str, value: Value) -> Result<V, String> {
+    return serde_json::from_value(value).map_err(|err| format!("{}: {}", name, err));
+}
+
+impl Replicated for Task {
+    const FIELDS: &'static [&'static str] = &["text", "status", "tags", "due"];
+
+    fn blank() -> Task {
+        return imports::task(String::new(), Status::Todo, None, Vec::new());
+    }
+
+    fn field(&self, name: &str) -> Value {
+        let value = match name {
+            "text"      => serde_json::to_value(&self.text),
+            "status"    => serde_json::to_value(self.status()),
+            "tags"      => serde_json::to_value(&self.tags),
+            _           => serde_json::to_value(self.due),
+        };
+        return value.unwrap_or_default();
+    }
+
+    fn set_field(&mut self, name: &str, value: Value) -> Result<(), String> {
+        match name {
+            "text"      => self.text = read(name, value)?,
+            "status"    => self.set_status(read(name, value)?),
+            "tags"      => self.tags = read(name, value)?,
+            _           => self.due = read(name, value)?,
+        }
+        return Ok(());
+    }
+}
+
+impl Replicated for Journal {
+    const FIELDS: &'static [&'static str] = &["title", "tags", "mood", "energy", "sleep_hours"];
+
+    fn blank() -> Journal {
+        let now = Utc::now();
+        return Journal {
+            title:       String::new(),
+            data:        String::new(),
+            encrypted:   false,
+            tags:        Vec::new(),
+            metadata:    None,
+            mood:        None,
+            energy:      None,
+            sleep_hours: None,
+            lat:         None,
+            lon:         None,
+            place:       None,
+            expires_at:  None,
+            publish_at:  None,
+            day:         None,
+            word_count:  0,
+            char_count:  0,
+            etag:        String::new(),
+            version:     0,
+            created_at:  now,
+            updated_at:  now,
+        };
+    }
+
+    fn field(&self, name: &str) -> Value {
+        let value = match name {
+            "title"     => serde_json::to_value(&self.title),
+            "tags"      => serde_json::to_value(&self.tags),
+            "mood"      => serde_json::to_value(self.mood),
+            "energy"    => serde_json::to_value(self.energy),
+            _           => serde_json::to_value(self.sleep_hours),
+        };
+        return value.unwrap_or_default();
+    }
+
+    fn set_field(&mut self, name: &str, value: Value) -> Result<(), String> {
+        match name {
+            "title"     => self.title = read(name, value)?,
+            "tags"      => self.tags = read(name, value)?,
+            "mood"      => self.mood = read(name, value)?,
+            "energy"    => self.energy = read(name, value)?,
+            _           => self.sleep_hours = read(name, value)?,
+        }
+        return Ok(());
+    }
+
+    // ciphertexts cannot be merged
+    fn text(&self) -> Option<&str> {
+        return (!self.encrypted).then_some(self.data.as_str());
+    }
+
+    fn set_text(&mut self, text: String) {
+        self.data = text;
+    }
+}
+
+// the offline edits of one entry
+#[derive(Deserialize)]
+pub(crate) struct Edit {
+    id:     Option<usize>,
+    // the device's name for an entry it created offline, instead of an id;
+    // edits with the same key always end up in the same entry
+    key:    Option<String>,
+    #[serde(default)]
+    fields: BTreeMap<String, Register>,
+    // in the order they were made
+    #[serde(default)]
+    text:   Vec<TextOp>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct Merge {
+    #[serde(default)]
+    tasks:      Vec<Edit>,
+    #[serde(default)]
+    journals:   Vec<Edit>,
+}
+
+#[derive(Serialize)]
+struct Merged {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id:         Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key:        Option<String>,
+    // 200, 201 for entries created by the merge, or why nothing was
+    status:     u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error:      Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag:       Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    replica:    Option<Replica>,
+}
+
+impl Merged {
+    fn refused(id: Option<usize>, key: Option<String>, status: StatusCode, error: String) -> Merged {
+        Merged { id, key, status: status.as_u16(), error: Some(error), etag: None, replica: None }
+    }
+}
+
+#[derive(Serialize)]
+struct MergeResult {
+    tasks:      Vec<Merged>,
+    journals:   Vec<Merged>,
+}
+
+// merges the edits into the entry and stores it, with the merged replica
+// or why it was refused
+fn merge_edit<T: Replicated>(
+    space:          &Space,
+    resources:      &mut Writer<'_, T>,
+    replicas:       &mut Replicas,
+    edit:           Edit,
+    transitions:    &Transitions,
+    hooks:          &Hooks,
+) -> Result<Merged, Merged> {
+    let key = edit.key.clone();
+    let id = edit.id.or_else(|| edit.key.as_ref().and_then(|key| replicas.keys.get(key).copied()));
+    let previous = match id {
+        Some(id) => {
+            space.allow::<T>(Some(id), Level::Write).map_err(|_| Merged::refused(Some(id), key.clone(), StatusCode::FORBIDDEN, String::from("No access to this resource")))?;
+            match resources.get(&id) {
+                Some(resource)  => Some(resource.clone()),
+                None            => {
+                    replicas.entries.remove(&id);
+                    return Err(Merged::refused(Some(id), key, StatusCode::NOT_FOUND, String::from("Not found")));
+                }
+            }
+        }
+        None if edit.key.is_some() => {
+            space.allow::<T>(None, Level::Write).map_err(|_| Merged::refused(None, key.clone(), StatusCode::FORBIDDEN, String::from("No access to this resource")))?;
+            None
+        }
+        None => return Err(Merged::refused(None, key, StatusCode::BAD_REQUEST, String::from("Every edit needs an id or a key"))),
+    };
+    let fail = |status, error: String| Merged::refused(id, key.clone(), status, error);
+    let original = previous.clone().unwrap_or_else(T::blank);
+
+    // merged into a copy, kept only if the entry is
+    let mut replica = match id.and_then(|id| replicas.entries.get(&id)) {
+        Some(replica)   => replica.clone(),
+        None            => Replica::of(&original),
+    };
+    replica.rebase(&original);
+    for (name, register) in edit.fields {
+        if !T::FIELDS.contains(&name.as_str()) {
+            return Err(fail(StatusCode::BAD_REQUEST, format!("{} is not merged, only {}", name, T::FIELDS.join(", "))));
+        }
+        replica.assign(name, register);
+    }
+    if !edit.text.is_empty() {
+        if T::KIND != Journal::KIND {
+            return Err(fail(StatusCode::BAD_REQUEST, format!("A {} has no text", T::KIND)));
+        }
+        if original.text().is_none() {
+            return Err(fail(StatusCode::CONFLICT, String::from("Encrypted journals cannot be merged")));
+        }
+    }
+    for op in edit.text {
+        replica.apply(op).map_err(|reason| fail(StatusCode::BAD_REQUEST, reason))?;
+    }
+
+    let mut resource = original.clone();
+    for (name, register) in &replica.fields {
+        resource.set_field(name, register.value.clone()).map_err(|reason| fail(StatusCode::BAD_REQUEST, reason))?;
+    }
+    if resource.text().is_some() {
+        resource.set_text(replica.visible());
+    }
+    let check = |resource: &T| {
+        resource.validate()
+            .and_then(|_| space.schemas.check(resource))
+            .and_then(|_| space.check_links(resource))
+            .map_err(|reason| fail(StatusCode::BAD_REQUEST, reason))?;
+        return transitions.check(previous.as_ref(), resource).map_err(|reason| fail(StatusCode::CONFLICT, reason));
+    };
+    check(&resource)?;
+    // hooks only see edits that change something, once they are known to go
+    // through, as with puts; what they change is checked again
+    let unchanged = |resource: &T| previous.as_ref()
+        .is_some_and(|previous| serde_json::to_value(resource).ok() == serde_json::to_value(previous).ok());
+    if !unchanged(&resource) {
+        let hooked = match (id, &previous) {
+            (Some(id), Some(_)) => hooks.updated(id, &mut resource),
+            _                   => hooks.created(&mut resource),
+        };
+        hooked.map_err(|rejection| fail(StatusCode::UNPROCESSABLE_ENTITY, rejection.to_string()))?;
+        check(&resource)?;
+    }
+
+    let now = Utc::now();
+    let (id, created) = match (id, &previous) {
+        (Some(id), Some(previous)) => {
+            if !unchanged(&resource) {
+                resource.set_timestamps(previous.get_created_at(), now);
+                resource.track_changes(Some(previous), now);
+                etag::refresh(&mut resource).map_err(|_| fail(StatusCode::INTERNAL_SERVER_ERROR, String::from("Error during serialization")))?;
+                resources.emit(Event::of(Action::Updated, id, &resource));
+                resources.insert(id, resource);
+            }
+            (id, false)
+        }
+        _ => {
+            resource.set_timestamps(now, now);
+            resource.track_changes(None, now);
+            let event = Event::of(Action::Created, 0, &resource);
+            let id = store_resource(resources, resource).map_err(|reason| fail(StatusCode::INTERNAL_SERVER_ERROR, reason))?;
+            resources.emit(Event { id, ..event });
+            if let Some(key) = &key {
+                replicas.keys.insert(key.clone(), id);
+            }
+            (id, true)
+        }
+    };
+    // what the hooks changed becomes the server's edits
+    let etag = match resources.get(&id) {
+        Some(stored)    => {
+            replica.rebase(&*stored);
+            stored.get_etag()
+        }
+        None            => String::new(),
+    };
+    replicas.entries.insert(id, replica.clone());
+    let status = if created { StatusCode::CREATED } else { StatusCode::OK };
+    return Ok(Merged { id: Some(id), key, status: status.as_u16(), error: None, etag: Some(etag), replica: Some(replica) });
+}
+
+// the entries the edits would create, those with a key not merged before,
+// and about how much they would store
+fn creating<T: Replicated>(space: &Space, edits: &[Edit]) -> (usize, usize) {
+    let replicas = space.replication.of::<T>().lock().unwrap();
+    let mut keys = BTreeSet::new();
+    let mut bytes = 0;
+    for edit in edits.iter().filter(|edit| edit.id.is_none()) {
+        let Some(key) = &edit.key else { continue };
+        if replicas.keys.contains_key(key) {
+            continue;
+        }
+        if keys.insert(key) {
+            bytes += quota::size(&T::blank());
+        }
+        bytes += edit.fields.values().map(|register| quota::size(&register.value)).sum::<usize>();
+        bytes += edit.text.iter().map(|op| match op {
+            TextOp::Insert { char, .. } => char.len_utf8(),
+            TextOp::Delete(_)           => 0,
+        }).sum::<usize>();
+    }
+    return (keys.len(), bytes);
+}
+
+async fn merge_all<T: Replicated>(
+    space:          &Space,
+    resources:      &Collection<T>,
+    edits:          Vec<Edit>,
+    transitions:    Transitions,
+    hooks:          Hooks,
+) -> Result<Vec<Merged>, Failed> {
+    if edits.is_empty() {
+        return Ok(Vec::new());
+    }
+    let space = space.clone();
+    // only changed inside the collection's changes, so never half merged
+    return resources.change(move |resources| {
+        let mut replicas = space.replication.of::<T>().lock().unwrap();
+        let mut results = Vec::new();
+        for edit in edits {
+            match merge_edit(&space, resources, &mut replicas, edit, &transitions, &hooks) {
+                Ok(merged)      => results.push(merged),
+                Err(refused)    => results.push(refused),
+            }
+        }
+        results
+    }).await;
+}
+
+// Merges the edits of every entry on its own, answering with a result per
+// edit: its merged replica, or why it was not merged. An edit without any
+// fields or text fetches the replica to start from.
+pub(crate) async fn merge(
+    json: web::Json<Merge>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if !state.flags.on(flags::CRDT_SYNC, &request) {
+        return HttpResponse::NotFound().body("Merging is not on for this client");
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let info = json.into_inner();
+    // admitted before anything is merged, like imports
+    let (new_tasks, task_bytes) = creating::<Task>(&space, &info.tasks);
+    let (new_journals, journal_bytes) = creating::<Journal>(&space, &info.journals);
+    let admitted = quota::admit(&state, &space.tasks, new_tasks, task_bytes + journal_bytes)
+        .and_then(|_| quota::admit(&state, &space.journals, new_journals, task_bytes + journal_bytes));
+    if let Err(rejection) = admitted {
+        return rejection.into();
+    }
+    let transitions = state.config.transitions.clone();
+    let tasks = match merge_all(&space, &space.tasks, info.tasks, transitions.clone(), state.hooks.clone()).await {
+        Ok(tasks)   => tasks,
+        Err(failed) => return failed.into(),
+    };
+    let journals = match merge_all(&space, &space.journals, info.journals, transitions, state.hooks.clone()).await {
+        Ok(journals)    => journals,
+        Err(failed)     => return failed.into(),
+    };
+    return HttpResponse::Ok().json(MergeResult { tasks, journals });
+}
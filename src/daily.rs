@@ -0,0 +1,152 @@
+// Daily notes: one journal per day, created from a template the first time
+// it is asked for, or at midnight (UTC) when scheduled
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{NaiveDate, Utc};
+use serde::Deserialize;
+
+use crate::models::{Journal, Timestamped, WithId};
+use crate::notify::{Action, Event};
+use crate::state::{store_resource, State};
+use crate::quota;
+use crate::replica;
+use crate::store::Collection;
+use crate::workspace::{Level, Space};
+
+// Read from the environment at startup, see Config
+#[derive(Clone)]
+pub(crate) struct DailyConfig {
+    // {date} (YYYY-MM-DD) and {weekday} are filled in, in both
+    pub(crate) title:       String,
+    pub(crate) template:    String,
+    // whether the entry of the root space is created every day
+    pub(crate) scheduled:   bool,
+}
+
+impl Default for DailyConfig {
+    fn default() -> DailyConfig {
+        DailyConfig {
+            title:      String::from("{date}"),
+            template:   String::new(),
+            scheduled:  false,
+        }
+    }
+}
+
+impl DailyConfig {
+    pub(crate) fn from_env() -> DailyConfig {
+        let default = DailyConfig::default();
+        DailyConfig {
+            title:      std::env::var("JOURNAL_DAILY_TITLE").ok().filter(|title| !title.trim().is_empty()).unwrap_or(default.title),
+            template:   std::env::var("JOURNAL_DAILY_TEMPLATE").unwrap_or(default.template),
+            scheduled:  std::env::var("JOURNAL_DAILY_SCHEDULE").is_ok_and(|on| on == "1" || on == "true"),
+        }
+    }
+}
+
+pub(crate) fn fill(template: &str, day: NaiveDate) -> String {
+    return template
+        .replace("{date}", &day.format("%Y-%m-%d").to_string())
+        .replace("{weekday}", &day.format("%A").to_string());
+}
+
+// the id of the day's entry and whether it was just created
+pub(crate) async fn ensure(journals: &Collection<Journal>, config: &DailyConfig, day: NaiveDate) -> Result<(usize, bool), String> {
+    let config = config.clone();
+    // looked up and created in one change, so it is never created twice
+    return journals.change(move |journals| {
+        let existing = journals.ids().into_iter()
+            .find(|id| journals.get(id).is_some_and(|journal| journal.day == Some(day)));
+        if let Some(id) = existing {
+            return Ok((id, false));
+        }
+        let now = Utc::now();
+        let mut journal = Journal {
+            title:       fill(&config.title, day),
+            data:        fill(&config.template, day),
+            encrypted:   false,
+            tags:        Vec::new(),
+            metadata:    None,
+            mood:        None,
+            energy:      None,
+            sleep_hours: None,
+            lat:         None,
+            lon:         None,
+            place:       None,
+            expires_at:  None,
+            publish_at:  None,
+            day:         Some(day),
+            word_count:  0,
+            char_count:  0,
+            etag:        String::new(),
+            version:     0,
+            created_at:  now,
+            updated_at:  now,
+        };
+        journal.track_changes(None, now);
+        let event = Event::of(Action::Created, 0, &journal);
+        let id = store_resource(journals, journal)?;
+        journals.emit(Event { id, ..event });
+        Ok((id, true))
+    }).await.unwrap_or_else(|failed| Err(failed.to_string()));
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TodayQuery {
+    // the client's today, when it is not the one of UTC
+    date: Option<NaiveDate>,
+}
+
+pub(crate) async fn today(
+    query: web::Query<TodayQuery>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    let day = query.date.unwrap_or_else(|| Utc::now().date_naive());
+    let existing = space.visible(&space.journals).into_iter()
+        .find(|id| space.journals.get(id).is_some_and(|journal| journal.day == Some(day)));
+    // only those who may write get it created
+    let allowed = match existing {
+        Some(id)    => space.allow::<Journal>(Some(id), Level::Read),
+        None        => space.allow::<Journal>(None, Level::Write),
+    };
+    if let Err(rejection) = allowed {
+        return rejection.into();
+    }
+    // a read that would write
+    if let (None, Some(maintenance)) = (existing, state.maintenance()) {
+        return maintenance.refusal();
+    }
+    // the primary creates it, the replica has it once it follows
+    if let (None, Some(replica)) = (existing, &state.config.replica) {
+        return replica::to_primary(&replica.primary, &request);
+    }
+    if existing.is_none() {
+        if let Err(rejection) = quota::admit(&state, &space.journals, 1, 0) {
+            return rejection.into();
+        }
+    }
+    let (id, created) = match ensure(&space.journals, &state.config.daily, day).await {
+        Ok(entry)   => entry,
+        Err(err)    => return HttpResponse::InternalServerError().body(err),
+    };
+    let Some(journal) = space.journals.get(&id) else {
+        return HttpResponse::NotFound().body("Not found");
+    };
+    let mut response = if created { HttpResponse::Created() } else { HttpResponse::Ok() };
+    if created {
+        response.append_header(("Location", format!("{}/journals/{}", space.root(&request), id)));
+    }
+    return response
+        .append_header(("ETag", journal.etag.clone()))
+        .json(WithId { id, resource: &*journal });
+}
+
+// creates the entry of the root space, a daily job of the scheduler
+pub(crate) async fn create(state: web::Data<State>) -> Result<(), String> {
+    // left to the first request after maintenance
+    if state.maintenance().is_some() {
+        return Ok(());
+    }
+    return ensure(&state.journals, &state.config.daily, Utc::now().date_naive()).await.map(|_| ());
+}
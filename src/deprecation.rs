@@ -0,0 +1,87 @@
+// Deprecation (RFC 9745) and Sunset (RFC 8594) headers for deprecated routes
+// and query parameters, all declared in RULES
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use chrono::DateTime;
+
+use crate::versioning;
+
+enum Target {
+    // every API route reached without a version prefix
+    Unversioned,
+    // a path (a trailing '*' matches any rest), optionally only when the
+    // query parameter is present
+    #[allow(dead_code)] // no rule needs it yet
+    Path(&'static str, Option<&'static str>),
+}
+
+struct Rule {
+    target:     Target,
+    // unix timestamps
+    deprecated: i64,
+    sunset:     Option<i64>,
+    // where to go instead, "{path}" is replaced with the request path
+    successor:  &'static str,
+}
+
+// the routing table of everything deprecated, first match wins
+const RULES: &[Rule] = &[
+    Rule {
+        target:     Target::Unversioned,
+        // 2026-10-16, when /v1 was introduced
+        deprecated: 1_792_108_800,
+        sunset:     None,
+        successor:  "/v1{path}",
+    },
+];
+
+fn has_param(query: &str, name: &str) -> bool {
+    query.split('&').any(|pair| pair.split('=').next() == Some(name))
+}
+
+impl Rule {
+    fn applies(&self, path: &str, query: &str) -> bool {
+        match &self.target {
+            Target::Unversioned => versioning::path_version(path).is_none(),
+            Target::Path(pattern, param) => {
+                let path_matches = match pattern.strip_suffix('*') {
+                    Some(prefix)    => path.starts_with(prefix),
+                    None            => path == *pattern,
+                };
+                path_matches && param.is_none_or(|param| has_param(query, param))
+            }
+        }
+    }
+}
+
+fn http_date(timestamp: i64) -> Option<String> {
+    let date = DateTime::from_timestamp(timestamp, 0)?;
+    return Some(date.format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+}
+
+fn set(response: &mut ServiceResponse<impl MessageBody>, name: &'static str, value: String) {
+    if let Ok(value) = HeaderValue::from_str(&value) {
+        response.headers_mut().insert(HeaderName::from_static(name), value);
+    }
+}
+
+pub async fn annotate(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let path = String::from(request.path());
+    let rule = RULES.iter().find(|rule| rule.applies(&path, request.query_string()));
+
+    let mut response = next.call(request).await?;
+    if let Some(rule) = rule {
+        set(&mut response, "deprecation", format!("@{}", rule.deprecated));
+        if let Some(sunset) = rule.sunset.and_then(http_date) {
+            set(&mut response, "sunset", sunset);
+        }
+        let successor = rule.successor.replace("{path}", &path);
+        set(&mut response, "link", format!("<{}>; rel=\"successor-version\"", successor));
+    }
+    return Ok(response);
+}
@@ -0,0 +1,61 @@
+// Dry runs: `X-Dry-Run: true` on a delete, bulk change, import or merge
+// checks it as if it were carried out, preconditions included, and answers
+// with what would happen without changing anything or using up the write
+// token. Hooks are not run, they may do more than decide. A write that
+// cannot be tried out this way is refused rather than carried out.
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::{HttpRequest, HttpResponse};
+
+const HEADER: &str = "x-dry-run";
+
+// the writes that honor the header, by the end of their route
+const SUPPORTED: [(Method, &str); 13] = [
+    (Method::DELETE, "/journals/{id}"),
+    (Method::DELETE, "/tasks/{id}"),
+    (Method::DELETE, "/habits/{id}"),
+    (Method::DELETE, "/notes/{id}"),
+    (Method::DELETE, "/bookmarks/{id}"),
+    (Method::POST, "/tasks/bulk_tag"),
+    (Method::POST, "/task_merger"),
+    (Method::POST, "/journal_merger"),
+    (Method::POST, "/import"),
+    (Method::POST, "/import/ics"),
+    (Method::POST, "/import/todoist"),
+    (Method::POST, "/import/markdown"),
+    (Method::POST, "/import/journals"),
+];
+
+pub(crate) fn wanted(request: &HttpRequest) -> bool {
+    return request.headers().get(HEADER)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("true") || value == "1");
+}
+
+fn supported(request: &ServiceRequest) -> bool {
+    let reading = matches!(request.method().as_str(), "GET" | "HEAD" | "OPTIONS" | "PROPFIND" | "REPORT");
+    let Some(pattern) = request.match_pattern() else {
+        return reading;
+    };
+    return reading || SUPPORTED.iter().any(|(method, route)| request.method() == method && pattern.ends_with(route));
+}
+
+// refuses the dry runs of other writes, and marks the answers of the others
+pub async fn guard(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    if !wanted(request.request()) {
+        return Ok(next.call(request).await?.map_into_left_body());
+    }
+    if !supported(&request) {
+        let refusal = HttpResponse::BadRequest().body(format!("{} {} cannot be a dry run", request.method(), request.path()));
+        return Ok(request.into_response(refusal).map_into_right_body());
+    }
+    let mut response = next.call(request).await?;
+    response.headers_mut().insert(HeaderName::from_static(HEADER), HeaderValue::from_static("true"));
+    return Ok(response.map_into_left_body());
+}
@@ -0,0 +1,144 @@
+// Near-duplicate journals, e.g. accidental double saves: journals sharing
+// most of their word 3-grams (shingles) are clustered. MinHash signatures cut
+// into bands find the candidate pairs without comparing every pair, the
+// exact Jaccard similarity of their shingles decides.
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+
+use crate::models::Journal;
+use crate::search::words;
+use crate::workspace::Space;
+
+const SHINGLE_WORDS: usize = 3;
+// 16 bands of 4 rows find a pair with a similarity of 0.7 about 99 times
+// in 100, one of 0.5 only about 64 times, hence the lowest threshold
+const BANDS: usize = 16;
+const ROWS: usize = 4;
+const DEFAULT_THRESHOLD: f64 = 0.8;
+const MIN_THRESHOLD: f64 = 0.5;
+
+#[derive(Deserialize)]
+pub(crate) struct DuplicatesQuery {
+    // the Jaccard similarity two journals need at least
+    threshold: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct Member {
+    id:     usize,
+    title:  String,
+}
+
+#[derive(Serialize)]
+struct Cluster {
+    journals:   Vec<Member>,
+    // of the least similar pair linking the cluster
+    similarity: f64,
+}
+
+// the word 3-grams of the data, the whole data when it is shorter, the
+// title does not count
+fn shingles(journal: &Journal) -> HashSet<String> {
+    let words: Vec<String> = words(&journal.data).into_iter().map(|word| word.lower).collect();
+    if words.is_empty() {
+        return HashSet::new();
+    }
+    if words.len() <= SHINGLE_WORDS {
+        return HashSet::from([words.join(" ")]);
+    }
+    return words.windows(SHINGLE_WORDS).map(|window| window.join(" ")).collect();
+}
+
+fn signature(shingles: &HashSet<String>) -> Vec<u64> {
+    return (0..BANDS * ROWS)
+        .map(|seed| shingles.iter()
+            .map(|shingle| {
+                let mut hasher = DefaultHasher::new();
+                (seed, shingle).hash(&mut hasher);
+                hasher.finish()
+            })
+            .min()
+            .unwrap_or(u64::MAX))
+        .collect();
+}
+
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let shared = a.intersection(b).count();
+    return shared as f64 / (a.len() + b.len() - shared) as f64;
+}
+
+fn root(parents: &mut [usize], mut node: usize) -> usize {
+    while parents[node] != node {
+        parents[node] = parents[parents[node]];
+        node = parents[node];
+    }
+    return node;
+}
+
+pub(crate) async fn duplicates(
+    query: web::Query<DuplicatesQuery>,
+    space: Space,
+) -> impl Responder {
+    let threshold = query.threshold.unwrap_or(DEFAULT_THRESHOLD);
+    if !(MIN_THRESHOLD..=1.0).contains(&threshold) {
+        return HttpResponse::BadRequest().body(format!("threshold has to be between {} and 1", MIN_THRESHOLD));
+    }
+    // ciphertexts of the same text differ and journals without words have
+    // nothing to compare, neither is ever a duplicate
+    let journals: Vec<(usize, String, HashSet<String>)> = space.visible(&space.journals).into_iter()
+        .filter_map(|id| {
+            let journal = space.journals.get(&id)?;
+            let shingles = shingles(&journal);
+            (!journal.encrypted && !shingles.is_empty()).then(|| (id, journal.title.clone(), shingles))
+        })
+        .collect();
+
+    // journals agreeing on every row of a band are candidates
+    let mut buckets: HashMap<(usize, &[u64]), Vec<usize>> = HashMap::new();
+    let signatures: Vec<Vec<u64>> = journals.iter().map(|(_, _, shingles)| signature(shingles)).collect();
+    for (index, signature) in signatures.iter().enumerate() {
+        for (band, rows) in signature.chunks(ROWS).enumerate() {
+            buckets.entry((band, rows)).or_default().push(index);
+        }
+    }
+    let mut pairs: HashSet<(usize, usize)> = HashSet::new();
+    for bucket in buckets.values() {
+        for (position, a) in bucket.iter().enumerate() {
+            pairs.extend(bucket[position + 1..].iter().map(|b| (*a, *b)));
+        }
+    }
+
+    let mut parents: Vec<usize> = (0..journals.len()).collect();
+    let mut links: Vec<(usize, f64)> = Vec::new();
+    for (a, b) in pairs {
+        let similarity = jaccard(&journals[a].2, &journals[b].2);
+        if similarity >= threshold {
+            let (root_a, root_b) = (root(&mut parents, a), root(&mut parents, b));
+            parents[root_a] = root_b;
+            links.push((a, similarity));
+        }
+    }
+    let mut clusters: BTreeMap<usize, (Vec<usize>, f64)> = BTreeMap::new();
+    for index in 0..journals.len() {
+        let cluster = clusters.entry(root(&mut parents, index)).or_insert((Vec::new(), 1.0));
+        cluster.0.push(index);
+    }
+    for (index, similarity) in links {
+        let cluster = clusters.get_mut(&root(&mut parents, index)).expect("every journal has a cluster");
+        cluster.1 = cluster.1.min(similarity);
+    }
+    let mut clusters: Vec<Cluster> = clusters.into_values()
+        .filter(|(members, _)| members.len() > 1)
+        .map(|(members, similarity)| Cluster {
+            journals:   members.into_iter()
+                .map(|index| Member { id: journals[index].0, title: journals[index].1.clone() })
+                .collect(),
+            similarity: (similarity * 1000.0).round() / 1000.0,
+        })
+        .collect();
+    clusters.sort_by_key(|cluster| cluster.journals[0].id);
+    return HttpResponse::Ok().json(clusters);
+}
@@ -0,0 +1,121 @@
+// Content negotiation between JSON, MessagePack and CBOR for the resource
+// endpoints, all sharing the same serde models
+use actix_web::dev::Payload;
+use actix_web::web::{self, Bytes};
+use actix_web::{FromRequest, HttpRequest, HttpResponse, HttpResponseBuilder};
+use futures_util::future::LocalBoxFuture;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Encoding {
+    Json,
+    MessagePack,
+    Cbor,
+}
+
+impl Encoding {
+    fn from_mime(mime: &str) -> Option<Encoding> {
+        // parameters such as "; charset=utf-8" do not matter here
+        let mime = mime.split(';').next().unwrap_or("").trim();
+        match mime.to_ascii_lowercase().as_str() {
+            "application/json" | "*/*"      => Some(Encoding::Json),
+            "application/msgpack"
+            | "application/x-msgpack"
+            | "application/vnd.msgpack"     => Some(Encoding::MessagePack),
+            "application/cbor"              => Some(Encoding::Cbor),
+            _                               => None,
+        }
+    }
+
+    // the first supported type listed in Accept, JSON otherwise
+    pub fn accepted(request: &HttpRequest) -> Encoding {
+        request.headers().get("Accept")
+            .and_then(|accept| accept.to_str().ok())
+            .and_then(|accept| accept.split(',').find_map(Encoding::from_mime))
+            .unwrap_or(Encoding::Json)
+    }
+
+    // what the body was sent as, JSON unless it says otherwise
+    pub fn sent(request: &HttpRequest) -> Encoding {
+        request.headers().get("Content-Type")
+            .and_then(|content_type| content_type.to_str().ok())
+            .and_then(Encoding::from_mime)
+            .unwrap_or(Encoding::Json)
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Encoding::Json          => "application/json",
+            Encoding::MessagePack   => "application/msgpack",
+            Encoding::Cbor          => "application/cbor",
+        }
+    }
+
+    pub fn encode<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Json          => serde_json::to_vec(value).map_err(|err| err.to_string()),
+            // named, so the models keep their field names (and #[serde(flatten)] works)
+            Encoding::MessagePack   => rmp_serde::to_vec_named(value).map_err(|err| err.to_string()),
+            Encoding::Cbor => {
+                let mut encoded = Vec::new();
+                ciborium::into_writer(value, &mut encoded).map_err(|err| err.to_string())?;
+                Ok(encoded)
+            }
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(&self, body: &[u8]) -> Result<T, String> {
+        match self {
+            Encoding::Json          => serde_json::from_slice(body).map_err(|err| err.to_string()),
+            Encoding::MessagePack   => rmp_serde::from_slice(body).map_err(|err| err.to_string()),
+            Encoding::Cbor          => ciborium::from_reader(body).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+// `value` as the body, in the encoding the client accepts
+pub fn respond<T: Serialize>(
+    request: &HttpRequest,
+    response: &mut HttpResponseBuilder,
+    value: &T,
+) -> HttpResponse {
+    let encoding = Encoding::accepted(request);
+    match encoding.encode(value) {
+        Ok(body) => response
+            .content_type(encoding.content_type())
+            .append_header(("Vary", "Accept"))
+            .body(body),
+        Err(_) => HttpResponse::InternalServerError().body("Error during serialization"),
+    }
+}
+
+// Like web::Json, but also takes MessagePack and CBOR bodies by Content-Type.
+// JSON bodies still go through web::Json and its configured limits.
+pub struct Body<T>(pub T);
+
+impl<T> Body<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: DeserializeOwned + 'static> FromRequest for Body<T> {
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Body<T>, actix_web::Error>>;
+
+    fn from_request(request: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let encoding = Encoding::sent(request);
+        if encoding == Encoding::Json {
+            let json = web::Json::<T>::from_request(request, payload);
+            return Box::pin(async move { Ok(Body(json.await?.into_inner())) });
+        }
+        let body = Bytes::from_request(request, payload);
+        Box::pin(async move {
+            let body = body.await?;
+            encoding.decode(&body)
+                .map(Body)
+                .map_err(actix_web::error::ErrorBadRequest)
+        })
+    }
+}
@@ -0,0 +1,25 @@
+// ETags derived from the resource itself: the representation a GET returns,
+// timestamps included, so equal tags always mean equal content
+use serde::Serialize;
+use sha256::digest;
+
+use crate::models::Etagged;
+
+pub fn calculate_hash(json_string: String) -> String {
+    digest(json_string)
+}
+
+// bumps the version and recomputes and stores the tag, to be called once
+// per write when every other field is set
+pub fn refresh<T: Etagged + Serialize>(resource: &mut T) -> serde_json::Result<String> {
+    resource.set_version(resource.get_version() + 1);
+    let etag = calculate_hash(serde_json::to_string(&*resource)?);
+    resource.set_etag(etag.clone());
+    return Ok(etag);
+}
+
+// whether an If-Match value names the resource as it is: its ETag, or its
+// version for clients that rather count than keep hashes
+pub fn matches<T: Etagged>(resource: &T, if_match: &str) -> bool {
+    return if_match == resource.get_etag() || if_match.parse() == Ok(resource.get_version());
+}
@@ -0,0 +1,487 @@
+// Event-sourced storage, on when JOURNAL_EVENT_LOG names a directory: every
+// change to a journal, task, habit, note, bookmark or attachment of the server's own space is appended
+// to events.jsonl there as the entry's new state (or null once deleted),
+// and that log is what the server starts from, the collections are only its
+// projection. A snapshot of them is written every JOURNAL_SNAPSHOT_MINUTES
+// (60 by default) so a start replays only what was logged after it; the
+// log itself is kept, it is the history of every entry.
+//
+// A line looks like
+//
+//     {"sequence": 12, "at": "2026-10-16T08:00:00Z", "kind": "task", "id": 3, "entry": {...}}
+//
+// Workspaces, tokens and the rest of the state stay in memory.
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::attachments::{self, Attachment, Attachments};
+use crate::models::{Bookmark, Etagged, Habit, Journal, Note, Resource, Task};
+use crate::state::State;
+use crate::store::{Collection, Entries, Observer};
+
+const LOG: &str = "events.jsonl";
+const SNAPSHOT: &str = "snapshot.json";
+const DEFAULT_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+// Read from the environment at startup, see Config
+#[derive(Debug, Clone)]
+pub(crate) struct EventLogConfig {
+    pub(crate) dir:                 PathBuf,
+    pub(crate) snapshot_interval:   Duration,
+}
+
+impl EventLogConfig {
+    pub(crate) fn from_env() -> Option<EventLogConfig> {
+        let dir = std::env::var_os("JOURNAL_EVENT_LOG").map(PathBuf::from)?;
+        let snapshot_interval = std::env::var("JOURNAL_SNAPSHOT_MINUTES").ok()
+            .and_then(|minutes| minutes.parse::<u64>().ok())
+            .filter(|minutes| *minutes > 0)
+            .map_or(DEFAULT_SNAPSHOT_INTERVAL, |minutes| Duration::from_secs(minutes * 60));
+        return Some(EventLogConfig { dir, snapshot_interval });
+    }
+}
+
+// An entry as it is logged: its JSON, with the fields the server keeps
+// and clients cannot set, which that JSON leaves out or ignores
+pub(crate) trait Recorded: Resource + Etagged + Serialize + DeserializeOwned {
+    fn kept(&self) -> Value;
+    fn restore(&mut self, recorded: &Value) -> serde_json::Result<()>;
+}
+
+// the named field of the record, its default when missing
+fn field<T: DeserializeOwned + Default>(recorded: &Value, name: &str) -> serde_json::Result<T> {
+    return recorded.get(name).map_or(Ok(T::default()), |value| serde_json::from_value(value.clone()));
+}
+
+impl Recorded for Journal {
+    fn kept(&self) -> Value {
+        return json!({
+            "day": self.day, "word_count": self.word_count, "char_count": self.char_count, "etag": self.etag,
+            "version": self.version, "created_at": self.created_at, "updated_at": self.updated_at,
+        });
+    }
+    fn restore(&mut self, recorded: &Value) -> serde_json::Result<()> {
+        self.day = field(recorded, "day")?;
+        self.word_count = field(recorded, "word_count")?;
+        self.char_count = field(recorded, "char_count")?;
+        self.etag = field(recorded, "etag")?;
+        self.version = field(recorded, "version")?;
+        self.created_at = field(recorded, "created_at")?;
+        self.updated_at = field(recorded, "updated_at")?;
+        return Ok(());
+    }
+}
+
+impl Recorded for Task {
+    fn kept(&self) -> Value {
+        return json!({
+            "etag": self.etag, "version": self.version, "created_at": self.created_at,
+            "updated_at": self.updated_at, "time_entries": self.time_entries, "position": self.position,
+        });
+    }
+    fn restore(&mut self, recorded: &Value) -> serde_json::Result<()> {
+        self.etag = field(recorded, "etag")?;
+        self.version = field(recorded, "version")?;
+        self.created_at = field(recorded, "created_at")?;
+        self.updated_at = field(recorded, "updated_at")?;
+        self.time_entries = field(recorded, "time_entries")?;
+        self.position = field(recorded, "position")?;
+        return Ok(());
+    }
+}
+
+impl Recorded for Habit {
+    fn kept(&self) -> Value {
+        return json!({
+            "checkins": self.checkins, "etag": self.etag, "version": self.version,
+            "created_at": self.created_at, "updated_at": self.updated_at,
+        });
+    }
+    fn restore(&mut self, recorded: &Value) -> serde_json::Result<()> {
+        self.checkins = field(recorded, "checkins")?;
+        self.etag = field(recorded, "etag")?;
+        self.version = field(recorded, "version")?;
+        self.created_at = field(recorded, "created_at")?;
+        self.updated_at = field(recorded, "updated_at")?;
+        return Ok(());
+    }
+}
+
+impl Recorded for Note {
+    fn kept(&self) -> Value {
+        return json!({
+            "etag": self.etag, "version": self.version, "created_at": self.created_at, "updated_at": self.updated_at,
+        });
+    }
+    fn restore(&mut self, recorded: &Value) -> serde_json::Result<()> {
+        self.etag = field(recorded, "etag")?;
+        self.version = field(recorded, "version")?;
+        self.created_at = field(recorded, "created_at")?;
+        self.updated_at = field(recorded, "updated_at")?;
+        return Ok(());
+    }
+}
+
+impl Recorded for Bookmark {
+    fn kept(&self) -> Value {
+        return json!({
+            "etag": self.etag, "version": self.version, "created_at": self.created_at, "updated_at": self.updated_at,
+        });
+    }
+    fn restore(&mut self, recorded: &Value) -> serde_json::Result<()> {
+        self.etag = field(recorded, "etag")?;
+        self.version = field(recorded, "version")?;
+        self.created_at = field(recorded, "created_at")?;
+        self.updated_at = field(recorded, "updated_at")?;
+        return Ok(());
+    }
+}
+
+fn record<T: Recorded>(entry: &T) -> Value {
+    let mut recorded = match serde_json::to_value(entry) {
+        Ok(Value::Object(fields))   => fields,
+        _                           => Map::new(),
+    };
+    if let Value::Object(kept) = entry.kept() {
+        recorded.extend(kept);
+    }
+    return Value::Object(recorded);
+}
+
+fn recorded<T: Recorded>(entries: &Entries<T>) -> BTreeMap<usize, Value> {
+    return entries.ids().into_iter()
+        .filter_map(|id| Some((id, record(&*entries.get(&id)?))))
+        .collect();
+}
+
+pub(crate) fn replay<T: Recorded>(recorded: &Value) -> serde_json::Result<T> {
+    let mut entry: T = serde_json::from_value(recorded.clone())?;
+    entry.restore(recorded)?;
+    return Ok(entry);
+}
+
+// What the log holds lines of: the entries, and the attachments, which are
+// no resources but logged the same way
+pub(crate) trait Replayable: Sized {
+    fn kind() -> &'static str;
+    fn replayed(recorded: &Value) -> serde_json::Result<Self>;
+}
+
+impl<T: Recorded> Replayable for T {
+    fn kind() -> &'static str {
+        return T::KIND;
+    }
+    fn replayed(recorded: &Value) -> serde_json::Result<T> {
+        return replay(recorded);
+    }
+}
+
+// recorded as it is kept, nothing of it is hidden from clients
+impl Replayable for Attachment {
+    fn kind() -> &'static str {
+        return attachments::KIND;
+    }
+    fn replayed(recorded: &Value) -> serde_json::Result<Attachment> {
+        return serde_json::from_value(recorded.clone());
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Line {
+    sequence:   u64,
+    at:         DateTime<Utc>,
+    kind:       String,
+    id:         usize,
+    // None once deleted
+    entry:      Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct Snapshot {
+    // of the last line it includes
+    sequence:   u64,
+    // the next id of each kind, which may be above every id left
+    next_ids:   HashMap<String, usize>,
+    journals:   BTreeMap<usize, Value>,
+    tasks:      BTreeMap<usize, Value>,
+    habits:     BTreeMap<usize, Value>,
+    // missing from snapshots written before there were notes or bookmarks
+    #[serde(default)]
+    notes:      BTreeMap<usize, Value>,
+    #[serde(default)]
+    bookmarks:  BTreeMap<usize, Value>,
+    // and before attachments were logged
+    #[serde(default)]
+    attachments: BTreeMap<usize, Value>,
+}
+
+// The entries of one kind as the log has them up to some line
+pub(crate) struct Projection<T> {
+    pub(crate) entries: HashMap<usize, T>,
+    // one more than any id ever logged, deleted ones included
+    pub(crate) next_id: usize,
+}
+
+impl<T: Replayable> Projection<T> {
+    fn from_snapshot(snapshot: &Snapshot, recorded: &BTreeMap<usize, Value>) -> serde_json::Result<Projection<T>> {
+        let next_id = snapshot.next_ids.get(T::kind()).copied().unwrap_or(0);
+        let mut projection = Projection { entries: HashMap::new(), next_id };
+        for (id, entry) in recorded {
+            projection.entries.insert(*id, T::replayed(entry)?);
+            projection.next_id = projection.next_id.max(id + 1);
+        }
+        return Ok(projection);
+    }
+
+    fn apply(&mut self, line: &Line) -> serde_json::Result<()> {
+        match &line.entry {
+            Some(entry) => {
+                self.entries.insert(line.id, T::replayed(entry)?);
+            }
+            None        => {
+                self.entries.remove(&line.id);
+            }
+        }
+        self.next_id = self.next_id.max(line.id + 1);
+        return Ok(());
+    }
+}
+
+// the journals, tasks, habits, notes, bookmarks and attachments the log holds
+pub(crate) struct Replayed {
+    pub(crate) journals:    Projection<Journal>,
+    pub(crate) tasks:       Projection<Task>,
+    pub(crate) habits:      Projection<Habit>,
+    pub(crate) notes:       Projection<Note>,
+    pub(crate) bookmarks:   Projection<Bookmark>,
+    pub(crate) attachments: Projection<Attachment>,
+}
+
+impl Replayed {
+    fn apply(&mut self, line: &Line) -> serde_json::Result<()> {
+        return match line.kind.as_str() {
+            Journal::KIND   => self.journals.apply(line),
+            Task::KIND      => self.tasks.apply(line),
+            Note::KIND      => self.notes.apply(line),
+            Bookmark::KIND  => self.bookmarks.apply(line),
+            Habit::KIND     => self.habits.apply(line),
+            attachments::KIND => self.attachments.apply(line),
+            kind            => Err(serde::de::Error::custom(format!("unknown kind {}", kind))),
+        };
+    }
+}
+
+fn broken(path: &Path, err: impl std::fmt::Display) -> io::Error {
+    return io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), err));
+}
+
+// every complete line of the log, oldest first; a crash can leave the last
+// one half written, which is dropped
+fn lines(dir: &Path) -> io::Result<Vec<Line>> {
+    let path = dir.join(LOG);
+    let file = match File::open(&path) {
+        Ok(file)                                            => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound   => return Ok(Vec::new()),
+        Err(err)                                            => return Err(err),
+    };
+    let texts: Vec<String> = BufReader::new(file).lines().collect::<io::Result<_>>()?;
+    let mut lines = Vec::new();
+    for (number, text) in texts.iter().enumerate() {
+        match serde_json::from_str(text) {
+            Ok(line)                                => lines.push(line),
+            Err(_) if number + 1 == texts.len()     => println!("Dropping the half written last line of {}", path.display()),
+            Err(err)                                => return Err(broken(&path, format!("line {}, {}", number + 1, err))),
+        }
+    }
+    return Ok(lines);
+}
+
+struct Writing {
+    file:       File,
+    // of the last line appended
+    sequence:   u64,
+    // the ETags logged last, so entries that were touched but not changed
+    // are left out
+    etags:      HashMap<(&'static str, usize), String>,
+}
+
+impl Writing {
+    // the next line, newline included
+    fn line(&mut self, kind: &str, id: usize, entry: Option<Value>) -> String {
+        self.sequence += 1;
+        let line = Line { sequence: self.sequence, at: Utc::now(), kind: String::from(kind), id, entry };
+        let mut text = serde_json::to_string(&line).unwrap_or_default();
+        text.push('\n');
+        return text;
+    }
+
+    // in one write, so a crash leaves at most the last line half written
+    fn append(&mut self, lines: &str) {
+        if let Err(err) = self.file.write_all(lines.as_bytes()) {
+            println!("Appending to the event log failed: {}", err);
+        }
+    }
+}
+
+pub(crate) struct EventLog {
+    dir:        PathBuf,
+    writing:    Mutex<Writing>,
+}
+
+impl EventLog {
+    // the log in the directory, created when there is none, and the entries
+    // it holds, from the snapshot on
+    pub(crate) fn open(dir: &Path) -> io::Result<(Arc<EventLog>, Replayed)> {
+        std::fs::create_dir_all(dir)?;
+        let snapshot_path = dir.join(SNAPSHOT);
+        let snapshot: Snapshot = match std::fs::read(&snapshot_path) {
+            Ok(bytes)                                           => serde_json::from_slice(&bytes).map_err(|err| broken(&snapshot_path, err))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound   => Snapshot::default(),
+            Err(err)                                            => return Err(err),
+        };
+        let from_snapshot = || -> serde_json::Result<Replayed> {
+            return Ok(Replayed {
+                journals:   Projection::from_snapshot(&snapshot, &snapshot.journals)?,
+                tasks:      Projection::from_snapshot(&snapshot, &snapshot.tasks)?,
+                habits:     Projection::from_snapshot(&snapshot, &snapshot.habits)?,
+                notes:      Projection::from_snapshot(&snapshot, &snapshot.notes)?,
+                bookmarks:  Projection::from_snapshot(&snapshot, &snapshot.bookmarks)?,
+                attachments: Projection::from_snapshot(&snapshot, &snapshot.attachments)?,
+            });
+        };
+        let mut replayed = from_snapshot().map_err(|err| broken(&snapshot_path, err))?;
+        let mut sequence = snapshot.sequence;
+        for line in lines(dir)? {
+            if line.sequence > snapshot.sequence {
+                replayed.apply(&line).map_err(|err| broken(&dir.join(LOG), format!("line {}, {}", line.sequence, err)))?;
+            }
+            sequence = sequence.max(line.sequence);
+        }
+
+        let mut etags = HashMap::new();
+        etags.extend(replayed.journals.entries.iter().map(|(id, entry)| ((Journal::KIND, *id), entry.get_etag())));
+        etags.extend(replayed.tasks.entries.iter().map(|(id, entry)| ((Task::KIND, *id), entry.get_etag())));
+        etags.extend(replayed.habits.entries.iter().map(|(id, entry)| ((Habit::KIND, *id), entry.get_etag())));
+        etags.extend(replayed.notes.entries.iter().map(|(id, entry)| ((Note::KIND, *id), entry.get_etag())));
+        etags.extend(replayed.bookmarks.entries.iter().map(|(id, entry)| ((Bookmark::KIND, *id), entry.get_etag())));
+        let file = OpenOptions::new().create(true).append(true).open(dir.join(LOG))?;
+        let log = EventLog { dir: dir.to_path_buf(), writing: Mutex::new(Writing { file, sequence, etags }) };
+        return Ok((Arc::new(log), replayed));
+    }
+
+    // the entries of the kind as they were at the time, replayed from the
+    // start of the log, and the sequence of the last line before it
+    pub(crate) fn as_of<T: Recorded>(&self, at: DateTime<Utc>) -> io::Result<(HashMap<usize, T>, u64)> {
+        let mut projection = Projection { entries: HashMap::new(), next_id: 0 };
+        let mut sequence = 0;
+        for line in lines(&self.dir)?.into_iter().filter(|line| line.at <= at) {
+            sequence = line.sequence;
+            if line.kind == T::KIND {
+                projection.apply(&line).map_err(|err| broken(&self.dir.join(LOG), format!("line {}, {}", line.sequence, err)))?;
+            }
+        }
+        return Ok((projection.entries, sequence));
+    }
+
+    // logs every change of the collections from here on
+    pub(crate) fn watch(
+        self:       &Arc<Self>,
+        journals:   &Collection<Journal>,
+        tasks:      &Collection<Task>,
+        habits:     &Collection<Habit>,
+        notes:      &Collection<Note>,
+        bookmarks:  &Collection<Bookmark>,
+    ) {
+        journals.observe(self.clone());
+        tasks.observe(self.clone());
+        habits.observe(self.clone());
+        notes.observe(self.clone());
+        bookmarks.observe(self.clone());
+    }
+
+    // logs the attachment as it is now, None once detached; called with the
+    // attachments locked, so the lines are in the order of the changes
+    pub(crate) fn attached(&self, id: usize, attachment: Option<&Attachment>) {
+        let mut writing = self.writing.lock().unwrap();
+        let line = writing.line(attachments::KIND, id, attachment.and_then(|attachment| serde_json::to_value(attachment).ok()));
+        writing.append(&line);
+    }
+
+    // Writes the collections as they are now. Appending waits meanwhile, so
+    // the snapshot has every line up to its sequence; changes applied but
+    // not yet appended are in it too, and replaying them again does no harm.
+    // The attachments are locked first, as when they are attached.
+    fn snapshot(
+        &self,
+        journals:   &Entries<Journal>,
+        tasks:      &Entries<Task>,
+        habits:     &Entries<Habit>,
+        notes:      &Entries<Note>,
+        bookmarks:  &Entries<Bookmark>,
+        attachments: &Attachments,
+    ) -> io::Result<()> {
+        return attachments.recorded(|attached, next_attachment| {
+            let writing = self.writing.lock().unwrap();
+            let snapshot = Snapshot {
+                sequence:   writing.sequence,
+                next_ids:   HashMap::from([
+                    (String::from(Journal::KIND), journals.next_id()),
+                    (String::from(Task::KIND), tasks.next_id()),
+                    (String::from(Habit::KIND), habits.next_id()),
+                    (String::from(Note::KIND), notes.next_id()),
+                    (String::from(Bookmark::KIND), bookmarks.next_id()),
+                    (String::from(attachments::KIND), next_attachment),
+                ]),
+                journals:   recorded(journals),
+                tasks:      recorded(tasks),
+                habits:     recorded(habits),
+                notes:      recorded(notes),
+                bookmarks:  recorded(bookmarks),
+                attachments: attached,
+            };
+            // renamed into place, so a crash never leaves half a snapshot
+            let written = self.dir.join(format!("{}.new", SNAPSHOT));
+            std::fs::write(&written, serde_json::to_vec(&snapshot)?)?;
+            return std::fs::rename(written, self.dir.join(SNAPSHOT));
+        });
+    }
+}
+
+impl<T: Recorded> Observer<T> for EventLog {
+    fn changed(&self, entries: &Entries<T>, ids: &BTreeSet<usize>) {
+        let mut writing = self.writing.lock().unwrap();
+        let mut appended = String::new();
+        for id in ids {
+            let entry = entries.get(id);
+            let etag = entry.as_ref().map(|entry| entry.get_etag());
+            if writing.etags.get(&(T::KIND, *id)) == etag.as_ref() {
+                continue;
+            }
+            match etag {
+                Some(etag)  => writing.etags.insert((T::KIND, *id), etag),
+                None        => writing.etags.remove(&(T::KIND, *id)),
+            };
+            let line = writing.line(T::KIND, *id, entry.map(|entry| record(&*entry)));
+            appended.push_str(&line);
+        }
+        writing.append(&appended);
+    }
+}
+
+// the snapshot job
+pub(crate) async fn snapshot(state: web::Data<State>) -> Result<(), String> {
+    let Some(log) = &state.event_log else {
+        return Ok(());
+    };
+    return log.snapshot(&state.journals, &state.tasks, &state.habits, &state.notes, &state.bookmarks, &state.attachments)
+        .map_err(|err| err.to_string());
+}
@@ -0,0 +1,86 @@
+// The event bus: the collection writers of every space publish what their
+// changes did (task.created, journal.updated, habit.deleted, ...) on it, in
+// the order they were applied, and whatever reacts to writes subscribes
+// instead of being called from the handlers: webhook deliveries, the audit
+// log and GET /events. The search indexes, change log and render cache stay
+// observers of the collections, they have to be up to date before a write
+// is answered.
+use actix_web::web::Bytes;
+use actix_web::{web, HttpResponse, Responder};
+use futures_util::stream;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::{broadcast, mpsc};
+
+use crate::notify::Event;
+use crate::state::State;
+use crate::workspace::Space;
+
+// how far a subscriber may fall behind before it misses events
+const CAPACITY: usize = 1024;
+
+#[derive(Clone)]
+pub(crate) struct Bus {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Bus {
+    // publishes what the collections send to the queue, in the background
+    pub(crate) fn start(mut queue: mpsc::UnboundedReceiver<Event>) -> Bus {
+        let (sender, _) = broadcast::channel(CAPACITY);
+        let publisher = sender.clone();
+        tokio::spawn(async move {
+            while let Some(event) = queue.recv().await {
+                // nobody may be listening
+                let _ = publisher.send(event);
+            }
+        });
+        return Bus { sender };
+    }
+
+    // the events published from now on
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<Event> {
+        return self.sender.subscribe();
+    }
+
+    // calls `consume` with every event published from now on, in the
+    // background; `name` is who missed events when it falls behind
+    pub(crate) fn consume(&self, name: &'static str, mut consume: impl FnMut(Event) + Send + 'static) {
+        let mut events = self.subscribe();
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event)                   => consume(event),
+                    Err(RecvError::Lagged(n))   => println!("The {} missed {} events", name, n),
+                    Err(RecvError::Closed)      => return,
+                }
+            }
+        });
+    }
+}
+
+fn message(event: &Event) -> Bytes {
+    let data = serde_json::to_string(event).unwrap_or_default();
+    return Bytes::from(format!("event: {}\ndata: {}\n\n", event.name(), data));
+}
+
+// Server-sent events of the space's writes from now on, those the request
+// may see. A client that falls behind is cut off, to reconnect and catch up
+// with GET /changes.
+pub(crate) async fn stream(space: Space, state: web::Data<State>) -> impl Responder {
+    let events = state.bus.subscribe();
+    let messages = stream::unfold((events, space), |(mut events, space)| async move {
+        loop {
+            match events.recv().await {
+                Ok(event) if space.sees(&event) => {
+                    return Some((Ok::<Bytes, actix_web::Error>(message(&event)), (events, space)));
+                }
+                Ok(_)   => continue,
+                Err(_)  => return None,
+            }
+        }
+    });
+    return HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(messages);
+}
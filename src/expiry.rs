@@ -0,0 +1,27 @@
+// Expiring entries: journals and tasks with an `expires_at` are left out of
+// listings once it has passed, and deleted by the sweeper soon after, as
+// if a client had deleted them, delete hooks included
+use actix_web::web;
+use std::time::Duration;
+
+use crate::state::State;
+use crate::workspace::Space;
+
+pub(crate) const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+// deletes expired entries in every space, a job of the scheduler
+pub(crate) async fn sweep(state: web::Data<State>) -> Result<(), String> {
+    // left to the first sweep after maintenance, listings hide them meanwhile
+    if state.maintenance().is_some() {
+        return Ok(());
+    }
+    let mut removed = 0;
+    for space in Space::all(&state) {
+        removed += space.journals.rm_expired(&state.hooks).await.map_err(|failed| failed.to_string())?;
+        removed += space.tasks.rm_expired(&state.hooks).await.map_err(|failed| failed.to_string())?;
+    }
+    if removed > 0 {
+        println!("Removed {} expired entries", removed);
+    }
+    return Ok(());
+}
@@ -0,0 +1,126 @@
+// Markdown export of journals, either as one document or a zip of files;
+// encrypted journals have no Markdown and are left out. Single journals are
+// also served as Markdown or their raw data, in byte ranges if asked for.
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::Deserialize;
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+use crate::handlers::IdPath;
+use crate::models::Journal;
+use crate::ranges;
+use crate::state::State;
+use crate::workspace::{Level, Space};
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    format: Option<String>,
+}
+
+pub fn journal_markdown(journal: &Journal) -> String {
+    let coordinates = journal.lat.zip(journal.lon).map(|(lat, lon)| format!("{}, {}", lat, lon));
+    let location = match (&journal.place, coordinates) {
+        (Some(place), Some(coordinates))    => format!("_{} ({})_\n\n", place, coordinates),
+        (Some(place), None)                 => format!("_{}_\n\n", place),
+        (None, Some(coordinates))           => format!("_{}_\n\n", coordinates),
+        (None, None)                        => String::new(),
+    };
+    format!("# {}\n\n{}{}\n", journal.title, location, journal.data)
+}
+
+// "12-my-first-entry.md"
+fn file_name(id: usize, journal: &Journal) -> String {
+    let slug: String = journal.title
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-");
+    if slug.is_empty() {
+        return format!("{}.md", id);
+    }
+    return format!("{}-{}.md", id, slug);
+}
+
+fn zip_journals(journals: &[(usize, Journal)]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default();
+    for (id, journal) in journals.iter().filter(|(_, journal)| !journal.encrypted) {
+        zip.start_file(file_name(*id, journal), options)?;
+        zip.write_all(journal_markdown(journal).as_bytes())?;
+    }
+    return Ok(zip.finish()?.into_inner());
+}
+
+pub async fn export_journals(
+    query: web::Query<ExportParams>,
+    space: Space,
+) -> impl Responder {
+    let entries: Vec<(usize, Journal)> = space.visible(&space.journals).into_iter()
+        .filter_map(|id| Some((id, space.journals.get(&id)?.clone())))
+        .collect();
+
+    match query.format.as_deref() {
+        None | Some("md") => {
+            let document = entries.iter()
+                .filter(|(_, journal)| !journal.encrypted)
+                .map(|(_, journal)| journal_markdown(journal))
+                .collect::<Vec<String>>()
+                .join("\n---\n\n");
+            return HttpResponse::Ok()
+                .content_type("text/markdown; charset=utf-8")
+                .body(document);
+        }
+        Some("zip") => match zip_journals(&entries) {
+            Ok(archive) => HttpResponse::Ok()
+                .content_type("application/zip")
+                .append_header(("Content-Disposition", "attachment; filename=\"journals.zip\""))
+                .body(archive),
+            Err(_) => HttpResponse::InternalServerError().body("Error during archiving"),
+        },
+        Some(_) => HttpResponse::BadRequest().body("Unknown export format"),
+    }
+}
+
+pub async fn export_journal(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    let id = path.into_inner();
+    match state.journals.get(&id) {
+        Some(journal) if journal.encrypted => HttpResponse::Conflict().body("Encrypted journals are not rendered"),
+        Some(journal) => {
+            let mut response = HttpResponse::Ok();
+            response.content_type("text/markdown; charset=utf-8").append_header(("ETag", journal.etag.as_str()));
+            ranges::respond(&request, response, &journal.etag, journal_markdown(&journal).into())
+        }
+        None => HttpResponse::NotFound().body("Not found"),
+    }
+}
+
+// the data of a journal as it was sent, ciphertexts included
+pub(crate) async fn journal_data(
+    path: web::Path<IdPath>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(Some(path.id), Level::Read) {
+        return rejection.into();
+    }
+    let Some(journal) = space.journals.get(&path.id) else {
+        return HttpResponse::NotFound().body("Not found");
+    };
+    let content_type = match journal.encrypted {
+        true    => "application/octet-stream",
+        false   => "text/plain; charset=utf-8",
+    };
+    let mut response = HttpResponse::Ok();
+    response.content_type(content_type).append_header(("ETag", journal.etag.as_str()));
+    return ranges::respond(&request, response, &journal.etag, journal.data.clone().into());
+}
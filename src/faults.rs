@@ -0,0 +1,104 @@
+// Fault injection, for testing clients and their retry logic against a
+// misbehaving server: built with the `faults` feature and JOURNAL_FAULTS
+// set, e.g. "latency=10%:250ms,contention=5%:100ms,errors=2%", that share of
+// requests is delayed, has the server's task and journal writers held up
+// while it runs, or fails with a 500 as if storage had. Faults are drawn
+// independently, so a request may get several; each shows in the
+// X-Injected-Fault header. The /admin routes are spared.
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use rand::Rng;
+use std::time::Duration;
+
+use crate::state::State;
+
+#[derive(Debug, Clone, Copy)]
+struct Fault {
+    // of requests, 0 to 100
+    percent:    f64,
+    duration:   Duration,
+}
+
+impl Fault {
+    fn parse(value: &str) -> Option<Fault> {
+        let (percent, duration) = value.split_once(':').unwrap_or((value, "0ms"));
+        let percent: f64 = percent.trim().trim_end_matches('%').parse().ok()?;
+        let duration = duration.trim().trim_end_matches("ms").parse().ok()?;
+        if !(0.0..=100.0).contains(&percent) {
+            return None;
+        }
+        return Some(Fault { percent, duration: Duration::from_millis(duration) });
+    }
+
+    fn hits(&self) -> bool {
+        return rand::thread_rng().gen_bool(self.percent / 100.0);
+    }
+}
+
+// Read from the environment at startup, see Config
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Faults {
+    latency:    Option<Fault>,
+    contention: Option<Fault>,
+    errors:     Option<Fault>,
+}
+
+impl Faults {
+    pub(crate) fn from_env() -> Option<Faults> {
+        let value = std::env::var("JOURNAL_FAULTS").ok().filter(|value| !value.trim().is_empty())?;
+        let mut faults = Faults::default();
+        for part in value.split(',') {
+            let parsed = part.split_once('=').and_then(|(kind, fault)| Some((kind.trim(), Fault::parse(fault)?)));
+            match parsed {
+                Some(("latency", fault))    => faults.latency = Some(fault),
+                Some(("contention", fault)) => faults.contention = Some(fault),
+                Some(("errors", fault))     => faults.errors = Some(fault),
+                _                           => println!("Ignoring {:?} in JOURNAL_FAULTS", part.trim()),
+            }
+        }
+        println!("Injecting faults: {:?}", faults);
+        return Some(faults);
+    }
+}
+
+pub async fn inject(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let state = request.app_data::<web::Data<State>>().cloned();
+    let faults = state.as_ref().and_then(|state| state.config.faults.clone());
+    let (Some(state), Some(faults)) = (state, faults.filter(|_| !request.path().starts_with("/admin/"))) else {
+        return Ok(next.call(request).await?.map_into_left_body());
+    };
+
+    let mut injected = Vec::new();
+    if let Some(latency) = faults.latency.filter(Fault::hits) {
+        tokio::time::sleep(latency.duration).await;
+        injected.push("latency");
+    }
+    if let Some(contention) = faults.contention.filter(Fault::hits) {
+        // a change that holds each writer, queued ahead of the request's own
+        let (tasks, journals) = (state.clone(), state.clone());
+        actix_web::rt::spawn(async move { tasks.tasks.change(move |_| std::thread::sleep(contention.duration)).await });
+        actix_web::rt::spawn(async move { journals.journals.change(move |_| std::thread::sleep(contention.duration)).await });
+        tokio::task::yield_now().await;
+        injected.push("contention");
+    }
+    let header = HeaderName::from_static("x-injected-fault");
+    if faults.errors.is_some_and(|errors| errors.hits()) {
+        injected.push("error");
+        let failed = HttpResponse::InternalServerError()
+            .insert_header((header, injected.join(", ")))
+            .body("Storage failed (injected fault)");
+        return Ok(request.into_response(failed).map_into_right_body());
+    }
+
+    let mut response = next.call(request).await?;
+    if !injected.is_empty() {
+        response.headers_mut().insert(header, HeaderValue::from_str(&injected.join(", "))?);
+    }
+    return Ok(response.map_into_left_body());
+}
@@ -0,0 +1,92 @@
+// Atom feed of recent journal entries
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use atom_syndication::{Content, Entry, Feed, Link, Text};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::render::journal_html;
+use crate::models::{Journal, Resource};
+use crate::state::State;
+
+const DEFAULT_FEED_LENGTH: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct FeedParams {
+    token: Option<String>,
+    limit: Option<usize>,
+}
+
+// a scheduled journal counts from when it was published
+fn updated(journal: &Journal) -> DateTime<Utc> {
+    return journal.publish_at.map_or(journal.updated_at, |publish_at| publish_at.max(journal.updated_at));
+}
+
+fn feed_entry(state: &State, base: &str, id: usize, journal: &Journal) -> Entry {
+    let link = Link {
+        href: format!("{}/journals/{}", base, id),
+        ..Default::default()
+    };
+    // encrypted entries only show up with their title
+    let content = (!journal.encrypted).then(|| Content {
+        value:          Some(journal_html(state, id, journal).to_string()),
+        content_type:   Some(String::from("html")),
+        ..Default::default()
+    });
+    Entry {
+        title:      Text::plain(journal.title.clone()),
+        id:         link.href.clone(),
+        updated:    updated(journal).into(),
+        published:  Some(journal.publish_at.unwrap_or(journal.created_at).into()),
+        links:      vec![link],
+        content,
+        ..Default::default()
+    }
+}
+
+pub async fn journal_feed(
+    query: web::Query<FeedParams>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Some(feed_token) = &state.config.feed_token {
+        if query.token.as_ref() != Some(feed_token) {
+            return HttpResponse::Unauthorized().body("Bad feed token");
+        }
+    }
+
+    // the Host header is only a guess behind a proxy
+    let base = match &state.config.base_url {
+        Some(base_url)  => base_url.clone(),
+        None            => {
+            let info = request.connection_info();
+            format!("{}://{}", info.scheme(), info.host())
+        }
+    };
+
+    // scheduled journals come in once published, expired ones leave before the sweeper runs
+    let now = Utc::now();
+    let mut recent: Vec<(usize, Journal)> = state.journals.snapshot();
+    recent.retain(|(_, journal)| !journal.draft(now) && !journal.expired(now));
+    recent.sort_by_key(|(_, journal)| std::cmp::Reverse(updated(journal)));
+    recent.truncate(query.limit.unwrap_or(DEFAULT_FEED_LENGTH));
+
+    let updated = recent.first().map_or(now, |(_, journal)| updated(journal));
+    let feed = Feed {
+        title:      Text::plain("Journals"),
+        id:         format!("{}/journals", base),
+        updated:    updated.into(),
+        links:      vec![Link {
+            href:   format!("{}/journals/feed.atom", base),
+            rel:    String::from("self"),
+            ..Default::default()
+        }],
+        entries:    recent.iter()
+            .map(|(id, journal)| feed_entry(&state, &base, *id, journal))
+            .collect(),
+        ..Default::default()
+    };
+
+    return HttpResponse::Ok()
+        .content_type("application/atom+xml")
+        .body(feed.to_string());
+}
@@ -0,0 +1,143 @@
+// Feature flags: experimental behavior that operators switch on at runtime,
+// for every client or a share of them to roll it out gradually. A flag is
+// on for a percentage of clients, told apart as in /admin/usage; a client
+// stays on the same side of it as long as the percentage does not shrink
+// below it. JOURNAL_FLAGS sets them at startup, e.g. "json_api=25,crdt_sync",
+// and PATCH /admin/flags while running; they are not kept across restarts.
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::RwLock;
+
+use crate::admin::require_admin;
+use crate::analytics;
+use crate::state::State;
+
+// JSON:API documents unless a client asks for another format
+pub(crate) const JSON_API: &str = "json_api";
+// POST /sync/merge
+#[cfg(feature = "crdt")]
+pub(crate) const CRDT_SYNC: &str = "crdt_sync";
+
+struct Flag {
+    description:    &'static str,
+    // of clients it is on for, 0 to 100
+    percent:        u8,
+}
+
+pub(crate) struct Flags {
+    flags:  RwLock<BTreeMap<&'static str, Flag>>,
+}
+
+#[derive(Serialize)]
+struct Shown {
+    description:    &'static str,
+    percent:        u8,
+}
+
+// a flag's percentage from the environment or a PATCH: on, off or a number
+fn percent(value: &Value) -> Option<u8> {
+    return match value {
+        Value::Bool(on) => Some(if *on { 100 } else { 0 }),
+        _               => value.as_u64().filter(|percent| *percent <= 100).map(|percent| percent as u8),
+    };
+}
+
+// JOURNAL_FLAGS, names alone are on for everyone; JOURNAL_JSON_API=true
+// stays the same as json_api
+pub(crate) fn from_env() -> HashMap<String, u8> {
+    let mut rollouts = HashMap::new();
+    if std::env::var("JOURNAL_JSON_API").is_ok_and(|on| on == "1" || on == "true") {
+        rollouts.insert(String::from(JSON_API), 100);
+    }
+    let flags = std::env::var("JOURNAL_FLAGS").unwrap_or_default();
+    for flag in flags.split(',').map(str::trim).filter(|flag| !flag.is_empty()) {
+        let (name, value) = flag.split_once('=').unwrap_or((flag, "100"));
+        match serde_json::from_str(value.trim()).ok().as_ref().and_then(percent) {
+            Some(percent)   => rollouts.insert(String::from(name.trim()), percent),
+            None            => {
+                println!("Ignoring {:?} in JOURNAL_FLAGS, flags are true, false or a percentage", flag);
+                continue;
+            }
+        };
+    }
+    return rollouts;
+}
+
+impl Flags {
+    // every flag there is, with its default
+    pub(crate) fn new(rollouts: &HashMap<String, u8>) -> Flags {
+        let mut flags = BTreeMap::new();
+        flags.insert(JSON_API, Flag { description: "JSON:API documents unless a client asks for another format", percent: 0 });
+        // on since before it was a flag
+        #[cfg(feature = "crdt")]
+        flags.insert(CRDT_SYNC, Flag { description: "merging offline edits with POST /sync/merge", percent: 100 });
+        for (name, percent) in rollouts {
+            match flags.get_mut(name.as_str()) {
+                Some(flag)  => flag.percent = *percent,
+                None        => println!("Ignoring JOURNAL_FLAGS, there is no flag {}", name),
+            }
+        }
+        return Flags { flags: RwLock::new(flags) };
+    }
+
+    // whether the flag is on for the client sending the request
+    pub(crate) fn on(&self, name: &str, request: &HttpRequest) -> bool {
+        let percent = self.flags.read().unwrap().get(name).map_or(0, |flag| flag.percent);
+        return match percent {
+            0       => false,
+            100     => true,
+            percent => {
+                let hash = Sha256::digest(format!("{}:{}", name, analytics::client(request)));
+                u16::from_be_bytes([hash[0], hash[1]]) % 100 < u16::from(percent)
+            }
+        };
+    }
+
+    fn shown(&self) -> BTreeMap<&'static str, Shown> {
+        return self.flags.read().unwrap().iter()
+            .map(|(name, flag)| (*name, Shown { description: flag.description, percent: flag.percent }))
+            .collect();
+    }
+}
+
+pub(crate) async fn show(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    return HttpResponse::Ok().json(state.flags.shown());
+}
+
+// sets the flags named in the body, all of them or none
+pub(crate) async fn update(payload: Bytes, state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    let changes: Map<String, Value> = match serde_json::from_slice(&payload) {
+        Ok(changes) => changes,
+        Err(_)      => return HttpResponse::BadRequest().body("Expected an object of flags"),
+    };
+    {
+        let mut flags = state.flags.flags.write().unwrap();
+        let mut checked = Vec::new();
+        for (name, value) in &changes {
+            if !flags.contains_key(name.as_str()) {
+                return HttpResponse::BadRequest().body(format!("There is no flag {}", name));
+            }
+            let Some(percent) = percent(value) else {
+                return HttpResponse::BadRequest().body(format!("{} is true, false or a percentage", name));
+            };
+            checked.push((name.as_str(), percent));
+        }
+        for (name, percent) in checked {
+            if let Some(flag) = flags.get_mut(name) {
+                println!("Flag {} is now on for {}% of clients", name, percent);
+                flag.percent = percent;
+            }
+        }
+    }
+    return HttpResponse::Ok().json(state.flags.shown());
+}
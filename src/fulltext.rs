@@ -0,0 +1,149 @@
+// Tantivy indexes of the words in the journals and tasks of a space, kept up
+// to date by the collection writers. Searches ask them which entries can
+// match and rank only those, instead of reading every entry.
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use tantivy::collector::DocSetCollector;
+use tantivy::query::{BooleanQuery, Occur, Query, TermQuery};
+use tantivy::schema::{Field, IndexRecordOption, Schema, TextFieldIndexing, TextOptions, Value, FAST, INDEXED, STORED};
+use tantivy::tokenizer::{LowerCaser, SimpleTokenizer, TextAnalyzer};
+use tantivy::{Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+use crate::models::{Journal, Resource, Task};
+use crate::search::{closeness, Searchable};
+use crate::store::{Collection, Entries, Observer};
+
+// splits and lowercases the same way search::words does, so the index holds
+// exactly the words a search compares with
+const TOKENIZER: &str = "words";
+// the least memory tantivy lets an indexing thread have
+const WRITER_BUDGET: usize = 15_000_000;
+
+pub(crate) struct TextIndex {
+    writer: Mutex<IndexWriter>,
+    reader: IndexReader,
+    id:     Field,
+    title:  Field,
+    body:   Field,
+}
+
+impl TextIndex {
+    fn new() -> tantivy::Result<TextIndex> {
+        let mut schema = Schema::builder();
+        let words = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(TOKENIZER)
+                .set_index_option(IndexRecordOption::Basic),
+        );
+        let id = schema.add_u64_field("id", INDEXED | STORED | FAST);
+        let title = schema.add_text_field("title", words.clone());
+        let body = schema.add_text_field("body", words);
+        let index = Index::create_in_ram(schema.build());
+        index.tokenizers().register(
+            TOKENIZER,
+            TextAnalyzer::builder(SimpleTokenizer::default()).filter(LowerCaser).build(),
+        );
+        // a single indexing thread with the smallest budget, every change is
+        // committed on its own anyway
+        let writer = index.writer_with_num_threads(1, WRITER_BUDGET)?;
+        let reader = index.reader_builder().reload_policy(ReloadPolicy::Manual).try_into()?;
+        return Ok(TextIndex { writer: Mutex::new(writer), reader, id, title, body });
+    }
+
+    fn update<T: Searchable>(&self, entries: &Entries<T>, ids: &BTreeSet<usize>) -> tantivy::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        for id in ids {
+            writer.delete_term(Term::from_field_u64(self.id, *id as u64));
+            let Some(entry) = entries.get(id) else { continue };
+            let (title, body) = entry.searchable();
+            let mut document = TantivyDocument::default();
+            document.add_u64(self.id, *id as u64);
+            document.add_text(self.title, title);
+            if let Some(body) = body {
+                document.add_text(self.body, body);
+            }
+            writer.add_document(document)?;
+        }
+        writer.commit()?;
+        return self.reader.reload();
+    }
+
+    // the indexed words of `field` matching the term as search::closeness
+    // decides it
+    fn words(&self, field: Field, term: &str, fuzzy: bool) -> tantivy::Result<Vec<Term>> {
+        let mut words = BTreeSet::new();
+        for segment in self.reader.searcher().segment_readers() {
+            let inverted = segment.inverted_index(field)?;
+            let mut stream = inverted.terms().stream()?;
+            while stream.advance() {
+                if let Ok(word) = std::str::from_utf8(stream.key()) {
+                    if closeness(word, term, fuzzy).is_some() {
+                        words.insert(String::from(word));
+                    }
+                }
+            }
+        }
+        return Ok(words.into_iter().map(|word| Term::from_field_text(field, &word)).collect());
+    }
+
+    // the ids of the entries with a word matching every term, in their title
+    // or their body
+    pub(crate) fn candidates(&self, terms: &[String], fuzzy: bool) -> tantivy::Result<BTreeSet<usize>> {
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        for term in terms {
+            let mut words = self.words(self.title, term, fuzzy)?;
+            words.extend(self.words(self.body, term, fuzzy)?);
+            if words.is_empty() {
+                return Ok(BTreeSet::new());
+            }
+            let any: Vec<Box<dyn Query>> = words.into_iter()
+                .map(|word| Box::new(TermQuery::new(word, IndexRecordOption::Basic)) as Box<dyn Query>)
+                .collect();
+            clauses.push((Occur::Must, Box::new(BooleanQuery::union(any))));
+        }
+        let searcher = self.reader.searcher();
+        let mut ids = BTreeSet::new();
+        for address in searcher.search(&BooleanQuery::new(clauses), &DocSetCollector)? {
+            let document: TantivyDocument = searcher.doc(address)?;
+            if let Some(id) = document.get_first(self.id).and_then(|id| id.as_u64()) {
+                ids.insert(id as usize);
+            }
+        }
+        return Ok(ids);
+    }
+}
+
+impl<T: Searchable> Observer<T> for TextIndex {
+    fn changed(&self, entries: &Entries<T>, ids: &BTreeSet<usize>) {
+        if let Err(err) = self.update(entries, ids) {
+            println!("Updating the search index failed: {}", err);
+        }
+    }
+}
+
+// the indexes of one space
+#[derive(Clone)]
+pub(crate) struct Indexes {
+    pub(crate) journals:    Arc<TextIndex>,
+    pub(crate) tasks:       Arc<TextIndex>,
+}
+
+impl Indexes {
+    // indexes what the collections hold and follows their changes
+    pub(crate) fn watch(journals: &Collection<Journal>, tasks: &Collection<Task>) -> Indexes {
+        let indexes = Indexes {
+            journals:   Arc::new(TextIndex::new().expect("cannot create the journal index")),
+            tasks:      Arc::new(TextIndex::new().expect("cannot create the task index")),
+        };
+        journals.observe(indexes.journals.clone());
+        tasks.observe(indexes.tasks.clone());
+        return indexes;
+    }
+
+    pub(crate) fn of<T: Searchable>(&self) -> &TextIndex {
+        if T::KIND == Journal::KIND {
+            return &self.journals;
+        }
+        return &self.tasks;
+    }
+}
@@ -0,0 +1,216 @@
+// GraphQL endpoint exposing journals and tasks in a single query, with their
+// tags and the entries they share a tag with
+use actix_web::{web, HttpResponse, Responder};
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{ComplexObject, Context, EmptyMutation, EmptySubscription, InputObject, Object, OutputType, Schema, SimpleObject};
+
+use crate::models::{Journal, Resource, Task};
+use crate::state::State;
+use crate::store::Collection;
+use crate::workspace::{Level, Space};
+
+pub type JournalSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+struct JournalNode {
+    id:         usize,
+    title:      String,
+    // the ciphertext, for encrypted journals
+    data:       String,
+    encrypted:  bool,
+    tags:       Vec<String>,
+}
+
+#[derive(SimpleObject)]
+#[graphql(complex)]
+struct TaskNode {
+    id:     usize,
+    text:   String,
+    done:   bool,
+    // todo, in_progress, blocked, done or cancelled
+    status: String,
+    tags:   Vec<String>,
+}
+
+#[derive(SimpleObject)]
+#[graphql(concrete(name = "JournalPage", params(JournalNode)))]
+#[graphql(concrete(name = "TaskPage", params(TaskNode)))]
+struct Page<T: OutputType> {
+    page:           usize,
+    total_entries:  usize,
+    total_pages:    usize,
+    entries:        Vec<T>,
+}
+
+#[derive(InputObject, Default)]
+struct JournalFilter {
+    title_contains: Option<String>,
+    data_contains:  Option<String>,
+    tag:            Option<String>,
+}
+
+#[derive(InputObject, Default)]
+struct TaskFilter {
+    done:           Option<bool>,
+    text_contains:  Option<String>,
+    tag:            Option<String>,
+}
+
+// the entries of the collection a listing of the space shows, by id
+fn visible<T: Resource + Clone>(space: &Space, resources: &Collection<T>) -> Vec<(usize, T)> {
+    return space.visible(resources).into_iter()
+        .filter_map(|id| Some((id, resources.get(&id)?.clone())))
+        .collect();
+}
+
+// the visible entries with at least one of the tags
+fn tagged<T: Resource + Clone>(space: &Space, resources: &Collection<T>, tags: &[String]) -> Vec<(usize, T)> {
+    let mut entries = visible(space, resources);
+    entries.retain(|(_, entry)| entry.tags().iter().any(|tag| tags.contains(tag)));
+    return entries;
+}
+
+// same defaults as the REST pagination
+fn paginate<T, N: OutputType>(
+    resources:  &[(usize, T)],
+    keep:       impl Fn(&T) -> bool,
+    to_node:    impl Fn(usize, &T) -> N,
+    page:       Option<usize>,
+    per_page:   Option<usize>,
+) -> Page<N> {
+    let page_num = page.unwrap_or(1).max(1);
+    let per_page = per_page.unwrap_or(5).max(1);
+
+    // resources come ordered by id
+    let kept: Vec<&(usize, T)> = resources.iter()
+        .filter(|(_, item)| keep(item))
+        .collect();
+
+    let total_entries = kept.len();
+    let entries = kept.into_iter()
+        .skip((page_num - 1) * per_page)
+        .take(per_page)
+        .map(|(id, item)| to_node(*id, item))
+        .collect();
+    Page {
+        page: page_num,
+        total_entries,
+        total_pages: total_entries.div_ceil(per_page),
+        entries,
+    }
+}
+
+fn journal_node(id: usize, journal: &Journal) -> JournalNode {
+    JournalNode {
+        id,
+        title:      journal.title.clone(),
+        data:       journal.data.clone(),
+        encrypted:  journal.encrypted,
+        tags:       journal.tags.clone(),
+    }
+}
+
+fn task_node(id: usize, task: &Task) -> TaskNode {
+    TaskNode {
+        id,
+        text:   task.text.clone(),
+        done:   task.done,
+        status: task.status().map_or(String::new(), |status| String::from(status.name())),
+        tags:   task.tags.clone(),
+    }
+}
+
+#[ComplexObject]
+impl JournalNode {
+    // the tasks sharing a tag with it
+    async fn tasks(&self, ctx: &Context<'_>) -> Vec<TaskNode> {
+        let space = ctx.data_unchecked::<Space>();
+        tagged(space, &space.tasks, &self.tags).iter().map(|(id, task)| task_node(*id, task)).collect()
+    }
+}
+
+#[ComplexObject]
+impl TaskNode {
+    // the journals sharing a tag with it
+    async fn journals(&self, ctx: &Context<'_>) -> Vec<JournalNode> {
+        let space = ctx.data_unchecked::<Space>();
+        tagged(space, &space.journals, &self.tags).iter().map(|(id, journal)| journal_node(*id, journal)).collect()
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn journal(&self, ctx: &Context<'_>, id: usize) -> Option<JournalNode> {
+        let space = ctx.data_unchecked::<Space>();
+        space.allow::<Journal>(Some(id), Level::Read).ok()?;
+        space.journals.get(&id).map(|journal| journal_node(id, &journal))
+    }
+
+    async fn journals(
+        &self,
+        ctx:        &Context<'_>,
+        filter:     Option<JournalFilter>,
+        page:       Option<usize>,
+        per_page:   Option<usize>,
+    ) -> Page<JournalNode> {
+        let space = ctx.data_unchecked::<Space>();
+        let journals = visible(space, &space.journals);
+        let filter = filter.unwrap_or_default();
+        let keep = |journal: &Journal| {
+            filter.title_contains.as_ref().is_none_or(|s| journal.title.contains(s.as_str()))
+                // ciphertexts never match a search
+                && filter.data_contains.as_ref().is_none_or(|s| !journal.encrypted && journal.data.contains(s.as_str()))
+                && filter.tag.as_ref().is_none_or(|tag| journal.tags.contains(tag))
+        };
+        paginate(&journals, keep, journal_node, page, per_page)
+    }
+
+    async fn task(&self, ctx: &Context<'_>, id: usize) -> Option<TaskNode> {
+        let space = ctx.data_unchecked::<Space>();
+        space.allow::<Task>(Some(id), Level::Read).ok()?;
+        space.tasks.get(&id).map(|task| task_node(id, &task))
+    }
+
+    async fn tasks(
+        &self,
+        ctx:        &Context<'_>,
+        filter:     Option<TaskFilter>,
+        page:       Option<usize>,
+        per_page:   Option<usize>,
+    ) -> Page<TaskNode> {
+        let space = ctx.data_unchecked::<Space>();
+        let tasks = visible(space, &space.tasks);
+        let filter = filter.unwrap_or_default();
+        let keep = |task: &Task| {
+            filter.done.is_none_or(|done| task.done == done)
+                && filter.text_contains.as_ref().is_none_or(|s| task.text.contains(s.as_str()))
+                && filter.tag.as_ref().is_none_or(|tag| task.tags.contains(tag))
+        };
+        paginate(&tasks, keep, task_node, page, per_page)
+    }
+}
+
+pub fn build_schema(state: web::Data<State>) -> JournalSchema {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .data(state)
+        .finish()
+}
+
+// queries run on the collections of the space, as REST requests do
+pub async fn graphql(
+    schema:     web::Data<JournalSchema>,
+    space:      Space,
+    request:    web::Json<async_graphql::Request>,
+) -> impl Responder {
+    let response = schema.execute(request.into_inner().data(space)).await;
+    HttpResponse::Ok().json(response)
+}
+
+pub async fn graphiql() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(GraphiQLSource::build().endpoint("/graphql").finish())
+}
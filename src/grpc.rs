@@ -0,0 +1,362 @@
+// gRPC service mirroring the REST CRUD operations on the shared State
+use actix_web::http::StatusCode;
+use actix_web::web;
+use serde::Serialize;
+use serde_json::Map;
+use std::net::SocketAddr;
+use tonic::{Request, Response, Status};
+
+use chrono::Utc;
+
+use crate::handlers::{store_new, store_put, Rejection};
+use crate::models::{Etagged, Journal, Resource, Task, Timestamped};
+use crate::state::{Readable, State};
+use crate::store::Collection;
+use crate::workspace::Space;
+
+pub mod proto {
+    tonic::include_proto!("journal");
+}
+
+use proto::journals_server::{Journals, JournalsServer};
+
+impl From<proto::Journal> for Journal {
+    fn from(journal: proto::Journal) -> Self {
+        Journal {
+            title:  journal.title,
+            data:   journal.data,
+            encrypted:  false,
+            tags:       Vec::new(),
+            metadata:   None,
+            mood:       None,
+            energy:     None,
+            sleep_hours: None,
+            lat:        None,
+            lon:        None,
+            place:      None,
+            expires_at: None,
+            publish_at: None,
+            day:        None,
+            word_count: 0,
+            char_count: 0,
+            etag:   String::new(),
+            version: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+}
+
+impl From<&Journal> for proto::Journal {
+    fn from(journal: &Journal) -> Self {
+        proto::Journal {
+            title:  journal.title.clone(),
+            data:   journal.data.clone(),
+        }
+    }
+}
+
+impl From<proto::Task> for Task {
+    fn from(task: proto::Task) -> Self {
+        Task {
+            text:   task.text,
+            done:   task.done,
+            // from done when the task is stored
+            status: None,
+            archived: task.archived,
+            tags:   Vec::new(),
+            etag:   String::new(),
+            version: 0,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            completed_at: None,
+            time_entries: Vec::new(),
+            metadata:   Map::new(),
+            position:   0,
+            due:        None,
+            expires_at: None,
+        }
+    }
+}
+
+impl From<&Task> for proto::Task {
+    fn from(task: &Task) -> Self {
+        proto::Task {
+            text:   task.text.clone(),
+            done:   task.done,
+            archived: task.archived,
+        }
+    }
+}
+
+// the fields a message has, put onto the entry stored at its id so the
+// ones it has none for are kept rather than wiped
+trait Onto<T>: Into<T> {
+    fn onto(self, stored: T) -> Result<T, Status>;
+}
+
+// the title and data of an encrypted journal are ciphertext the client
+// sealed, which a message has no flag for
+impl Onto<Journal> for proto::Journal {
+    fn onto(self, mut stored: Journal) -> Result<Journal, Status> {
+        if stored.encrypted {
+            return Err(Status::failed_precondition("Encrypted journals are put through the REST API only"));
+        }
+        stored.title = self.title;
+        stored.data = self.data;
+        return Ok(stored);
+    }
+}
+
+// the stored status is kept while it agrees with done, as track_changes
+// tells
+impl Onto<Task> for proto::Task {
+    fn onto(self, mut stored: Task) -> Result<Task, Status> {
+        stored.text = self.text;
+        stored.done = self.done;
+        stored.archived = self.archived;
+        return Ok(stored);
+    }
+}
+
+fn journal_entry(id: usize, journal: &Journal) -> proto::JournalEntry {
+    proto::JournalEntry {
+        id:         id as u64,
+        etag:       journal.get_etag(),
+        journal:    Some(journal.into()),
+    }
+}
+
+fn task_entry(id: usize, task: &Task) -> proto::TaskEntry {
+    proto::TaskEntry {
+        id:     id as u64,
+        etag:   task.get_etag(),
+        task:   Some(task.into()),
+    }
+}
+
+// the gRPC status closest to what the REST API answers with
+impl From<Rejection> for Status {
+    fn from(rejection: Rejection) -> Status {
+        let message = rejection.to_string();
+        match rejection.status() {
+            StatusCode::NOT_FOUND               => Status::not_found(message),
+            StatusCode::FORBIDDEN               => Status::permission_denied(message),
+            StatusCode::BAD_REQUEST
+            | StatusCode::UNPROCESSABLE_ENTITY  => Status::invalid_argument(message),
+            StatusCode::CONFLICT
+            | StatusCode::PRECONDITION_FAILED
+            | StatusCode::PRECONDITION_REQUIRED => Status::failed_precondition(message),
+            StatusCode::INSUFFICIENT_STORAGE    => Status::resource_exhausted(message),
+            _                                   => Status::internal(message),
+        }
+    }
+}
+
+pub struct JournalService {
+    state: web::Data<State>,
+}
+
+// (page, total_entries, total_pages, entries), same defaults and entries as
+// the REST listing of the server's own collections
+fn list<T, N>(
+    state:      &State,
+    request:    proto::ListRequest,
+    to_entry:   fn(usize, &T) -> N,
+) -> (u64, u64, u64, Vec<N>) where Space: Readable<T>, T: Resource + Clone {
+    let space = Space::server(state);
+    let resources: &Collection<T> = space.get_hmap();
+    let resources: Vec<(usize, T)> = space.visible(resources).into_iter()
+        .filter_map(|id| Some((id, resources.get(&id)?.clone())))
+        .collect();
+
+    let page_num = request.page.unwrap_or(1).max(1) as usize;
+    let per_page = request.per_page.unwrap_or(5).max(1) as usize;
+
+    let entries = resources.iter()
+        .skip((page_num - 1) * per_page)
+        .take(per_page)
+        .map(|(id, resource)| to_entry(*id, resource))
+        .collect();
+    let total_entries = resources.len();
+    return (
+        page_num as u64,
+        total_entries as u64,
+        total_entries.div_ceil(per_page) as u64,
+        entries,
+    );
+}
+
+fn get<T, N>(
+    state:      &State,
+    id:         u64,
+    to_entry:   fn(usize, &T) -> N,
+) -> Result<Response<N>, Status> where State: Readable<T> {
+    let resources: &Collection<T> = state.get_hmap();
+    match resources.get(&(id as usize)) {
+        Some(resource)  => Ok(Response::new(to_entry(id as usize, &resource))),
+        None            => Err(Status::not_found("Not found")),
+    }
+}
+
+// writes are refused during maintenance, on a replica, where the next sync
+// with the primary would undo them, and when writes have to be signed, as
+// the service takes no signatures a replay could be told apart by
+fn writable(state: &State) -> Result<(), Status> {
+    if let Some(maintenance) = state.maintenance() {
+        return Err(Status::unavailable(maintenance.message()));
+    }
+    if let Some(replica) = &state.config.replica {
+        return Err(Status::failed_precondition(format!("Read-only replica, writes go to {}", replica.primary)));
+    }
+    if state.config.signing_secret.is_some() {
+        return Err(Status::unauthenticated("Writes have to be signed, which only the REST API takes"));
+    }
+    return Ok(());
+}
+
+async fn create<T>(
+    state:      &State,
+    token:      &str,
+    resource:   Option<T>,
+) -> Result<Response<proto::Created>, Status>
+where Space: Readable<T>, T: Etagged + Timestamped + Resource + Serialize + Send + Sync + 'static {
+    writable(state)?;
+    if !state.consume_token(token) {
+        return Err(Status::permission_denied("Bad token"));
+    }
+    let resource = match resource {
+        Some(resource)  => resource,
+        None            => return Err(Status::invalid_argument("Missing resource")),
+    };
+    let id = store_new(state, &Space::server(state), resource).await?;
+    return Ok(Response::new(proto::Created { id: id as u64 }));
+}
+
+// a stale entry the message was put onto fails the If-Match check of
+// store_put, as the entry it is checked against has changed since
+async fn put<T, M>(
+    state:      &State,
+    id:         u64,
+    if_match:   Option<String>,
+    message:    Option<M>,
+) -> Result<Response<proto::Updated>, Status>
+where Space: Readable<T>, T: Etagged + Timestamped + Resource + Serialize + Clone + Send + Sync + 'static, M: Onto<T> {
+    writable(state)?;
+    let message = match message {
+        Some(message)   => message,
+        None            => return Err(Status::invalid_argument("Missing resource")),
+    };
+    let space = Space::server(state);
+    let resources: &Collection<T> = space.get_hmap();
+    let stored = resources.get(&(id as usize)).map(|stored| stored.clone());
+    let resource = match stored {
+        Some(stored)    => message.onto(stored)?,
+        None            => message.into(),
+    };
+    let etag = store_put(state, &space, id as usize, if_match, resource).await?;
+    return Ok(Response::new(proto::Updated { etag }));
+}
+
+async fn delete<T>(state: &State, id: u64) -> Result<Response<proto::Deleted>, Status>
+where State: Readable<T>, T: Resource + Send + Sync + 'static {
+    writable(state)?;
+    let resources: &Collection<T> = state.get_hmap();
+    match resources.rm_resource(id as usize, &state.hooks).await {
+        Ok(Some(_))     => Ok(Response::new(proto::Deleted {})),
+        Ok(None)        => Err(Status::not_found("Not found")),
+        Err(rejection)  => Err(rejection.into()),
+    }
+}
+
+#[tonic::async_trait]
+impl Journals for JournalService {
+    async fn list_journals(
+        &self,
+        request: Request<proto::ListRequest>,
+    ) -> Result<Response<proto::JournalList>, Status> {
+        let (page, total_entries, total_pages, entries) =
+            list(&self.state, request.into_inner(), journal_entry);
+        Ok(Response::new(proto::JournalList { page, total_entries, total_pages, entries }))
+    }
+
+    async fn get_journal(
+        &self,
+        request: Request<proto::IdRequest>,
+    ) -> Result<Response<proto::JournalEntry>, Status> {
+        get(&self.state, request.into_inner().id, journal_entry)
+    }
+
+    async fn create_journal(
+        &self,
+        request: Request<proto::CreateJournalRequest>,
+    ) -> Result<Response<proto::Created>, Status> {
+        let request = request.into_inner();
+        create(&self.state, &request.token, request.journal.map(Journal::from)).await
+    }
+
+    async fn put_journal(
+        &self,
+        request: Request<proto::PutJournalRequest>,
+    ) -> Result<Response<proto::Updated>, Status> {
+        let request = request.into_inner();
+        put::<Journal, _>(&self.state, request.id, request.if_match, request.journal).await
+    }
+
+    async fn delete_journal(
+        &self,
+        request: Request<proto::IdRequest>,
+    ) -> Result<Response<proto::Deleted>, Status> {
+        delete::<Journal>(&self.state, request.into_inner().id).await
+    }
+
+    async fn list_tasks(
+        &self,
+        request: Request<proto::ListRequest>,
+    ) -> Result<Response<proto::TaskList>, Status> {
+        let (page, total_entries, total_pages, entries) =
+            list(&self.state, request.into_inner(), task_entry);
+        Ok(Response::new(proto::TaskList { page, total_entries, total_pages, entries }))
+    }
+
+    async fn get_task(
+        &self,
+        request: Request<proto::IdRequest>,
+    ) -> Result<Response<proto::TaskEntry>, Status> {
+        get(&self.state, request.into_inner().id, task_entry)
+    }
+
+    async fn create_task(
+        &self,
+        request: Request<proto::CreateTaskRequest>,
+    ) -> Result<Response<proto::Created>, Status> {
+        let request = request.into_inner();
+        create(&self.state, &request.token, request.task.map(Task::from)).await
+    }
+
+    async fn put_task(
+        &self,
+        request: Request<proto::PutTaskRequest>,
+    ) -> Result<Response<proto::Updated>, Status> {
+        let request = request.into_inner();
+        put::<Task, _>(&self.state, request.id, request.if_match, request.task).await
+    }
+
+    async fn delete_task(
+        &self,
+        request: Request<proto::IdRequest>,
+    ) -> Result<Response<proto::Deleted>, Status> {
+        delete::<Task>(&self.state, request.into_inner().id).await
+    }
+}
+
+pub async fn serve(state: web::Data<State>, addr: SocketAddr) {
+    println!("gRPC service listening on {}", addr);
+    let result = tonic::transport::Server::builder()
+        .add_service(JournalsServer::new(JournalService { state }))
+        .serve(addr)
+        .await;
+    if let Err(err) = result {
+        println!("gRPC service stopped: {}", err);
+    }
+}
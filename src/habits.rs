@@ -0,0 +1,159 @@
+// Habits: check-ins, one per day, and the streaks they add up to. The
+// habits themselves are plain resources with the generic handlers.
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{Days, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::auth::response_token;
+use crate::etag;
+use crate::handlers::{IdPath, Rejection};
+use crate::models::{Etagged, Habit};
+use crate::notify::{Action, Event};
+use crate::state::State;
+use crate::workspace::{Level, Space};
+
+#[derive(Deserialize, Default)]
+struct Checkin {
+    // today (UTC) when left out
+    date:   Option<NaiveDate>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CheckinPath {
+    id:     usize,
+    date:   NaiveDate,
+}
+
+#[derive(Debug, Serialize)]
+struct Stats {
+    checkins:       usize,
+    // consecutive days up to the last check-in, while that was today or
+    // yesterday, so a streak only breaks once a whole day was missed
+    current_streak: usize,
+    longest_streak: usize,
+    last_checkin:   Option<NaiveDate>,
+}
+
+fn stats(checkins: &BTreeSet<NaiveDate>, today: NaiveDate) -> Stats {
+    let (mut run, mut longest) = (0, 0);
+    let mut last: Option<NaiveDate> = None;
+    for day in checkins {
+        run = if last.and_then(|last| last.succ_opt()) == Some(*day) { run + 1 } else { 1 };
+        longest = longest.max(run);
+        last = Some(*day);
+    }
+    let ongoing = last.and_then(|last| last.succ_opt()).is_some_and(|next| next >= today);
+    return Stats {
+        checkins:       checkins.len(),
+        current_streak: if ongoing { run } else { 0 },
+        longest_streak: longest,
+        last_checkin:   last,
+    };
+}
+
+// Checks in on the day in the body, or today. Checking in twice on the same
+// day changes nothing and answers 200 instead of 201.
+pub(crate) async fn checkin(
+    payload: Bytes,
+    state: web::Data<State>,
+    space: Space,
+    path: web::Path<IdPath>,
+    request: HttpRequest,
+) -> impl Responder {
+    let id = path.id;
+    if let Err(rejection) = space.allow::<Habit>(Some(id), Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let checkin: Checkin = if payload.is_empty() {
+        Checkin::default()
+    } else {
+        match serde_json::from_slice(&payload) {
+            Ok(checkin) => checkin,
+            Err(_)      => return HttpResponse::BadRequest().body("Broken json"),
+        }
+    };
+    let today = Utc::now().date_naive();
+    let date = checkin.date.unwrap_or(today);
+    // a day of slack for clients ahead of UTC
+    if today.checked_add_days(Days::new(1)).is_some_and(|tomorrow| date > tomorrow) {
+        return HttpResponse::BadRequest().body("Check-ins cannot be in the future");
+    }
+
+    let checked = space.habits.change(move |habits| {
+        let mut habit = match habits.get_mut(&id) {
+            Some(habit) => habit,
+            None        => return Err(Rejection::new(StatusCode::NOT_FOUND, "No such habit")),
+        };
+        let added = habit.checkins.insert(date);
+        if added {
+            habit.updated_at = Utc::now();
+            if etag::refresh(&mut *habit).is_err() {
+                return Err(Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, "Json error"));
+            }
+        }
+        let (event, etag, stats) = (Event::of(Action::Updated, id, &*habit), habit.get_etag(), stats(&habit.checkins, today));
+        drop(habit);
+        if added {
+            habits.emit(event);
+        }
+        Ok((added, etag, stats))
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    return match checked {
+        Ok((true, etag, stats))     => HttpResponse::Created().append_header(("ETag", etag)).json(stats),
+        Ok((false, etag, stats))    => HttpResponse::Ok().append_header(("ETag", etag)).json(stats),
+        Err(rejection)              => rejection.into(),
+    };
+}
+
+pub(crate) async fn uncheck(
+    space: Space,
+    path: web::Path<CheckinPath>,
+) -> impl Responder {
+    let CheckinPath { id, date } = path.into_inner();
+    if let Err(rejection) = space.allow::<Habit>(Some(id), Level::Write) {
+        return rejection.into();
+    }
+    let today = Utc::now().date_naive();
+    let unchecked = space.habits.change(move |habits| {
+        let mut habit = match habits.get_mut(&id) {
+            Some(habit) => habit,
+            None        => return Err(Rejection::new(StatusCode::NOT_FOUND, "No such habit")),
+        };
+        if !habit.checkins.remove(&date) {
+            return Err(Rejection::new(StatusCode::NOT_FOUND, "No check-in on that day"));
+        }
+        habit.updated_at = Utc::now();
+        let etag = match etag::refresh(&mut *habit) {
+            Ok(etag)    => etag,
+            Err(_)      => return Err(Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, "Json error")),
+        };
+        let (event, stats) = (Event::of(Action::Updated, id, &*habit), stats(&habit.checkins, today));
+        drop(habit);
+        habits.emit(event);
+        Ok((etag, stats))
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    return match unchecked {
+        Ok((etag, stats))   => HttpResponse::Ok().append_header(("ETag", etag)).json(stats),
+        Err(rejection)      => rejection.into(),
+    };
+}
+
+pub(crate) async fn show_stats(
+    space: Space,
+    path: web::Path<IdPath>,
+) -> impl Responder {
+    let id = path.id;
+    if let Err(rejection) = space.allow::<Habit>(Some(id), Level::Read) {
+        return rejection.into();
+    }
+    return match space.habits.get(&id) {
+        Some(habit) => HttpResponse::Ok().json(stats(&habit.checkins, Utc::now().date_naive())),
+        None        => HttpResponse::NotFound().body("Not found"),
+    };
+}
@@ -0,0 +1,1252 @@
+// The REST handlers and the request-independent logic they run inside a change
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::auth::response_token;
+use crate::encoding::{self, Body, Encoding};
+use crate::eventlog::Recorded;
+use crate::hooks::Hooks;
+use crate::models::{Etagged, Journal, Resource, Status, Task, TimeEntry, Timestamped, Transitions, WithId};
+use crate::notify::{Action, Event};
+use crate::state::{store_resource, Readable, State};
+use crate::workspace::{Level, Space};
+use crate::store::{Collection, Entries, Failed, Writer};
+use crate::problem::{self, Problem};
+use crate::{dryrun, etag, history, jsonapi, links, ndjson, quota};
+
+// an error response decided away from the request, e.g. by a collection writer
+#[derive(Debug)]
+pub(crate) struct Rejection {
+    status:     StatusCode,
+    message:    String,
+    // the stored entry a write conflicted with
+    current:    Option<(String, Value)>,
+}
+
+impl Rejection {
+    // for the gRPC status it is answered with
+    #[cfg(feature = "grpc")]
+    pub(crate) fn status(&self) -> StatusCode {
+        return self.status;
+    }
+
+    pub(crate) fn new(status: StatusCode, message: impl Into<String>) -> Rejection {
+        Rejection { status, message: message.into(), current: None }
+    }
+
+    // answered with the entry as it is now and its ETag, so clients can
+    // merge their edit into it without fetching it again
+    pub(crate) fn conflict<T: Etagged + Serialize>(status: StatusCode, message: impl Into<String>, current: &T) -> Rejection {
+        let current = serde_json::to_value(current).ok().map(|resource| (current.get_etag(), resource));
+        Rejection { status, message: message.into(), current }
+    }
+}
+
+// a change that panicked in the collection's writer
+impl From<Failed> for Rejection {
+    fn from(failed: Failed) -> Rejection {
+        return Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, failed.to_string());
+    }
+}
+
+impl From<Failed> for HttpResponse {
+    fn from(failed: Failed) -> HttpResponse {
+        return Rejection::from(failed).into();
+    }
+}
+
+// the message alone, for rejections that end up in logs or chat replies rather than responses
+impl std::fmt::Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        return f.write_str(&self.message);
+    }
+}
+
+impl From<Rejection> for HttpResponse {
+    fn from(rejection: Rejection) -> HttpResponse {
+        let Some((etag, current)) = rejection.current else {
+            return HttpResponse::build(rejection.status).body(rejection.message);
+        };
+        let problem = Problem::new(rejection.status, rejection.message)
+            .with("etag", Value::String(etag.clone()))
+            .with("current", current);
+        HttpResponse::build(rejection.status)
+            .append_header(("ETag", etag))
+            .content_type(problem::CONTENT_TYPE)
+            .json(problem)
+    }
+}
+#[derive(Debug, Deserialize)]
+pub(crate) struct PaginationParams {
+    page: Option<usize>,
+    per_page: Option<usize>,
+    // only entries completed after this time, e.g. for weekly reviews
+    completed_after: Option<DateTime<Utc>>,
+    // "lat,lon,radius": only entries within radius km of the point
+    near: Option<String>,
+    // "position" for the manual order of tasks, by id otherwise
+    sort: Option<String>,
+}
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+
+// the parsed ?near=, a point and a radius in km
+fn parse_near(near: &str) -> Option<(f64, f64, f64)> {
+    let parts: Vec<f64> = near.split(',').map(|part| part.trim().parse().ok()).collect::<Option<_>>()?;
+    match parts[..] {
+        [lat, lon, radius] if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) && radius >= 0.0 => {
+            return Some((lat, lon, radius));
+        }
+        _ => return None,
+    }
+}
+
+// great-circle distance in km (haversine)
+fn distance_km((lat1, lon1): (f64, f64), (lat2, lon2): (f64, f64)) -> f64 {
+    let (lat1, lat2) = (lat1.to_radians(), lat2.to_radians());
+    let (d_lat, d_lon) = (lat2 - lat1, (lon2 - lon1).to_radians());
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    return 2.0 * EARTH_RADIUS_KM * a.sqrt().asin();
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct PaginationResponse<T> {
+    pub(crate) page: usize,
+    pub(crate) total_entries: usize,
+    pub(crate) total_pages: usize,
+    pub(crate) entries: Vec<T>,
+}
+
+impl<T> PaginationResponse<T> {
+    pub(crate) fn map<U>(self, f: impl FnMut(T) -> U) -> PaginationResponse<U> {
+        PaginationResponse {
+            page:           self.page,
+            total_entries:  self.total_entries,
+            total_pages:    self.total_pages,
+            entries:        self.entries.into_iter().map(f).collect(),
+        }
+    }
+}
+
+// one page of the resources with the given ids, in their order, paired
+// with their ids
+pub(crate) fn paginate<T: Clone>(
+    resources: &Entries<T>,
+    ids: Vec<usize>,
+    query: &PaginationParams,
+) -> PaginationResponse<(usize, T)> {
+    let page_num = query.page.unwrap_or(1).max(1);
+    let per_page = query.per_page.unwrap_or(5).max(1);
+
+    let total_entries = ids.len();
+    let total_pages = total_entries.div_ceil(per_page);
+
+    let start_index = (page_num - 1) * per_page;
+
+    // only the ids are collected up front, the page entries are copied out
+    // one by one and skipped if they were removed in the meantime
+    let entries = ids.into_iter().skip(start_index).take(per_page)
+        .filter_map(|id| Some((id, resources.get(&id)?.clone())))
+        .collect();
+
+    PaginationResponse {
+        page: page_num,
+        total_entries,
+        total_pages,
+        entries,
+    }
+}
+
+// the {id} of a resource path, by name since workspace paths also carry {wid}
+#[derive(Debug, Deserialize)]
+pub(crate) struct IdPath {
+    pub(crate) id: usize,
+}
+
+pub(crate) async fn get_by_id<T: Serialize + Etagged + Recorded>(
+    path: web::Path<IdPath>,
+    space: Space,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder where Space: Readable<T>
+{
+    let id = path.id;
+    if let Err(rejection) = space.allow::<T>(Some(id), Level::Read) {
+        return rejection.into();
+    }
+    let past = match history::past::<T>(&state, &space, &request) {
+        Ok(past)        => past,
+        Err(rejection)  => return rejection.into(),
+    };
+
+    let resources: &Entries<T> = match &past {
+        Some(past)  => past,
+        None        => space.get_hmap(),
+    };
+    let found = resources.get(&id);
+    if let Some(resource) = found {
+        let etag = resource.get_etag();
+        if jsonapi::wanted(&request) {
+            return jsonapi::single(&request, &space, HttpResponse::Ok().append_header(("ETag", etag)), id, &*resource);
+        }
+        let linked = links::Linked { resource: &*resource, links: links::resource::<T>(&space.root(&request), id) };
+        return encoding::respond(&request, HttpResponse::Ok().append_header(("ETag", etag)), &linked);
+    } else {
+        return HttpResponse::NotFound().body("Not found");
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum DoneStrategy {
+    // merged task is done only when every source is
+    #[default]
+    All,
+    // merged task is done when any source is
+    Any,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum SourceStrategy {
+    #[default]
+    Delete,
+    Archive,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct TaskMerge {
+    ids: Vec<usize>,
+    // placed between the source texts, a newline by default
+    separator:  Option<String>,
+    #[serde(default)]
+    done:       DoneStrategy,
+    #[serde(default)]
+    sources:    SourceStrategy,
+    // put before every source text, "{id}" is replaced with the source id
+    prefix:     Option<String>,
+    // current ETag or version of every source task, in the same order as
+    // ids; the sources are not checked against any without it
+    etags:      Option<Vec<String>>,
+}
+#[derive(Debug, Deserialize)]
+pub(crate) struct MergeParams {
+    dry_run: Option<bool>,
+}
+
+#[derive(Serialize)]
+struct MergePreview<'a> {
+    text:       String,
+    done:       bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    deleted:    Vec<WithId<'a, Task>>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    archived:   Vec<WithId<'a, Task>>,
+}
+
+// copies of the requested tasks that exist, so a merge can be planned
+// without keeping any shard locked
+fn merge_sources(tasks: &Entries<Task>, ids: &[usize]) -> HashMap<usize, Task> {
+    ids.iter()
+        .filter_map(|id| Some((*id, tasks.get(id)?.clone())))
+        .collect()
+}
+
+// validates the ids and builds the merged (text, done)
+fn plan_task_merge(
+    tasks: &HashMap<usize, Task>,
+    info: &TaskMerge,
+) -> Result<(String, bool), Rejection> {
+    let ids = &info.ids;
+    if ids.is_empty() {
+        return Err(Rejection::new(StatusCode::BAD_REQUEST, "Nothing to merge"));
+    }
+    let mut unique = ids.to_vec();
+    unique.sort();
+    unique.dedup();
+    if unique.len() != ids.len() {
+        return Err(Rejection::new(StatusCode::CONFLICT, "Duplicate ids"));
+    }
+    if let Some(missing) = ids.iter().find(|id| !tasks.contains_key(id)) {
+        return Err(Rejection::new(StatusCode::NOT_FOUND, format!("Task {} not found", missing)));
+    }
+    if let Some(etags) = &info.etags {
+        if etags.len() != ids.len() {
+            return Err(Rejection::new(StatusCode::BAD_REQUEST, "Every id needs exactly one ETag"));
+        }
+        if let Some((id, _)) = ids.iter().zip(etags).find(|(id, etag)| !etag::matches(&tasks[id], etag)) {
+            return Err(Rejection::conflict(StatusCode::PRECONDITION_FAILED, format!("ETag of task {} does not match!", id), &tasks[id]));
+        }
+    }
+    let separator = info.separator.as_deref().unwrap_or("\n");
+    let text = ids.iter()
+        .map(|id| match &info.prefix {
+            Some(prefix) => format!("{}{}", prefix.replace("{id}", &id.to_string()), tasks[id].text),
+            None => tasks[id].text.clone(),
+        })
+        .collect::<Vec<String>>()
+        .join(separator);
+    let mut sources = ids.iter().map(|id| tasks[id].done);
+    let done = match info.done {
+        DoneStrategy::All => sources.all(|done| done),
+        DoneStrategy::Any => sources.any(|done| done),
+    };
+    return Ok((text, done));
+}
+
+pub(crate) async fn merge_tasks(
+    json: web::Json<TaskMerge>,
+    query: web::Query<MergeParams>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Task>(None, Level::Write) {
+        return rejection.into();
+    }
+    let info: TaskMerge = json.into_inner();
+
+    // previews neither consume the token nor touch the tasks
+    if query.dry_run.unwrap_or(false) || dryrun::wanted(&request) {
+        let tasks = merge_sources(&space.tasks, &info.ids);
+        let (text, done) = match plan_task_merge(&tasks, &info) {
+            Ok(merged)      => merged,
+            Err(rejection)  => return rejection.into(),
+        };
+        let sources: Vec<WithId<'_, Task>> = info.ids.iter()
+            .map(|id| WithId { id: *id, resource: &tasks[id] })
+            .collect();
+        let (deleted, archived) = match info.sources {
+            SourceStrategy::Delete  => (sources, Vec::new()),
+            SourceStrategy::Archive => (Vec::new(), sources),
+        };
+        return HttpResponse::Ok().json(MergePreview { text, done, deleted, archived });
+    }
+
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+
+    // the whole merge is one change: every source is checked before anything
+    // is written, and sources are only removed once the merged task has been
+    // stored, so a failure leaves the tasks untouched
+    let hooks = state.hooks.clone();
+    let merged = space.tasks.change(move |tasks| {
+        let (merged_text, all_done) = plan_task_merge(&merge_sources(tasks, &info.ids), &info)?;
+        println!("Merged task data: {}", merged_text.clone());
+        store_merged_task(tasks, &info, merged_text, all_done, &hooks)
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    let (index, etag) = match merged {
+        Ok(merged)      => merged,
+        Err(rejection)  => return rejection.into(),
+    };
+
+    return HttpResponse::Created()
+            .append_header(("Location", format!("{}/tasks/{}", space.root(&request), index)))
+            .append_header(("ETag", etag))
+            .body(String::from("OK"));
+}
+
+// stores the merged task and retires its sources, returns its (id, etag);
+// everything that can fail is done before the first entry is written, so
+// a refused merge leaves the tasks as they were
+fn store_merged_task(
+    tasks: &mut Writer<'_, Task>,
+    info: &TaskMerge,
+    merged_text: String,
+    all_done: bool,
+    hooks: &Hooks,
+) -> Result<(usize, String), Rejection> {
+    let now = Utc::now();
+    let mut sources: Vec<(usize, Task)> = Vec::new();
+    for id in &info.ids {
+        match tasks.get(id) {
+            Some(task)  => sources.push((*id, task.clone())),
+            None        => return Err(Rejection::new(StatusCode::NOT_FOUND, format!("Task {} not found", id))),
+        }
+    }
+    // the time spent on the sources now counts for the merged task
+    let mut time_entries: Vec<TimeEntry> = sources.iter()
+        .flat_map(|(_, task)| task.time_entries.clone())
+        .collect();
+    time_entries.sort_by_key(|entry| entry.started_at);
+    // the earliest of the sources
+    let due = sources.iter().filter_map(|(_, task)| task.due).min();
+    let mut tags: Vec<String> = Vec::new();
+    for (_, task) in &sources {
+        for tag in &task.tags {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+    }
+    let mut new_task = Task {
+        text: merged_text,
+        done: all_done,
+        status: Some(if all_done { Status::Done } else { Status::Todo }),
+        archived: false,
+        tags,
+        etag: String::from(""),
+        version: 0,
+        created_at: now,
+        updated_at: now,
+        completed_at: all_done.then_some(now),
+        time_entries,
+        metadata: Map::new(),
+        position: 0,
+        due,
+        expires_at: None,
+    };
+    hooks.merged(&info.ids, &mut new_task)?;
+    // the archived sources as they will be stored, None for deleted ones
+    let mut retired: Vec<(usize, Option<Task>)> = Vec::new();
+    for (id, mut source) in sources {
+        if info.sources == SourceStrategy::Delete {
+            retired.push((id, None));
+            continue;
+        }
+        source.archived = true;
+        source.time_entries.clear();
+        source.updated_at = now;
+        if etag::refresh(&mut source).is_err() {
+            return Err(Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, "Json error"));
+        }
+        retired.push((id, Some(source)));
+    }
+    let event = Event::of(Action::Merged, 0, &new_task);
+    let index = match store_resource(tasks, new_task) {
+        Ok(index)   => index,
+        Err(res)    => return Err(Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, res)),
+    };
+    for (id, source) in retired {
+        match source {
+            Some(source)    => tasks.insert(id, source),
+            None            => tasks.remove(&id),
+        };
+    }
+    let etag = tasks.get(&index).map(|task| task.get_etag()).unwrap_or_default();
+    tasks.emit(Event { id: index, ..event });
+    return Ok((index, etag));
+}
+
+#[derive(Deserialize, Default)]
+struct JournalClone {
+    // appended to the title, e.g. " (copy)"
+    #[serde(default)]
+    suffix:         String,
+}
+
+#[derive(Deserialize, Default)]
+struct TaskClone {
+    // appended to the text
+    #[serde(default)]
+    suffix:         String,
+    // starts the copy as todo, whatever the status of the original
+    #[serde(default)]
+    clear_done:     bool,
+    // moves the due time by that many days, back when negative
+    shift_due_days: Option<i64>,
+}
+
+// the options of a clone, all of them left out without a body
+fn clone_options<O: DeserializeOwned + Default>(payload: &Bytes) -> Result<O, HttpResponse> {
+    if payload.is_empty() {
+        return Ok(O::default());
+    }
+    return serde_json::from_slice(payload).map_err(|_| HttpResponse::BadRequest().body("Broken json"));
+}
+
+// a new journal with the content of another, but not what was true of the
+// day it was written: mood, energy, sleep and place
+pub(crate) async fn clone_journal(
+    payload: Bytes,
+    state: web::Data<State>,
+    space: Space,
+    path: web::Path<IdPath>,
+    request: HttpRequest
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(None, Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let info: JournalClone = match clone_options(&payload) {
+        Ok(info)        => info,
+        Err(response)   => return response,
+    };
+    let Some(original) = space.journals.get(&path.id).map(|journal| journal.clone()) else {
+        return HttpResponse::NotFound().body("Not found");
+    };
+    let now = Utc::now();
+    let copy = Journal {
+        title:       original.title + &info.suffix,
+        data:        original.data,
+        encrypted:   original.encrypted,
+        tags:        original.tags,
+        metadata:    original.metadata,
+        mood:        None,
+        energy:      None,
+        sleep_hours: None,
+        lat:         None,
+        lon:         None,
+        place:       None,
+        expires_at:  None,
+        publish_at:  None,
+        day:         None,
+        word_count:  0,
+        char_count:  0,
+        etag:        String::new(),
+        version:     0,
+        created_at:  now,
+        updated_at:  now,
+    };
+    return create(&state, &space, &request, copy).await;
+}
+
+// a new task with the text, tags and fields of another, without its time
+pub(crate) async fn clone_task(
+    payload: Bytes,
+    state: web::Data<State>,
+    space: Space,
+    path: web::Path<IdPath>,
+    request: HttpRequest
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Task>(None, Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let info: TaskClone = match clone_options(&payload) {
+        Ok(info)        => info,
+        Err(response)   => return response,
+    };
+    let Some(original) = space.tasks.get(&path.id).map(|task| task.clone()) else {
+        return HttpResponse::NotFound().body("Not found");
+    };
+    let due = match (original.due, info.shift_due_days) {
+        (Some(due), Some(days)) => {
+            match chrono::Duration::try_days(days).and_then(|shift| due.checked_add_signed(shift)) {
+                Some(due)   => Some(due),
+                None        => return HttpResponse::BadRequest().body("shift_due_days is out of range"),
+            }
+        }
+        (due, _)                => due,
+    };
+    let (done, status, completed_at) = match info.clear_done {
+        true    => (false, Some(Status::Todo), None),
+        false   => (original.done, original.status, original.completed_at),
+    };
+    let now = Utc::now();
+    let copy = Task {
+        text:         original.text + &info.suffix,
+        done,
+        status,
+        archived:     false,
+        tags:         original.tags,
+        etag:         String::new(),
+        version:      0,
+        created_at:   now,
+        updated_at:   now,
+        completed_at,
+        time_entries: Vec::new(),
+        metadata:     original.metadata,
+        position:     0,
+        due,
+        expires_at:   None,
+    };
+    return create(&state, &space, &request, copy).await;
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct TaskSplit {
+    // the task text is split on newlines when omitted
+    parts: Option<Vec<String>>,
+}
+
+#[derive(Serialize)]
+struct SplitResult {
+    locations: Vec<String>,
+}
+
+pub(crate) async fn split_task(
+    payload: Bytes,
+    state: web::Data<State>,
+    space: Space,
+    path: web::Path<IdPath>,
+    request: HttpRequest
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Task>(None, Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let info: TaskSplit = if payload.is_empty() {
+        TaskSplit::default()
+    } else {
+        match serde_json::from_slice(&payload) {
+            Ok(info)    => info,
+            Err(_)      => return HttpResponse::BadRequest().body("Broken json"),
+        }
+    };
+
+    let if_match = match if_match(&request) {
+        Ok(if_match)    => if_match,
+        Err(response)   => return response,
+    };
+
+    // the parts take the place of the task, so all but one are new entries
+    let parts = space.tasks.get(&path.id).map_or(0, |task| split_parts(info.parts.clone(), &task.text).len());
+    if let Err(rejection) = quota::admit(&state, &space.tasks, parts.saturating_sub(1), payload.len()) {
+        return rejection.into();
+    }
+    let id = path.id;
+    let hooks = state.hooks.clone();
+    let split = space.tasks.change(move |tasks| split_stored_task(tasks, id, if_match, info, &hooks)).await.unwrap_or_else(|failed| Err(failed.into()));
+    return match split {
+        Ok(ids) => {
+            let root = space.root(&request);
+            let locations = ids.iter().map(|index| format!("{}/tasks/{}", root, index)).collect();
+            HttpResponse::Created().json(SplitResult { locations })
+        }
+        Err(rejection)  => rejection.into(),
+    };
+}
+
+// the texts given, or else the lines of the task, without blank ones
+fn split_parts(parts: Option<Vec<String>>, text: &str) -> Vec<String> {
+    return parts
+        .unwrap_or_else(|| text.lines().map(String::from).collect())
+        .into_iter()
+        .map(|part| String::from(part.trim()))
+        .filter(|part| !part.is_empty())
+        .collect();
+}
+
+// replaces the task with its parts, returns their ids; the hooks see every
+// part and the task's deletion before the first entry is written, so a
+// refused split leaves the task as it was
+fn split_stored_task(
+    tasks: &mut Writer<'_, Task>,
+    id: usize,
+    if_match: Option<String>,
+    info: TaskSplit,
+    hooks: &Hooks,
+) -> Result<Vec<usize>, Rejection> {
+    let original = match tasks.get(&id) {
+        Some(task)  => task.clone(),
+        None        => return Err(Rejection::new(StatusCode::NOT_FOUND, "Not found")),
+    };
+    check_etag(&original, if_match.as_deref())?;
+
+    let parts = split_parts(info.parts, &original.text);
+    if parts.is_empty() {
+        return Err(Rejection::new(StatusCode::BAD_REQUEST, "Nothing to split"));
+    }
+
+    let now = Utc::now();
+    let done = original.done;
+    let event = Event::of(Action::Split, id, &original);
+    // the time spent so far stays with the first part
+    let mut time_entries = original.time_entries.clone();
+    let mut new_tasks = Vec::new();
+    for text in parts {
+        let mut task = Task {
+            text,
+            done,
+            status: original.status,
+            archived: false,
+            tags: original.tags.clone(),
+            etag: String::new(),
+            version: 0,
+            created_at: now,
+            updated_at: now,
+            completed_at: original.completed_at,
+            time_entries: std::mem::take(&mut time_entries),
+            metadata: original.metadata.clone(),
+            position: 0,
+            due: original.due,
+            expires_at: None,
+        };
+        hooks.created(&mut task)?;
+        task.validate().map_err(|reason| Rejection::new(StatusCode::BAD_REQUEST, reason))?;
+        new_tasks.push(task);
+    }
+    hooks.deleted::<Task>(id)?;
+    let mut ids = Vec::new();
+    for task in new_tasks {
+        match store_resource(tasks, task) {
+            Ok(index)   => ids.push(index),
+            Err(res)    => return Err(Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, res)),
+        }
+    }
+    tasks.remove(&id);
+    tasks.emit(event);
+    return Ok(ids);
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct JournalMerge {
+    ids:    Vec<usize>,
+    // current ETag or version of every source journal, in the same order as ids
+    etags:  Option<Vec<String>>,
+    // defaults to the source titles joined together
+    title:  Option<String>,
+}
+
+#[derive(Serialize)]
+struct JournalMergePreview<'a> {
+    title:      String,
+    data:       String,
+    tags:       Vec<String>,
+    deleted:    Vec<WithId<'a, Journal>>,
+}
+
+pub(crate) async fn merge_journals(
+    json: web::Json<JournalMerge>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(None, Level::Write) {
+        return rejection.into();
+    }
+    let dry_run = dryrun::wanted(&request);
+    if !dry_run {
+        if let Err(resp) = response_token(&state, &request) {
+            return resp;
+        }
+    }
+    let info: JournalMerge = json.into_inner();
+    let etags = match info.etags {
+        Some(etags) => etags,
+        None        => return HttpResponse::PreconditionRequired().body("ETags are missing!"),
+    };
+    if info.ids.is_empty() || etags.len() != info.ids.len() {
+        return HttpResponse::BadRequest().body("Every id needs exactly one ETag");
+    }
+    let mut unique = info.ids.clone();
+    unique.sort();
+    unique.dedup();
+    if unique.len() != info.ids.len() {
+        return HttpResponse::BadRequest().body("Duplicate ids");
+    }
+    if dry_run {
+        let (merged, sources) = match plan_journal_merge(&space.journals, &info.ids, &etags, info.title) {
+            Ok(planned)     => planned,
+            Err(rejection)  => return rejection.into(),
+        };
+        let deleted = info.ids.iter().zip(&sources).map(|(id, journal)| WithId { id: *id, resource: journal }).collect();
+        return HttpResponse::Ok().json(JournalMergePreview { title: merged.title, data: merged.data, tags: merged.tags, deleted });
+    }
+
+    // validation, creation and removal all happen in one change
+    let hooks = state.hooks.clone();
+    let merged = space.journals.change(move |journals| {
+        store_merged_journal(journals, &info.ids, &etags, info.title, &hooks)
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    let (index, etag) = match merged {
+        Ok(merged)      => merged,
+        Err(rejection)  => return rejection.into(),
+    };
+
+    return HttpResponse::Created()
+        .append_header(("Location", format!("{}/journals/{}", space.root(&request), index)))
+        .append_header(("ETag", etag))
+        .body(String::from("OK"));
+}
+
+// checks the sources and joins them, returns the merge and the sources
+fn plan_journal_merge(
+    journals: &Entries<Journal>,
+    ids: &[usize],
+    etags: &[String],
+    title: Option<String>,
+) -> Result<(Journal, Vec<Journal>), Rejection> {
+    let mut sources: Vec<Journal> = Vec::new();
+    for (id, etag) in ids.iter().zip(etags) {
+        let journal = match journals.get(id) {
+            Some(journal)   => journal.clone(),
+            None            => return Err(Rejection::new(StatusCode::NOT_FOUND, format!("Journal {} not found", id))),
+        };
+        if !etag::matches(&journal, etag) {
+            return Err(Rejection::conflict(StatusCode::PRECONDITION_FAILED, format!("ETag of journal {} does not match!", id), &journal));
+        }
+        // ciphertexts cannot be joined on the server
+        if journal.encrypted {
+            return Err(Rejection::new(StatusCode::CONFLICT, format!("Journal {} is encrypted", id)));
+        }
+        sources.push(journal);
+    }
+
+    let title = title.unwrap_or_else(|| sources.iter()
+        .map(|journal| journal.title.as_str())
+        .collect::<Vec<&str>>()
+        .join(" / "));
+    let data = sources.iter()
+        .map(|journal| format!("## {}\n\n{}", journal.title, journal.data))
+        .collect::<Vec<String>>()
+        .join("\n\n");
+    let mut tags: Vec<String> = Vec::new();
+    for tag in sources.iter().flat_map(|journal| &journal.tags) {
+        if !tags.contains(tag) {
+            tags.push(tag.clone());
+        }
+    }
+    let now = Utc::now();
+    let merged = Journal {
+        title,
+        data,
+        encrypted: false,
+        tags,
+        metadata: None,
+        mood: None,
+        energy: None,
+        sleep_hours: None,
+        lat: None,
+        lon: None,
+        place: None,
+        expires_at: None,
+        publish_at: None,
+        day: None,
+        word_count: 0,
+        char_count: 0,
+        etag: String::new(),
+        version: 0,
+        created_at: now,
+        updated_at: now,
+    };
+    return Ok((merged, sources));
+}
+
+// replaces the journals with their merge, returns its (id, etag)
+fn store_merged_journal(
+    journals: &mut Writer<'_, Journal>,
+    ids: &[usize],
+    etags: &[String],
+    title: Option<String>,
+    hooks: &Hooks,
+) -> Result<(usize, String), Rejection> {
+    let (mut merged, _) = plan_journal_merge(journals, ids, etags, title)?;
+    let now = merged.created_at;
+    hooks.merged(ids, &mut merged)?;
+    merged.track_changes(None, now);
+    let event = Event::of(Action::Merged, 0, &merged);
+
+    let index = match store_resource(journals, merged) {
+        Ok(index)   => index,
+        Err(res)    => return Err(Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, res)),
+    };
+    for id in ids {
+        journals.remove(id);
+    }
+    let etag = journals.get(&index).map(|journal| journal.get_etag()).unwrap_or_default();
+    journals.emit(Event { id: index, ..event });
+    return Ok((index, etag));
+}
+
+pub(crate) async fn post_resource<T>(
+    json: Body<T>, 
+    state: web::Data<State>, 
+    space: Space,
+    request: HttpRequest
+) -> impl Responder
+where Space: Readable<T>, T: Etagged + Timestamped + Resource + Serialize + Send + Sync + 'static {
+    if let Err(rejection) = space.allow::<T>(None, Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    return create(&state, &space, &request, json.into_inner()).await;
+}
+
+async fn create<T>(state: &State, space: &Space, request: &HttpRequest, resource: T) -> HttpResponse
+where Space: Readable<T>, T: Etagged + Timestamped + Resource + Serialize + Send + Sync + 'static {
+    let uri = format!("{}/{}", space.root(request), links::collection::<T>());
+    match store_new(state, space, resource).await {
+        Ok(index)       => return HttpResponse::Created()
+            .append_header(("Location", format!("{}/{}", uri, index))).body(String::from("OK")),
+        Err(rejection)  => return rejection.into(),
+    }
+}
+
+// the checks every entry a client sends has to pass: its own, the schema of
+// its kind and the journals it links to
+fn check<T: Resource>(space: &Space, resource: &T) -> Result<(), Rejection> {
+    return resource.validate()
+        .and_then(|_| space.schemas.check(resource))
+        .and_then(|_| space.check_links(resource))
+        .map_err(|reason| Rejection::new(StatusCode::BAD_REQUEST, reason));
+}
+
+// stores a new entry as every client-made one is, through the hooks, the
+// checks and the quota, whatever protocol it came in by; returns its id
+pub(crate) async fn store_new<T>(state: &State, space: &Space, mut resource: T) -> Result<usize, Rejection>
+where Space: Readable<T>, T: Etagged + Timestamped + Resource + Serialize + Send + Sync + 'static {
+    state.hooks.created(&mut resource)?;
+    check(space, &resource)?;
+    let resources: &Collection<T> = space.get_hmap();
+    quota::admit(state, resources, 1, quota::size(&resource))?;
+    return resources.add_resource(resource).await
+        .map_err(|text| Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, text));
+}
+
+pub(crate) async fn delete_resource<T>(
+    path: web::Path<IdPath>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder where Space: Readable<T>, T: Resource + Serialize + Send + Sync + 'static {
+    if let Err(rejection) = space.allow::<T>(None, Level::Write) {
+        return rejection.into();
+    }
+    let resources: &Collection<T> = space.get_hmap();
+    // answered with what would be removed
+    if dryrun::wanted(&request) {
+        return match resources.get(&path.id) {
+            Some(resource)  => HttpResponse::Ok().json(WithId { id: path.id, resource: &*resource }),
+            None            => HttpResponse::NotFound().body("Not found"),
+        };
+    }
+    match resources.rm_resource(path.id, &state.hooks).await {
+        Ok(Some(_)) => {
+            return HttpResponse::Ok().body("Removed");
+        }
+        Ok(None)        => return HttpResponse::NotFound().body("Not found"),
+        Err(rejection)  => return rejection.into(),
+    };
+}
+
+// the If-Match header, read up front so the check can run inside a change
+fn if_match(request: &HttpRequest) -> Result<Option<String>, HttpResponse> {
+    let etag = match request.headers().get("If-Match") {
+        Some(etag)  => etag,
+        None        => return Ok(None),
+    };
+    match etag.to_str() {
+        Ok(etag)    => return Ok(Some(String::from(etag))),
+        Err(_)      => return Err(HttpResponse::BadRequest().body("Broken header!")),
+    }
+}
+
+fn check_etag<T: Etagged + Serialize>(
+    resource: &T, 
+    if_match: Option<&str>) -> Result<(), Rejection> {
+    let etag = match if_match {
+        Some(etag)  => etag,
+        None        => return Err(Rejection::new(StatusCode::PRECONDITION_REQUIRED, "ETag is missing!")),
+    };
+    if !etag::matches(resource, etag) {
+        return Err(Rejection::conflict(StatusCode::PRECONDITION_FAILED, "ETag does not match!", resource));
+    }
+    return Ok(());
+}
+
+pub(crate) async fn patch_task(
+    payload:    Bytes,
+    state:      web::Data<State>,
+    space:      Space,
+    path:       web::Path<IdPath>,
+    request:    HttpRequest,
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Task>(None, Level::Write) {
+        return rejection.into();
+    }
+    let if_match = match if_match(&request) {
+        Ok(if_match)    => if_match,
+        Err(response)   => return response,
+    };
+    // the patch can grow the task by at most its own size
+    if let Err(rejection) = quota::admit(&state, &space.tasks, 0, payload.len()) {
+        return rejection.into();
+    }
+    let id = path.id;
+    // decoded up front, but reported only after the task and ETag checks
+    let json = Encoding::sent(&request).decode::<Value>(&payload);
+    let transitions = state.config.transitions.clone();
+    let hooks = state.hooks.clone();
+    let patched = space.tasks.change(move |tasks| {
+        patch_stored_task(tasks, id, if_match, json, &transitions, &hooks)
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    match patched {
+        Ok(new_etag) => return HttpResponse::Ok()
+            .append_header(("ETag", new_etag))
+            .body("Updated"),
+        Err(rejection) => return rejection.into(),
+    }
+}
+
+// applies the "done", "status", "text" and "tags" fields of the patch,
+// returns the new ETag
+fn patch_stored_task(
+    tasks:          &mut Writer<'_, Task>,
+    id:             usize,
+    if_match:       Option<String>,
+    json:           Result<Value, String>,
+    transitions:    &Transitions,
+    hooks:          &Hooks,
+) -> Result<String, Rejection> {
+    let bad_request = |reason| Err(Rejection::new(StatusCode::BAD_REQUEST, reason));
+
+    let mut task = match tasks.get_mut(&id) {
+        Some(task)  => task,
+        None        => return bad_request("No such resource"),
+    };
+
+    check_etag(&*task, if_match.as_deref())?;
+
+    let json: Value = match json {
+        Ok(json)    => json,
+        Err(_)      => return bad_request("Broken json"),
+    };
+
+    let previous = task.clone();
+    let mut is_updated = false;
+    if let Some(done) = json.get("done") {
+        if let Some(done) = done.as_bool() {
+            task.mark_done(done);
+            is_updated = true;
+        }
+    }
+
+    // after done, so it wins when both are given
+    if let Some(status) = json.get("status") {
+        match serde_json::from_value(status.clone()) {
+            Ok(status)  => {
+                task.set_status(status);
+                is_updated = true;
+            }
+            Err(_)      => {
+                *task = previous;
+                return bad_request("status is todo, in_progress, blocked, done or cancelled");
+            }
+        }
+    }
+
+    if let Some(text) = json.get("text") {
+        if let Some(text) = text.as_str() {
+            task.text = String::from(text);
+            is_updated = true;
+        }
+    }
+
+    if let Some(tags) = json.get("tags") {
+        match serde_json::from_value(tags.clone()) {
+            Ok(tags)    => {
+                task.tags = tags;
+                is_updated = true;
+            }
+            Err(_)      => {
+                *task = previous;
+                return bad_request("tags has to be a list of strings");
+            }
+        }
+    }
+    // hooks only see patches that change something, so a refused no-op
+    // cannot leave their edits behind
+    if !is_updated {
+        return bad_request("Nothing to update");
+    }
+    // the patch is applied in place, so undo it when the result is refused
+    if let Err(rejection) = hooks.updated(id, &mut *task) {
+        *task = previous;
+        return Err(rejection);
+    }
+    if let Err(reason) = task.validate() {
+        *task = previous;
+        return Err(Rejection::new(StatusCode::BAD_REQUEST, reason));
+    }
+    if let Err(reason) = transitions.check(Some(&previous), &*task) {
+        let rejection = Rejection::conflict(StatusCode::CONFLICT, reason, &previous);
+        *task = previous;
+        return Err(rejection);
+    }
+
+    let now = Utc::now();
+    task.updated_at = now;
+    task.track_changes(Some(&previous), now);
+    let new_etag = match etag::refresh(&mut *task) {
+        Ok(etag)    => etag,
+        Err(_)      => return bad_request("Json error"),
+    };
+    let event = Event::of(Action::Updated, id, &*task);
+    drop(task);
+    tasks.emit(event);
+    return Ok(new_etag);
+}
+
+pub(crate) async fn put_resource<T>(
+    json:       Body<T>,
+    state:      web::Data<State>,
+    space:      Space,
+    path:       web::Path<IdPath>,
+    request:    HttpRequest
+) -> impl Responder
+where Space: Readable<T>, T: Serialize + Etagged + Timestamped + Resource + Clone + Send + Sync + 'static {
+    let id = path.id;
+    if let Err(rejection) = space.allow::<T>(Some(id), Level::Write) {
+        return rejection.into();
+    }
+    let if_match = match if_match(&request) {
+        Ok(if_match)    => if_match,
+        Err(response)   => return response,
+    };
+    match store_put(&state, &space, id, if_match, json.into_inner()).await {
+        Ok(new_etag) => return HttpResponse::Ok()
+            .append_header(("ETag", new_etag))
+            .body("Updated"),
+        Err(rejection) => return rejection.into(),
+    }
+}
+
+// stores the entry under the id, replacing the one there if `if_match`
+// names it, through the same checks, hooks and quota whatever protocol it
+// came in by; returns the new ETag
+pub(crate) async fn store_put<T>(
+    state:          &State,
+    space:          &Space,
+    id:             usize,
+    if_match:       Option<String>,
+    mut new_resource: T,
+) -> Result<String, Rejection>
+where Space: Readable<T>, T: Serialize + Etagged + Timestamped + Resource + Clone + Send + Sync + 'static {
+    let resources: &Collection<T> = space.get_hmap();
+    check(space, &new_resource)?;
+
+    let transitions = state.config.transitions.clone();
+    let hooks = state.hooks.clone();
+    let checked = space.clone();
+    // a new id is one more entry, a replaced entry only adds what it grows by
+    let existing_size = resources.get(&id).map(|resource| quota::size(&*resource));
+    let grows = quota::size(&new_resource).saturating_sub(existing_size.unwrap_or(0));
+    quota::admit(state, resources, usize::from(existing_size.is_none()), grows)?;
+    return resources.change(move |resources| {
+        // the entry is copied so no shard lock is held while inserting below
+        let existing = resources.get(&id).map(|resource| resource.clone());
+        if let Some(resource) = &existing {
+            check_etag(resource, if_match.as_deref())?;
+        }
+
+        // else put the element in the HashMap of the resource
+        let now = Utc::now();
+        let created_at = existing.as_ref().map_or(now, |resource| resource.get_created_at());
+        new_resource.set_timestamps(created_at, now);
+        new_resource.track_changes(existing.as_ref(), now);
+        let refuse = |reason| match &existing {
+            Some(existing)  => Rejection::conflict(StatusCode::CONFLICT, reason, existing),
+            None            => Rejection::new(StatusCode::CONFLICT, reason),
+        };
+        transitions.check(existing.as_ref(), &new_resource).map_err(refuse)?;
+        // only once the put is known to go through, so a refused one never
+        // reaches them; what they change is checked again
+        match &existing {
+            Some(_) => hooks.updated(id, &mut new_resource)?,
+            None    => hooks.created(&mut new_resource)?,
+        }
+        check(&checked, &new_resource)?;
+        transitions.check(existing.as_ref(), &new_resource).map_err(refuse)?;
+        // a new id goes after every other entry, as it would when posted
+        if existing.is_none() {
+            new_resource.place(id);
+        }
+        let new_etag = match etag::refresh(&mut new_resource) {
+            Ok(etag)    => etag,
+            Err(_)      => return Err(Rejection::new(StatusCode::BAD_REQUEST, "json error")),
+        };
+        let action = if existing.is_some() { Action::Updated } else { Action::Created };
+        resources.emit(Event::of(action, id, &new_resource));
+        resources.insert(id, new_resource);
+        Ok(new_etag)
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+}
+
+pub(crate) async fn get_resources<T>(
+    query: web::Query<PaginationParams>,
+    space: Space,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder where Space: Readable<T>, T: Serialize + Clone + Recorded + Timestamped + 'static {
+    // I'll end up in hell for this...
+    let resources: Arc<Entries<T>> = match history::past::<T>(&state, &space, &request) {
+        Ok(Some(past))  => Arc::new(past),
+        Ok(None)        => Readable::<T>::get_hmap(&space).shared(),
+        Err(rejection)  => return rejection.into(),
+    };
+
+    // read before the entries, so it can only be older than what is sent
+    let version = resources.tag();
+    let mut ids = space.visible(&resources);
+    // drafts get published and entries expire without any write, so the
+    // ids shown are part of the tag, as is the format they are sent in
+    let media_type = if ndjson::wanted(&request) {
+        ndjson::CONTENT_TYPE
+    } else if jsonapi::wanted(&request) {
+        jsonapi::CONTENT_TYPE
+    } else {
+        Encoding::accepted(&request).content_type()
+    };
+    let tag = format!("{}-{}", version, &etag::calculate_hash(format!("{} {:?}", media_type, ids))[..16]);
+    let if_none_match = request.headers().get("If-None-Match").and_then(|etag| etag.to_str().ok());
+    if if_none_match.is_some_and(|etag| etag == "*" || etag.split(',').any(|etag| etag.trim() == tag)) {
+        return HttpResponse::NotModified()
+            .append_header(("ETag", tag))
+            .append_header(("Vary", "Accept"))
+            .finish();
+    }
+
+    if let Some(after) = query.completed_after {
+        ids.retain(|id| resources.get(id).and_then(|resource| resource.get_completed_at()).is_some_and(|at| at > after));
+    }
+    if let Some(near) = &query.near {
+        let (lat, lon, radius) = match parse_near(near) {
+            Some(near)  => near,
+            None        => return HttpResponse::BadRequest().body("Expected near=lat,lon,radius_km"),
+        };
+        ids.retain(|id| resources.get(id)
+            .and_then(|resource| resource.location())
+            .is_some_and(|location| distance_km(location, (lat, lon)) <= radius));
+    }
+    match query.sort.as_deref() {
+        None | Some("id")   => {}
+        Some("position")    => {
+            let mut positioned = Vec::new();
+            for id in ids {
+                match resources.get(&id).map(|resource| resource.position()) {
+                    Some(Some(position))    => positioned.push((position, id)),
+                    Some(None)              => return HttpResponse::BadRequest().body("Only tasks have a position"),
+                    None                    => {}
+                }
+            }
+            positioned.sort();
+            ids = positioned.into_iter().map(|(_, id)| id).collect();
+        }
+        Some(_)             => return HttpResponse::BadRequest().body("sort is id or position"),
+    }
+
+    // NDJSON streams every entry with its id, pagination does not apply
+    if ndjson::wanted(&request) {
+        let entries = resources.clone();
+        let lines = ids.into_iter().filter_map(move |id| {
+            let resource = entries.get(&id)?;
+            Some(serde_json::to_string(&WithId { id, resource: &*resource }))
+        });
+        return ndjson::respond(HttpResponse::Ok().append_header(("ETag", tag)).append_header(("Vary", "Accept")), lines);
+    }
+
+    let response = paginate(&resources, ids, &query);
+    if jsonapi::wanted(&request) {
+        return jsonapi::listing(&request, &space, HttpResponse::Ok().append_header(("ETag", tag)), &response);
+    }
+    let response = links::listing(&request, &space.root(&request), response);
+    encoding::respond(&request, HttpResponse::Ok().append_header(("ETag", tag)), &response)
+}
@@ -0,0 +1,45 @@
+// Point-in-time reads: with the event log on, the single entries and the
+// listings of the server's own space take ?as_of=<RFC 3339 time> and show
+// the entries as they were then, replayed from the log
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::eventlog::Recorded;
+use crate::handlers::Rejection;
+use crate::state::State;
+use crate::store::Entries;
+use crate::workspace::Space;
+
+#[derive(Deserialize)]
+struct AsOf {
+    as_of: Option<DateTime<Utc>>,
+}
+
+// the time the request asks about, None for now
+fn requested(request: &HttpRequest) -> Result<Option<DateTime<Utc>>, Rejection> {
+    return web::Query::<AsOf>::from_query(request.query_string())
+        .map(|query| query.as_of)
+        .map_err(|_| Rejection::new(StatusCode::BAD_REQUEST, "as_of is an RFC 3339 time, e.g. 2026-10-16T08:00:00Z"));
+}
+
+// the space's entries of the kind as they were at the time; their tag
+// stands for the last change before it
+fn entries<T: Recorded>(state: &State, space: &Space, at: DateTime<Utc>) -> Result<Entries<T>, Rejection> {
+    if space.workspace().is_some() {
+        return Err(Rejection::new(StatusCode::BAD_REQUEST, "Only the server's own entries have a history"));
+    }
+    let Some(log) = &state.event_log else {
+        return Err(Rejection::new(StatusCode::BAD_REQUEST, "Entries have a history only with the event log"));
+    };
+    let (entries, sequence) = log.as_of::<T>(at)
+        .map_err(|err| Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+    return Ok(Entries::detached(entries, sequence));
+}
+
+// the entries of the kind as the request asks for them, None for the ones
+// there are now
+pub(crate) fn past<T: Recorded>(state: &State, space: &Space, request: &HttpRequest) -> Result<Option<Entries<T>>, Rejection> {
+    return requested(request)?.map(|at| entries(state, space, at)).transpose();
+}
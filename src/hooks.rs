@@ -0,0 +1,107 @@
+// Hooks for embedders: code registered with State::register_hook sees the
+// journals, tasks, habits, notes and bookmarks clients write through the
+// REST API before they are stored: those posted, cloned or imported from a
+// takeout archive are created, a PUT creates or updates, a task PATCH
+// updates, a DELETE or the expiry sweeper deletes, the mergers merge and a
+// task split creates its parts and deletes the task. The creates, puts and
+// deletes of gRPC and CalDAV run them as well, as do the entries CRDT
+// merges create or change, the tasks added with /todo over Telegram and
+// those schedules create.
+// A hook can change the entry (enrichment), refuse it with a reason
+// (validation, answered 422) or pass it on elsewhere (mirroring). Entries
+// the server derives from others, e.g. tagged or moved ones, timers,
+// check-ins, file imports, daily journals and tasks completed over
+// Telegram, do not go through them.
+// Hooks run in the order they were registered. Updates, deletes, merges,
+// splits and CRDT merges run them inside the collection's writer, after
+// their preconditions, so they have to be quick; creates run them before
+// the entry is checked.
+use actix_web::http::StatusCode;
+use std::sync::{Arc, RwLock};
+
+use crate::handlers::Rejection;
+use crate::models::{Bookmark, Habit, Journal, Note, Resource, Task};
+use crate::state::State;
+
+// what a hook is shown, to change in place
+pub enum Entry<'a> {
+    Journal(&'a mut Journal),
+    Task(&'a mut Task),
+    Habit(&'a mut Habit),
+    Note(&'a mut Note),
+    Bookmark(&'a mut Bookmark),
+}
+
+impl Entry<'_> {
+    // "journal", "task", "habit", "note" or "bookmark"
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Entry::Journal(_)   => Journal::KIND,
+            Entry::Task(_)      => Task::KIND,
+            Entry::Habit(_)     => Habit::KIND,
+            Entry::Note(_)      => Note::KIND,
+            Entry::Bookmark(_)  => Bookmark::KIND,
+        }
+    }
+}
+
+// Every method lets the write through by default; an Err refuses it with
+// the message as the reason.
+pub trait Hook: Send + Sync {
+    // a new entry, before it has an id
+    fn on_create(&self, _entry: &mut Entry<'_>) -> Result<(), String> {
+        return Ok(());
+    }
+    // the entry as it will be stored under the id
+    fn on_update(&self, _id: usize, _entry: &mut Entry<'_>) -> Result<(), String> {
+        return Ok(());
+    }
+    fn on_delete(&self, _kind: &str, _id: usize) -> Result<(), String> {
+        return Ok(());
+    }
+    // the entry the ones with these ids are merged into
+    fn on_merge(&self, _ids: &[usize], _merged: &mut Entry<'_>) -> Result<(), String> {
+        return Ok(());
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    hooks: Arc<RwLock<Vec<Arc<dyn Hook>>>>,
+}
+
+impl Hooks {
+    fn run(&self, mut call: impl FnMut(&dyn Hook) -> Result<(), String>) -> Result<(), Rejection> {
+        for hook in self.hooks.read().unwrap().iter() {
+            call(hook.as_ref()).map_err(|reason| Rejection::new(StatusCode::UNPROCESSABLE_ENTITY, reason))?;
+        }
+        return Ok(());
+    }
+
+    pub(crate) fn created<T: Resource>(&self, resource: &mut T) -> Result<(), Rejection> {
+        return self.run(|hook| hook.on_create(&mut resource.entry()));
+    }
+
+    pub(crate) fn updated<T: Resource>(&self, id: usize, resource: &mut T) -> Result<(), Rejection> {
+        return self.run(|hook| hook.on_update(id, &mut resource.entry()));
+    }
+
+    pub(crate) fn deleted<T: Resource>(&self, id: usize) -> Result<(), Rejection> {
+        return self.run(|hook| hook.on_delete(T::KIND, id));
+    }
+
+    pub(crate) fn merged<T: Resource>(&self, ids: &[usize], resource: &mut T) -> Result<(), Rejection> {
+        return self.run(|hook| hook.on_merge(ids, &mut resource.entry()));
+    }
+
+    pub(crate) fn add(&self, hook: Arc<dyn Hook>) {
+        self.hooks.write().unwrap().push(hook);
+    }
+}
+
+impl State {
+    // runs the hook on every later write, after those registered before
+    pub fn register_hook(&self, hook: impl Hook + 'static) {
+        self.hooks.add(Arc::new(hook));
+    }
+}
@@ -0,0 +1,138 @@
+// Minimal iCalendar (RFC 5545) reading and writing
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+const MAX_LINE_OCTETS: usize = 75;
+
+#[derive(Debug, Clone)]
+pub struct Property {
+    pub name:   String,
+    pub value:  String,
+}
+
+pub fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+pub fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N')   => unescaped.push('\n'),
+            Some(other)             => unescaped.push(other),
+            None                    => unescaped.push('\\'),
+        }
+    }
+    return unescaped;
+}
+
+pub fn format_timestamp(timestamp: &DateTime<Utc>) -> String {
+    timestamp.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// a DATE-TIME or a DATE (taken as its midnight); parameters are dropped, so
+// times in a TZID or floating are read as UTC
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim().trim_end_matches('Z');
+    if let Ok(timestamp) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return Some(timestamp.and_utc());
+    }
+    return NaiveDate::parse_from_str(value, "%Y%m%d").ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc());
+}
+
+// long content lines are folded with CRLF + space, never inside a UTF-8 sequence
+fn fold_line(line: &str, output: &mut String) {
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            output.push_str("\r\n ");
+            octets = 1;
+        }
+        output.push(c);
+        octets += c.len_utf8();
+    }
+    output.push_str("\r\n");
+}
+
+// builds an iCalendar document from content lines
+pub fn write_calendar(lines: &[String]) -> String {
+    let mut output = String::new();
+    fold_line("BEGIN:VCALENDAR", &mut output);
+    fold_line("VERSION:2.0", &mut output);
+    fold_line("PRODID:-//rest-journal//EN", &mut output);
+    for line in lines {
+        fold_line(line, &mut output);
+    }
+    fold_line("END:VCALENDAR", &mut output);
+    return output;
+}
+
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for raw in ics.split('\n') {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        if let Some(continuation) = raw.strip_prefix([' ', '\t']) {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(continuation);
+                continue;
+            }
+        }
+        if !raw.is_empty() {
+            lines.push(String::from(raw));
+        }
+    }
+    return lines;
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    // the value starts at the first colon outside a quoted parameter
+    let mut in_quotes = false;
+    let split = line.char_indices().find(|(_, c)| {
+        if *c == '"' {
+            in_quotes = !in_quotes;
+        }
+        *c == ':' && !in_quotes
+    })?.0;
+    let (head, value) = (&line[..split], &line[split + 1..]);
+    // parameters (";KEY=value") are not needed yet
+    let name = head.split(';').next()?.to_ascii_uppercase();
+    Some(Property { name, value: String::from(value) })
+}
+
+// properties of every top-level `component` (e.g. "VTODO") in the document,
+// nested components such as VALARM are skipped
+pub fn components(ics: &str, component: &str) -> Vec<Vec<Property>> {
+    let mut found = Vec::new();
+    let mut current: Option<Vec<Property>> = None;
+    let mut nested = 0;
+    for line in unfold(ics) {
+        let property = match parse_property(&line) {
+            Some(property)  => property,
+            None            => continue,
+        };
+        let is_target = property.value.eq_ignore_ascii_case(component);
+        match (property.name.as_str(), current.as_mut()) {
+            ("BEGIN", None) if is_target => current = Some(Vec::new()),
+            ("BEGIN", Some(_)) => nested += 1,
+            ("END", Some(_)) if nested > 0 => nested -= 1,
+            ("END", Some(_)) if is_target => found.extend(current.take()),
+            (_, Some(properties)) if nested == 0 => properties.push(property),
+            _ => (),
+        }
+    }
+    return found;
+}
+
+pub fn find<'a>(properties: &'a [Property], name: &str) -> Option<&'a Property> {
+    properties.iter().find(|property| property.name == name)
+}
@@ -0,0 +1,539 @@
+// Tasks and journals brought over from other tools. Every task importer
+// turns its format into tasks; they are then checked and stored, or only
+// listed on a dry run. A task with the same text and due time as one already
+// there is skipped, so importing a file twice adds nothing. Journals come as
+// a zip of Markdown files.
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+use zip::ZipArchive;
+
+use crate::auth::response_token;
+use crate::{dryrun, ical, quota};
+use crate::models::{Journal, Resource, Status, Task, Timestamped};
+use crate::notify::{Action, Event};
+use crate::state::{store_resource, State};
+use crate::workspace::{Level, Space};
+
+#[derive(Deserialize)]
+pub(crate) struct ImportQuery {
+    // lists what would be created and skipped, without storing anything
+    #[serde(default)]
+    dry_run: bool,
+}
+
+impl ImportQuery {
+    // asked for in the query or with X-Dry-Run
+    fn dry_run(&self, request: &HttpRequest) -> bool {
+        return self.dry_run || dryrun::wanted(request);
+    }
+}
+
+#[derive(Serialize)]
+struct Created {
+    // none on a dry run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id:     Option<usize>,
+    text:   String,
+    status: Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    due:    Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct Skipped {
+    text:   String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct ImportReport {
+    dry_run:    bool,
+    created:    Vec<Created>,
+    skipped:    Vec<Skipped>,
+}
+
+// a new task as an importer sees it, the rest is filled in when storing
+pub(crate) fn task(text: String, status: Status, due: Option<DateTime<Utc>>, tags: Vec<String>) -> Task {
+    let now = Utc::now();
+    let mut task = Task {
+        text,
+        done:         false,
+        status:       None,
+        archived:     false,
+        tags,
+        etag:         String::new(),
+        version:      0,
+        created_at:   now,
+        updated_at:   now,
+        completed_at: None,
+        time_entries: Vec::new(),
+        metadata:     Map::new(),
+        position:     0,
+        due,
+        expires_at: None,
+    };
+    task.set_status(status);
+    return task;
+}
+
+// what makes two tasks duplicates
+fn key(task: &Task) -> (String, Option<DateTime<Utc>>) {
+    return (task.text.trim().to_lowercase(), task.due);
+}
+
+async fn import(space: &Space, tasks: Vec<Task>, dry_run: bool) -> Result<ImportReport, String> {
+    let mut created = Vec::new();
+    let mut skipped = Vec::new();
+    let mut valid = Vec::new();
+    for task in tasks {
+        let checked = match task.text.trim().is_empty() {
+            true    => Err(String::from("no text")),
+            false   => task.validate().and_then(|_| space.schemas.check(&task)),
+        };
+        match checked {
+            Ok(_)       => valid.push(task),
+            Err(reason) => skipped.push(Skipped { text: task.text, reason }),
+        }
+    }
+
+    // compared with the stored tasks inside the change, so a file imported
+    // twice at once still adds its tasks once
+    let stored = space.tasks.change(move |tasks| {
+        let mut seen: HashSet<_> = tasks.ids().into_iter()
+            .filter_map(|id| Some(key(&*tasks.get(&id)?)))
+            .collect();
+        let mut stored = Vec::new();
+        for mut task in valid {
+            if !seen.insert(key(&task)) {
+                stored.push(Err(task));
+                continue;
+            }
+            if dry_run {
+                stored.push(Ok((None, task)));
+                continue;
+            }
+            let now = Utc::now();
+            task.set_timestamps(now, now);
+            task.track_changes(None, now);
+            let event = Event::of(Action::Created, 0, &task);
+            let id = store_resource(tasks, task.clone())?;
+            tasks.emit(Event { id, ..event });
+            stored.push(Ok((Some(id), task)));
+        }
+        Ok::<_, String>(stored)
+    }).await.unwrap_or_else(|failed| Err(failed.to_string()))?;
+
+    for result in stored {
+        match result {
+            Ok((id, task))  => created.push(Created {
+                id,
+                status: task.status().unwrap_or(Status::Todo),
+                due:    task.due,
+                text:   task.text,
+            }),
+            Err(task)       => skipped.push(Skipped { text: task.text, reason: String::from("duplicate") }),
+        }
+    }
+    return Ok(ImportReport { dry_run, created, skipped });
+}
+
+async fn respond(state: &web::Data<State>, space: &Space, request: &HttpRequest, tasks: Vec<Task>, dry_run: bool) -> HttpResponse {
+    if let Err(rejection) = space.allow::<Task>(None, Level::Write) {
+        return rejection.into();
+    }
+    // a dry run writes nothing, so it needs no token
+    if !dry_run {
+        if let Err(resp) = response_token(state, request) {
+            return resp;
+        }
+        // duplicates are left out later, so this may turn away an import
+        // that would just fit
+        let bytes = tasks.iter().map(quota::size).sum();
+        if let Err(rejection) = quota::admit(state, &space.tasks, tasks.len(), bytes) {
+            return rejection.into();
+        }
+    }
+    match import(space, tasks, dry_run).await {
+        Ok(report)  => return HttpResponse::Ok().json(report),
+        Err(err)    => return HttpResponse::InternalServerError().body(err),
+    }
+}
+
+// VTODOs are due at their DUE, VEVENTs at their DTSTART; CATEGORIES become
+// tags
+fn from_ics(body: &str) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    for (component, due) in [("VTODO", "DUE"), ("VEVENT", "DTSTART")] {
+        for properties in ical::components(body, component) {
+            let text = ical::find(&properties, "SUMMARY")
+                .map(|summary| ical::unescape_text(&summary.value))
+                .unwrap_or_default();
+            let status = match ical::find(&properties, "STATUS").map(|status| status.value.to_ascii_uppercase()) {
+                Some(status) if status == "COMPLETED"   => Status::Done,
+                Some(status) if status == "IN-PROCESS"  => Status::InProgress,
+                Some(status) if status == "CANCELLED"   => Status::Cancelled,
+                _                                       => Status::Todo,
+            };
+            let due = ical::find(&properties, due).and_then(|due| ical::parse_timestamp(&due.value));
+            let mut tags: Vec<String> = Vec::new();
+            for categories in properties.iter().filter(|property| property.name == "CATEGORIES") {
+                for tag in categories.value.split(',') {
+                    add_tag(&mut tags, &ical::unescape_text(tag));
+                }
+            }
+            tasks.push(task(text, status, due, tags));
+        }
+    }
+    return tasks;
+}
+
+// a date as other tools write it: RFC 3339, or without a zone (read as UTC),
+// or only the day (its midnight)
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(value) {
+        return Some(timestamp.with_timezone(&Utc));
+    }
+    if let Ok(timestamp) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        return Some(timestamp.and_utc());
+    }
+    return NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc());
+}
+
+fn add_tag(tags: &mut Vec<String>, tag: &str) {
+    let tag = tag.trim();
+    if !tag.is_empty() && !tags.iter().any(|known| known == tag) {
+        tags.push(String::from(tag));
+    }
+}
+
+// RFC 4180 records: quoted fields may hold commas, line breaks and doubled
+// quotes
+fn csv_records(text: &str) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.trim_start_matches('\u{feff}').chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"'                 => quoted = !quoted,
+            ',' if !quoted      => record.push(std::mem::take(&mut field)),
+            '\r' if !quoted    => {}
+            '\n' if !quoted    => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _                   => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    return records;
+}
+
+fn field(record: &[String], index: usize) -> &str {
+    return record.get(index).map_or("", |field| field.trim());
+}
+
+// Todoist's CSV template: TYPE, CONTENT and DATE columns among others;
+// tasks are tagged with the section they are in, dates in words are left out
+fn from_todoist_csv(body: &str) -> Result<Vec<Task>, String> {
+    let records = csv_records(body);
+    let Some((header, rows)) = records.split_first() else {
+        return Ok(Vec::new());
+    };
+    let column = |name: &str| header.iter().position(|column| column.trim().eq_ignore_ascii_case(name));
+    let (Some(kind), Some(content)) = (column("TYPE"), column("CONTENT")) else {
+        return Err(String::from("Expected TYPE and CONTENT columns"));
+    };
+    let date = column("DATE");
+
+    let mut tasks = Vec::new();
+    let mut section = None;
+    for row in rows {
+        match field(row, kind) {
+            "section"   => section = Some(String::from(field(row, content))),
+            "task"      => {
+                let mut tags = Vec::new();
+                if let Some(section) = &section {
+                    add_tag(&mut tags, section);
+                }
+                let due = date.and_then(|date| parse_date(field(row, date)));
+                tasks.push(task(String::from(field(row, content)), Status::Todo, due, tags));
+            }
+            _           => {}
+        }
+    }
+    return Ok(tasks);
+}
+
+#[derive(Deserialize)]
+struct TodoistDue {
+    date:       Option<String>,
+    datetime:   Option<String>,
+}
+
+// of the REST API, or an item of the sync API
+#[derive(Deserialize)]
+struct TodoistTask {
+    content:        String,
+    #[serde(default, alias = "checked")]
+    is_completed:   bool,
+    #[serde(default)]
+    labels:         Vec<String>,
+    due:            Option<TodoistDue>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TodoistExport {
+    Tasks(Vec<TodoistTask>),
+    Sync { items: Vec<TodoistTask> },
+}
+
+fn from_todoist_json(body: &str) -> Result<Vec<Task>, String> {
+    let items = match serde_json::from_str(body) {
+        Ok(TodoistExport::Tasks(items)) | Ok(TodoistExport::Sync { items }) => items,
+        Err(_) => return Err(String::from("Expected a list of Todoist tasks, or an object with their items")),
+    };
+    return Ok(items.into_iter().map(|item| {
+        let mut tags = Vec::new();
+        for label in &item.labels {
+            add_tag(&mut tags, label);
+        }
+        let due = item.due.and_then(|due| due.datetime.or(due.date)).and_then(|due| parse_date(&due));
+        let status = if item.is_completed { Status::Done } else { Status::Todo };
+        task(item.content, status, due, tags)
+    }).collect());
+}
+
+pub(crate) async fn import_todoist(
+    body: String,
+    query: web::Query<ImportQuery>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    let parsed = match body.trim_start().starts_with(['[', '{']) {
+        true    => from_todoist_json(&body),
+        false   => from_todoist_csv(&body),
+    };
+    return match parsed {
+        Ok(tasks)   => respond(&state, &space, &request, tasks, query.dry_run(&request)).await,
+        Err(reason) => HttpResponse::BadRequest().body(reason),
+    };
+}
+
+// "- [ ] text" items, "* ", "+ " and "1. " too, at any depth; "[x]" ones are
+// done, and the heading above an item tags it
+fn from_markdown(body: &str) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let mut heading: Option<&str> = None;
+    for line in body.lines().map(str::trim) {
+        if line.starts_with('#') {
+            heading = Some(line.trim_start_matches('#').trim()).filter(|heading| !heading.is_empty());
+            continue;
+        }
+        let item = line.strip_prefix(['-', '*', '+'])
+            .or_else(|| line.split_once(". ").filter(|(number, _)| number.parse::<u32>().is_ok()).map(|(_, item)| item));
+        let Some(item) = item.map(str::trim_start) else { continue };
+        let status = if item.starts_with("[ ]") {
+            Status::Todo
+        } else if item.starts_with("[x]") || item.starts_with("[X]") {
+            Status::Done
+        } else {
+            continue;
+        };
+        let mut tags = Vec::new();
+        if let Some(heading) = heading {
+            add_tag(&mut tags, heading);
+        }
+        tasks.push(task(String::from(item[3..].trim()), status, None, tags));
+    }
+    return tasks;
+}
+
+pub(crate) async fn import_markdown(
+    body: String,
+    query: web::Query<ImportQuery>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    return respond(&state, &space, &request, from_markdown(&body), query.dry_run(&request)).await;
+}
+
+pub(crate) async fn import_ics(
+    body: String,
+    query: web::Query<ImportQuery>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if ical::components(&body, "VCALENDAR").is_empty() {
+        return HttpResponse::BadRequest().body("Expected an iCalendar file");
+    }
+    return respond(&state, &space, &request, from_ics(&body), query.dry_run(&request)).await;
+}
+
+#[derive(Serialize)]
+struct ImportedJournal {
+    // none on a dry run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id:         Option<usize>,
+    file:       String,
+    title:      String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct SkippedFile {
+    file:   String,
+    reason: String,
+}
+
+#[derive(Serialize)]
+struct JournalReport {
+    dry_run:    bool,
+    created:    Vec<ImportedJournal>,
+    skipped:    Vec<SkippedFile>,
+}
+
+// zips store local times without a zone, they are read as UTC
+fn modified(file: &zip::read::ZipFile<'_>) -> Option<DateTime<Utc>> {
+    let time = file.last_modified()?;
+    return NaiveDate::from_ymd_opt(time.year().into(), time.month().into(), time.day().into())?
+        .and_hms_opt(time.hour().into(), time.minute().into(), time.second().into())
+        .map(|modified| modified.and_utc());
+}
+
+// the first line is the title when it is a heading, the file name otherwise
+fn journal_from_markdown(name: &str, text: &str, modified: DateTime<Utc>) -> Journal {
+    let text = text.trim_start_matches('\u{feff}').trim();
+    let heading = text.lines().next()
+        .filter(|line| line.starts_with('#'))
+        .map(|line| line.trim_start_matches('#').trim())
+        .filter(|heading| !heading.is_empty());
+    let (title, data) = match heading {
+        Some(heading)   => (String::from(heading), text.split_once('\n').map_or("", |(_, rest)| rest).trim()),
+        None            => {
+            let file = name.rsplit('/').next().unwrap_or(name);
+            (String::from(&file[..file.len() - ".md".len()]), text)
+        }
+    };
+    let mut journal = Journal {
+        title,
+        data:        String::from(data),
+        encrypted:   false,
+        tags:        Vec::new(),
+        metadata:    None,
+        mood:        None,
+        energy:      None,
+        sleep_hours: None,
+        lat:         None,
+        lon:         None,
+        place:       None,
+        expires_at:  None,
+        publish_at:  None,
+        day:         None,
+        word_count:  0,
+        char_count:  0,
+        etag:        String::new(),
+        version:     0,
+        created_at:  modified,
+        updated_at:  modified,
+    };
+    journal.track_changes(None, modified);
+    return journal;
+}
+
+// every .md file of the zip, at any depth, as a journal dated by the file's
+// modification time; hidden files and macOS resource forks are left out
+pub(crate) async fn import_journals(
+    body: web::Bytes,
+    query: web::Query<ImportQuery>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<Journal>(None, Level::Write) {
+        return rejection.into();
+    }
+    let dry_run = query.dry_run(&request);
+    if !dry_run {
+        if let Err(resp) = response_token(&state, &request) {
+            return resp;
+        }
+    }
+    let mut archive = match ZipArchive::new(Cursor::new(body)) {
+        Ok(archive) => archive,
+        Err(_)      => return HttpResponse::BadRequest().body("Expected a zip of Markdown files"),
+    };
+
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+    for index in 0..archive.len() {
+        let Ok(mut file) = archive.by_index(index) else { continue };
+        let name = String::from(file.name());
+        let hidden = name.split('/').any(|part| part.starts_with('.') || part == "__MACOSX");
+        if file.is_dir() || hidden || !name.to_lowercase().ends_with(".md") {
+            continue;
+        }
+        let modified = modified(&file).unwrap_or_else(Utc::now);
+        let mut text = String::new();
+        if file.read_to_string(&mut text).is_err() {
+            skipped.push(SkippedFile { file: name, reason: String::from("not UTF-8 text") });
+            continue;
+        }
+        let journal = journal_from_markdown(&name, &text, modified);
+        match journal.validate().and_then(|_| space.schemas.check(&journal)) {
+            Ok(_)       => files.push((name, journal)),
+            Err(reason) => skipped.push(SkippedFile { file: name, reason }),
+        }
+    }
+    // oldest first, so the ids follow the dates
+    files.sort_by(|a, b| a.1.created_at.cmp(&b.1.created_at).then_with(|| a.0.cmp(&b.0)));
+
+    let mut created: Vec<ImportedJournal> = files.iter()
+        .map(|(file, journal)| ImportedJournal {
+            id:         None,
+            file:       file.clone(),
+            title:      journal.title.clone(),
+            created_at: journal.created_at,
+        })
+        .collect();
+    if !dry_run {
+        let bytes = files.iter().map(|(_, journal)| quota::size(journal)).sum();
+        if let Err(rejection) = quota::admit(&state, &space.journals, files.len(), bytes) {
+            return rejection.into();
+        }
+        let stored = space.journals.change(move |journals| {
+            let mut ids = Vec::new();
+            for (_, journal) in files {
+                let event = Event::of(Action::Created, 0, &journal);
+                let id = store_resource(journals, journal)?;
+                journals.emit(Event { id, ..event });
+                ids.push(id);
+            }
+            Ok::<_, String>(ids)
+        }).await.unwrap_or_else(|failed| Err(failed.to_string()));
+        match stored {
+            Ok(ids)     => created.iter_mut().zip(ids).for_each(|(journal, id)| journal.id = Some(id)),
+            Err(text)   => return HttpResponse::InternalServerError().body(text),
+        }
+    }
+    return HttpResponse::Ok().json(JournalReport { dry_run, created, skipped });
+}
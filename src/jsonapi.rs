@@ -0,0 +1,141 @@
+// JSON:API (https://jsonapi.org) responses, for clients built on its
+// tooling: asked for with `Accept: application/vnd.api+json`, or the default
+// with JOURNAL_JSON_API. Journals, tasks, habits, notes and bookmarks come as
+// resource objects with their relationships, listings with pagination links,
+// and errors as error objects. Request bodies stay the plain resources.
+use actix_web::body::{self, EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::encoding::Encoding;
+use crate::handlers::PaginationResponse;
+use crate::models::Resource;
+use crate::{flags, links, ndjson};
+use crate::state::State;
+use crate::workspace::Space;
+
+pub(crate) const CONTENT_TYPE: &str = "application/vnd.api+json";
+
+// asked for, or the default for clients that did not ask for another format
+pub(crate) fn wanted(request: &HttpRequest) -> bool {
+    let accept = request.headers().get("Accept").and_then(|accept| accept.to_str().ok()).unwrap_or("");
+    if accept.split(',').any(|kind| kind.trim().starts_with(CONTENT_TYPE)) {
+        return true;
+    }
+    let default = request.app_data::<web::Data<State>>().is_some_and(|state| state.flags.on(flags::JSON_API, request));
+    return default && Encoding::accepted(request) == Encoding::Json && !ndjson::wanted(request);
+}
+
+// what a resource leads to besides itself
+fn relationships<T: Resource>(base: &str) -> Value {
+    let relationships: Map<String, Value> = links::related::<T>().iter()
+        .map(|name| (String::from(*name), json!({ "links": { "related": format!("{}/{}", base, name) } })))
+        .collect();
+    return Value::Object(relationships);
+}
+
+fn resource_object<T: Resource + Serialize>(root: &str, id: usize, resource: &T) -> Value {
+    let link = format!("{}/{}/{}", root, links::collection::<T>(), id);
+    let mut object = json!({
+        "type":         links::collection::<T>(),
+        "id":           id.to_string(),
+        "attributes":   resource,
+        "links":        { "self": link },
+    });
+    let relationships = relationships::<T>(&link);
+    if relationships.as_object().is_some_and(|relationships| !relationships.is_empty()) {
+        object["relationships"] = relationships;
+    }
+    return object;
+}
+
+fn respond(response: &mut actix_web::HttpResponseBuilder, document: Value) -> HttpResponse {
+    return response
+        .content_type(CONTENT_TYPE)
+        .append_header(("Vary", "Accept"))
+        .json(document);
+}
+
+pub(crate) fn single<T: Resource + Serialize>(
+    request: &HttpRequest,
+    space: &Space,
+    response: &mut actix_web::HttpResponseBuilder,
+    id: usize,
+    resource: &T,
+) -> HttpResponse {
+    return respond(response, json!({ "data": resource_object(&space.root(request), id, resource) }));
+}
+
+pub(crate) fn listing<T: Resource + Serialize>(
+    request: &HttpRequest,
+    space: &Space,
+    response: &mut actix_web::HttpResponseBuilder,
+    listing: &PaginationResponse<(usize, T)>,
+) -> HttpResponse {
+    let root = space.root(request);
+    let data: Vec<Value> = listing.entries.iter()
+        .map(|(id, resource)| resource_object(&root, *id, resource))
+        .collect();
+    let last = listing.total_pages.max(1);
+    let mut links = json!({
+        "self":     links::page(request, listing.page),
+        "first":    links::page(request, 1),
+        "last":     links::page(request, last),
+        "prev":     null,
+        "next":     null,
+    });
+    if listing.page > 1 {
+        links["prev"] = json!(links::page(request, (listing.page - 1).min(last)));
+    }
+    if listing.page < last {
+        links["next"] = json!(links::page(request, listing.page + 1));
+    }
+    let meta = json!({ "page": listing.page, "total_entries": listing.total_entries, "total_pages": listing.total_pages });
+    return respond(response, json!({ "data": data, "links": links, "meta": meta }));
+}
+
+// Turns failed responses into error documents, for every route of the API.
+// The plain-text message or the problem's detail becomes the detail, what
+// else a problem carries, like the current entry of a conflict, its meta.
+pub async fn errors(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let wanted = wanted(request.request());
+    let response = next.call(request).await?;
+    let status = response.status();
+    if !wanted || !(status.is_client_error() || status.is_server_error()) {
+        return Ok(response.map_into_left_body());
+    }
+    let (request, response) = response.into_parts();
+    let (mut response, content) = response.into_parts();
+    let content = body::to_bytes(content).await.unwrap_or_default();
+    let mut error = json!({
+        "status":   status.as_str(),
+        "title":    status.canonical_reason().unwrap_or("Error"),
+    });
+    match serde_json::from_slice::<Value>(&content) {
+        Ok(Value::Object(mut problem)) => {
+            if let Some(detail) = problem.remove("detail") {
+                error["detail"] = detail;
+            }
+            for member in ["type", "title", "status", "instance"] {
+                problem.remove(member);
+            }
+            if !problem.is_empty() {
+                error["meta"] = Value::Object(problem);
+            }
+        }
+        _ if !content.is_empty() => error["detail"] = json!(String::from_utf8_lossy(&content)),
+        _ => {}
+    }
+    let document = serde_json::to_vec(&json!({ "errors": [error] })).unwrap_or_default();
+    response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+    response.headers_mut().remove(header::CONTENT_LENGTH);
+    let response = response.set_body(document).map_into_boxed_body();
+    return Ok(ServiceResponse::new(request, response).map_into_right_body());
+}
@@ -0,0 +1,133 @@
+#![deny(elided_lifetimes_in_paths)]
+#![allow(clippy::needless_return, clippy::result_large_err)]
+// A journal and task server: the REST API with its CalDAV, feed, export and
+// optional UI, GraphQL and gRPC frontends, all over one shared State
+use actix_web::web;
+
+use crate::scheduler::Schedule;
+
+#[cfg(feature = "client")]
+pub mod client;
+pub mod etag;
+pub mod hooks;
+pub mod models;
+pub mod routes;
+pub mod state;
+
+mod admin;
+mod analytics;
+mod attachments;
+mod audit;
+mod autocomplete;
+mod board;
+mod bulk;
+mod auth;
+mod blobs;
+mod bookmarks;
+mod caldav;
+mod daily;
+mod deprecation;
+mod dryrun;
+mod duplicates;
+mod encoding;
+mod eventlog;
+mod events;
+mod expiry;
+mod export;
+mod feed;
+mod flags;
+mod habits;
+mod history;
+mod imports;
+mod handlers;
+mod ical;
+mod jsonapi;
+mod links;
+mod maintenance;
+mod metrics;
+mod ndjson;
+mod notify;
+mod operations;
+mod ordering;
+mod outbound;
+mod problem;
+mod quota;
+mod ranges;
+mod ratelimit;
+mod related;
+mod reload;
+mod render;
+mod replica;
+mod report;
+mod scheduler;
+mod schedules;
+mod schema;
+mod search;
+mod seed;
+mod share;
+mod signing;
+mod slow;
+mod stats;
+mod store;
+mod summary;
+mod sync;
+mod takeout;
+mod telegram;
+mod telemetry;
+mod timers;
+mod versioning;
+mod workspace;
+#[cfg(feature = "ui")]
+mod ui;
+#[cfg(feature = "crdt")]
+mod crdt;
+#[cfg(feature = "faults")]
+mod faults;
+#[cfg(feature = "fulltext")]
+mod fulltext;
+#[cfg(feature = "graphql")]
+mod graphql;
+#[cfg(feature = "grpc")]
+mod grpc;
+#[cfg(feature = "plugins")]
+mod plugins;
+
+pub use hooks::{Entry, Hook};
+pub use models::{Journal, Task};
+pub use routes::{app, create_test_app};
+pub use seed::generate;
+pub use state::{Config, State};
+#[cfg(feature = "otel")]
+pub use telemetry::{init_tracing, Tracing};
+
+// the work that runs next to the HTTP server: the jobs of the scheduler,
+// reloads on SIGHUP, bookmark titles, and the Telegram bot and gRPC server
+// when enabled
+pub fn spawn_background(state: &web::Data<State>) {
+    state.settings.read().unwrap().apply();
+    #[cfg(unix)]
+    actix_web::rt::spawn(reload::watch(state.clone()));
+    scheduler::spawn(state, "token_sweep", Schedule::Every(auth::TOKEN_SWEEP_INTERVAL), auth::sweep);
+    if let Some(event_log) = &state.config.event_log {
+        scheduler::spawn(state, "snapshot", Schedule::Every(event_log.snapshot_interval), eventlog::snapshot);
+    }
+    // reads only on a replica
+    #[cfg(feature = "grpc")]
+    actix_web::rt::spawn(grpc::serve(state.clone(), ([127, 0, 0, 1], 50051).into()));
+    // a replica writes nothing of its own, what the primary does reaches it
+    if let Some(replica) = state.config.replica.clone() {
+        actix_web::rt::spawn(replica::follow(state.clone(), replica));
+        return;
+    }
+    scheduler::spawn(state, "expiry_sweep", Schedule::Every(expiry::EXPIRY_SWEEP_INTERVAL), expiry::sweep);
+    scheduler::spawn(state, "schedules", Schedule::Every(schedules::CHECK_INTERVAL), schedules::run_due);
+    if state.config.fetch_titles {
+        bookmarks::fetch_titles(state);
+    }
+    if state.config.daily.scheduled {
+        scheduler::spawn(state, "daily_journal", Schedule::Daily, daily::create);
+    }
+    if let Some(telegram) = state.config.telegram.clone() {
+        actix_web::rt::spawn(telegram::run(state.clone(), telegram));
+    }
+}
@@ -0,0 +1,94 @@
+// Hypermedia links, so clients can follow the API instead of building its
+// URLs: every journal, task, habit, note and bookmark served as JSON carries
+// `_links` to itself, its collection and what belongs to it, listings to
+// their pages.
+use actix_web::HttpRequest;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::handlers::PaginationResponse;
+use crate::models::Resource;
+use crate::versioning;
+
+#[derive(Serialize)]
+pub(crate) struct Link {
+    href: String,
+}
+
+pub(crate) type Links = BTreeMap<&'static str, Link>;
+
+// a resource or listing with its links next to its own fields
+#[derive(Serialize)]
+pub(crate) struct Linked<T> {
+    #[serde(flatten)]
+    pub(crate) resource:    T,
+    #[serde(rename = "_links")]
+    pub(crate) links:       Links,
+}
+
+fn link(href: String) -> Link {
+    return Link { href };
+}
+
+// "journals" for journals, the collection's path
+pub(crate) fn collection<T: Resource>() -> String {
+    return format!("{}s", T::KIND);
+}
+
+// what a resource leads to besides itself, each below its own path
+pub(crate) fn related<T: Resource>() -> &'static [&'static str] {
+    match T::KIND {
+        "journal"   => &["attachments", "bookmarks", "related"],
+        "task"      => &["time"],
+        "habit"     => &["stats"],
+        _           => &[],
+    }
+}
+
+pub(crate) fn resource<T: Resource>(root: &str, id: usize) -> Links {
+    let path = format!("{}/{}/{}", root, collection::<T>(), id);
+    let mut links = Links::new();
+    for name in related::<T>() {
+        links.insert(name, link(format!("{}/{}", path, name)));
+    }
+    links.insert("collection", link(format!("{}/{}", root, collection::<T>())));
+    links.insert("self", link(path));
+    return links;
+}
+
+// the request's URL on another page, its other parameters kept
+pub(crate) fn page(request: &HttpRequest, page: usize) -> String {
+    let mut query: Vec<String> = request.query_string().split('&')
+        .filter(|pair| !pair.is_empty() && pair.split('=').next() != Some("page"))
+        .map(String::from)
+        .collect();
+    query.push(format!("page={}", page));
+    return format!("{}{}?{}", versioning::base(request), request.path(), query.join("&"));
+}
+
+// self, first and last, and prev and next where there are such pages
+pub(crate) fn pages<T>(request: &HttpRequest, listing: &PaginationResponse<T>) -> Links {
+    let last = listing.total_pages.max(1);
+    let mut links = Links::new();
+    links.insert("self", link(page(request, listing.page)));
+    links.insert("first", link(page(request, 1)));
+    links.insert("last", link(page(request, last)));
+    if listing.page > 1 {
+        links.insert("prev", link(page(request, (listing.page - 1).min(last))));
+    }
+    if listing.page < last {
+        links.insert("next", link(page(request, listing.page + 1)));
+    }
+    return links;
+}
+
+// the page with every entry linked
+pub(crate) fn listing<T: Resource>(
+    request: &HttpRequest,
+    root: &str,
+    listing: PaginationResponse<(usize, T)>,
+) -> Linked<PaginationResponse<Linked<T>>> {
+    let links = pages(request, &listing);
+    let listing = listing.map(|(id, entry)| Linked { resource: entry, links: resource::<T>(root, id) });
+    return Linked { resource: listing, links };
+}
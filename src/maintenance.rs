@@ -0,0 +1,130 @@
+// Read-only maintenance mode: while it is on, every write answers 503 with
+// Retry-After and reads keep working, so backups and migrations can run
+// against a dataset nothing changes underneath
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::middleware::Next;
+use actix_web::web::{self, Bytes};
+use actix_web::{HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::admin::require_admin;
+use crate::state::State;
+
+// what clients are told to wait when the operator did not say
+const DEFAULT_RETRY_AFTER: u64 = 300;
+
+#[derive(Clone, Copy)]
+pub(crate) struct Maintenance {
+    since:          DateTime<Utc>,
+    // seconds, sent as Retry-After
+    retry_after:    u64,
+}
+
+impl State {
+    pub(crate) fn maintenance(&self) -> Option<Maintenance> {
+        return *self.maintenance.lock().unwrap();
+    }
+}
+
+impl Maintenance {
+    pub(crate) fn message(&self) -> String {
+        return format!("Read-only maintenance, retry in {} seconds", self.retry_after);
+    }
+
+    // what a refused write is answered with
+    pub(crate) fn refusal(&self) -> HttpResponse {
+        return HttpResponse::ServiceUnavailable()
+            .append_header(("Retry-After", self.retry_after.to_string()))
+            .body(self.message());
+    }
+}
+
+// anything but reads; GraphQL has no mutations, in whichever space
+fn changes(request: &ServiceRequest) -> bool {
+    let reading = matches!(request.method().as_str(), "GET" | "HEAD" | "OPTIONS" | "PROPFIND" | "REPORT");
+    let graphql = request.method() == Method::POST && request.path().trim_end_matches('/').rsplit('/').next() == Some("graphql");
+    return !reading && !graphql;
+}
+
+// the writes of clients, which replicas send on and signatures cover; the
+// admin routes act on this server only
+pub(crate) fn is_write(request: &ServiceRequest) -> bool {
+    return changes(request) && !request.path().starts_with("/admin/");
+}
+
+pub async fn guard(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let maintenance = request.app_data::<web::Data<State>>().and_then(|state| state.maintenance());
+    // admin writes included, all but the toggle so the mode can be switched off again
+    if let Some(maintenance) = maintenance.filter(|_| changes(&request) && request.path() != "/admin/maintenance") {
+        return Ok(request.into_response(maintenance.refusal()).map_into_right_body());
+    }
+    return Ok(next.call(request).await?.map_into_left_body());
+}
+
+#[derive(Deserialize, Default)]
+struct Toggle {
+    // flips the mode when omitted
+    enabled:        Option<bool>,
+    retry_after:    Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Status {
+    enabled:        bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    since:          Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after:    Option<u64>,
+}
+
+fn status(maintenance: Option<Maintenance>) -> Status {
+    return Status {
+        enabled:        maintenance.is_some(),
+        since:          maintenance.map(|maintenance| maintenance.since),
+        retry_after:    maintenance.map(|maintenance| maintenance.retry_after),
+    };
+}
+
+pub(crate) async fn show(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    return HttpResponse::Ok().json(status(state.maintenance()));
+}
+
+pub(crate) async fn toggle(payload: Bytes, state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    let toggle: Toggle = if payload.is_empty() {
+        Toggle::default()
+    } else {
+        match serde_json::from_slice(&payload) {
+            Ok(toggle)  => toggle,
+            Err(_)      => return HttpResponse::BadRequest().body("Broken json"),
+        }
+    };
+
+    let mut maintenance = state.maintenance.lock().unwrap();
+    let enabled = toggle.enabled.unwrap_or(maintenance.is_none());
+    *maintenance = match (enabled, *maintenance) {
+        (false, _)              => None,
+        // turning it on again only changes what clients are told
+        (true, Some(current))   => Some(Maintenance {
+            retry_after: toggle.retry_after.unwrap_or(current.retry_after),
+            ..current
+        }),
+        (true, None)            => Some(Maintenance {
+            since:          Utc::now(),
+            retry_after:    toggle.retry_after.unwrap_or(DEFAULT_RETRY_AFTER),
+        }),
+    };
+    println!("Maintenance mode {}", if enabled { "on" } else { "off" });
+    return HttpResponse::Ok().json(status(*maintenance));
+}
@@ -0,0 +1,157 @@
+// Prometheus metrics, served in the text format at /metrics
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse, Responder};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::state::State;
+
+// upper bounds of the latency buckets in seconds, Prometheus' defaults
+const BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+// what requests are counted under: their method and route pattern, never
+// the raw path, so ids do not turn into new series
+type Route = (String, String);
+
+#[derive(Default)]
+struct Histogram {
+    // per bucket, not yet cumulative; slower ones only show up in count
+    buckets:    [u64; BUCKETS.len()],
+    sum:        f64,
+    count:      u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(bucket) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[bucket] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Metrics {
+    slow_requests:  Mutex<HashMap<Route, u64>>,
+    durations:      Mutex<HashMap<Route, Histogram>>,
+    in_flight:      Mutex<HashMap<Route, i64>>,
+    // journal bodies served from the render cache, and rendered for it
+    render_hits:    Mutex<u64>,
+    render_misses:  Mutex<u64>,
+}
+
+impl Metrics {
+    pub(crate) fn count_slow(&self, method: &str, route: &str) {
+        let mut slow_requests = self.slow_requests.lock().unwrap();
+        *slow_requests.entry((String::from(method), String::from(route))).or_insert(0) += 1;
+    }
+
+    pub(crate) fn count_render(&self, hit: bool) {
+        let counter = if hit { &self.render_hits } else { &self.render_misses };
+        *counter.lock().unwrap() += 1;
+    }
+
+    fn render(&self) -> String {
+        let mut output = String::new();
+        let _ = writeln!(output, "# HELP journal_slow_requests_total Requests slower than JOURNAL_SLOW_REQUEST_MS.");
+        let _ = writeln!(output, "# TYPE journal_slow_requests_total counter");
+        for (route, count) in sorted(&self.slow_requests.lock().unwrap()) {
+            let _ = writeln!(output, "journal_slow_requests_total{{{}}} {}", labels(route), count);
+        }
+
+        let _ = writeln!(output, "# HELP journal_request_duration_seconds Time until the response was ready.");
+        let _ = writeln!(output, "# TYPE journal_request_duration_seconds histogram");
+        for (route, histogram) in sorted(&self.durations.lock().unwrap()) {
+            let labels = labels(route);
+            let mut cumulative = 0;
+            for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(output, "journal_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, cumulative);
+            }
+            let _ = writeln!(output, "journal_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, histogram.count);
+            let _ = writeln!(output, "journal_request_duration_seconds_sum{{{}}} {}", labels, histogram.sum);
+            let _ = writeln!(output, "journal_request_duration_seconds_count{{{}}} {}", labels, histogram.count);
+        }
+
+        let _ = writeln!(output, "# HELP journal_requests_in_flight Requests being handled right now.");
+        let _ = writeln!(output, "# TYPE journal_requests_in_flight gauge");
+        for (route, count) in sorted(&self.in_flight.lock().unwrap()) {
+            let _ = writeln!(output, "journal_requests_in_flight{{{}}} {}", labels(route), count);
+        }
+
+        let _ = writeln!(output, "# HELP journal_render_cache_hits_total Journal bodies served as HTML without rendering them.");
+        let _ = writeln!(output, "# TYPE journal_render_cache_hits_total counter");
+        let _ = writeln!(output, "journal_render_cache_hits_total {}", self.render_hits.lock().unwrap());
+        let _ = writeln!(output, "# HELP journal_render_cache_misses_total Journal bodies rendered to HTML.");
+        let _ = writeln!(output, "# TYPE journal_render_cache_misses_total counter");
+        let _ = writeln!(output, "journal_render_cache_misses_total {}", self.render_misses.lock().unwrap());
+        return output;
+    }
+}
+
+fn sorted<V>(series: &HashMap<Route, V>) -> Vec<(&Route, &V)> {
+    let mut series: Vec<(&Route, &V)> = series.iter().collect();
+    series.sort_by_key(|(route, _)| *route);
+    return series;
+}
+
+// a label value, escaped the way the text format wants it
+fn label(value: &str) -> String {
+    return value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+}
+
+fn labels((method, route): &Route) -> String {
+    return format!("method=\"{}\",route=\"{}\"", label(method), label(route));
+}
+
+// takes the request off the in-flight gauge when it is done, also when the
+// client went away and the request was dropped halfway
+struct InFlight {
+    state:  web::Data<State>,
+    route:  Route,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Some(count) = self.state.metrics.in_flight.lock().unwrap().get_mut(&self.route) {
+            *count -= 1;
+        }
+    }
+}
+
+// times every request into the histogram of its route
+pub async fn observe(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let state = match request.app_data::<web::Data<State>>().cloned() {
+        Some(state) => state,
+        None        => return next.call(request).await,
+    };
+    let route = (
+        request.method().to_string(),
+        request.match_pattern().unwrap_or_else(|| String::from("unmatched")),
+    );
+    *state.metrics.in_flight.lock().unwrap().entry(route.clone()).or_insert(0) += 1;
+    let in_flight = InFlight { state, route };
+
+    let started = Instant::now();
+    let response = next.call(request).await;
+    let seconds = started.elapsed().as_secs_f64();
+    in_flight.state.metrics.durations.lock().unwrap()
+        .entry(in_flight.route.clone())
+        .or_default()
+        .observe(seconds);
+    return response;
+}
+
+pub(crate) async fn render(state: web::Data<State>) -> impl Responder {
+    return HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4; charset=utf-8")
+        .body(state.metrics.render());
+}
@@ -0,0 +1,714 @@
+// The stored resources and what the rest of the crate needs to know about them
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::hooks::Entry;
+
+// journal entry
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Journal {
+    pub title:       String,
+    pub data:        String,
+    // data is a ciphertext only the client can read, stored as it is and
+    // never searched or rendered
+    #[serde(default)]
+    pub encrypted:   bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags:        Vec<String>,
+    // whatever the client keeps next to the ciphertext, e.g. a key id, or
+    // custom fields, checked against the schema for journals if there is one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata:    Option<Value>,
+    // how the day went and how it felt, 1 (worst) to 5 (best)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mood:        Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub energy:      Option<u8>,
+    // the night before, 0 to 24
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sleep_hours: Option<f64>,
+    // where it was written, in degrees, always both or neither
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lat:         Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lon:         Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub place:       Option<String>,
+    // when the sweeper deletes it, listings leave it out from then on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at:  Option<DateTime<Utc>>,
+    // a draft until then, left out of listings, the feed and share links
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publish_at:  Option<DateTime<Utc>>,
+    // the date of a daily entry, set only when the server creates one
+    #[serde(skip_deserializing, default, skip_serializing_if = "Option::is_none")]
+    pub day:         Option<NaiveDate>,
+    // of data, counted on every write, both 0 while encrypted
+    #[serde(skip_deserializing, default)]
+    pub word_count:  usize,
+    #[serde(skip_deserializing, default)]
+    pub char_count:  usize,
+    #[serde(skip_serializing, default)]
+    pub etag:        String,
+    // bumped on every write, 1 once stored
+    #[serde(skip_deserializing, default)]
+    pub version:     u64,
+    #[serde(skip_deserializing, default)]
+    pub created_at:  DateTime<Utc>,
+    #[serde(skip_deserializing, default)]
+    pub updated_at:  DateTime<Utc>,
+}
+
+// task entry
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Task {
+    pub text:         String,
+    // whether the status is done, kept for clients that know no statuses:
+    // given alone, it closes or reopens the task
+    #[serde(default)]
+    pub done:         bool,
+    // set on every write, from done when not given
+    #[serde(default)]
+    pub status:       Option<Status>,
+    // set instead of deleting, e.g. for merge sources
+    #[serde(default)]
+    pub archived:     bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags:         Vec<String>,
+    #[serde(skip_serializing, default)]
+    pub etag:         String,
+    #[serde(skip_deserializing, default)]
+    pub version:      u64,
+    #[serde(skip_deserializing, default)]
+    pub created_at:   DateTime<Utc>,
+    #[serde(skip_deserializing, default)]
+    pub updated_at:   DateTime<Utc>,
+    // when done last became true, recomputed on every write
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due:          Option<DateTime<Utc>>,
+    // as for journals
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at:   Option<DateTime<Utc>>,
+    // only changed through the task's timer
+    #[serde(skip_deserializing, default, skip_serializing_if = "Vec::is_empty")]
+    pub time_entries: Vec<TimeEntry>,
+    // custom fields, checked against the schema for tasks if there is one
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata:     Map<String, Value>,
+    // where the task goes in the manual order, ascending; new tasks go last
+    // and only moving one changes it
+    #[serde(skip_deserializing, default)]
+    pub position:     i64,
+}
+
+// where a task is in its workflow
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Todo,
+    InProgress,
+    Blocked,
+    Done,
+    Cancelled,
+}
+
+impl Status {
+    pub const ALL: [Status; 5] = [Status::Todo, Status::InProgress, Status::Blocked, Status::Done, Status::Cancelled];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Status::Todo        => "todo",
+            Status::InProgress  => "in_progress",
+            Status::Blocked     => "blocked",
+            Status::Done        => "done",
+            Status::Cancelled   => "cancelled",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Status> {
+        return Status::ALL.into_iter().find(|status| status.name() == name);
+    }
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(self.name());
+    }
+}
+
+// the status changes tasks may make, any when no list is configured; a task
+// may always keep its status
+#[derive(Debug, Clone, Default)]
+pub struct Transitions {
+    allowed: Option<BTreeSet<(Status, Status)>>,
+}
+
+impl Transitions {
+    // "todo>in_progress,in_progress>done,...", each from>to pair allowed
+    pub fn parse(list: &str) -> Result<Transitions, String> {
+        let mut allowed = BTreeSet::new();
+        for pair in list.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let statuses = pair.split_once('>')
+                .and_then(|(from, to)| Some((Status::parse(from.trim())?, Status::parse(to.trim())?)));
+            match statuses {
+                Some(statuses)  => allowed.insert(statuses),
+                None            => return Err(format!("{:?} is not a from>to pair of statuses", pair)),
+            };
+        }
+        return Ok(Transitions { allowed: Some(allowed) });
+    }
+
+    pub fn allows(&self, from: Status, to: Status) -> bool {
+        return from == to || self.allowed.as_ref().is_none_or(|allowed| allowed.contains(&(from, to)));
+    }
+
+    // for a resource replacing previous, Err tells why it may not
+    pub fn check<T: Resource>(&self, previous: Option<&T>, resource: &T) -> Result<(), String> {
+        let (Some(from), Some(to)) = (previous.and_then(T::status), resource.status()) else {
+            return Ok(());
+        };
+        if !self.allows(from, to) {
+            return Err(format!("a {} cannot go from {} to {}", T::KIND, from, to));
+        }
+        return Ok(());
+    }
+}
+
+// between the positions of tasks created one after the other, so a task
+// moved between two of them rarely needs the others renumbered
+pub const POSITION_GAP: i64 = 1024;
+
+// time spent on a task, still running while stopped_at is None
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TimeEntry {
+    pub started_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+}
+
+impl TimeEntry {
+    // up to now if running
+    pub fn duration(&self) -> chrono::Duration {
+        return (self.stopped_at.unwrap_or_else(Utc::now) - self.started_at).max(chrono::Duration::zero());
+    }
+
+    // how much of the entry falls between from and to, up to now if running
+    pub fn within(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> chrono::Duration {
+        let end = self.stopped_at.unwrap_or_else(Utc::now).min(to);
+        let start = self.started_at.max(from);
+        return (end - start).max(chrono::Duration::zero());
+    }
+}
+
+// recurring item, checked in at most once a day
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Habit {
+    pub name:       String,
+    // the days it was done on, only changed through its check-ins
+    #[serde(skip_deserializing, default)]
+    pub checkins:   BTreeSet<NaiveDate>,
+    // custom fields, checked against the schema for habits if there is one
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata:   Map<String, Value>,
+    #[serde(skip_serializing, default)]
+    pub etag:       String,
+    #[serde(skip_deserializing, default)]
+    pub version:    u64,
+    #[serde(skip_deserializing, default)]
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_deserializing, default)]
+    pub updated_at: DateTime<Utc>,
+}
+
+// quick capture, for what is neither a journal entry nor a task
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Note {
+    pub text:       String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags:       Vec<String>,
+    // custom fields, checked against the schema for notes if there is one
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata:   Map<String, Value>,
+    #[serde(skip_serializing, default)]
+    pub etag:       String,
+    #[serde(skip_deserializing, default)]
+    pub version:    u64,
+    #[serde(skip_deserializing, default)]
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_deserializing, default)]
+    pub updated_at: DateTime<Utc>,
+}
+
+// the longest a note can be, in characters
+pub const NOTE_MAX_CHARS: usize = 2000;
+
+// page to read later
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Bookmark {
+    // http or https only
+    pub url:         String,
+    // fetched from the page when left out and JOURNAL_FETCH_TITLES is on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title:       Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags:        Vec<String>,
+    // the journals linking to it, which list it as their bookmarks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub journals:    Vec<usize>,
+    // custom fields, checked against the schema for bookmarks if there is one
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata:    Map<String, Value>,
+    #[serde(skip_serializing, default)]
+    pub etag:        String,
+    #[serde(skip_deserializing, default)]
+    pub version:     u64,
+    #[serde(skip_deserializing, default)]
+    pub created_at:  DateTime<Utc>,
+    #[serde(skip_deserializing, default)]
+    pub updated_at:  DateTime<Utc>,
+}
+
+pub trait Etagged {
+    fn get_etag(&self) -> String;
+    fn set_etag(&mut self, etag: String);
+    fn get_version(&self) -> u64;
+    fn set_version(&mut self, version: u64);
+}
+
+impl Etagged for Journal {
+    fn get_etag(&self) -> String {
+        return self.etag.clone();
+    }
+    fn set_etag(&mut self, etag: String) {
+        self.etag = etag;
+    }
+    fn get_version(&self) -> u64 {
+        return self.version;
+    }
+    fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+}
+
+impl Etagged for Task {
+    fn get_etag(&self) -> String {
+        return self.etag.clone();
+    }
+    fn set_etag(&mut self, etag: String) {
+        self.etag = etag;
+    }
+    fn get_version(&self) -> u64 {
+        return self.version;
+    }
+    fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+}
+
+impl Etagged for Habit {
+    fn get_etag(&self) -> String {
+        return self.etag.clone();
+    }
+    fn set_etag(&mut self, etag: String) {
+        self.etag = etag;
+    }
+    fn get_version(&self) -> u64 {
+        return self.version;
+    }
+    fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+}
+
+impl Etagged for Note {
+    fn get_etag(&self) -> String {
+        return self.etag.clone();
+    }
+    fn set_etag(&mut self, etag: String) {
+        self.etag = etag;
+    }
+    fn get_version(&self) -> u64 {
+        return self.version;
+    }
+    fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+}
+
+impl Etagged for Bookmark {
+    fn get_etag(&self) -> String {
+        return self.etag.clone();
+    }
+    fn set_etag(&mut self, etag: String) {
+        self.etag = etag;
+    }
+    fn get_version(&self) -> u64 {
+        return self.version;
+    }
+    fn set_version(&mut self, version: u64) {
+        self.version = version;
+    }
+}
+
+// kind name and one-line description used in notifications, the checks a
+// resource has to pass before it is stored and its custom fields
+pub trait Resource {
+    const KIND: &'static str;
+    fn summary(&self) -> &str;
+    // itself, as hooks are shown it
+    fn entry(&mut self) -> Entry<'_>;
+    fn metadata(&self) -> Value;
+    // (lat, lon) in degrees, for resources that have one
+    fn location(&self) -> Option<(f64, f64)> {
+        return None;
+    }
+    fn validate(&self) -> Result<(), String> {
+        return Ok(());
+    }
+    // free-form labels, for resources that have them
+    fn tags(&self) -> &[String] {
+        return &[];
+    }
+    // the ids of the journals it links to, for resources that link to some
+    fn journals(&self) -> &[usize] {
+        return &[];
+    }
+    // called once, when the resource is stored under a new id
+    fn place(&mut self, _id: usize) {}
+    // in the manual order, for resources that have one
+    fn position(&self) -> Option<i64> {
+        return None;
+    }
+    // in the workflow, for resources that have one
+    fn status(&self) -> Option<Status> {
+        return None;
+    }
+    // when it goes, for resources that can expire
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        return None;
+    }
+    fn expired(&self, now: DateTime<Utc>) -> bool {
+        return self.expires_at().is_some_and(|expires_at| expires_at <= now);
+    }
+    // when it stops being a draft, for resources that can be scheduled
+    fn publish_at(&self) -> Option<DateTime<Utc>> {
+        return None;
+    }
+    fn draft(&self, now: DateTime<Utc>) -> bool {
+        return self.publish_at().is_some_and(|publish_at| publish_at > now);
+    }
+}
+
+// tags are trimmed, not blank and given once each
+pub(crate) fn validate_tags(tags: &[String]) -> Result<(), String> {
+    for (index, tag) in tags.iter().enumerate() {
+        if tag.trim().is_empty() || tag.trim() != tag {
+            return Err(format!("tag {:?} has to be trimmed and not blank", tag));
+        }
+        if tags[..index].contains(tag) {
+            return Err(format!("tag {:?} is given twice", tag));
+        }
+    }
+    return Ok(());
+}
+
+impl Resource for Journal {
+    const KIND: &'static str = "journal";
+    fn summary(&self) -> &str {
+        return &self.title;
+    }
+    fn entry(&mut self) -> Entry<'_> {
+        return Entry::Journal(self);
+    }
+    fn metadata(&self) -> Value {
+        return self.metadata.clone().unwrap_or_else(|| Value::Object(Map::new()));
+    }
+    fn validate(&self) -> Result<(), String> {
+        for (name, rating) in [("mood", self.mood), ("energy", self.energy)] {
+            if rating.is_some_and(|rating| !(1..=5).contains(&rating)) {
+                return Err(format!("{} has to be between 1 and 5", name));
+            }
+        }
+        if self.sleep_hours.is_some_and(|hours| !(0.0..=24.0).contains(&hours)) {
+            return Err(String::from("sleep_hours has to be between 0 and 24"));
+        }
+        if self.lat.is_some() != self.lon.is_some() {
+            return Err(String::from("lat and lon go together"));
+        }
+        if self.lat.is_some_and(|lat| !(-90.0..=90.0).contains(&lat)) {
+            return Err(String::from("lat has to be between -90 and 90"));
+        }
+        if self.lon.is_some_and(|lon| !(-180.0..=180.0).contains(&lon)) {
+            return Err(String::from("lon has to be between -180 and 180"));
+        }
+        if self.place.as_ref().is_some_and(|place| place.trim().is_empty()) {
+            return Err(String::from("place cannot be blank"));
+        }
+        return validate_tags(&self.tags);
+    }
+    fn location(&self) -> Option<(f64, f64)> {
+        return self.lat.zip(self.lon);
+    }
+    fn tags(&self) -> &[String] {
+        return &self.tags;
+    }
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        return self.expires_at;
+    }
+    fn publish_at(&self) -> Option<DateTime<Utc>> {
+        return self.publish_at;
+    }
+}
+
+impl Resource for Task {
+    const KIND: &'static str = "task";
+    fn summary(&self) -> &str {
+        return &self.text;
+    }
+    fn entry(&mut self) -> Entry<'_> {
+        return Entry::Task(self);
+    }
+    fn metadata(&self) -> Value {
+        return Value::Object(self.metadata.clone());
+    }
+    fn validate(&self) -> Result<(), String> {
+        return validate_tags(&self.tags);
+    }
+    fn tags(&self) -> &[String] {
+        return &self.tags;
+    }
+    // ids only grow, so this puts new tasks after every other
+    fn place(&mut self, id: usize) {
+        self.position = id as i64 * POSITION_GAP;
+    }
+    fn position(&self) -> Option<i64> {
+        return Some(self.position);
+    }
+    fn status(&self) -> Option<Status> {
+        return Some(self.status.unwrap_or(if self.done { Status::Done } else { Status::Todo }));
+    }
+    fn expires_at(&self) -> Option<DateTime<Utc>> {
+        return self.expires_at;
+    }
+}
+
+impl Task {
+    pub fn set_status(&mut self, status: Status) {
+        self.status = Some(status);
+        self.done = status == Status::Done;
+    }
+
+    // closes or reopens the task as done alone would, other statuses than
+    // done stay while the task is not done
+    pub fn mark_done(&mut self, done: bool) {
+        if done != (self.status() == Some(Status::Done)) || self.status.is_none() {
+            self.set_status(if done { Status::Done } else { Status::Todo });
+        }
+    }
+}
+
+impl Resource for Habit {
+    const KIND: &'static str = "habit";
+    fn summary(&self) -> &str {
+        return &self.name;
+    }
+    fn entry(&mut self) -> Entry<'_> {
+        return Entry::Habit(self);
+    }
+    fn metadata(&self) -> Value {
+        return Value::Object(self.metadata.clone());
+    }
+}
+
+impl Resource for Note {
+    const KIND: &'static str = "note";
+    fn summary(&self) -> &str {
+        return &self.text;
+    }
+    fn entry(&mut self) -> Entry<'_> {
+        return Entry::Note(self);
+    }
+    fn metadata(&self) -> Value {
+        return Value::Object(self.metadata.clone());
+    }
+    fn validate(&self) -> Result<(), String> {
+        if self.text.trim().is_empty() {
+            return Err(String::from("text cannot be blank"));
+        }
+        if self.text.chars().count() > NOTE_MAX_CHARS {
+            return Err(format!("text cannot be longer than {} characters", NOTE_MAX_CHARS));
+        }
+        return validate_tags(&self.tags);
+    }
+    fn tags(&self) -> &[String] {
+        return &self.tags;
+    }
+}
+
+impl Resource for Bookmark {
+    const KIND: &'static str = "bookmark";
+    fn summary(&self) -> &str {
+        return self.title.as_deref().unwrap_or(&self.url);
+    }
+    fn entry(&mut self) -> Entry<'_> {
+        return Entry::Bookmark(self);
+    }
+    fn metadata(&self) -> Value {
+        return Value::Object(self.metadata.clone());
+    }
+    fn validate(&self) -> Result<(), String> {
+        let url = reqwest::Url::parse(&self.url).map_err(|err| format!("url {:?} is invalid: {}", self.url, err))?;
+        if !matches!(url.scheme(), "http" | "https") || url.host_str().is_none_or(str::is_empty) {
+            return Err(format!("url {:?} has to be an http or https URL with a host", self.url));
+        }
+        for (name, text) in [("title", &self.title), ("description", &self.description)] {
+            if text.as_ref().is_some_and(|text| text.trim().is_empty()) {
+                return Err(format!("{} cannot be blank", name));
+            }
+        }
+        for (index, journal) in self.journals.iter().enumerate() {
+            if self.journals[..index].contains(journal) {
+                return Err(format!("journal {} is given twice", journal));
+            }
+        }
+        return validate_tags(&self.tags);
+    }
+    fn tags(&self) -> &[String] {
+        return &self.tags;
+    }
+    fn journals(&self) -> &[usize] {
+        return &self.journals;
+    }
+}
+
+// server-managed creation and modification times
+pub trait Timestamped {
+    fn get_created_at(&self) -> DateTime<Utc>;
+    fn set_timestamps(&mut self, created_at: DateTime<Utc>, updated_at: DateTime<Utc>);
+    // for resources that can be completed
+    fn get_completed_at(&self) -> Option<DateTime<Utc>> {
+        return None;
+    }
+    // fills the server-managed fields on every write, with the version it
+    // replaces or None for new resources; its version counter is carried
+    // over for etag::refresh to bump
+    fn track_changes(&mut self, _previous: Option<&Self>, _now: DateTime<Utc>) {}
+}
+
+impl Timestamped for Journal {
+    fn get_created_at(&self) -> DateTime<Utc> {
+        return self.created_at;
+    }
+    fn set_timestamps(&mut self, created_at: DateTime<Utc>, updated_at: DateTime<Utc>) {
+        self.created_at = created_at;
+        self.updated_at = updated_at;
+    }
+    // a daily entry stays one
+    fn track_changes(&mut self, previous: Option<&Self>, _now: DateTime<Utc>) {
+        (self.word_count, self.char_count) = match self.encrypted {
+            true    => (0, 0),
+            false   => (self.data.split_whitespace().count(), self.data.chars().count()),
+        };
+        if let Some(previous) = previous {
+            self.day = previous.day;
+            self.version = previous.version;
+        }
+    }
+}
+
+impl Timestamped for Task {
+    fn get_created_at(&self) -> DateTime<Utc> {
+        return self.created_at;
+    }
+    fn set_timestamps(&mut self, created_at: DateTime<Utc>, updated_at: DateTime<Utc>) {
+        self.created_at = created_at;
+        self.updated_at = updated_at;
+    }
+    fn get_completed_at(&self) -> Option<DateTime<Utc>> {
+        return self.completed_at;
+    }
+    // a status that was given and changed decides done, otherwise done
+    // decides the status as mark_done does; completed_at is set when done
+    // flips to true, kept while it stays true and cleared when the task is
+    // reopened; the time entries and the position are kept
+    fn track_changes(&mut self, previous: Option<&Self>, now: DateTime<Utc>) {
+        let status = match (self.status, previous.and_then(Resource::status)) {
+            (Some(status), previous) if previous != Some(status)                    => status,
+            (Some(status), _) | (None, Some(status)) if (status == Status::Done) == self.done => status,
+            _ if self.done                                                          => Status::Done,
+            _                                                                       => Status::Todo,
+        };
+        self.set_status(status);
+        self.completed_at = match previous {
+            _ if !self.done                 => None,
+            Some(previous) if previous.done => previous.completed_at.or(Some(now)),
+            _                               => Some(now),
+        };
+        if let Some(previous) = previous {
+            self.time_entries = previous.time_entries.clone();
+            self.position = previous.position;
+            self.version = previous.version;
+        }
+    }
+}
+
+impl Timestamped for Habit {
+    fn get_created_at(&self) -> DateTime<Utc> {
+        return self.created_at;
+    }
+    fn set_timestamps(&mut self, created_at: DateTime<Utc>, updated_at: DateTime<Utc>) {
+        self.created_at = created_at;
+        self.updated_at = updated_at;
+    }
+    // replacing a habit keeps its check-ins
+    fn track_changes(&mut self, previous: Option<&Self>, _now: DateTime<Utc>) {
+        if let Some(previous) = previous {
+            self.checkins = previous.checkins.clone();
+            self.version = previous.version;
+        }
+    }
+}
+
+impl Timestamped for Note {
+    fn get_created_at(&self) -> DateTime<Utc> {
+        return self.created_at;
+    }
+    fn set_timestamps(&mut self, created_at: DateTime<Utc>, updated_at: DateTime<Utc>) {
+        self.created_at = created_at;
+        self.updated_at = updated_at;
+    }
+    fn track_changes(&mut self, previous: Option<&Self>, _now: DateTime<Utc>) {
+        if let Some(previous) = previous {
+            self.version = previous.version;
+        }
+    }
+}
+
+impl Timestamped for Bookmark {
+    fn get_created_at(&self) -> DateTime<Utc> {
+        return self.created_at;
+    }
+    fn set_timestamps(&mut self, created_at: DateTime<Utc>, updated_at: DateTime<Utc>) {
+        self.created_at = created_at;
+        self.updated_at = updated_at;
+    }
+    fn track_changes(&mut self, previous: Option<&Self>, _now: DateTime<Utc>) {
+        if let Some(previous) = previous {
+            self.version = previous.version;
+        }
+    }
+}
+
+// a resource together with its id, which the stored models do not carry
+#[derive(Debug, Serialize)]
+pub struct WithId<'a, T> {
+    pub id: usize,
+    #[serde(flatten)]
+    pub resource: &'a T,
+}
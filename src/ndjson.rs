@@ -0,0 +1,36 @@
+// Newline-delimited JSON responses, serialized while they are sent so large
+// listings and exports never sit in memory as a whole
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+use futures_util::stream::{self, StreamExt};
+
+pub const CONTENT_TYPE: &str = "application/x-ndjson";
+
+// lines are sent in chunks of this many
+const CHUNK_LINES: usize = 64;
+
+// the client asked for NDJSON through the Accept header
+pub fn wanted(request: &HttpRequest) -> bool {
+    match request.headers().get("Accept").and_then(|accept| accept.to_str().ok()) {
+        Some(accept)    => accept.split(',').any(|kind| kind.trim().starts_with(CONTENT_TYPE)),
+        None            => false,
+    }
+}
+
+// one JSON document per line, a failing line ends the response early
+pub fn respond<I>(response: &mut HttpResponseBuilder, lines: I) -> HttpResponse
+where I: Iterator<Item = serde_json::Result<String>> + 'static {
+    let chunks = stream::iter(lines)
+        .chunks(CHUNK_LINES)
+        .map(|lines| {
+            let mut chunk = String::new();
+            for line in lines {
+                chunk.push_str(&line.map_err(actix_web::error::ErrorInternalServerError)?);
+                chunk.push('\n');
+            }
+            Ok::<Bytes, actix_web::Error>(Bytes::from(chunk))
+        });
+    response
+        .content_type(CONTENT_TYPE)
+        .streaming(chunks)
+}
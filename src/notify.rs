@@ -0,0 +1,188 @@
+// Slack / Discord incoming-webhook notifications for task and journal events
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::mpsc;
+
+use crate::models::Resource;
+use crate::signing;
+
+const SIGNATURE_HEADER: &str = "X-Journal-Signature";
+
+// A JSON POST of the payload, signed when the webhook has a secret so its
+// receiver can tell the delivery is ours: "X-Journal-Signature: sha256=<hex
+// HMAC-SHA256 of the body>"
+pub(crate) fn post<T: Serialize>(client: &reqwest::Client, url: &str, payload: &T, secret: Option<&str>) -> reqwest::RequestBuilder {
+    let body = serde_json::to_vec(payload).unwrap_or_default();
+    let mut request = client.post(url).header("Content-Type", "application/json");
+    if let Some(secret) = secret {
+        request = request.header(SIGNATURE_HEADER, format!("sha256={}", signing::sign(secret, &body)));
+    }
+    return request.body(body);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Created,
+    Updated,
+    Deleted,
+    Merged,
+    Split,
+}
+
+impl Action {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Action::Created => "created",
+            Action::Updated => "updated",
+            Action::Deleted => "deleted",
+            Action::Merged  => "merged",
+            Action::Split   => "split",
+        }
+    }
+}
+
+// What a change did to one entry, published on the event bus
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub kind:       &'static str,
+    pub action:     Action,
+    pub id:         usize,
+    pub summary:    String,
+    // the workspace the entry is in, None for the server's own
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace:  Option<usize>,
+    pub at:         DateTime<Utc>,
+}
+
+impl Event {
+    pub fn of<T: Resource>(action: Action, id: usize, resource: &T) -> Event {
+        Event {
+            kind:       T::KIND,
+            action,
+            id,
+            summary:    String::from(resource.summary()),
+            workspace:  None,
+            at:         Utc::now(),
+        }
+    }
+
+    // "task.created"
+    pub fn name(&self) -> String {
+        format!("{}.{}", self.kind, self.action.as_str())
+    }
+
+    // where the entry can be read, absolute with the configured base URL;
+    // deleted ones are nowhere
+    fn url(&self, base_url: &str) -> Option<String> {
+        if self.action == Action::Deleted {
+            return None;
+        }
+        let space = self.workspace.map(|wid| format!("/workspaces/{}", wid)).unwrap_or_default();
+        return Some(format!("{}/v1{}/{}s/{}", base_url, space, self.kind, self.id));
+    }
+
+    fn message(&self, base_url: Option<&str>) -> String {
+        let message = format!("{} #{} {}: {}", capitalize(self.kind), self.id, self.action.as_str(), self.summary);
+        match base_url.and_then(|base_url| self.url(base_url)) {
+            Some(url)   => format!("{}\n{}", message, url),
+            None        => message,
+        }
+    }
+}
+
+// hands the events of a workspace's collections on, marked as theirs
+pub(crate) async fn forward(
+    mut queue: mpsc::UnboundedReceiver<Event>,
+    events: mpsc::UnboundedSender<Event>,
+    workspace: usize,
+) {
+    while let Some(event) = queue.recv().await {
+        let _ = events.send(Event { workspace: Some(workspace), ..event });
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None        => String::new(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flavor {
+    Slack,
+    Discord,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookTarget {
+    pub flavor: Flavor,
+    pub url:    String,
+    // event names or patterns ("task.created", "journal.*", "*"), empty means all
+    pub events: Vec<String>,
+    // what deliveries are signed with, unsigned without
+    pub secret: Option<String>,
+}
+
+impl WebhookTarget {
+    // reads <PREFIX>_WEBHOOK_URL, the optional comma separated <PREFIX>_EVENTS
+    // and the optional <PREFIX>_WEBHOOK_SECRET
+    pub fn from_env(flavor: Flavor, prefix: &str) -> Option<WebhookTarget> {
+        let url = std::env::var(format!("{}_WEBHOOK_URL", prefix)).ok()?;
+        let events = std::env::var(format!("{}_EVENTS", prefix))
+            .map(|events| events.split(',')
+                .map(|event| String::from(event.trim()))
+                .filter(|event| !event.is_empty())
+                .collect())
+            .unwrap_or_default();
+        let secret = std::env::var(format!("{}_WEBHOOK_SECRET", prefix)).ok().filter(|secret| !secret.is_empty());
+        Some(WebhookTarget { flavor, url, events, secret })
+    }
+
+    fn wants(&self, event: &Event) -> bool {
+        if self.events.is_empty() {
+            return true;
+        }
+        let name = event.name();
+        return self.events.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix)    => name.starts_with(prefix),
+            None            => *pattern == name,
+        });
+    }
+
+    fn payload(&self, event: &Event, base_url: Option<&str>) -> Value {
+        match self.flavor {
+            Flavor::Slack   => json!({ "text": event.message(base_url) }),
+            Flavor::Discord => json!({ "content": event.message(base_url) }),
+        }
+    }
+}
+
+pub struct Notifier {
+    client:     reqwest::Client,
+    // messages link to the entry when set
+    base_url:   Option<String>,
+}
+
+impl Notifier {
+    pub fn new(base_url: Option<String>) -> Notifier {
+        Notifier { client: reqwest::Client::new(), base_url }
+    }
+
+    // deliveries run in the background and never fail the request
+    pub fn notify(&self, targets: &[WebhookTarget], event: &Event) {
+        for target in targets.iter().filter(|target| target.wants(event)) {
+            let payload = target.payload(event, self.base_url.as_deref());
+            let delivery = post(&self.client, &target.url, &payload, target.secret.as_deref()).send();
+            let name = event.name();
+            tokio::spawn(async move {
+                if let Err(err) = delivery.await.and_then(|resp| resp.error_for_status()) {
+                    println!("Webhook delivery of {} failed: {}", name, err);
+                }
+            });
+        }
+    }
+}
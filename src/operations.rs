@@ -0,0 +1,241 @@
+// Long-running operations in the background: exports and imports sent with
+// `Prefer: respond-async` answer 202 Accepted at once, with a Location of
+// /jobs/{id} that tells how far the operation got and, once it finished,
+// where its result is or why it failed. Without the preference they answer
+// as they always did.
+use actix_web::body::{self, EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::http::header::HeaderMap;
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use actix_web::web::{self, Bytes, BytesMut};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Duration, Utc};
+use futures_util::StreamExt;
+use serde::Serialize;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+use crate::auth::random_key;
+use crate::handlers::Rejection;
+use crate::state::State;
+use crate::takeout::IMPORT_LIMIT;
+use crate::versioning;
+
+// how long a finished operation can still be asked about
+const RETENTION: Duration = Duration::hours(1);
+
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Status {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Clone, Serialize)]
+struct Progress {
+    done:   usize,
+    total:  usize,
+}
+
+#[derive(Clone, Serialize)]
+struct Failure {
+    status: u16,
+    detail: String,
+}
+
+// what the operation answered, kept for GET /jobs/{id}/result
+struct Output {
+    status:     StatusCode,
+    headers:    HeaderMap,
+    body:       Bytes,
+}
+
+pub(crate) struct Operation {
+    // the method and path that started it
+    kind:           String,
+    status:         Status,
+    progress:       Option<Progress>,
+    created_at:     DateTime<Utc>,
+    finished_at:    Option<DateTime<Utc>>,
+    output:         Option<Output>,
+}
+
+// handed to the handler in the request's extensions, so it can report
+#[derive(Clone)]
+struct Running(Arc<Mutex<Operation>>);
+
+// Tells the client of a deferred request how far it got; requests answered
+// right away have nobody to tell
+pub(crate) fn report(request: &HttpRequest, done: usize, total: usize) {
+    if let Some(Running(operation)) = request.extensions().get::<Running>() {
+        operation.lock().unwrap().progress = Some(Progress { done, total });
+    }
+}
+
+fn respond_async(request: &ServiceRequest) -> bool {
+    return request.headers().get_all("Prefer")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|preference| preference.trim().eq_ignore_ascii_case("respond-async"));
+}
+
+// Reads the whole body and puts it back for the handler, for middleware
+// that needs it before the handler runs
+pub(crate) async fn buffer(request: &mut ServiceRequest) -> Result<Bytes, HttpResponse> {
+    let mut payload = request.take_payload();
+    let mut body = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|_| HttpResponse::BadRequest().body("Broken payload"))?;
+        if body.len() + chunk.len() > IMPORT_LIMIT {
+            return Err(HttpResponse::PayloadTooLarge().finish());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let body = body.freeze();
+    let replay = body.clone();
+    request.set_payload(Payload::Stream { payload: Box::pin(futures_util::stream::once(async { Ok(replay) })) });
+    return Ok(body);
+}
+
+async fn output<B: MessageBody>(response: ServiceResponse<B>) -> Output {
+    let status = response.status();
+    let headers = response.headers().clone();
+    return match body::to_bytes(response.into_body()).await {
+        Ok(body)    => Output { status, headers, body },
+        Err(_)      => Output {
+            status:     StatusCode::INTERNAL_SERVER_ERROR,
+            headers:    HeaderMap::new(),
+            body:       Bytes::from_static(b"Broken response"),
+        },
+    };
+}
+
+pub async fn defer(
+    mut request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    if !respond_async(&request) {
+        return Ok(next.call(request).await?.map_into_left_body());
+    }
+    // read before answering, as the connection moves on then
+    if let Err(resp) = buffer(&mut request).await {
+        return Ok(request.into_response(resp).map_into_right_body());
+    }
+
+    let state = request.app_data::<web::Data<State>>().cloned()
+        .ok_or_else(|| actix_web::error::ErrorInternalServerError("No state"))?;
+    let id = random_key();
+    let operation = Arc::new(Mutex::new(Operation {
+        kind:           format!("{} {}", request.method(), request.path()),
+        status:         Status::Running,
+        progress:       None,
+        created_at:     Utc::now(),
+        finished_at:    None,
+        output:         None,
+    }));
+    {
+        let mut operations = state.operations.lock().unwrap();
+        let now = Utc::now();
+        operations.retain(|_, operation| {
+            let operation = operation.lock().unwrap();
+            return operation.finished_at.is_none_or(|finished| now - finished < RETENTION);
+        });
+        operations.insert(id.clone(), operation.clone());
+    }
+    request.extensions_mut().insert(Running(operation.clone()));
+
+    let location = format!("{}/jobs/{}", versioning::root(request.request()), id);
+    let view = view(&operation.lock().unwrap(), &location);
+    let accepted = request.request().clone();
+    let http = request.request().clone();
+    let running = next.call(request);
+    actix_web::rt::spawn(async move {
+        let output = match running.await {
+            Ok(response)    => output(response).await,
+            Err(error)      => output(ServiceResponse::new(http, error.error_response())).await,
+        };
+        let mut operation = operation.lock().unwrap();
+        operation.status = if output.status.is_success() { Status::Succeeded } else { Status::Failed };
+        operation.finished_at = Some(Utc::now());
+        operation.output = Some(output);
+    });
+
+    let response = HttpResponse::Accepted()
+        .append_header(("Location", location))
+        .append_header(("Preference-Applied", "respond-async"))
+        .json(view);
+    return Ok(ServiceResponse::new(accepted, response).map_into_right_body());
+}
+
+#[derive(Serialize)]
+struct OperationView {
+    kind:           String,
+    status:         Status,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    progress:       Option<Progress>,
+    created_at:     DateTime<Utc>,
+    finished_at:    Option<DateTime<Utc>>,
+    // where what it answered is, once it succeeded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result:         Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error:          Option<Failure>,
+}
+
+// the detail of a problem, or the whole body of any other answer
+fn detail(body: &Bytes) -> String {
+    let problem: Option<Value> = serde_json::from_slice(body).ok();
+    return match problem.as_ref().and_then(|problem| problem["detail"].as_str()) {
+        Some(detail)    => String::from(detail),
+        None            => String::from_utf8_lossy(body).into_owned(),
+    };
+}
+
+fn view(operation: &Operation, location: &str) -> OperationView {
+    let succeeded = operation.status == Status::Succeeded;
+    return OperationView {
+        kind:           operation.kind.clone(),
+        status:         operation.status,
+        progress:       operation.progress.clone(),
+        created_at:     operation.created_at,
+        finished_at:    operation.finished_at,
+        result:         succeeded.then(|| format!("{}/result", location)),
+        error:          operation.output.as_ref().filter(|_| !succeeded).map(|output| Failure {
+            status: output.status.as_u16(),
+            detail: detail(&output.body),
+        }),
+    };
+}
+
+fn find(state: &State, id: &str) -> Result<Arc<Mutex<Operation>>, Rejection> {
+    return state.operations.lock().unwrap().get(id).cloned()
+        .ok_or_else(|| Rejection::new(StatusCode::NOT_FOUND, format!("No job {}", id)));
+}
+
+pub(crate) async fn show(state: web::Data<State>, id: web::Path<String>, request: HttpRequest) -> impl Responder {
+    let operation = match find(&state, &id) {
+        Ok(operation)   => operation,
+        Err(rejection)  => return rejection.into(),
+    };
+    let location = format!("{}/jobs/{}", versioning::root(&request), id);
+    return HttpResponse::Ok().json(view(&operation.lock().unwrap(), &location));
+}
+
+// what the operation answered, as it would have right away
+pub(crate) async fn result(state: web::Data<State>, id: web::Path<String>) -> impl Responder {
+    let operation = match find(&state, &id) {
+        Ok(operation)   => operation,
+        Err(rejection)  => return rejection.into(),
+    };
+    let operation = operation.lock().unwrap();
+    let Some(output) = &operation.output else {
+        return Rejection::new(StatusCode::CONFLICT, format!("Job {} is still running", id)).into();
+    };
+    let mut response = HttpResponse::build(output.status);
+    for (name, value) in output.headers.iter() {
+        response.append_header((name.clone(), value.clone()));
+    }
+    return response.body(output.body.clone());
+}
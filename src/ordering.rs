@@ -0,0 +1,121 @@
+// Manual order of tasks, e.g. for drag and drop: a task is moved right
+// before or after another one by giving it a position between theirs
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::response_token;
+use crate::etag;
+use crate::handlers::{IdPath, Rejection};
+use crate::models::{Task, POSITION_GAP};
+use crate::notify::{Action, Event};
+use crate::state::State;
+use crate::store::Writer;
+use crate::workspace::{Level, Space};
+
+// exactly one of them, the id of the task to move next to
+#[derive(Deserialize)]
+pub(crate) struct Move {
+    before: Option<usize>,
+    after:  Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Moved {
+    id:         usize,
+    position:   i64,
+}
+
+// the position between the neighbours of the slot, None when there is no
+// room left between them
+fn between(previous: Option<i64>, next: Option<i64>) -> Option<i64> {
+    match (previous, next) {
+        (Some(previous), Some(next))    => (next - previous >= 2).then(|| previous + (next - previous) / 2),
+        (Some(previous), None)          => Some(previous + POSITION_GAP),
+        (None, Some(next))              => Some(next - POSITION_GAP),
+        (None, None)                    => Some(0),
+    }
+}
+
+fn refresh(task: &mut Task) -> Result<(), Rejection> {
+    task.updated_at = Utc::now();
+    return etag::refresh(task)
+        .map(|_| ())
+        .map_err(|_| Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, "Json error"));
+}
+
+fn move_task(tasks: &mut Writer<'_, Task>, id: usize, anchor: usize, after: bool) -> Result<(i64, String), Rejection> {
+    if id == anchor {
+        return Err(Rejection::new(StatusCode::BAD_REQUEST, "A task cannot move next to itself"));
+    }
+    if tasks.get(&id).is_none() {
+        return Err(Rejection::new(StatusCode::NOT_FOUND, "Not found"));
+    }
+    // every other task in order, and where the moved one goes among them
+    let mut order: Vec<(i64, usize)> = tasks.ids().into_iter()
+        .filter(|other| *other != id)
+        .filter_map(|other| Some((tasks.get(&other)?.position, other)))
+        .collect();
+    order.sort();
+    let slot = match order.iter().position(|(_, other)| *other == anchor) {
+        Some(index) if after    => index + 1,
+        Some(index)             => index,
+        None                    => return Err(Rejection::new(StatusCode::NOT_FOUND, format!("Task {} not found", anchor))),
+    };
+
+    let previous = slot.checked_sub(1).map(|index| order[index].0);
+    let next = order.get(slot).map(|(position, _)| *position);
+    let position = match between(previous, next) {
+        Some(position)  => position,
+        // out of room: every task is spread out again, in the new order
+        None            => {
+            order.insert(slot, (0, id));
+            for (index, (_, other)) in order.iter().enumerate() {
+                if *other != id {
+                    let mut task = tasks.get_mut(other).expect("the task was just listed");
+                    task.position = index as i64 * POSITION_GAP;
+                    refresh(&mut task)?;
+                }
+            }
+            slot as i64 * POSITION_GAP
+        }
+    };
+
+    let mut task = tasks.get_mut(&id).expect("the task was just found");
+    task.position = position;
+    refresh(&mut task)?;
+    let event = Event::of(Action::Updated, id, &*task);
+    let etag = task.etag.clone();
+    drop(task);
+    tasks.emit(event);
+    return Ok((position, etag));
+}
+
+pub(crate) async fn move_to(
+    json: web::Json<Move>,
+    state: web::Data<State>,
+    space: Space,
+    path: web::Path<IdPath>,
+    request: HttpRequest,
+) -> impl Responder {
+    let id = path.id;
+    if let Err(rejection) = space.allow::<Task>(Some(id), Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let (anchor, after) = match (json.before, json.after) {
+        (Some(before), None)    => (before, false),
+        (None, Some(after))     => (after, true),
+        _                       => return HttpResponse::BadRequest().body("Expected either before or after"),
+    };
+    let moved = space.tasks.change(move |tasks| move_task(tasks, id, anchor, after)).await.unwrap_or_else(|failed| Err(failed.into()));
+    return match moved {
+        Ok((position, etag)) => HttpResponse::Ok()
+            .append_header(("ETag", etag))
+            .json(Moved { id, position }),
+        Err(rejection) => rejection.into(),
+    };
+}
@@ -0,0 +1,117 @@
+// Requests the server sends to URLs clients gave it, bookmark pages and
+// the webhooks of schedules: only to public addresses, every redirect and
+// every resolved name checked again, so clients cannot reach services
+// inside the network through the server unless JOURNAL_FETCH_PRIVATE
+// allows loopback, private and link-local ones too.
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::{self, Attempt};
+use reqwest::Url;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+const MAX_REDIRECTS: usize = 5;
+
+// whether the address is reachable from anywhere, rather than only from
+// this host or its network
+pub(crate) fn public(ip: IpAddr) -> bool {
+    return match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+                || ip.is_broadcast() || ip.is_documentation() || ip.is_multicast()
+                // this network, shared address space (carrier-grade NAT), reserved
+                || a == 0 || (a == 100 && (64..128).contains(&b)) || a >= 240)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped)    => public(IpAddr::V4(mapped)),
+            None            => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback() || ip.is_unspecified() || ip.is_multicast()
+                    // unique local, link-local, documentation
+                    || (first & 0xfe00) == 0xfc00 || (first & 0xffc0) == 0xfe80 || first == 0x2001 && ip.segments()[1] == 0x0db8)
+            }
+        },
+    };
+}
+
+// Err unless the URL is http or https on a host that may be fetched; names
+// are checked once resolved, by PublicOnly
+pub(crate) fn fetchable(url: &Url, private: bool) -> Result<(), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("{} is not an http or https URL", url));
+    }
+    let Some(host) = url.host_str() else {
+        return Err(format!("{} has no host", url));
+    };
+    // IPv6 addresses are in brackets
+    let Ok(ip) = host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() else {
+        return Ok(());
+    };
+    if !private && !public(ip) {
+        return Err(format!("{} is not a public address", ip));
+    }
+    return Ok(());
+}
+
+// resolves host names to their public addresses only, so a name cannot
+// point the fetch inside the network, not even after it was checked
+struct PublicOnly;
+
+impl Resolve for PublicOnly {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = String::from(name.as_str());
+        return Box::pin(async move {
+            let resolving = host.clone();
+            let addrs: Vec<_> = tokio::task::spawn_blocking(move || (resolving.as_str(), 0).to_socket_addrs()).await??
+                .filter(|addr| public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", host).into());
+            }
+            return Ok(Box::new(addrs.into_iter()) as Addrs);
+        });
+    }
+}
+
+// the client to fetch them with, following redirects only to where the
+// first request could have gone
+pub(crate) fn client(private: bool, timeout: Duration) -> reqwest::Result<reqwest::Client> {
+    let policy = redirect::Policy::custom(move |attempt: Attempt<'_>| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error("too many redirects");
+        }
+        return match fetchable(attempt.url(), private) {
+            Ok(())      => attempt.follow(),
+            Err(reason) => attempt.error(reason),
+        };
+    });
+    let builder = reqwest::Client::builder().timeout(timeout).redirect(policy).no_proxy();
+    return match private {
+        true    => builder.build(),
+        false   => builder.dns_resolver(Arc::new(PublicOnly)).build(),
+    };
+}
+
+// Err unless the URL may be fetched and its host resolves to public
+// addresses only, for URLs kept to fetch later; where they resolve to by
+// then is checked again
+pub(crate) async fn check(url: &str, private: bool) -> Result<(), String> {
+    let url = Url::parse(url).map_err(|err| format!("{} is no URL: {}", url, err))?;
+    fetchable(&url, private)?;
+    let (Some(host), Some(port)) = (url.host_str(), url.port_or_known_default()) else {
+        return Ok(());
+    };
+    if private {
+        return Ok(());
+    }
+    let resolving = String::from(host.trim_start_matches('[').trim_end_matches(']'));
+    let addrs: Vec<_> = tokio::task::spawn_blocking(move || (resolving.as_str(), port).to_socket_addrs()).await
+        .map_err(|err| err.to_string())?
+        .map_err(|err| format!("{} does not resolve: {}", host, err))?
+        .collect();
+    if let Some(addr) = addrs.iter().find(|addr| !public(addr.ip())) {
+        return Err(format!("{} resolves to {}, which is not a public address", host, addr.ip()));
+    }
+    return Ok(());
+}
@@ -0,0 +1,229 @@
+// WASM plugins, hooks that are loaded instead of compiled in: every *.wasm
+// in JOURNAL_PLUGIN_DIR is registered as a hook at startup, in file name
+// order. A plugin exports its memory, alloc(len) -> ptr for the server to
+// write its input into, and any of on_create, on_update, on_delete and
+// on_merge, each (ptr, len) -> i64. The input is JSON,
+//
+//     {"event": "create", "kind": "task", "id": 3, "ids": [1, 2], "entry": {...}}
+//
+// with id, ids and entry only where the event has them. Returning 0 lets the
+// write through unchanged; anything else is (ptr << 32) | len of a JSON
+// answer in its memory, {"entry": {...}} with the fields to change or
+// {"error": "reason"} to refuse the write.
+//
+// Plugins are sandboxed: they may import nothing, run on a budget of fuel
+// and get little memory, in a fresh instance for every call. One that traps
+// or runs out refuses the write.
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::Path;
+use std::sync::Arc;
+use wasmi::{Engine, Linker, Module, Store, StoreLimits, StoreLimitsBuilder};
+
+use crate::hooks::{Entry, Hook};
+
+// instructions, roughly, a call may run
+const FUEL: u64 = 10_000_000;
+const MEMORY: usize = 16 << 20;
+
+struct Plugin {
+    name:   String,
+    engine: Engine,
+    module: Module,
+}
+
+#[derive(Serialize)]
+struct Input<'a> {
+    event:  &'static str,
+    kind:   &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id:     Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ids:    Option<&'a [usize]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    entry:  Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct Answer {
+    #[serde(default)]
+    entry:  Option<Map<String, Value>>,
+    #[serde(default)]
+    error:  Option<String>,
+}
+
+// every plugin in the directory, those that do not load are left out
+pub(crate) fn load(dir: &Path) -> Vec<Arc<dyn Hook>> {
+    let mut paths: Vec<_> = match std::fs::read_dir(dir) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|extension| extension == "wasm"))
+            .collect(),
+        Err(err)    => {
+            println!("Ignoring JOURNAL_PLUGIN_DIR, {}: {}", dir.display(), err);
+            return Vec::new();
+        }
+    };
+    paths.sort();
+    let mut plugins: Vec<Arc<dyn Hook>> = Vec::new();
+    for path in paths {
+        match Plugin::load(&path) {
+            Ok(plugin)  => {
+                println!("Loaded plugin {}", plugin.name);
+                plugins.push(Arc::new(plugin));
+            }
+            Err(err)    => println!("Ignoring plugin {}: {}", path.display(), err),
+        }
+    }
+    return plugins;
+}
+
+impl Plugin {
+    fn load(path: &Path) -> Result<Plugin, String> {
+        let wasm = std::fs::read(path).map_err(|err| err.to_string())?;
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, &wasm[..]).map_err(|err| err.to_string())?;
+        if let Some(import) = module.imports().next() {
+            return Err(format!("plugins may import nothing, it imports {}.{}", import.module(), import.name()));
+        }
+        let name = path.file_stem().map_or_else(|| path.display().to_string(), |stem| stem.to_string_lossy().into_owned());
+        return Ok(Plugin { name, engine, module });
+    }
+
+    // the plugin's answer, None when it has no such export or returns 0
+    fn call(&self, export: &str, input: &Input<'_>) -> Result<Option<Answer>, String> {
+        if self.module.get_export(export).is_none() {
+            return Ok(None);
+        }
+        let failed = |err: &dyn std::fmt::Display| format!("Plugin {} failed: {}", self.name, err);
+        let mut store = Store::new(&self.engine, StoreLimitsBuilder::new().memory_size(MEMORY).instances(1).build());
+        store.limiter(|limits: &mut StoreLimits| limits);
+        store.set_fuel(FUEL).map_err(|err| failed(&err))?;
+        let instance = Linker::<StoreLimits>::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|err| failed(&err))?;
+        let memory = instance.get_memory(&store, "memory").ok_or_else(|| failed(&"it exports no memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32>(&store, "alloc").map_err(|err| failed(&err))?;
+        let handler = instance.get_typed_func::<(i32, i32), i64>(&store, export).map_err(|err| failed(&err))?;
+
+        let input = serde_json::to_vec(input).map_err(|err| failed(&err))?;
+        let len = i32::try_from(input.len()).map_err(|err| failed(&err))?;
+        let at = alloc.call(&mut store, len).map_err(|err| failed(&err))?;
+        memory.write(&mut store, at as u32 as usize, &input).map_err(|err| failed(&err))?;
+        let packed = handler.call(&mut store, (at, len)).map_err(|err| failed(&err))? as u64;
+        if packed == 0 {
+            return Ok(None);
+        }
+        let mut output = vec![0; (packed & 0xffff_ffff) as usize];
+        memory.read(&store, (packed >> 32) as usize, &mut output).map_err(|err| failed(&err))?;
+        let answer = serde_json::from_slice(&output).map_err(|err| failed(&format!("broken answer, {}", err)))?;
+        return Ok(Some(answer));
+    }
+
+    // runs the export on the entry and applies the answer to it
+    fn change(&self, export: &str, input: Input<'_>, entry: &mut Entry<'_>) -> Result<(), String> {
+        let answer = match self.call(export, &Input { entry: Some(json(entry)?), ..input })? {
+            Some(answer)    => answer,
+            None            => return Ok(()),
+        };
+        if let Some(reason) = answer.error {
+            return Err(reason);
+        }
+        let Some(fields) = answer.entry else {
+            return Ok(());
+        };
+        let applied = match entry {
+            Entry::Journal(journal) => apply(&mut **journal, fields, |journal, old| {
+                journal.day = old.day;
+                journal.etag = old.etag;
+                journal.version = old.version;
+                journal.created_at = old.created_at;
+                journal.updated_at = old.updated_at;
+            }),
+            Entry::Task(task)       => apply(&mut **task, fields, |task, old| {
+                task.etag = old.etag;
+                task.version = old.version;
+                task.created_at = old.created_at;
+                task.updated_at = old.updated_at;
+                task.time_entries = old.time_entries;
+                task.position = old.position;
+            }),
+            Entry::Habit(habit)     => apply(&mut **habit, fields, |habit, old| {
+                habit.checkins = old.checkins;
+                habit.etag = old.etag;
+                habit.version = old.version;
+                habit.created_at = old.created_at;
+                habit.updated_at = old.updated_at;
+            }),
+            Entry::Note(note)       => apply(&mut **note, fields, |note, old| {
+                note.etag = old.etag;
+                note.version = old.version;
+                note.created_at = old.created_at;
+                note.updated_at = old.updated_at;
+            }),
+            Entry::Bookmark(bookmark) => apply(&mut **bookmark, fields, |bookmark, old| {
+                bookmark.etag = old.etag;
+                bookmark.version = old.version;
+                bookmark.created_at = old.created_at;
+                bookmark.updated_at = old.updated_at;
+            }),
+        };
+        return applied.map_err(|err| format!("Plugin {} failed: broken entry, {}", self.name, err));
+    }
+}
+
+fn json(entry: &Entry<'_>) -> Result<Value, String> {
+    let json = match entry {
+        Entry::Journal(journal) => serde_json::to_value(&**journal),
+        Entry::Task(task)       => serde_json::to_value(&**task),
+        Entry::Habit(habit)     => serde_json::to_value(&**habit),
+        Entry::Note(note)       => serde_json::to_value(&**note),
+        Entry::Bookmark(bookmark) => serde_json::to_value(&**bookmark),
+    };
+    return json.map_err(|err| err.to_string());
+}
+
+// the fields the plugin gave over those of the entry; the ones clients
+// cannot set are kept from the entry as it was
+fn apply<T: Serialize + DeserializeOwned>(
+    entry:  &mut T,
+    fields: Map<String, Value>,
+    keep:   impl FnOnce(&mut T, T),
+) -> Result<(), serde_json::Error> {
+    let mut merged = match serde_json::to_value(&*entry)? {
+        Value::Object(merged)   => merged,
+        _                       => Map::new(),
+    };
+    merged.extend(fields);
+    let old = std::mem::replace(entry, serde_json::from_value(Value::Object(merged))?);
+    keep(entry, old);
+    return Ok(());
+}
+
+impl Hook for Plugin {
+    fn on_create(&self, entry: &mut Entry<'_>) -> Result<(), String> {
+        let input = Input { event: "create", kind: entry.kind(), id: None, ids: None, entry: None };
+        return self.change("on_create", input, entry);
+    }
+
+    fn on_update(&self, id: usize, entry: &mut Entry<'_>) -> Result<(), String> {
+        let input = Input { event: "update", kind: entry.kind(), id: Some(id), ids: None, entry: None };
+        return self.change("on_update", input, entry);
+    }
+
+    fn on_delete(&self, kind: &str, id: usize) -> Result<(), String> {
+        let input = Input { event: "delete", kind, id: Some(id), ids: None, entry: None };
+        return match self.call("on_delete", &input)? {
+            Some(Answer { error: Some(reason), .. })    => Err(reason),
+            _                                           => Ok(()),
+        };
+    }
+
+    fn on_merge(&self, ids: &[usize], entry: &mut Entry<'_>) -> Result<(), String> {
+        let input = Input { event: "merge", kind: entry.kind(), id: None, ids: Some(ids), entry: None };
+        return self.change("on_merge", input, entry);
+    }
+}
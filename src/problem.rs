@@ -0,0 +1,88 @@
+// Errors as RFC 7807 problem details (`application/problem+json`), so
+// clients get the same machine-readable shape from every route. Handlers
+// keep answering with a status and a message; the middleware turns those
+// into problems, and rejections that carry more build their own.
+use actix_web::body::{self, EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::middleware::Next;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+pub(crate) const CONTENT_TYPE: &str = "application/problem+json";
+
+#[derive(Serialize)]
+pub(crate) struct Problem {
+    // nothing more specific than the status is known about any of them
+    #[serde(rename = "type")]
+    kind:       &'static str,
+    title:      &'static str,
+    status:     u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail:     Option<String>,
+    // the path the problem occurred at, filled in by the middleware
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance:   Option<String>,
+    // e.g. the current entry of a failed precondition
+    #[serde(flatten)]
+    extensions: Map<String, Value>,
+}
+
+impl Problem {
+    pub(crate) fn new(status: StatusCode, detail: impl Into<String>) -> Problem {
+        let detail: String = detail.into();
+        return Problem {
+            kind:       "about:blank",
+            title:      status.canonical_reason().unwrap_or("Error"),
+            status:     status.as_u16(),
+            detail:     (!detail.trim().is_empty()).then_some(detail),
+            instance:   None,
+            extensions: Map::new(),
+        };
+    }
+
+    pub(crate) fn with(mut self, name: &str, value: Value) -> Problem {
+        self.extensions.insert(String::from(name), value);
+        return self;
+    }
+}
+
+fn content_type(response: &ServiceResponse<impl MessageBody>) -> &str {
+    return response.headers().get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .unwrap_or("");
+}
+
+// Answers every failed request with a problem: plain-text messages become
+// its detail, problems built by handlers get their instance. Other bodies,
+// e.g. JSON:API errors, are left as they are.
+pub async fn render(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let response = next.call(request).await?;
+    let status = response.status();
+    let content_type = content_type(&response);
+    let is_problem = content_type.starts_with(CONTENT_TYPE);
+    let is_text = content_type.is_empty() || content_type.starts_with("text/plain");
+    if !(status.is_client_error() || status.is_server_error()) || !(is_problem || is_text) {
+        return Ok(response.map_into_left_body());
+    }
+    let (request, response) = response.into_parts();
+    let (mut response, content) = response.into_parts();
+    let content = body::to_bytes(content).await.unwrap_or_default();
+    let mut problem = match serde_json::from_slice::<Value>(&content) {
+        Ok(Value::Object(problem)) if is_problem => problem,
+        _ => match serde_json::to_value(Problem::new(status, String::from_utf8_lossy(&content))) {
+            Ok(Value::Object(problem))  => problem,
+            _                           => Map::new(),
+        },
+    };
+    problem.entry("instance").or_insert_with(|| Value::String(String::from(request.path())));
+    let document = serde_json::to_vec(&problem).unwrap_or_default();
+    response.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+    response.headers_mut().remove(header::CONTENT_LENGTH);
+    let response = response.set_body(document).map_into_boxed_body();
+    return Ok(ServiceResponse::new(request, response).map_into_right_body());
+}
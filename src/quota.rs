@@ -0,0 +1,175 @@
+// Storage quotas, so one busy client cannot fill the server: the entries of
+// every collection of a space, the bytes of all journals, tasks, habits,
+// notes and bookmarks of the server together, and the bytes of their attachments. Writes over a
+// limit answer 507 Insufficient Storage; GET /quota shows where a space is.
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, Responder};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::handlers::Rejection;
+use crate::models::{Bookmark, Habit, Journal, Note, Resource, Task};
+use crate::state::State;
+use crate::store::{Collection, Entries, Observer};
+use crate::workspace::Space;
+
+// none of them by default
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Quotas {
+    max_entries:            Option<usize>,
+    max_bytes:              Option<usize>,
+    max_attachment_bytes:   Option<usize>,
+}
+
+impl Quotas {
+    pub(crate) fn from_env() -> Quotas {
+        let limit = |name: &str| std::env::var(name).ok().and_then(|limit| limit.parse()
+            .inspect_err(|_| println!("Ignoring {}, {:?} is no number", name, limit))
+            .ok());
+        return Quotas {
+            max_entries:            limit("JOURNAL_MAX_ENTRIES"),
+            max_bytes:              limit("JOURNAL_MAX_BYTES"),
+            max_attachment_bytes:   limit("JOURNAL_MAX_ATTACHMENT_BYTES"),
+        };
+    }
+}
+
+// what the whole server stores, followed as it changes
+#[derive(Default)]
+pub(crate) struct Usage {
+    // of the entries as JSON
+    bytes:              AtomicUsize,
+    // of the distinct attachment contents
+    attachment_bytes:   AtomicUsize,
+}
+
+impl Usage {
+    // counts the entries of a space, until its collections go
+    pub(crate) fn watch(
+        self:       &Arc<Self>,
+        journals:   &Collection<Journal>,
+        tasks:      &Collection<Task>,
+        habits:     &Collection<Habit>,
+        notes:      &Collection<Note>,
+        bookmarks:  &Collection<Bookmark>,
+    ) {
+        journals.observe(self.sizes());
+        tasks.observe(self.sizes());
+        habits.observe(self.sizes());
+        notes.observe(self.sizes());
+        bookmarks.observe(self.sizes());
+    }
+
+    fn sizes(self: &Arc<Self>) -> Arc<Sizes> {
+        return Arc::new(Sizes { usage: self.clone(), sizes: Mutex::default() });
+    }
+
+    pub(crate) fn attached(&self, bytes: usize) {
+        self.attachment_bytes.fetch_add(bytes, Ordering::SeqCst);
+    }
+
+    pub(crate) fn detached(&self, bytes: usize) {
+        self.attachment_bytes.fetch_sub(bytes, Ordering::SeqCst);
+    }
+}
+
+pub(crate) fn size<T: Serialize>(resource: &T) -> usize {
+    return serde_json::to_vec(resource).map_or(0, |json| json.len());
+}
+
+// the size of every entry of a collection, taken off the usage again when
+// the collection goes, as those of deleted workspaces do
+struct Sizes {
+    usage:  Arc<Usage>,
+    sizes:  Mutex<HashMap<usize, usize>>,
+}
+
+impl<T: Serialize> Observer<T> for Sizes {
+    fn changed(&self, entries: &Entries<T>, ids: &BTreeSet<usize>) {
+        let mut sizes = self.sizes.lock().unwrap();
+        for id in ids {
+            let old = match entries.get(id) {
+                Some(resource)  => sizes.insert(*id, size(&*resource)),
+                None            => sizes.remove(id),
+            };
+            self.usage.bytes.fetch_add(sizes.get(id).copied().unwrap_or(0), Ordering::SeqCst);
+            self.usage.bytes.fetch_sub(old.unwrap_or(0), Ordering::SeqCst);
+        }
+    }
+}
+
+impl Drop for Sizes {
+    fn drop(&mut self) {
+        let total: usize = self.sizes.get_mut().unwrap().values().sum();
+        self.usage.bytes.fetch_sub(total, Ordering::SeqCst);
+    }
+}
+
+fn exceeded(message: String) -> Rejection {
+    return Rejection::new(StatusCode::INSUFFICIENT_STORAGE, message);
+}
+
+// Rejects a write adding `entries` new entries to the collection and growing
+// what is stored by `bytes`
+pub(crate) fn admit<T: Resource>(state: &State, collection: &Collection<T>, entries: usize, bytes: usize) -> Result<(), Rejection> {
+    let quotas = &state.config.quotas;
+    if let Some(max) = quotas.max_entries {
+        if entries > 0 && collection.len() + entries > max {
+            return Err(exceeded(format!("Quota exceeded, there are at most {} {}s", max, T::KIND)));
+        }
+    }
+    if let Some(max) = quotas.max_bytes {
+        if state.usage.bytes.load(Ordering::SeqCst) + bytes > max {
+            return Err(exceeded(format!("Quota exceeded, the entries take at most {} bytes", max)));
+        }
+    }
+    return Ok(());
+}
+
+pub(crate) fn admit_attachment(state: &State, bytes: usize) -> Result<(), Rejection> {
+    if let Some(max) = state.config.quotas.max_attachment_bytes {
+        if state.usage.attachment_bytes.load(Ordering::SeqCst) + bytes > max {
+            return Err(exceeded(format!("Quota exceeded, attachments take at most {} bytes", max)));
+        }
+    }
+    return Ok(());
+}
+
+#[derive(Serialize)]
+struct Measure {
+    used:   usize,
+    // null without a limit
+    limit:  Option<usize>,
+}
+
+#[derive(Serialize)]
+struct QuotaView {
+    // of the space asked in
+    journals:           Measure,
+    tasks:              Measure,
+    habits:             Measure,
+    notes:              Measure,
+    bookmarks:          Measure,
+    // of the whole server
+    bytes:              Measure,
+    attachment_bytes:   Measure,
+}
+
+pub(crate) async fn show(state: web::Data<State>, space: Space) -> impl Responder {
+    let quotas = &state.config.quotas;
+    let entries = |used: usize| Measure { used, limit: quotas.max_entries };
+    return HttpResponse::Ok().json(QuotaView {
+        journals:           entries(space.journals.len()),
+        tasks:              entries(space.tasks.len()),
+        habits:             entries(space.habits.len()),
+        notes:              entries(space.notes.len()),
+        bookmarks:          entries(space.bookmarks.len()),
+        bytes:              Measure { used: state.usage.bytes.load(Ordering::SeqCst), limit: quotas.max_bytes },
+        attachment_bytes:   Measure {
+            used:   state.usage.attachment_bytes.load(Ordering::SeqCst),
+            limit:  quotas.max_attachment_bytes,
+        },
+    });
+}
@@ -0,0 +1,62 @@
+// Byte ranges of downloaded bodies, so clients can resume interrupted
+// downloads of long journals: a single `Range: bytes=...` is answered with
+// 206, anything this does not serve in parts with the whole body
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder};
+
+// the [start, end) of the body a Range header asks for, None when the
+// header is missing, broken or asks for several ranges, Err when nothing of
+// the body is in it
+fn requested(range: &str, len: usize) -> Option<Result<(usize, usize), ()>> {
+    let spec = range.trim().strip_prefix("bytes=")?;
+    if spec.contains(',') {
+        return None;
+    }
+    let (start, end) = spec.split_once('-')?;
+    let (start, end) = (start.trim(), end.trim());
+    let range = if start.is_empty() {
+        // bytes=-500, the last 500 bytes
+        let suffix: usize = end.parse().ok()?;
+        (len - suffix.min(len), len)
+    } else {
+        let start: usize = start.parse().ok()?;
+        let last: usize = if end.is_empty() { usize::MAX } else { end.parse().ok()? };
+        if last < start {
+            return None;
+        }
+        (start, last.saturating_add(1).min(len))
+    };
+    if range.0 >= range.1 {
+        return Some(Err(()));
+    }
+    return Some(Ok(range));
+}
+
+// `body` whole or in the part the request asks for; `etag` is what If-Range
+// has to match for a part to be sent, the ETag header is left to `response`
+pub(crate) fn respond(
+    request: &HttpRequest,
+    mut response: HttpResponseBuilder,
+    etag: &str,
+    body: Bytes,
+) -> HttpResponse {
+    response.append_header(("Accept-Ranges", "bytes"));
+    let range = request.headers().get("Range").and_then(|range| range.to_str().ok());
+    // a part of another version would corrupt what the client has
+    let current = request.headers().get("If-Range")
+        .and_then(|if_range| if_range.to_str().ok())
+        .is_none_or(|if_range| if_range.trim_matches('"') == etag);
+    let len = body.len();
+    match range.filter(|_| current).and_then(|range| requested(range, len)) {
+        None            => return response.body(body),
+        Some(Err(()))   => return response
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .append_header(("Content-Range", format!("bytes */{}", len)))
+            .finish(),
+        Some(Ok((start, end))) => return response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .append_header(("Content-Range", format!("bytes {}-{}/{}", start, end - 1, len)))
+            .body(body.slice(start..end)),
+    }
+}
@@ -0,0 +1,115 @@
+// Rate limiting, off unless JOURNAL_RATE_LIMIT is set: every client gets that
+// many requests per window (JOURNAL_RATE_LIMIT_WINDOW seconds, a minute by
+// default), told with the X-RateLimit-* headers of every response so it can
+// slow down by itself, and answered 429 with Retry-After past the limit.
+// Clients are told apart by the address they connect from.
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::state::State;
+
+const DEFAULT_WINDOW: Duration = Duration::from_secs(60);
+// clients seen before old windows are dropped
+const TRACKED: usize = 1024;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RateLimit {
+    pub(crate) limit:  u64,
+    pub(crate) window: Duration,
+}
+
+impl RateLimit {
+    pub(crate) fn from_env() -> Option<RateLimit> {
+        let limit = std::env::var("JOURNAL_RATE_LIMIT").ok()?;
+        let limit = limit.parse().inspect_err(|_| println!("Ignoring JOURNAL_RATE_LIMIT, {:?} is no number", limit)).ok()?;
+        let window = std::env::var("JOURNAL_RATE_LIMIT_WINDOW").ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .filter(|seconds| *seconds > 0)
+            .map_or(DEFAULT_WINDOW, Duration::from_secs);
+        return Some(RateLimit { limit, window });
+    }
+}
+
+// the requests of a client in its current window
+struct Window {
+    reset:  DateTime<Utc>,
+    used:   u64,
+}
+
+#[derive(Default)]
+pub(crate) struct Limiter {
+    windows: Mutex<HashMap<String, Window>>,
+}
+
+// what the client was allowed, after counting this request
+struct Allowance {
+    remaining:  u64,
+    reset:      DateTime<Utc>,
+    exceeded:   bool,
+}
+
+impl Limiter {
+    fn count(&self, client: String, rate: RateLimit) -> Allowance {
+        let now = Utc::now();
+        let mut windows = self.windows.lock().unwrap();
+        if windows.len() >= TRACKED {
+            windows.retain(|_, window| window.reset > now);
+        }
+        let window = windows.entry(client).or_insert(Window { reset: now, used: 0 });
+        if window.reset <= now {
+            *window = Window { reset: now + rate.window, used: 0 };
+        }
+        window.used += 1;
+        return Allowance {
+            remaining:  rate.limit.saturating_sub(window.used),
+            reset:      window.reset,
+            exceeded:   window.used > rate.limit,
+        };
+    }
+}
+
+fn annotate(headers: &mut HeaderMap, rate: RateLimit, allowance: &Allowance) {
+    let values = [
+        ("x-ratelimit-limit", rate.limit),
+        ("x-ratelimit-remaining", allowance.remaining),
+        // when the window ends, in seconds since the epoch
+        ("x-ratelimit-reset", allowance.reset.timestamp().max(0) as u64),
+    ];
+    for (name, value) in values {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+    }
+}
+
+pub async fn limit(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let state = request.app_data::<web::Data<State>>().cloned();
+    let rate = state.as_ref().and_then(|state| state.settings.read().unwrap().rate_limit);
+    let (state, rate) = match state.zip(rate) {
+        Some(limited)   => limited,
+        None            => return Ok(next.call(request).await?.map_into_left_body()),
+    };
+    // not the forwarded address, which the client picks
+    let client = request.peer_addr().map_or(String::from("unknown"), |address| address.ip().to_string());
+    let allowance = state.limiter.count(client, rate);
+
+    let mut response = if allowance.exceeded {
+        let retry_after = (allowance.reset - Utc::now()).num_seconds().max(1);
+        let refusal = HttpResponse::TooManyRequests()
+            .append_header(("Retry-After", retry_after.to_string()))
+            .body(format!("Rate limit exceeded, retry in {} seconds", retry_after));
+        request.into_response(refusal).map_into_right_body()
+    } else {
+        next.call(request).await?.map_into_left_body()
+    };
+    annotate(response.headers_mut(), rate, &allowance);
+    return Ok(response);
+}
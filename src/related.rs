@@ -0,0 +1,118 @@
+// "See also" suggestions: the journals sharing tags or significant words with
+// a journal. Words are weighed by TF-IDF over the journals the request may
+// see, so the ones every journal uses count for nothing.
+use actix_web::{web, HttpResponse, Responder};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::handlers::IdPath;
+use crate::models::Journal;
+use crate::search::words;
+use crate::workspace::{Level, Space};
+
+const DEFAULT_LIMIT: usize = 5;
+const MAX_LIMIT: usize = 20;
+// shorter words are hardly ever significant
+const MIN_WORD_LENGTH: usize = 3;
+// what a shared tag adds, the word similarity is between 0 and 1
+const TAG_WEIGHT: f64 = 1.0;
+const SHOWN_WORDS: usize = 5;
+
+#[derive(Deserialize)]
+pub(crate) struct RelatedQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct Related {
+    id:             usize,
+    title:          String,
+    score:          f64,
+    shared_tags:    Vec<String>,
+    // the words adding the most to the similarity
+    shared_words:   Vec<String>,
+}
+
+// how often every word occurs in the journal, nothing for ciphertexts
+fn term_counts(journal: &Journal) -> HashMap<String, f64> {
+    let mut counts = HashMap::new();
+    if journal.encrypted {
+        return counts;
+    }
+    let text = format!("{}\n{}", journal.title, journal.data);
+    for word in words(&text) {
+        if word.lower.chars().count() >= MIN_WORD_LENGTH {
+            *counts.entry(word.lower).or_default() += 1.0;
+        }
+    }
+    return counts;
+}
+
+fn norm(vector: &HashMap<String, f64>) -> f64 {
+    return vector.values().map(|weight| weight * weight).sum::<f64>().sqrt();
+}
+
+pub(crate) async fn related(
+    path: web::Path<IdPath>,
+    query: web::Query<RelatedQuery>,
+    space: Space,
+) -> impl Responder {
+    let id = path.id;
+    if let Err(rejection) = space.allow::<Journal>(Some(id), Level::Read) {
+        return rejection.into();
+    }
+    let journals: Vec<(usize, Journal)> = space.visible(&space.journals).into_iter()
+        .filter_map(|id| Some((id, space.journals.get(&id)?.clone())))
+        .collect();
+    let Some(target_index) = journals.iter().position(|(other, _)| *other == id) else {
+        return HttpResponse::NotFound().body("Not found");
+    };
+    let target = &journals[target_index].1;
+
+    // inverse document frequencies over every visible journal
+    let counts: Vec<HashMap<String, f64>> = journals.iter().map(|(_, journal)| term_counts(journal)).collect();
+    let mut frequencies: HashMap<&str, f64> = HashMap::new();
+    for journal in &counts {
+        for word in journal.keys() {
+            *frequencies.entry(word).or_default() += 1.0;
+        }
+    }
+    let total = journals.len() as f64;
+    let weigh = |counts: &HashMap<String, f64>| -> HashMap<String, f64> {
+        return counts.iter()
+            .map(|(word, count)| (word.clone(), count * (total / frequencies[word.as_str()]).ln()))
+            .filter(|(_, weight)| *weight > 0.0)
+            .collect();
+    };
+    let target_vector = weigh(&counts[target_index]);
+    let target_norm = norm(&target_vector);
+
+    let mut related = Vec::new();
+    for ((other_id, other), other_counts) in journals.iter().zip(&counts) {
+        if *other_id == id {
+            continue;
+        }
+        let shared_tags: Vec<String> = other.tags.iter().filter(|tag| target.tags.contains(tag)).cloned().collect();
+        let vector = weigh(other_counts);
+        let mut contributions: Vec<(&String, f64)> = target_vector.iter()
+            .filter_map(|(word, weight)| Some((word, weight * vector.get(word)?)))
+            .collect();
+        contributions.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let dot: f64 = contributions.iter().map(|(_, product)| product).sum();
+        let similarity = if dot > 0.0 { dot / (target_norm * norm(&vector)) } else { 0.0 };
+        let score = shared_tags.len() as f64 * TAG_WEIGHT + similarity;
+        if score <= 0.0 {
+            continue;
+        }
+        related.push(Related {
+            id:             *other_id,
+            title:          other.title.clone(),
+            score:          (score * 1000.0).round() / 1000.0,
+            shared_tags,
+            shared_words:   contributions.into_iter().take(SHOWN_WORDS).map(|(word, _)| word.clone()).collect(),
+        });
+    }
+    related.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    related.truncate(query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT));
+    return HttpResponse::Ok().json(related);
+}
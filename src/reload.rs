@@ -0,0 +1,121 @@
+// Settings that change without a restart: how long write tokens last, the
+// rate limit and the log level. They are read with the rest of the
+// configuration at startup, and again on SIGHUP or POST /admin/reload after
+// the lines of JOURNAL_CONFIG_FILE are put into the environment, so nothing
+// in memory is lost. The other settings keep the values they started with.
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use log::LevelFilter;
+use serde::Serialize;
+use std::time::Duration;
+
+use crate::admin::require_admin;
+use crate::auth::DEFAULT_TOKEN_TTL;
+use crate::ratelimit::RateLimit;
+use crate::state::State;
+
+#[derive(Debug, Clone)]
+pub(crate) struct Settings {
+    // for a write token to become invalid
+    pub(crate) token_ttl:  Duration,
+    // how many requests a client may make, unlimited when unset
+    pub(crate) rate_limit: Option<RateLimit>,
+    pub(crate) log_level:  LevelFilter,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        return Settings { token_ttl: DEFAULT_TOKEN_TTL, rate_limit: None, log_level: LevelFilter::Debug };
+    }
+}
+
+#[derive(Serialize)]
+struct Shown {
+    token_ttl:         u64,
+    rate_limit:        Option<u64>,
+    rate_limit_window: Option<u64>,
+    log_level:         String,
+}
+
+impl Settings {
+    pub(crate) fn from_env() -> Settings {
+        let token_ttl = std::env::var("JOURNAL_TOKEN_TTL").ok()
+            .and_then(|seconds| seconds.parse().ok().filter(|seconds| *seconds > 0)
+                .or_else(|| {
+                    println!("Ignoring JOURNAL_TOKEN_TTL, {:?} is no number of seconds", seconds);
+                    None
+                }))
+            .map_or(DEFAULT_TOKEN_TTL, Duration::from_secs);
+        let log_level = std::env::var("JOURNAL_LOG_LEVEL").ok()
+            .and_then(|level| level.parse()
+                .inspect_err(|_| println!("Ignoring JOURNAL_LOG_LEVEL, {:?} is no level", level))
+                .ok())
+            .unwrap_or(LevelFilter::Debug);
+        return Settings { token_ttl, rate_limit: RateLimit::from_env(), log_level };
+    }
+
+    // what only takes effect when told
+    pub(crate) fn apply(&self) {
+        log::set_max_level(self.log_level);
+    }
+
+    fn shown(&self) -> Shown {
+        return Shown {
+            token_ttl:         self.token_ttl.as_secs(),
+            rate_limit:        self.rate_limit.map(|rate| rate.limit),
+            rate_limit_window: self.rate_limit.map(|rate| rate.window.as_secs()),
+            log_level:         self.log_level.to_string().to_lowercase(),
+        };
+    }
+}
+
+// puts the NAME=value lines of JOURNAL_CONFIG_FILE into the environment,
+// over what was there; blank lines and those starting with # are skipped
+pub(crate) fn load_file() -> Result<(), String> {
+    let Some(path) = std::env::var_os("JOURNAL_CONFIG_FILE") else {
+        return Ok(());
+    };
+    let content = std::fs::read_to_string(&path)
+        .map_err(|err| format!("Could not read JOURNAL_CONFIG_FILE {}: {}", path.to_string_lossy(), err))?;
+    for line in content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+        match line.split_once('=') {
+            Some((name, value)) => std::env::set_var(name.trim(), value.trim()),
+            None                => println!("Ignoring {:?} in JOURNAL_CONFIG_FILE, lines are NAME=value", line),
+        }
+    }
+    return Ok(());
+}
+
+// reads the settings again, keeping the current ones when the file is gone
+pub(crate) fn reload(state: &State) -> Result<(), String> {
+    load_file()?;
+    let settings = Settings::from_env();
+    settings.apply();
+    println!("Reloaded settings: {:?}", settings);
+    *state.settings.write().unwrap() = settings;
+    return Ok(());
+}
+
+// reloads on every SIGHUP, for as long as the server runs
+#[cfg(unix)]
+pub(crate) async fn watch(state: web::Data<State>) {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangups = match signal(SignalKind::hangup()) {
+        Ok(hangups) => hangups,
+        Err(err)    => return println!("Not reloading on SIGHUP: {}", err),
+    };
+    while hangups.recv().await.is_some() {
+        if let Err(err) = reload(&state) {
+            println!("Not reloading: {}", err);
+        }
+    }
+}
+
+pub(crate) async fn update(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    if let Err(err) = reload(&state) {
+        return HttpResponse::InternalServerError().body(err);
+    }
+    return HttpResponse::Ok().json(state.settings.read().unwrap().shown());
+}
@@ -0,0 +1,96 @@
+// Markdown to HTML rendering of journal bodies, cached for the journals
+// the feed, share pages and UI show over and over
+use lru::LruCache;
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use std::collections::BTreeSet;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+
+use crate::models::Journal;
+use crate::state::State;
+use crate::store::{Collection, Entries, Observer};
+
+pub(crate) const DEFAULT_CACHE_SIZE: usize = 256;
+
+// the schemes links and images may use, anything else could run script
+// in the page, e.g. javascript:
+const SAFE_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+// relative links have no scheme: no colon before the first /, ? or #
+fn safe_url(url: CowStr<'_>) -> CowStr<'_> {
+    let trimmed = url.trim_start_matches(|c: char| c <= ' ');
+    let scheme = trimmed.split(['/', '?', '#']).next().unwrap_or("")
+        .split_once(':')
+        .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    match scheme {
+        Some(scheme) if !SAFE_SCHEMES.contains(&scheme.as_str()) => return CowStr::Borrowed(""),
+        _ => return url,
+    }
+}
+
+// raw HTML in the source is escaped rather than passed through, link and
+// image destinations are kept only with a safe scheme
+pub fn markdown_to_html(markdown: &str) -> String {
+    let parser = Parser::new_ext(markdown, Options::empty())
+        .map(|event| match event {
+            Event::Html(raw) | Event::InlineHtml(raw) => Event::Text(raw),
+            Event::Start(Tag::Link { link_type, dest_url, title, id }) =>
+                Event::Start(Tag::Link { link_type, dest_url: safe_url(dest_url), title, id }),
+            Event::Start(Tag::Image { link_type, dest_url, title, id }) =>
+                Event::Start(Tag::Image { link_type, dest_url: safe_url(dest_url), title, id }),
+            event => event,
+        });
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, parser);
+    rendered
+}
+
+// The HTML of the most recently shown journals, each with the ETag it was
+// rendered from, so only a journal as it is now is ever served from here.
+// Writes drop the journals they change right away instead of leaving them
+// to age out. None when the cache is turned off.
+type Cache = LruCache<usize, (String, Arc<str>)>;
+
+#[derive(Clone)]
+pub(crate) struct Rendered(Option<Arc<Mutex<Cache>>>);
+
+impl Rendered {
+    pub(crate) fn watch(journals: &Collection<Journal>, size: usize) -> Rendered {
+        let Some(size) = NonZeroUsize::new(size) else {
+            return Rendered(None);
+        };
+        let rendered = Rendered(Some(Arc::new(Mutex::new(LruCache::new(size)))));
+        journals.observe(Arc::new(rendered.clone()));
+        return rendered;
+    }
+}
+
+impl Observer<Journal> for Rendered {
+    fn changed(&self, _entries: &Entries<Journal>, ids: &BTreeSet<usize>) {
+        if let Some(cache) = &self.0 {
+            let mut cache = cache.lock().unwrap();
+            for id in ids {
+                cache.pop(id);
+            }
+        }
+    }
+}
+
+// the body of one of the root journals as HTML, from the cache when it
+// has not changed since
+pub(crate) fn journal_html(state: &State, id: usize, journal: &Journal) -> Arc<str> {
+    let Some(cache) = &state.rendered.0 else {
+        return markdown_to_html(&journal.data).into();
+    };
+    if let Some((etag, html)) = cache.lock().unwrap().get(&id) {
+        if *etag == journal.etag {
+            state.metrics.count_render(true);
+            return html.clone();
+        }
+    }
+    state.metrics.count_render(false);
+    // rendered without the lock, a journal rendered twice meanwhile is no harm
+    let html: Arc<str> = markdown_to_html(&journal.data).into();
+    cache.lock().unwrap().put(id, (journal.etag.clone(), html.clone()));
+    return html;
+}
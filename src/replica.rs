@@ -0,0 +1,217 @@
+// Read replicas: with JOURNAL_PRIMARY_URL set, the server follows that
+// primary instead of taking writes. It copies the journals, tasks, habits,
+// notes and bookmarks of the primary's own space with GET /v1/changes, once in full and then
+// since the last sync token, every time the primary's GET /v1/events stream
+// tells of a write and at least every JOURNAL_REPLICA_POLL_SECONDS (10 by
+// default), so a dropped stream only delays it. Writes sent to a replica
+// are redirected to the primary with 307, which keeps their method and body,
+// as are reads that would write, like a daily journal not there yet; gRPC
+// writes are refused.
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+use crate::admin::require_admin;
+use crate::eventlog::{replay, Recorded};
+use crate::maintenance::is_write;
+use crate::state::State;
+use crate::store::Collection;
+
+const DEFAULT_POLL: Duration = Duration::from_secs(10);
+// before connecting to the events of the primary again
+const RECONNECT: Duration = Duration::from_secs(1);
+
+// Read from the environment at startup, see Config
+#[derive(Debug, Clone)]
+pub(crate) struct ReplicaConfig {
+    // without a trailing slash
+    pub(crate) primary: String,
+    poll:               Duration,
+}
+
+impl ReplicaConfig {
+    pub(crate) fn from_env() -> Option<ReplicaConfig> {
+        let primary = std::env::var("JOURNAL_PRIMARY_URL").ok()
+            .map(|url| String::from(url.trim().trim_end_matches('/')))
+            .filter(|url| !url.is_empty())?;
+        let poll = std::env::var("JOURNAL_REPLICA_POLL_SECONDS").ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .filter(|seconds| *seconds > 0)
+            .map_or(DEFAULT_POLL, Duration::from_secs);
+        return Some(ReplicaConfig { primary, poll });
+    }
+}
+
+// how far the replica got, for /admin/replication
+#[derive(Default, Clone, Serialize)]
+pub(crate) struct Following {
+    // the since of the next sync, None before the first
+    sync_token:     Option<String>,
+    last_sync:      Option<DateTime<Utc>>,
+    // entries copied, since the server started
+    changes:        u64,
+    // of the last failed sync, kept after later ones succeed
+    last_error:     Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Change {
+    kind:       String,
+    id:         usize,
+    #[serde(default)]
+    resource:   Option<Value>,
+    #[serde(default)]
+    etag:       Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Changes {
+    changes:    Vec<Change>,
+    sync_token: String,
+}
+
+// The changes of one kind, applied in one change of the collection. A full
+// sync lists every entry, so those not listed are gone on the primary.
+async fn apply<T: Recorded + Send + Sync + 'static>(collection: &Collection<T>, changes: &[Change], full: bool) -> Result<(), String> {
+    let mut copied = Vec::new();
+    for change in changes.iter().filter(|change| change.kind == T::KIND) {
+        let entry = match &change.resource {
+            Some(resource)  => {
+                let mut entry: T = replay(resource).map_err(|err| format!("{} {}: {}", T::KIND, change.id, err))?;
+                entry.set_etag(change.etag.clone().unwrap_or_default());
+                Some(entry)
+            }
+            None            => None,
+        };
+        copied.push((change.id, entry));
+    }
+    collection.change(move |entries| {
+        if full {
+            let listed: BTreeSet<usize> = copied.iter().map(|(id, _)| *id).collect();
+            for id in entries.ids().into_iter().filter(|id| !listed.contains(id)) {
+                entries.remove(&id);
+            }
+        }
+        for (id, entry) in copied {
+            match entry {
+                Some(entry) => entries.insert(id, entry),
+                None        => entries.remove(&id),
+            };
+        }
+    }).await.map_err(|failed| failed.to_string())?;
+    return Ok(());
+}
+
+// copies what changed on the primary since the token, everything without
+// one or once the primary no longer knows it; returns the next token
+async fn sync(client: &reqwest::Client, state: &State, primary: &str, since: Option<String>) -> Result<String, String> {
+    let fetch = |since: Option<&str>| {
+        let url = match since {
+            Some(since) => format!("{}/v1/changes?since={}", primary, since),
+            None        => format!("{}/v1/changes", primary),
+        };
+        client.get(url).header("Accept", "application/json").send()
+    };
+    let mut response = fetch(since.as_deref()).await.map_err(|err| err.to_string())?;
+    let mut full = since.is_none();
+    if response.status() == reqwest::StatusCode::GONE {
+        response = fetch(None).await.map_err(|err| err.to_string())?;
+        full = true;
+    }
+    let changes: Changes = response.error_for_status()
+        .map_err(|err| err.to_string())?
+        .json().await
+        .map_err(|err| err.to_string())?;
+    apply(&state.journals, &changes.changes, full).await?;
+    apply(&state.tasks, &changes.changes, full).await?;
+    apply(&state.habits, &changes.changes, full).await?;
+    apply(&state.notes, &changes.changes, full).await?;
+    apply(&state.bookmarks, &changes.changes, full).await?;
+    let mut following = state.following.lock().unwrap();
+    following.changes += changes.changes.len() as u64;
+    following.last_sync = Some(Utc::now());
+    return Ok(changes.sync_token);
+}
+
+// follows the primary for as long as the server runs
+pub(crate) async fn follow(state: web::Data<State>, config: ReplicaConfig) {
+    let client = reqwest::Client::new();
+    let mut events: Option<reqwest::Response> = None;
+    loop {
+        // connected before syncing, so no write falls in between
+        if events.is_none() {
+            let url = format!("{}/v1/events", config.primary);
+            events = client.get(url).send().await.and_then(|response| response.error_for_status()).ok();
+        }
+        let since = state.following.lock().unwrap().sync_token.clone();
+        match sync(&client, &state, &config.primary, since).await {
+            Ok(token)   => state.following.lock().unwrap().sync_token = Some(token),
+            Err(err)    => {
+                println!("Syncing with the primary failed: {}", err);
+                state.following.lock().unwrap().last_error = Some(err);
+            }
+        }
+
+        // until the primary tells of a write, or the poll is due
+        let woken = match events.as_mut() {
+            Some(stream)    => tokio::select! {
+                chunk = stream.chunk() => matches!(chunk, Ok(Some(_))),
+                _ = tokio::time::sleep(config.poll) => true,
+            },
+            None            => false,
+        };
+        if !woken {
+            events = None;
+            tokio::time::sleep(RECONNECT.min(config.poll)).await;
+        }
+    }
+}
+
+// sends writes on to the primary
+pub async fn redirect(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let primary = request.app_data::<web::Data<State>>()
+        .and_then(|state| state.config.replica.as_ref().map(|replica| replica.primary.clone()));
+    let Some(primary) = primary.filter(|_| is_write(&request)) else {
+        return Ok(next.call(request).await?.map_into_left_body());
+    };
+    let redirect = to_primary(&primary, request.request());
+    return Ok(request.into_response(redirect).map_into_right_body());
+}
+
+// the same request sent on to the primary, for writes and reads that would write
+pub(crate) fn to_primary(primary: &str, request: &HttpRequest) -> HttpResponse {
+    let location = match request.query_string() {
+        ""      => format!("{}{}", primary, request.path()),
+        query   => format!("{}{}?{}", primary, request.path(), query),
+    };
+    return HttpResponse::TemporaryRedirect()
+        .append_header(("Location", location))
+        .body(format!("Read-only replica, writes go to {}", primary));
+}
+
+#[derive(Serialize)]
+struct Status<'a> {
+    primary:    &'a str,
+    #[serde(flatten)]
+    following:  Following,
+}
+
+pub(crate) async fn show(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    let Some(replica) = &state.config.replica else {
+        return HttpResponse::NotFound().body("Not a replica");
+    };
+    let following = state.following.lock().unwrap().clone();
+    return HttpResponse::Ok().json(Status { primary: &replica.primary, following });
+}
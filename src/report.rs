@@ -0,0 +1,210 @@
+// Error reporting: handler panics and 5xx responses are sent, together with
+// the request they happened on, to Sentry and/or a generic JSON webhook
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{error, web};
+use chrono::{DateTime, Utc};
+use futures_util::FutureExt;
+use serde::Serialize;
+use serde_json::json;
+use std::panic::AssertUnwindSafe;
+
+use crate::notify;
+use crate::state::State;
+
+// where reports go, from JOURNAL_SENTRY_DSN and JOURNAL_ERROR_WEBHOOK_URL,
+// signed with JOURNAL_ERROR_WEBHOOK_SECRET
+#[derive(Debug, Clone)]
+pub(crate) enum Sink {
+    Sentry(Dsn),
+    Webhook { url: String, secret: Option<String> },
+}
+
+impl Sink {
+    pub(crate) fn from_env() -> Vec<Sink> {
+        let sentry = std::env::var("JOURNAL_SENTRY_DSN").ok().and_then(|dsn| {
+            let parsed = Dsn::parse(&dsn);
+            if parsed.is_none() {
+                println!("Ignoring JOURNAL_SENTRY_DSN, not a DSN: {}", dsn);
+            }
+            parsed
+        });
+        let webhook = std::env::var("JOURNAL_ERROR_WEBHOOK_URL").ok().map(|url| Sink::Webhook {
+            url,
+            secret: std::env::var("JOURNAL_ERROR_WEBHOOK_SECRET").ok().filter(|secret| !secret.is_empty()),
+        });
+        return sentry.map(Sink::Sentry).into_iter()
+            .chain(webhook)
+            .collect();
+    }
+}
+
+// "https://<key>@<host>/<project>", possibly with a path before the project
+#[derive(Debug, Clone)]
+pub(crate) struct Dsn {
+    dsn:        String,
+    key:        String,
+    envelope:   String,
+}
+
+impl Dsn {
+    fn parse(dsn: &str) -> Option<Dsn> {
+        let (scheme, rest) = dsn.trim().split_once("://")?;
+        let (key, location) = rest.split_once('@')?;
+        let key = key.split(':').next()?;
+        let (base, project) = location.trim_end_matches('/').rsplit_once('/')?;
+        if key.is_empty() || project.is_empty() || !project.chars().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        return Some(Dsn {
+            dsn:        String::from(dsn.trim()),
+            key:        String::from(key),
+            envelope:   format!("{}://{}/api/{}/envelope/", scheme, base, project),
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Kind {
+    Panic,
+    ServerError,
+}
+
+// what went wrong and on which request
+#[derive(Debug, Serialize)]
+struct Report {
+    kind:       Kind,
+    message:    String,
+    status:     u16,
+    method:     String,
+    path:       String,
+    // the route pattern, e.g. /v1/tasks/{id}
+    route:      Option<String>,
+    query:      String,
+    user_agent: Option<String>,
+    timestamp:  DateTime<Utc>,
+}
+
+impl Report {
+    fn sentry_event(&self, event_id: &str) -> serde_json::Value {
+        let mut headers = serde_json::Map::new();
+        if let Some(user_agent) = &self.user_agent {
+            headers.insert(String::from("User-Agent"), json!(user_agent));
+        }
+        return json!({
+            "event_id":     event_id,
+            "timestamp":    self.timestamp.to_rfc3339(),
+            "platform":     "other",
+            "level":        match self.kind { Kind::Panic => "fatal", Kind::ServerError => "error" },
+            "logger":       "rest-journal",
+            "transaction":  format!("{} {}", self.method, self.route.as_deref().unwrap_or(&self.path)),
+            "message":      { "formatted": self.message },
+            "request":      {
+                "method":       self.method,
+                "url":          self.path,
+                "query_string": self.query,
+                "headers":      headers,
+            },
+            "tags":         { "kind": self.kind, "status": self.status.to_string() },
+        });
+    }
+}
+
+pub(crate) struct Reporter {
+    sinks:  Vec<Sink>,
+    client: reqwest::Client,
+}
+
+impl Reporter {
+    pub(crate) fn new(sinks: Vec<Sink>) -> Reporter {
+        Reporter { sinks, client: reqwest::Client::new() }
+    }
+
+    // printed in any case, deliveries run in the background
+    fn report(&self, report: Report) {
+        println!(
+            "{} on {} {}: {}",
+            if matches!(report.kind, Kind::Panic) { "Panic" } else { "Server error" },
+            report.method, report.path, report.message,
+        );
+        for sink in &self.sinks {
+            let delivery = match sink {
+                Sink::Sentry(dsn) => {
+                    let event_id = format!("{:032x}", rand::random::<u128>());
+                    let header = json!({ "event_id": event_id, "dsn": dsn.dsn, "sent_at": Utc::now().to_rfc3339() });
+                    let envelope = format!("{}\n{}\n{}\n", header, json!({ "type": "event" }), report.sentry_event(&event_id));
+                    self.client.post(&dsn.envelope)
+                        .header("Content-Type", "application/x-sentry-envelope")
+                        .header("X-Sentry-Auth", format!(
+                            "Sentry sentry_version=7, sentry_key={}, sentry_client=rest-journal/{}",
+                            dsn.key, env!("CARGO_PKG_VERSION"),
+                        ))
+                        .body(envelope)
+                        .send()
+                }
+                Sink::Webhook { url, secret } => notify::post(&self.client, url, &report, secret.as_deref()).send(),
+            };
+            tokio::spawn(async move {
+                if let Err(err) = delivery.await.and_then(|resp| resp.error_for_status()) {
+                    println!("Error report delivery failed: {}", err);
+                }
+            });
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    if let Some(message) = panic.downcast_ref::<&str>() {
+        return String::from(*message);
+    }
+    if let Some(message) = panic.downcast_ref::<String>() {
+        return message.clone();
+    }
+    return String::from("Box<dyn Any>");
+}
+
+// Reports panics, turned into a 500 so the connection still gets an answer,
+// and every response or error with a 5xx status
+pub async fn capture(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    // copied out, the request has to stay unshared for the router
+    let state = request.app_data::<web::Data<State>>().cloned();
+    let method = request.method().to_string();
+    let path = String::from(request.path());
+    let route = request.match_pattern();
+    let query = String::from(request.query_string());
+    let user_agent = request.headers().get("User-Agent")
+        .and_then(|agent| agent.to_str().ok())
+        .map(String::from);
+    let report = move |kind, status: u16, message: String| {
+        let report = Report { kind, message, status, method, path, route, query, user_agent, timestamp: Utc::now() };
+        if let Some(state) = &state {
+            state.reporter.report(report);
+        }
+    };
+
+    match AssertUnwindSafe(next.call(request)).catch_unwind().await {
+        Ok(Ok(response)) => {
+            let status = response.status();
+            if status.is_server_error() {
+                report(Kind::ServerError, status.as_u16(), String::from(status.canonical_reason().unwrap_or("Server error")));
+            }
+            return Ok(response);
+        }
+        Ok(Err(err)) => {
+            let status = err.as_response_error().status_code();
+            if status.is_server_error() {
+                report(Kind::ServerError, status.as_u16(), err.to_string());
+            }
+            return Err(err);
+        }
+        Err(panic) => {
+            report(Kind::Panic, 500, panic_message(&*panic));
+            return Err(error::ErrorInternalServerError("Internal server error"));
+        }
+    }
+}
@@ -0,0 +1,468 @@
+// Everything the server serves, put together into an App
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::middleware::from_fn;
+use actix_web::{web, App};
+
+use crate::handlers::{
+    clone_journal, clone_task, delete_resource, get_by_id, get_resources, merge_journals, merge_tasks,
+    patch_task, post_resource, put_resource, split_task,
+};
+use crate::models::{Bookmark, Habit, Journal, Note, Task};
+use crate::state::{Config, State};
+use crate::{admin, analytics, attachments, auth, autocomplete, board, bookmarks, bulk, caldav, daily, deprecation, dryrun, duplicates, events, export, feed, habits, imports, jsonapi, maintenance, metrics, operations, ordering, problem, quota, ratelimit, related, replica, report, schedules, schema, search, share, signing, slow, stats, summary, sync, takeout, telemetry, timers, versioning, workspace};
+#[cfg(feature = "crdt")]
+use crate::crdt;
+#[cfg(feature = "faults")]
+use crate::faults;
+#[cfg(feature = "graphql")]
+use crate::graphql;
+#[cfg(feature = "ui")]
+use crate::ui;
+
+// the REST API, mounted under /v1 and, deprecated, without a prefix
+fn api_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/tokens")
+        .route(web::post().to(auth::gen_token))
+    )
+    .service(
+        web::resource("/summary")
+        .route(web::get().to(summary::daily_summary))
+    )
+    .service(
+        web::resource("/export")
+        .wrap(from_fn(operations::defer))
+        .route(web::get().to(takeout::export))
+    )
+    .service(
+        web::resource("/import")
+        .wrap(from_fn(operations::defer))
+        .app_data(web::JsonConfig::default().limit(takeout::IMPORT_LIMIT))
+        .route(web::post().to(takeout::import))
+    )
+    .service(
+        web::resource("/jobs/{id}")
+        .route(web::get().to(operations::show))
+    )
+    .service(
+        web::resource("/jobs/{id}/result")
+        .route(web::get().to(operations::result))
+    )
+    .service(
+        web::resource("/journals/feed.atom")
+        .route(web::get().to(feed::journal_feed))
+    )
+    .service(
+        web::resource("/journals/export.md")
+        .wrap(from_fn(operations::defer))
+        .route(web::get().to(export::export_journals))
+    )
+    .service(
+        web::resource("/journals/{id:\\d+}.md")
+        .route(web::get().to(export::export_journal))
+    )
+    .service(
+        web::resource("/journals/{id}/share")
+        .route(web::get().to(share::list))
+        .route(web::post().to(share::create))
+        .route(web::delete().to(share::revoke_all))
+    )
+    .service(
+        web::resource("/journals/{id}/share/{token}")
+        .route(web::delete().to(share::revoke))
+    )
+    .service(
+        web::resource("/workspaces")
+        .route(web::post().to(workspace::create))
+    )
+    .service(
+        web::resource("/workspaces/{wid}")
+        .route(web::get().to(workspace::get))
+        .route(web::delete().to(workspace::delete))
+    )
+    .service(
+        web::scope("/workspaces/{wid}")
+        .service(
+            web::resource("/members")
+            .route(web::post().to(workspace::add_member))
+        )
+        .service(
+            web::resource("/members/{name}")
+            .route(web::delete().to(workspace::remove_member))
+        )
+        .configure(resource_routes)
+    )
+    .configure(resource_routes);
+}
+
+// the journals, tasks, habits, notes and bookmarks of a space, either the
+// server's own or a workspace's
+fn resource_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/tasks")
+        .route(web::get().to(get_resources::<Task>))
+        .route(web::post().to(post_resource::<Task>))
+    )
+    // before /tasks/{id}, which would take it for an id
+    .service(
+        web::resource("/tasks/bulk_tag")
+        .route(web::post().to(bulk::tag_tasks))
+    )
+    .service(
+        web::resource("/tasks/{id}")
+        .route(web::get().to(get_by_id::<Task>))
+        .route(web::delete().to(delete_resource::<Task>))
+        .route(web::put().to(put_resource::<Task>))
+        .route(web::patch().to(patch_task))
+    )
+    .service(
+        web::resource("/tasks/{id}/clone")
+        .route(web::post().to(clone_task))
+    )
+    .service(
+        web::resource("/tasks/{id}/split")
+        .route(web::post().to(split_task))
+    )
+    .service(
+        web::resource("/tasks/{id}/move")
+        .route(web::post().to(ordering::move_to))
+    )
+    .service(
+        web::resource("/tasks/{id}/timer/start")
+        .route(web::post().to(timers::start))
+    )
+    .service(
+        web::resource("/tasks/{id}/timer/stop")
+        .route(web::post().to(timers::stop))
+    )
+    .service(
+        web::resource("/tasks/{id}/time")
+        .route(web::get().to(timers::show))
+    )
+    .service(
+        web::resource("/time/report")
+        .route(web::get().to(timers::report))
+    )
+    .service(
+        web::resource("/task_merger")
+        .route(web::post().to(merge_tasks))
+    )
+    .service(
+        web::resource("/journal_merger")
+        .route(web::post().to(merge_journals))
+    )
+    .service(
+        web::resource("/journals")
+        .route(web::get().to(get_resources::<Journal>))
+        .route(web::post().to(post_resource::<Journal>))
+    )
+    // before /journals/{id}, which would take them for ids
+    .service(
+        web::resource("/journals/duplicates")
+        .route(web::get().to(duplicates::duplicates))
+    )
+    .service(
+        web::resource("/journals/today")
+        .route(web::get().to(daily::today))
+    )
+    .service(
+        web::resource("/journals/{id}")
+        .route(web::get().to(get_by_id::<Journal>))
+        .route(web::delete().to(delete_resource::<Journal>))
+        .route(web::put().to(put_resource::<Journal>))
+    )
+    .service(
+        web::resource("/journals/{id}/clone")
+        .route(web::post().to(clone_journal))
+    )
+    .service(
+        web::resource("/journals/{id}/data")
+        .route(web::get().to(export::journal_data))
+    )
+    .service(
+        web::resource("/journals/{id}/attachments")
+        .route(web::get().to(attachments::list))
+        .route(web::post().to(attachments::attach_known))
+    )
+    .service(
+        web::resource("/journals/{id}/attachments/{attachment}")
+        .route(web::get().to(attachments::download))
+        .route(web::delete().to(attachments::remove))
+    )
+    .service(
+        web::resource("/journals/{id}/uploads")
+        .route(web::post().to(attachments::start_upload))
+    )
+    .service(
+        web::resource("/journals/{id}/uploads/{upload}")
+        .app_data(web::PayloadConfig::new(attachments::CHUNK_LIMIT))
+        .route(web::get().to(attachments::show_upload))
+        .route(web::patch().to(attachments::append_chunk))
+        .route(web::delete().to(attachments::abort_upload))
+    )
+    .service(
+        web::resource("/journals/{id}/uploads/{upload}/finalize")
+        .route(web::post().to(attachments::finalize))
+    )
+    .service(
+        web::resource("/journals/{id}/related")
+        .route(web::get().to(related::related))
+    )
+    .service(
+        web::resource("/journals/{id}/bookmarks")
+        .route(web::get().to(bookmarks::of_journal))
+    )
+    .service(
+        web::resource("/journals/{id}/collaborators")
+        .route(web::get().to(workspace::list_collaborators))
+        .route(web::post().to(workspace::add_collaborator))
+    )
+    .service(
+        web::resource("/journals/{id}/collaborators/{name}")
+        .route(web::delete().to(workspace::remove_collaborator))
+    )
+    .service(
+        web::resource("/schema/journals")
+        .route(web::get().to(schema::show::<Journal>))
+        .route(web::put().to(schema::replace::<Journal>))
+        .route(web::delete().to(schema::remove::<Journal>))
+    )
+    .service(
+        web::resource("/schema/tasks")
+        .route(web::get().to(schema::show::<Task>))
+        .route(web::put().to(schema::replace::<Task>))
+        .route(web::delete().to(schema::remove::<Task>))
+    )
+    .service(
+        web::resource("/schema/habits")
+        .route(web::get().to(schema::show::<Habit>))
+        .route(web::put().to(schema::replace::<Habit>))
+        .route(web::delete().to(schema::remove::<Habit>))
+    )
+    .service(
+        web::resource("/schema/notes")
+        .route(web::get().to(schema::show::<Note>))
+        .route(web::put().to(schema::replace::<Note>))
+        .route(web::delete().to(schema::remove::<Note>))
+    )
+    .service(
+        web::resource("/schema/bookmarks")
+        .route(web::get().to(schema::show::<Bookmark>))
+        .route(web::put().to(schema::replace::<Bookmark>))
+        .route(web::delete().to(schema::remove::<Bookmark>))
+    )
+    .service(
+        web::resource("/search")
+        .route(web::get().to(search::search))
+    )
+    .service(
+        web::resource("/autocomplete")
+        .route(web::get().to(autocomplete::complete))
+    )
+    .service(
+        web::resource("/import/ics")
+        .wrap(from_fn(operations::defer))
+        .app_data(web::PayloadConfig::new(takeout::IMPORT_LIMIT))
+        .route(web::post().to(imports::import_ics))
+    )
+    .service(
+        web::resource("/import/todoist")
+        .wrap(from_fn(operations::defer))
+        .app_data(web::PayloadConfig::new(takeout::IMPORT_LIMIT))
+        .route(web::post().to(imports::import_todoist))
+    )
+    .service(
+        web::resource("/import/markdown")
+        .wrap(from_fn(operations::defer))
+        .app_data(web::PayloadConfig::new(takeout::IMPORT_LIMIT))
+        .route(web::post().to(imports::import_markdown))
+    )
+    .service(
+        web::resource("/import/journals")
+        .wrap(from_fn(operations::defer))
+        .app_data(web::PayloadConfig::new(takeout::IMPORT_LIMIT))
+        .route(web::post().to(imports::import_journals))
+    )
+    .service(
+        web::resource("/changes")
+        .route(web::get().to(sync::changes))
+    )
+    .service(
+        web::resource("/events")
+        .route(web::get().to(events::stream))
+    )
+    .service(
+        web::resource("/board")
+        .route(web::get().to(board::board))
+    )
+    .service(
+        web::resource("/stats/mood")
+        .route(web::get().to(stats::mood))
+    )
+    .service(
+        web::resource("/stats/writing")
+        .route(web::get().to(stats::writing))
+    )
+    .service(
+        web::resource("/quota")
+        .route(web::get().to(quota::show))
+    )
+    .service(
+        web::resource("/schedules")
+        .route(web::get().to(schedules::list))
+        .route(web::post().to(schedules::create))
+    )
+    .service(
+        web::resource("/schedules/{id}")
+        .route(web::get().to(schedules::get))
+        .route(web::patch().to(schedules::update))
+        .route(web::delete().to(schedules::remove))
+    )
+    .service(
+        web::resource("/schedules/{id}/run")
+        .route(web::post().to(schedules::run_now))
+    )
+    .service(
+        web::resource("/schedules/{id}/export")
+        .route(web::get().to(schedules::latest_export))
+    )
+    .service(
+        web::resource("/habits")
+        .route(web::get().to(get_resources::<Habit>))
+        .route(web::post().to(post_resource::<Habit>))
+    )
+    .service(
+        web::resource("/habits/{id}")
+        .route(web::get().to(get_by_id::<Habit>))
+        .route(web::delete().to(delete_resource::<Habit>))
+        .route(web::put().to(put_resource::<Habit>))
+    )
+    .service(
+        web::resource("/habits/{id}/checkins")
+        .route(web::post().to(habits::checkin))
+    )
+    .service(
+        web::resource("/habits/{id}/checkins/{date}")
+        .route(web::delete().to(habits::uncheck))
+    )
+    .service(
+        web::resource("/habits/{id}/stats")
+        .route(web::get().to(habits::show_stats))
+    )
+    .service(
+        web::resource("/notes")
+        .route(web::get().to(get_resources::<Note>))
+        .route(web::post().to(post_resource::<Note>))
+    )
+    .service(
+        web::resource("/notes/{id}")
+        .route(web::get().to(get_by_id::<Note>))
+        .route(web::delete().to(delete_resource::<Note>))
+        .route(web::put().to(put_resource::<Note>))
+    )
+    .service(
+        web::resource("/bookmarks")
+        .route(web::get().to(get_resources::<Bookmark>))
+        .route(web::post().to(post_resource::<Bookmark>))
+    )
+    .service(
+        web::resource("/bookmarks/{id}")
+        .route(web::get().to(get_by_id::<Bookmark>))
+        .route(web::delete().to(delete_resource::<Bookmark>))
+        .route(web::put().to(put_resource::<Bookmark>))
+    );
+    #[cfg(feature = "crdt")]
+    cfg.service(
+        web::resource("/sync/merge")
+        .route(web::post().to(crdt::merge))
+    );
+}
+
+// The complete application around `state`, for HttpServer::new or
+// actix_web::test::init_service. The state is shared, so build it once.
+pub fn app(
+    state: web::Data<State>,
+) -> App<impl ServiceFactory<
+    ServiceRequest,
+    Config = (),
+    Response = ServiceResponse<impl MessageBody>,
+    Error = actix_web::Error,
+    InitError = (),
+>> {
+    let app = App::new()
+        .app_data(state.clone())
+        .configure(caldav::configure)
+        .configure(admin::configure)
+        .service(
+            web::resource("/metrics")
+            .route(web::get().to(metrics::render))
+        )
+        // share links are handed out, so they stay valid across API versions
+        .service(
+            web::resource("/shared/{token}")
+            .route(web::get().to(share::view))
+        );
+    #[cfg(feature = "ui")]
+    let app = app.configure(ui::configure);
+    #[cfg(feature = "graphql")]
+    let app = app
+        .app_data(web::Data::new(graphql::build_schema(state.clone())))
+        .service(
+            web::resource("/graphql")
+            .route(web::get().to(graphql::graphiql))
+            .route(web::post().to(graphql::graphql))
+        )
+        .service(
+            web::resource("/workspaces/{wid}/graphql")
+            .route(web::post().to(graphql::graphql))
+        );
+    // the unprefixed scope matches every path, so it has to come last
+    let app = app
+        .service(
+            web::scope("/v1")
+            .wrap(from_fn(jsonapi::errors))
+            .wrap(from_fn(deprecation::annotate))
+            .wrap(from_fn(versioning::negotiate))
+            // outermost, so every error of the API ends up a problem
+            .wrap(from_fn(problem::render))
+            .configure(api_routes)
+        )
+        .service(
+            web::scope("")
+            .wrap(from_fn(jsonapi::errors))
+            .wrap(from_fn(deprecation::annotate))
+            .wrap(from_fn(versioning::negotiate))
+            // outermost, so every error of the API ends up a problem
+            .wrap(from_fn(problem::render))
+            .configure(api_routes)
+        )
+        // inside the guard, its 503s are no errors
+        .wrap(from_fn(report::capture))
+        .wrap(from_fn(dryrun::guard))
+        .wrap(from_fn(signing::verify))
+        .wrap(from_fn(maintenance::guard))
+        .wrap(from_fn(replica::redirect))
+        .wrap(from_fn(slow::watch))
+        .wrap(from_fn(ratelimit::limit))
+        // outside the limit, so who runs into it shows
+        .wrap(from_fn(analytics::record))
+        .wrap(from_fn(metrics::observe))
+        .wrap(from_fn(telemetry::trace));
+    // outermost, the faults happen before the server sees the request
+    #[cfg(feature = "faults")]
+    let app = app.wrap(from_fn(faults::inject));
+    return app;
+}
+
+// the app over the sample data, with nothing read from the environment and
+// nothing running in the background, for tests
+pub fn create_test_app() -> App<impl ServiceFactory<
+    ServiceRequest,
+    Config = (),
+    Response = ServiceResponse<impl MessageBody>,
+    Error = actix_web::Error,
+    InitError = (),
+>> {
+    return app(web::Data::new(State::with_sample_data(Config::default())));
+}
@@ -0,0 +1,125 @@
+// Work that runs on a timer, like the sweepers and the daily journal. Every
+// job runs in a task of its own, first when the server starts and then on
+// its schedule, each run put off by a random jitter so the jobs of several
+// servers do not all fire at once. GET /admin/jobs shows when they ran, when
+// they run next and how they failed.
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Days, Utc};
+use rand::Rng;
+use serde::Serialize;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::admin::require_admin;
+use crate::state::State;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Schedule {
+    Every(Duration),
+    // at midnight, UTC
+    Daily,
+}
+
+impl Schedule {
+    fn next(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        match self {
+            Schedule::Every(period) => now + period,
+            Schedule::Daily         => now.date_naive().checked_add_days(Days::new(1))
+                .and_then(|tomorrow| tomorrow.and_hms_opt(0, 0, 0))
+                .map_or(now + Days::new(1), |midnight| midnight.and_utc()),
+        }
+    }
+
+    // a tenth of the period, at most a minute
+    fn jitter(self) -> Duration {
+        return match self {
+            Schedule::Every(period) => period / 10,
+            Schedule::Daily         => Duration::from_secs(60),
+        }.min(Duration::from_secs(60));
+    }
+
+    fn describe(self) -> String {
+        match self {
+            Schedule::Every(period) => format!("every {}s", period.as_secs()),
+            Schedule::Daily         => String::from("daily at 00:00 UTC"),
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct JobView {
+    name:               &'static str,
+    schedule:           String,
+    runs:               u64,
+    failures:           u64,
+    last_run:           Option<DateTime<Utc>>,
+    last_duration_ms:   Option<u64>,
+    // of the last failed run, kept after later ones succeed
+    last_error:         Option<String>,
+    // None while running
+    next_run:           Option<DateTime<Utc>>,
+}
+
+// the jobs registered so far, in that order
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    jobs: Mutex<Vec<Arc<Mutex<JobView>>>>,
+}
+
+// Registers the job and runs it for as long as the server runs; a run
+// that fails or panics is counted and the job goes on.
+pub(crate) fn spawn<F, Fut>(state: &web::Data<State>, name: &'static str, schedule: Schedule, run: F)
+where
+    F: Fn(web::Data<State>) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), String>> + Send + 'static,
+{
+    let job = Arc::new(Mutex::new(JobView {
+        name,
+        schedule:           schedule.describe(),
+        runs:               0,
+        failures:           0,
+        last_run:           None,
+        last_duration_ms:   None,
+        last_error:         None,
+        next_run:           Some(Utc::now()),
+    }));
+    state.scheduler.jobs.lock().unwrap().push(job.clone());
+    let state = state.clone();
+    actix_web::rt::spawn(async move {
+        loop {
+            let started = (Utc::now(), Instant::now());
+            job.lock().unwrap().next_run = None;
+            let result = match tokio::spawn(run(state.clone())).await {
+                Ok(result)  => result,
+                Err(_)      => Err(String::from("panicked")),
+            };
+            let finished = Utc::now();
+            let jitter = rand::thread_rng().gen_range(Duration::ZERO..=schedule.jitter());
+            let next_run = schedule.next(finished) + jitter;
+            {
+                let mut job = job.lock().unwrap();
+                job.runs += 1;
+                job.last_run = Some(started.0);
+                job.last_duration_ms = Some(started.1.elapsed().as_millis() as u64);
+                job.next_run = Some(next_run);
+                if let Err(error) = result {
+                    println!("Job {} failed: {}", name, error);
+                    job.failures += 1;
+                    job.last_error = Some(error);
+                }
+            }
+            tokio::time::sleep((next_run - finished).to_std().unwrap_or_default()).await;
+        }
+    });
+}
+
+pub(crate) async fn list(state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    let jobs: Vec<JobView> = state.scheduler.jobs.lock().unwrap().iter()
+        .map(|job| job.lock().unwrap().clone())
+        .collect();
+    return HttpResponse::Ok().json(jobs);
+}
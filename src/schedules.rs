@@ -0,0 +1,383 @@
+// Actions clients schedule with cron expressions: creating a task from a
+// template, exporting the space into the blob store, or calling a webhook
+// on a public address (see outbound).
+// The scheduler checks every minute for schedules that are due; each can be
+// paused, changed, run right away or removed.
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::auth::response_token;
+use crate::daily;
+use crate::handlers::{store_new, IdPath, Rejection};
+use crate::models::{Resource, Task, WithId};
+use crate::notify;
+use crate::outbound;
+use crate::state::State;
+use crate::takeout;
+use crate::workspace::{Level, Space};
+
+// how often the scheduler looks for due schedules, cron has no finer steps
+pub(crate) const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum Action {
+    // "{date}" and "{weekday}" in the text are filled in for the day it runs
+    CreateTask { template: Task },
+    // a takeout of the space, kept in the blob store
+    Export,
+    // POSTs the body as JSON, signed with the secret when there is one,
+    // which is never shown again
+    Webhook {
+        url:    String,
+        #[serde(default)]
+        body:   Value,
+        #[serde(default, skip_serializing)]
+        secret: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct ScheduledAction {
+    // "min hour day month weekday", or with seconds (and years) in front
+    cron:               String,
+    action:             Action,
+    paused:             bool,
+    created_at:         DateTime<Utc>,
+    runs:               u64,
+    failures:           u64,
+    last_run:           Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_error:         Option<String>,
+    // the blob key of the latest export
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_export:        Option<String>,
+    // None while paused
+    next_run:           Option<DateTime<Utc>>,
+    // the space it acts in
+    #[serde(skip)]
+    workspace:          Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NewSchedule {
+    cron:       String,
+    action:     Action,
+    #[serde(default)]
+    paused:     bool,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ScheduleUpdate {
+    cron:       Option<String>,
+    action:     Option<Action>,
+    paused:     Option<bool>,
+}
+
+fn parse(cron: &str) -> Result<cron::Schedule, String> {
+    // the usual five fields run at second 0
+    let expression = match cron.split_whitespace().count() {
+        5   => format!("0 {}", cron.trim()),
+        _   => String::from(cron.trim()),
+    };
+    return cron::Schedule::from_str(&expression).map_err(|err| format!("{:?} is no cron expression: {}", cron, err));
+}
+
+fn next_run(cron: &str, paused: bool, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if paused {
+        return None;
+    }
+    return parse(cron).ok()?.after(&after).next();
+}
+
+async fn check(state: &State, cron: &str, action: &Action, space: &Space) -> Result<(), String> {
+    parse(cron)?;
+    match action {
+        Action::CreateTask { template } => template.validate().and_then(|_| space.schemas.check(template)),
+        Action::Export                  => Ok(()),
+        // checked again when it fires, the name may resolve elsewhere by then
+        Action::Webhook { url, .. }     => outbound::check(url, state.config.fetch_private).await,
+    }
+}
+
+// the schedule with the id if it belongs to the space
+fn find(state: &State, space: &Space, id: usize) -> Option<ScheduledAction> {
+    return state.schedules.get(&id)
+        .filter(|schedule| schedule.workspace == space.workspace())
+        .map(|schedule| schedule.clone());
+}
+
+fn not_found() -> HttpResponse {
+    return HttpResponse::NotFound().body("No such schedule");
+}
+
+fn allow(space: &Space) -> Result<(), HttpResponse> {
+    // collaborators only ever see the journals granted to them
+    return space.allow::<Task>(None, Level::Write).map_err(Rejection::into);
+}
+
+pub(crate) async fn list(state: web::Data<State>, space: Space) -> impl Responder {
+    if let Err(resp) = allow(&space) {
+        return resp;
+    }
+    let schedules: Vec<(usize, ScheduledAction)> = state.schedules.snapshot().into_iter()
+        .filter(|(_, schedule)| schedule.workspace == space.workspace())
+        .collect();
+    let schedules: Vec<WithId<'_, ScheduledAction>> = schedules.iter()
+        .map(|(id, schedule)| WithId { id: *id, resource: schedule })
+        .collect();
+    return HttpResponse::Ok().json(schedules);
+}
+
+pub(crate) async fn create(
+    json: web::Json<NewSchedule>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(resp) = allow(&space) {
+        return resp;
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let info = json.into_inner();
+    if let Err(reason) = check(&state, &info.cron, &info.action, &space).await {
+        return HttpResponse::BadRequest().body(reason);
+    }
+    let now = Utc::now();
+    let schedule = ScheduledAction {
+        next_run:       next_run(&info.cron, info.paused, now),
+        cron:           info.cron,
+        action:         info.action,
+        paused:         info.paused,
+        created_at:     now,
+        runs:           0,
+        failures:       0,
+        last_run:       None,
+        last_error:     None,
+        last_export:    None,
+        workspace:      space.workspace(),
+    };
+    let view = schedule.clone();
+    let id = state.schedules.change(move |schedules| {
+        let id = schedules.allocate_id();
+        schedules.insert(id, schedule);
+        id
+    }).await;
+    let id = match id {
+        Ok(id)      => id,
+        Err(failed) => return failed.into(),
+    };
+    return HttpResponse::Created()
+        .append_header(("Location", format!("{}/schedules/{}", space.root(&request), id)))
+        .json(WithId { id, resource: &view });
+}
+
+pub(crate) async fn get(path: web::Path<IdPath>, state: web::Data<State>, space: Space) -> impl Responder {
+    if let Err(resp) = allow(&space) {
+        return resp;
+    }
+    match find(&state, &space, path.id) {
+        Some(schedule)  => HttpResponse::Ok().json(WithId { id: path.id, resource: &schedule }),
+        None            => not_found(),
+    }
+}
+
+// changes what is given, e.g. {"paused": true}
+pub(crate) async fn update(
+    path: web::Path<IdPath>,
+    json: web::Json<ScheduleUpdate>,
+    state: web::Data<State>,
+    space: Space,
+) -> impl Responder {
+    if let Err(resp) = allow(&space) {
+        return resp;
+    }
+    let id = path.id;
+    let Some(mut schedule) = find(&state, &space, id) else {
+        return not_found();
+    };
+    let update = json.into_inner();
+    schedule.cron = update.cron.unwrap_or(schedule.cron);
+    schedule.action = update.action.unwrap_or(schedule.action);
+    schedule.paused = update.paused.unwrap_or(schedule.paused);
+    if let Err(reason) = check(&state, &schedule.cron, &schedule.action, &space).await {
+        return HttpResponse::BadRequest().body(reason);
+    }
+    schedule.next_run = next_run(&schedule.cron, schedule.paused, Utc::now());
+    let view = schedule.clone();
+    let updated = state.schedules.change(move |schedules| match schedules.get_mut(&id) {
+        Some(mut stored) => {
+            // what ran meanwhile is kept
+            stored.cron = schedule.cron;
+            stored.action = schedule.action;
+            stored.paused = schedule.paused;
+            stored.next_run = schedule.next_run;
+            true
+        }
+        None => false,
+    }).await;
+    match updated {
+        Ok(true)    => {}
+        Ok(false)   => return not_found(),
+        Err(failed) => return failed.into(),
+    }
+    return HttpResponse::Ok().json(WithId { id, resource: &view });
+}
+
+pub(crate) async fn remove(path: web::Path<IdPath>, state: web::Data<State>, space: Space) -> impl Responder {
+    if let Err(resp) = allow(&space) {
+        return resp;
+    }
+    if find(&state, &space, path.id).is_none() {
+        return not_found();
+    }
+    let id = path.id;
+    return match state.schedules.change(move |schedules| schedules.remove(&id)).await {
+        Ok(_)       => HttpResponse::Ok().body("Removed"),
+        Err(failed) => failed.into(),
+    };
+}
+
+// runs the action now, paused or not, without moving its next run
+pub(crate) async fn run_now(
+    path: web::Path<IdPath>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(resp) = allow(&space) {
+        return resp;
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let Some(schedule) = find(&state, &space, path.id) else {
+        return not_found();
+    };
+    let result = fire(&state, &space, path.id, &schedule).await;
+    let schedule = find(&state, &space, path.id).unwrap_or(schedule);
+    return match result {
+        Ok(())      => HttpResponse::Ok().json(WithId { id: path.id, resource: &schedule }),
+        Err(error)  => HttpResponse::BadGateway().body(error),
+    };
+}
+
+// the latest export of an export schedule
+pub(crate) async fn latest_export(path: web::Path<IdPath>, state: web::Data<State>, space: Space) -> impl Responder {
+    if let Err(resp) = allow(&space) {
+        return resp;
+    }
+    let Some(key) = find(&state, &space, path.id).and_then(|schedule| schedule.last_export) else {
+        return HttpResponse::NotFound().body("Nothing exported yet");
+    };
+    match state.blobs.get(&key).await {
+        Ok(Some(export))    => HttpResponse::Ok()
+            .content_type("application/json")
+            .append_header(("Content-Disposition", "attachment; filename=\"takeout.json\""))
+            .body(export),
+        Ok(None)            => HttpResponse::NotFound().body("Nothing exported yet"),
+        Err(error)          => {
+            println!("Export {} was not read: {}", key, error);
+            HttpResponse::InternalServerError().body("Error reading the export")
+        }
+    }
+}
+
+// what the action did, or why it failed
+async fn act(state: &State, space: &Space, id: usize, action: &Action, now: DateTime<Utc>) -> Result<Option<String>, String> {
+    match action {
+        Action::CreateTask { template } => {
+            let mut task = template.clone();
+            task.text = daily::fill(&task.text, now.date_naive());
+            // checked and hooked like a task posted over REST, quota included
+            store_new(state, space, task).await.map_err(|rejection| rejection.to_string())?;
+            return Ok(None);
+        }
+        Action::Export => {
+            let export = serde_json::to_vec(&takeout::takeout(space)).map_err(|err| err.to_string())?;
+            let key = format!("exports/schedule-{}/{}.json", id, now.format("%Y%m%dT%H%M%SZ"));
+            state.blobs.put(&key, export.into()).await.map_err(|err| err.to_string())?;
+            return Ok(Some(key));
+        }
+        Action::Webhook { url, body, secret } => {
+            let private = state.config.fetch_private;
+            outbound::check(url, private).await?;
+            let client = outbound::client(private, WEBHOOK_TIMEOUT).map_err(|err| err.to_string())?;
+            let response = notify::post(&client, url, body, secret.as_deref()).send().await
+                .map_err(|err| err.to_string())?;
+            if !response.status().is_success() {
+                return Err(format!("{} answered {}", url, response.status()));
+            }
+            return Ok(None);
+        }
+    }
+}
+
+// runs the schedule's action and notes how it went
+async fn fire(state: &State, space: &Space, id: usize, schedule: &ScheduledAction) -> Result<(), String> {
+    let now = Utc::now();
+    let result = act(state, space, id, &schedule.action, now).await;
+    let outcome = result.clone();
+    state.schedules.change(move |schedules| {
+        if let Some(mut stored) = schedules.get_mut(&id) {
+            stored.runs += 1;
+            stored.last_run = Some(now);
+            match outcome {
+                Ok(export)  => stored.last_export = export.or(stored.last_export.take()),
+                Err(error)  => {
+                    stored.failures += 1;
+                    stored.last_error = Some(error);
+                }
+            }
+        }
+    }).await.map_err(|failed| failed.to_string())?;
+    return result.map(|_| ());
+}
+
+// runs every schedule that is due, a job of the scheduler
+pub(crate) async fn run_due(state: web::Data<State>) -> Result<(), String> {
+    // due ones stay due and run once maintenance is over
+    if state.maintenance().is_some() {
+        return Ok(());
+    }
+    let now = Utc::now();
+    let due: Vec<(usize, ScheduledAction)> = state.schedules.snapshot().into_iter()
+        .filter(|(_, schedule)| schedule.next_run.is_some_and(|next_run| next_run <= now))
+        .collect();
+    let spaces = Space::all(&state);
+    let mut failed = 0;
+    for (id, schedule) in due {
+        // gone with its workspace
+        let Some(space) = spaces.iter().find(|space| space.workspace() == schedule.workspace) else {
+            continue;
+        };
+        if let Err(error) = fire(&state, space, id, &schedule).await {
+            println!("Schedule {} failed: {}", id, error);
+            failed += 1;
+        }
+        let next = next_run(&schedule.cron, schedule.paused, now);
+        let moved = state.schedules.change(move |schedules| {
+            if let Some(mut stored) = schedules.get_mut(&id) {
+                // unless changed meanwhile
+                if stored.cron == schedule.cron && stored.paused == schedule.paused {
+                    stored.next_run = next;
+                }
+            }
+        }).await;
+        if let Err(error) = moved {
+            println!("Schedule {} was not moved to its next run: {}", id, error);
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(format!("{} schedules failed", failed));
+    }
+    return Ok(());
+}
@@ -0,0 +1,92 @@
+// User-defined JSON Schemas for the metadata of journals, tasks and habits,
+// one per kind and space, checked whenever a resource is created or replaced
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use jsonschema::Validator;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::auth::response_token;
+use crate::models::Resource;
+use crate::state::State;
+use crate::workspace::{Level, Space};
+
+pub(crate) struct Schema {
+    // as it was sent, for GET
+    source:     Value,
+    validator:  Validator,
+}
+
+// the schemas of a space by resource kind, shared by every request to it
+#[derive(Clone, Default)]
+pub(crate) struct Schemas(Arc<Mutex<HashMap<&'static str, Arc<Schema>>>>);
+
+impl Schemas {
+    fn get(&self, kind: &str) -> Option<Arc<Schema>> {
+        return self.0.lock().unwrap().get(kind).cloned();
+    }
+
+    // Err with the first violation, e.g. "metadata/priority: 7 is greater
+    // than the maximum of 5"
+    pub(crate) fn check<T: Resource>(&self, resource: &T) -> Result<(), String> {
+        let schema = match self.get(T::KIND) {
+            Some(schema)    => schema,
+            None            => return Ok(()),
+        };
+        let metadata = resource.metadata();
+        return schema.validator.validate(&metadata)
+            .map_err(|error| format!("metadata{}: {}", error.instance_path, error));
+    }
+}
+
+pub(crate) async fn show<T: Resource>(space: Space) -> impl Responder {
+    if let Err(rejection) = space.allow::<T>(None, Level::Read) {
+        return rejection.into();
+    }
+    match space.schemas.get(T::KIND) {
+        Some(schema)    => return HttpResponse::Ok().json(&schema.source),
+        None            => return HttpResponse::NotFound().body("No schema"),
+    }
+}
+
+// applies to writes from now on, stored resources are not checked again
+pub(crate) async fn replace<T: Resource>(
+    json: web::Json<Value>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<T>(None, Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let source = json.into_inner();
+    let validator = match jsonschema::validator_for(&source) {
+        Ok(validator)   => validator,
+        Err(error)      => return HttpResponse::BadRequest().body(format!("Invalid schema: {}", error)),
+    };
+    let schema = Arc::new(Schema { source, validator });
+    match space.schemas.0.lock().unwrap().insert(T::KIND, schema) {
+        Some(_) => return HttpResponse::Ok().body("Updated"),
+        None    => return HttpResponse::Created().body("Created"),
+    }
+}
+
+pub(crate) async fn remove<T: Resource>(
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(rejection) = space.allow::<T>(None, Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    match space.schemas.0.lock().unwrap().remove(T::KIND) {
+        Some(_) => return HttpResponse::Ok().body("Removed"),
+        None    => return HttpResponse::NotFound().body("No schema"),
+    }
+}
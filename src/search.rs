@@ -0,0 +1,306 @@
+// Full-text search over the journals and tasks of a space: every word of the
+// query has to occur, title matches rank above body matches and recent
+// entries a little above old ones. Fuzzy searches also take words a few typos
+// away from a query word, ranked below exact matches
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use quick_xml::escape::escape;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::models::{Journal, Resource, Task};
+use crate::store::Entries;
+use crate::workspace::Space;
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+// characters of context shown before the first match, and in total
+const SNIPPET_LEAD: usize = 60;
+const SNIPPET_LENGTH: usize = 200;
+// a title match outweighs everything the body and recency can add
+const TITLE_WEIGHT: f64 = 10.0;
+const RECENCY_HALF_LIFE_DAYS: f64 = 30.0;
+// what a word within the edit distance of a query word counts for, exact is 1
+const FUZZY_WEIGHT: f64 = 0.5;
+
+// what a search looks at in a resource
+pub(crate) trait Searchable: Resource + Send + Sync + 'static {
+    // the title and, when it may be searched, the body
+    fn searchable(&self) -> (&str, Option<&str>);
+    fn updated_at(&self) -> DateTime<Utc>;
+}
+
+impl Searchable for Journal {
+    fn searchable(&self) -> (&str, Option<&str>) {
+        // ciphertexts never match a search
+        return (&self.title, (!self.encrypted).then_some(self.data.as_str()));
+    }
+    fn updated_at(&self) -> DateTime<Utc> {
+        return self.updated_at;
+    }
+}
+
+impl Searchable for Task {
+    fn searchable(&self) -> (&str, Option<&str>) {
+        return (&self.text, None);
+    }
+    fn updated_at(&self) -> DateTime<Utc> {
+        return self.updated_at;
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SearchQuery {
+    q:      String,
+    // "journals" or "tasks", both when left out
+    kind:   Option<String>,
+    limit:  Option<usize>,
+    #[serde(default)]
+    fuzzy:  bool,
+}
+
+#[derive(Serialize)]
+struct Hit {
+    kind:           &'static str,
+    id:             usize,
+    title:          String,
+    score:          f64,
+    // where the snippet comes from, "title" or "data"
+    field:          &'static str,
+    snippet:        String,
+    // [start, end) of every match in the snippet, in characters
+    highlights:     Vec<(usize, usize)>,
+    // the snippet as HTML, with the matches in <mark>
+    highlighted:    String,
+}
+
+#[derive(Serialize)]
+struct Results {
+    query:      String,
+    total:      usize,
+    results:    Vec<Hit>,
+}
+
+// a word of a searched text, by character offsets
+pub(crate) struct Word {
+    start:              usize,
+    end:                usize,
+    pub(crate) lower:   String,
+}
+
+// the runs of letters and digits in the text, lowercased
+pub(crate) fn words(text: &str) -> Vec<Word> {
+    let mut words = Vec::new();
+    let mut current: Option<Word> = None;
+    for (index, c) in text.chars().enumerate() {
+        if c.is_alphanumeric() {
+            let word = current.get_or_insert_with(|| Word { start: index, end: index, lower: String::new() });
+            word.end = index + 1;
+            word.lower.extend(c.to_lowercase());
+        } else if let Some(word) = current.take() {
+            words.push(word);
+        }
+    }
+    words.extend(current);
+    return words;
+}
+
+// Levenshtein distance, None once it is certainly above `limit`
+fn distance(a: &str, b: &str, limit: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > limit {
+        return None;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        if current.iter().all(|cost| *cost > limit) {
+            return None;
+        }
+        previous = current;
+    }
+    return previous.last().copied().filter(|cost| *cost <= limit);
+}
+
+// the typos a query word may have: none up to 3 characters, then one more
+// for every 2, at most 3 ("groserys" still finds "groceries")
+fn allowed_typos(term: &str) -> usize {
+    return (term.chars().count().saturating_sub(2) / 2).min(3);
+}
+
+// how well a word matches a term: 1 when it contains it, FUZZY_WEIGHT when
+// it is a few typos away
+pub(crate) fn closeness(word: &str, term: &str, fuzzy: bool) -> Option<f64> {
+    if word.contains(term) {
+        return Some(1.0);
+    }
+    if fuzzy && distance(word, term, allowed_typos(term)).is_some() {
+        return Some(FUZZY_WEIGHT);
+    }
+    return None;
+}
+
+// how often a term occurs in a field and how well it matches at best
+#[derive(Clone, Copy, Default)]
+struct Occurrences {
+    count:  usize,
+    best:   f64,
+}
+
+struct Field<'a> {
+    name:   &'static str,
+    text:   &'a str,
+    spans:  Vec<(usize, usize)>,
+}
+
+// the spans of the words matching any term in the text, and the
+// occurrences of each term
+fn field<'a>(name: &'static str, text: &'a str, terms: &[String], fuzzy: bool) -> (Field<'a>, Vec<Occurrences>) {
+    let words = words(text);
+    let mut spans = Vec::new();
+    let mut occurrences = vec![Occurrences::default(); terms.len()];
+    for word in &words {
+        for (term, occurrences) in terms.iter().zip(occurrences.iter_mut()) {
+            if let Some(closeness) = closeness(&word.lower, term, fuzzy) {
+                occurrences.count += 1;
+                occurrences.best = occurrences.best.max(closeness);
+                spans.push((word.start, word.end));
+            }
+        }
+    }
+    spans.dedup();
+    return (Field { name, text, spans }, occurrences);
+}
+
+// up to SNIPPET_LENGTH characters around the first match, with the spans
+// moved to the snippet
+fn snippet(field: &Field<'_>) -> (String, Vec<(usize, usize)>) {
+    let first = field.spans.first().map_or(0, |span| span.0);
+    let start = first.saturating_sub(SNIPPET_LEAD);
+    let length = field.text.chars().count();
+    let end = (start + SNIPPET_LENGTH).min(length);
+    let mut snippet: String = field.text.chars().skip(start).take(end - start).collect();
+    let mut shift = 0;
+    if start > 0 {
+        snippet.insert(0, '…');
+        shift = 1;
+    }
+    if end < length {
+        snippet.push('…');
+    }
+    let spans = field.spans.iter()
+        .filter(|(span_start, span_end)| *span_start >= start && *span_end <= end)
+        .map(|(span_start, span_end)| (span_start - start + shift, span_end - start + shift))
+        .collect();
+    return (snippet, spans);
+}
+
+fn highlight(snippet: &str, spans: &[(usize, usize)]) -> String {
+    let chars: Vec<char> = snippet.chars().collect();
+    let mut html = String::new();
+    let mut position = 0;
+    for (start, end) in spans {
+        html.push_str(&escape(chars[position..*start].iter().collect::<String>().as_str()));
+        html.push_str("<mark>");
+        html.push_str(&escape(chars[*start..*end].iter().collect::<String>().as_str()));
+        html.push_str("</mark>");
+        position = *end;
+    }
+    html.push_str(&escape(chars[position..].iter().collect::<String>().as_str()));
+    return html;
+}
+
+// None unless every term is in the title or the body
+fn rank<T: Searchable>(id: usize, resource: &T, terms: &[String], fuzzy: bool) -> Option<Hit> {
+    let (title, body) = resource.searchable();
+    let (title_field, in_title) = field("title", title, terms, fuzzy);
+    let (body_field, in_body) = field("data", body.unwrap_or(""), terms, fuzzy);
+    if in_title.iter().zip(&in_body).any(|(title, body)| title.count + body.count == 0) {
+        return None;
+    }
+
+    let mut score = 0.0;
+    for (in_title, in_body) in in_title.iter().zip(&in_body) {
+        score += TITLE_WEIGHT * in_title.best;
+        // 1 for the first occurrence in the body, a little more for every further one
+        if in_body.count > 0 {
+            score += in_body.best * (1.0 + (in_body.count - 1).min(4) as f64 * 0.25);
+        }
+    }
+    let age_days = (Utc::now() - resource.updated_at()).num_seconds().max(0) as f64 / 86400.0;
+    score += 0.5_f64.powf(age_days / RECENCY_HALF_LIFE_DAYS);
+
+    let shown = if body_field.spans.is_empty() { &title_field } else { &body_field };
+    let (snippet, highlights) = snippet(shown);
+    return Some(Hit {
+        kind:           T::KIND,
+        id,
+        title:          String::from(title),
+        score:          (score * 1000.0).round() / 1000.0,
+        field:          shown.name,
+        highlighted:    highlight(&snippet, &highlights),
+        snippet,
+        highlights,
+    });
+}
+
+// the ids of the entries that can match, None to look at every entry
+#[cfg(feature = "fulltext")]
+fn candidates<T: Searchable>(space: &Space, terms: &[String], fuzzy: bool) -> Option<BTreeSet<usize>> {
+    match space.index.of::<T>().candidates(terms, fuzzy) {
+        Ok(ids)     => return Some(ids),
+        Err(err)    => {
+            println!("Searching without the {} index: {}", T::KIND, err);
+            return None;
+        }
+    }
+}
+
+#[cfg(not(feature = "fulltext"))]
+fn candidates<T: Searchable>(_space: &Space, _terms: &[String], _fuzzy: bool) -> Option<BTreeSet<usize>> {
+    return None;
+}
+
+// ranks the entries the request may see that can match
+fn collect<T: Searchable>(space: &Space, entries: &Entries<T>, terms: &[String], fuzzy: bool) -> Vec<Hit> {
+    let mut ids = space.visible(entries);
+    if let Some(candidates) = candidates::<T>(space, terms, fuzzy) {
+        ids.retain(|id| candidates.contains(id));
+    }
+    return ids.into_iter()
+        .filter_map(|id| rank(id, &*entries.get(&id)?, terms, fuzzy))
+        .collect();
+}
+
+pub(crate) async fn search(
+    query: web::Query<SearchQuery>,
+    space: Space,
+) -> impl Responder {
+    let terms: Vec<String> = words(&query.q).into_iter().map(|word| word.lower).collect();
+    if terms.is_empty() {
+        return HttpResponse::BadRequest().body("Nothing to search for");
+    }
+    let (journals, tasks) = match query.kind.as_deref() {
+        None                => (true, true),
+        Some("journals")    => (true, false),
+        Some("tasks")       => (false, true),
+        Some(_)             => return HttpResponse::BadRequest().body("kind is journals or tasks"),
+    };
+
+    let mut hits = Vec::new();
+    if journals {
+        hits.extend(collect(&space, &space.journals, &terms, query.fuzzy));
+    }
+    if tasks {
+        hits.extend(collect(&space, &space.tasks, &terms, query.fuzzy));
+    }
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.id.cmp(&b.id)));
+    let total = hits.len();
+    hits.truncate(query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT));
+    return HttpResponse::Ok().json(Results { query: query.q.clone(), total, results: hits });
+}
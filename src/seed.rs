@@ -0,0 +1,199 @@
+// Made-up data for trying the server at scale: `--generate N` at startup or
+// POST /admin/seed adds journals and tasks to the server's own space, with
+// words, tags, moods, places and dates spread over the last year. They are
+// written like any entry, so search, the change log and the event log have
+// them, but no events are published for them.
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Duration, Utc};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+
+use crate::admin::require_admin;
+use crate::etag;
+use crate::models::{Etagged, Journal, Resource, Status, Task};
+use crate::state::State;
+use crate::store::Collection;
+
+// entries of a kind one seeding may add
+pub(crate) const MAX: usize = 1_000_000;
+// entries per change, so other writes get their turn in between
+const BATCH: usize = 1000;
+
+const WORDS: [&str; 48] = [
+    "morning", "coffee", "meeting", "walk", "garden", "book", "train", "email", "report", "lunch",
+    "friend", "call", "project", "idea", "rain", "sun", "bike", "groceries", "dinner", "movie",
+    "plan", "review", "draft", "budget", "doctor", "gym", "run", "kitchen", "letter", "music",
+    "week", "weekend", "office", "city", "river", "park", "notes", "deadline", "sleep", "tea",
+    "quiet", "long", "short", "good", "busy", "slow", "early", "late",
+];
+const TAGS: [&str; 8] = ["work", "home", "health", "ideas", "errands", "reading", "travel", "family"];
+const PLACES: [(&str, f64, f64); 4] = [
+    ("Berlin", 52.52, 13.405),
+    ("Lisbon", 38.722, -9.139),
+    ("Oslo", 59.913, 10.752),
+    ("Kyoto", 35.011, 135.768),
+];
+
+#[derive(Deserialize)]
+struct Seed {
+    #[serde(default)]
+    journals:   usize,
+    #[serde(default)]
+    tasks:      usize,
+    // the same seed makes the same entries
+    seed:       Option<u64>,
+}
+
+#[derive(Serialize)]
+struct Seeded {
+    journals:   usize,
+    tasks:      usize,
+    seed:       u64,
+}
+
+// `min` to `max` words, a sentence when `sentence` is set
+fn words(rng: &mut StdRng, min: usize, max: usize, sentence: bool) -> String {
+    let count = rng.gen_range(min..=max);
+    let text = (0..count).map(|_| *WORDS.choose(rng).unwrap()).collect::<Vec<_>>().join(" ");
+    let mut chars = text.chars();
+    let Some(first) = chars.next() else {
+        return text;
+    };
+    let capitalized = first.to_uppercase().chain(chars).collect::<String>();
+    return if sentence { capitalized + "." } else { capitalized };
+}
+
+fn tags(rng: &mut StdRng) -> Vec<String> {
+    let count = rng.gen_range(0..=3);
+    return TAGS.choose_multiple(rng, count).map(|tag| String::from(*tag)).collect();
+}
+
+// some time in the last year
+fn past(rng: &mut StdRng, now: DateTime<Utc>) -> DateTime<Utc> {
+    return now - Duration::seconds(rng.gen_range(0..365 * 24 * 3600));
+}
+
+// some time between then and now
+fn after(rng: &mut StdRng, then: DateTime<Utc>, now: DateTime<Utc>) -> DateTime<Utc> {
+    let seconds = (now - then).num_seconds().max(1);
+    return then + Duration::seconds(rng.gen_range(0..seconds));
+}
+
+fn journal(rng: &mut StdRng, now: DateTime<Utc>) -> Journal {
+    let paragraphs = rng.gen_range(1..=4);
+    let data = (0..paragraphs)
+        .map(|_| (0..rng.gen_range(1..=5)).map(|_| words(rng, 4, 14, true)).collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let place = rng.gen_bool(0.3).then(|| *PLACES.choose(rng).unwrap());
+    let created_at = past(rng, now);
+    return Journal {
+        title:       words(rng, 2, 6, false),
+        word_count:  data.split_whitespace().count(),
+        char_count:  data.chars().count(),
+        data,
+        encrypted:   false,
+        tags:        tags(rng),
+        metadata:    None,
+        mood:        rng.gen_bool(0.6).then(|| rng.gen_range(1..=5)),
+        energy:      rng.gen_bool(0.4).then(|| rng.gen_range(1..=5)),
+        sleep_hours: rng.gen_bool(0.4).then(|| f64::from(rng.gen_range(8..=18_u8)) / 2.0),
+        lat:         place.map(|(_, lat, _)| lat),
+        lon:         place.map(|(_, _, lon)| lon),
+        place:       place.map(|(name, _, _)| String::from(name)),
+        expires_at:  None,
+        publish_at:  None,
+        day:         None,
+        etag:        String::new(),
+        version:     0,
+        created_at,
+        updated_at:  after(rng, created_at, now),
+    };
+}
+
+fn task(rng: &mut StdRng, now: DateTime<Utc>) -> Task {
+    let status = *[Status::Todo, Status::Todo, Status::InProgress, Status::Blocked, Status::Done, Status::Done, Status::Cancelled]
+        .choose(rng)
+        .unwrap();
+    let created_at = past(rng, now);
+    let updated_at = after(rng, created_at, now);
+    return Task {
+        text:         words(rng, 2, 8, false),
+        done:         status == Status::Done,
+        status:       Some(status),
+        archived:     false,
+        tags:         tags(rng),
+        etag:         String::new(),
+        version:      0,
+        created_at,
+        updated_at,
+        completed_at: (status == Status::Done).then_some(updated_at),
+        due:          rng.gen_bool(0.3).then(|| created_at + Duration::days(rng.gen_range(1..=30))),
+        expires_at:   None,
+        time_entries: Vec::new(),
+        metadata:     Map::new(),
+        position:     0,
+    };
+}
+
+// adds `count` entries made by `make`, a batch per change
+async fn fill<T: Resource + Etagged + Serialize + Send + Sync + 'static>(
+    collection: &Collection<T>,
+    count:      usize,
+    rng:        &mut StdRng,
+    make:       fn(&mut StdRng, DateTime<Utc>) -> T,
+) -> Result<(), String> {
+    let now = Utc::now();
+    for start in (0..count).step_by(BATCH) {
+        let batch: Vec<T> = (start..count.min(start + BATCH)).map(|_| make(rng, now)).collect();
+        collection.change(move |entries| {
+            for mut entry in batch {
+                let id = entries.allocate_id();
+                entry.place(id);
+                etag::refresh(&mut entry).map_err(|_| String::from("Error during serialization"))?;
+                entries.insert(id, entry);
+            }
+            return Ok::<(), String>(());
+        }).await.unwrap_or_else(|failed| Err(failed.to_string()))?;
+    }
+    return Ok(());
+}
+
+// adds the journals and tasks, made from the seed or a random one, which
+// is returned
+pub async fn generate(state: &State, journals: usize, tasks: usize, seed: Option<u64>) -> Result<u64, String> {
+    if journals > MAX || tasks > MAX {
+        return Err(format!("At most {} entries of a kind at once", MAX));
+    }
+    let seed = seed.unwrap_or_else(rand::random);
+    let mut rng = StdRng::seed_from_u64(seed);
+    fill(&state.journals, journals, &mut rng, journal).await?;
+    fill(&state.tasks, tasks, &mut rng, task).await?;
+    println!("Generated {} journals and {} tasks from seed {}", journals, tasks, seed);
+    return Ok(seed);
+}
+
+pub(crate) async fn create(payload: Bytes, state: web::Data<State>, request: HttpRequest) -> impl Responder {
+    if let Err(resp) = require_admin(&state, &request) {
+        return resp;
+    }
+    if state.config.replica.is_some() {
+        return HttpResponse::Conflict().body("A replica only has the entries of its primary");
+    }
+    // an admin route, but a write all the same
+    if let Some(maintenance) = state.maintenance() {
+        return maintenance.refusal();
+    }
+    let seed: Seed = match serde_json::from_slice(&payload) {
+        Ok(seed)    => seed,
+        Err(_)      => return HttpResponse::BadRequest().body("Broken json"),
+    };
+    return match generate(&state, seed.journals, seed.tasks, seed.seed).await {
+        Ok(used)    => HttpResponse::Created().json(Seeded { journals: seed.journals, tasks: seed.tasks, seed: used }),
+        Err(err)    => HttpResponse::BadRequest().body(err),
+    };
+}
@@ -0,0 +1,179 @@
+// Public read-only links to single journals: anyone holding the link can read
+// the journal at /shared/{token} until the share is revoked
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use quick_xml::escape::escape;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::auth::{random_key, response_token};
+use crate::models::{Journal, Resource};
+use crate::render::journal_html;
+use crate::state::State;
+use crate::store::{Collection, Entries, Observer};
+
+pub(crate) struct Share {
+    journal:    usize,
+    created_at: DateTime<Utc>,
+}
+
+// The shares of every root journal, keyed by their token. Those of a
+// journal go away with it, so a journal put under the same id later is not
+// published through an old link.
+#[derive(Clone)]
+pub(crate) struct Shares(Arc<Mutex<HashMap<String, Share>>>);
+
+impl Shares {
+    pub(crate) fn watch(journals: &Collection<Journal>) -> Shares {
+        let shares = Shares(Arc::new(Mutex::new(HashMap::new())));
+        journals.observe(Arc::new(shares.clone()));
+        return shares;
+    }
+}
+
+impl std::ops::Deref for Shares {
+    type Target = Mutex<HashMap<String, Share>>;
+    fn deref(&self) -> &Mutex<HashMap<String, Share>> {
+        return &self.0;
+    }
+}
+
+impl Observer<Journal> for Shares {
+    fn changed(&self, entries: &Entries<Journal>, ids: &BTreeSet<usize>) {
+        self.0.lock().unwrap()
+            .retain(|_, share| !ids.contains(&share.journal) || entries.get(&share.journal).is_some());
+    }
+}
+
+#[derive(Serialize)]
+struct ShareView {
+    token:      String,
+    url:        String,
+    created_at: DateTime<Utc>,
+}
+
+// absolute with a configured base URL, a path otherwise
+fn url(state: &State, token: &str) -> String {
+    return format!("{}/shared/{}", state.config.base_url.as_deref().unwrap_or(""), token);
+}
+
+pub(crate) async fn create(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let journal = path.into_inner();
+    if state.journals.get(&journal).is_none() {
+        return HttpResponse::NotFound().body("Not found");
+    }
+    let token = random_key();
+    let created_at = Utc::now();
+    state.shares.lock().unwrap().insert(token.clone(), Share { journal, created_at });
+    return HttpResponse::Created()
+        .append_header(("Location", url(&state, &token)))
+        .json(ShareView { url: url(&state, &token), token, created_at });
+}
+
+// the active shares of a journal, oldest first
+pub(crate) async fn list(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let journal = path.into_inner();
+    let mut shares: Vec<ShareView> = state.shares.lock().unwrap().iter()
+        .filter(|(_, share)| share.journal == journal)
+        .map(|(token, share)| ShareView { token: token.clone(), url: url(&state, token), created_at: share.created_at })
+        .collect();
+    shares.sort_by_key(|share| share.created_at);
+    return HttpResponse::Ok().json(shares);
+}
+
+fn remove(state: &State, journal: usize, token: Option<&str>) -> HttpResponse {
+    let mut shares = state.shares.lock().unwrap();
+    let before = shares.len();
+    shares.retain(|key, share| share.journal != journal || token.is_some_and(|token| token != key));
+    if shares.len() == before {
+        return HttpResponse::NotFound().body("No such share");
+    }
+    return HttpResponse::Ok().body("Revoked");
+}
+
+pub(crate) async fn revoke(
+    path: web::Path<(usize, String)>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let (journal, token) = path.into_inner();
+    return remove(&state, journal, Some(&token));
+}
+
+// every share of the journal at once
+pub(crate) async fn revoke_all(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    return remove(&state, path.into_inner(), None);
+}
+
+fn page(state: &State, id: usize, journal: &Journal) -> String {
+    let body = if journal.encrypted {
+        String::from("<p><em>Encrypted, only the app that wrote it can show it.</em></p>\n")
+    } else {
+        journal_html(state, id, journal).to_string()
+    };
+    return format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{title}</title>\n</head>\n<body>\n<h1>{title}</h1>\n{body}</body>\n</html>\n",
+        title = escape(&journal.title),
+    );
+}
+
+// HTML for browsers, JSON otherwise
+pub(crate) async fn view(
+    path: web::Path<String>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    let token = path.into_inner();
+    let journal = match state.shares.lock().unwrap().get(&token) {
+        Some(share) => share.journal,
+        None        => return HttpResponse::NotFound().body("Not found"),
+    };
+    // drafts are not there yet for anyone with the link, expired journals are gone already
+    let now = Utc::now();
+    let (id, journal) = match state.journals.get(&journal).filter(|entry| !entry.draft(now) && !entry.expired(now)) {
+        Some(entry)     => (journal, entry.clone()),
+        None            => return HttpResponse::NotFound().body("Not found"),
+    };
+
+    let wants_html = request.headers().get("Accept")
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    let mut response = HttpResponse::Ok();
+    // the token is the whole secret, so it should not travel any further
+    response
+        .append_header(("Cache-Control", "no-store"))
+        .append_header(("Referrer-Policy", "no-referrer"))
+        .append_header(("X-Robots-Tag", "noindex"))
+        .append_header(("Vary", "Accept"));
+    if wants_html {
+        return response
+            .content_type("text/html; charset=utf-8")
+            .body(page(&state, id, &journal));
+    }
+    return response.json(journal);
+}
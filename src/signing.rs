@@ -0,0 +1,121 @@
+// Signed writes, on top of the one-shot tokens, for servers that are reached
+// without TLS: with JOURNAL_SIGNING_SECRET set, every write carries
+// X-Journal-Timestamp (seconds since the epoch), a fresh X-Journal-Nonce and
+// X-Journal-Signature, the hex HMAC-SHA256 with the secret of
+//
+//     {timestamp}\n{nonce}\n{method}\n{path and query}\n{hex SHA-256 of the body}
+//
+// A captured request replayed later is refused: its timestamp is too old,
+// or, within the allowed skew, its nonce was seen already.
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, TimeDelta, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::maintenance::is_write;
+use crate::operations;
+use crate::state::State;
+
+const TIMESTAMP: &str = "X-Journal-Timestamp";
+const NONCE: &str = "X-Journal-Nonce";
+const SIGNATURE: &str = "X-Journal-Signature";
+// how far a timestamp may be off, either way; nonces are kept as long
+const SKEW: TimeDelta = TimeDelta::minutes(5);
+
+// the nonces of the signed requests within the skew, with their timestamps
+#[derive(Default)]
+pub(crate) struct Nonces {
+    seen: Mutex<HashMap<String, DateTime<Utc>>>,
+}
+
+impl Nonces {
+    // false when the nonce was used already
+    fn admit(&self, nonce: &str, timestamp: DateTime<Utc>) -> bool {
+        let oldest = Utc::now() - SKEW;
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, timestamp| *timestamp >= oldest);
+        if seen.contains_key(nonce) {
+            return false;
+        }
+        seen.insert(String::from(nonce), timestamp);
+        return true;
+    }
+}
+
+fn mac(secret: &str) -> Hmac<Sha256> {
+    return Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+}
+
+// the hex HMAC-SHA256 of the message, as webhook deliveries are signed
+pub(crate) fn sign(secret: &str, message: &[u8]) -> String {
+    let mut mac = mac(secret);
+    mac.update(message);
+    return mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    return (0..text.len()).step_by(2)
+        .map(|at| u8::from_str_radix(text.get(at..at + 2)?, 16).ok())
+        .collect();
+}
+
+fn refused(message: &str) -> HttpResponse {
+    return HttpResponse::Unauthorized().body(String::from(message));
+}
+
+fn check(state: &State, secret: &str, request: &ServiceRequest, body: &[u8]) -> Result<(), HttpResponse> {
+    let header = |name: &str| request.headers().get(name).and_then(|value| value.to_str().ok());
+    let (Some(timestamp), Some(nonce), Some(signature)) = (header(TIMESTAMP), header(NONCE), header(SIGNATURE)) else {
+        return Err(refused("Writes have to be signed"));
+    };
+    let signed_at = timestamp.parse().ok()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .ok_or_else(|| refused("Broken timestamp"))?;
+    if (Utc::now() - signed_at).abs() > SKEW {
+        return Err(refused("The signature is too old"));
+    }
+
+    let target = match request.query_string() {
+        ""      => String::from(request.path()),
+        query   => format!("{}?{}", request.path(), query),
+    };
+    let signed = format!("{}\n{}\n{}\n{}\n{}", timestamp, nonce, request.method(), target, sha256::digest(body));
+    let mut mac = mac(secret);
+    mac.update(signed.as_bytes());
+    let valid = unhex(signature).is_some_and(|signature| mac.verify_slice(&signature).is_ok());
+    if !valid {
+        return Err(refused("Bad signature"));
+    }
+    // only signed ones use up nonces, or anyone could use them up
+    if !state.nonces.admit(nonce, signed_at) {
+        return Err(refused("The nonce was used already"));
+    }
+    return Ok(());
+}
+
+pub async fn verify(
+    mut request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let state = request.app_data::<web::Data<State>>().cloned();
+    let secret = state.as_ref().and_then(|state| state.config.signing_secret.clone());
+    let (Some(state), Some(secret)) = (state, secret.filter(|_| is_write(&request))) else {
+        return Ok(next.call(request).await?.map_into_left_body());
+    };
+    let checked = match operations::buffer(&mut request).await {
+        Ok(body)    => check(&state, &secret, &request, &body),
+        Err(resp)   => Err(resp),
+    };
+    if let Err(resp) = checked {
+        return Ok(request.into_response(resp).map_into_right_body());
+    }
+    return Ok(next.call(request).await?.map_into_left_body());
+}
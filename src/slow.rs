@@ -0,0 +1,51 @@
+// Logs every request slower than JOURNAL_SLOW_REQUEST_MS with its route,
+// parameters and what its changes spent in the collection writers, and
+// counts it in the metrics, to find out what is waiting on what
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::web;
+use std::time::Instant;
+
+use crate::state::State;
+use crate::store;
+
+// the path parameters the router matched, e.g. "wid=1 id=3"
+fn params<B>(response: &ServiceResponse<B>) -> String {
+    return response.request().match_info().iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<String>>()
+        .join(" ");
+}
+
+pub async fn watch(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let state = request.app_data::<web::Data<State>>().cloned();
+    let threshold = match state.as_ref().and_then(|state| state.config.slow_request) {
+        Some(threshold) => threshold,
+        None            => return next.call(request).await,
+    };
+    let method = request.method().to_string();
+    let route = request.match_pattern().unwrap_or_else(|| String::from("unmatched"));
+    let query = String::from(request.query_string());
+
+    let started = Instant::now();
+    let (response, changes) = store::timed(next.call(request)).await;
+    let elapsed = started.elapsed();
+    if elapsed < threshold {
+        return response;
+    }
+
+    let params = response.as_ref().map(params).unwrap_or_default();
+    let query = if query.is_empty() { query } else { format!(" ?{}", query) };
+    println!(
+        "Slow request: {} {} [{}]{} took {:?}, {} changes waited {:?} and held the writers {:?}",
+        method, route, params, query, elapsed, changes.changes, changes.waited, changes.held,
+    );
+    if let Some(state) = state {
+        state.metrics.count_slow(&method, &route);
+    }
+    return response;
+}
@@ -0,0 +1,500 @@
+// Application state shared by every handler
+use chrono::Utc;
+use serde::Serialize;
+use serde_json::Map;
+use std::collections::HashMap;
+#[cfg(feature = "plugins")]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+use crate::analytics::Analytics;
+use crate::attachments::{self, Attachments};
+use crate::audit::Audit;
+use crate::auth::Token;
+use crate::blobs::{BlobConfig, BlobStore};
+#[cfg(feature = "crdt")]
+use crate::crdt::Replication;
+use crate::daily::DailyConfig;
+use crate::eventlog::{EventLog, EventLogConfig};
+use crate::events::Bus;
+#[cfg(feature = "faults")]
+use crate::faults::Faults;
+use crate::flags::Flags;
+#[cfg(feature = "fulltext")]
+use crate::fulltext::Indexes;
+use crate::handlers::Rejection;
+use crate::hooks::Hooks;
+use crate::maintenance::Maintenance;
+use crate::metrics::Metrics;
+use crate::models::{Bookmark, Etagged, Habit, Journal, Note, Resource, Status, Task, Timestamped, Transitions, POSITION_GAP};
+use crate::notify::{Action, Event, Flavor, Notifier, WebhookTarget};
+use crate::operations::Operation;
+#[cfg(feature = "plugins")]
+use crate::plugins;
+use crate::quota::{Quotas, Usage};
+use crate::ratelimit::Limiter;
+use crate::reload::Settings;
+use crate::render::{self, Rendered};
+use crate::replica::{Following, ReplicaConfig};
+use crate::report::{Reporter, Sink};
+use crate::scheduler::Scheduler;
+use crate::schedules::ScheduledAction;
+use crate::schema::Schemas;
+use crate::share::Shares;
+use crate::signing::Nonces;
+use crate::store::{Collection, Failed, Writer};
+use crate::sync::ChangeLog;
+use crate::workspace::Workspace;
+use crate::{etag, flags, reload, telegram};
+
+const DEFAULT_SLOW_REQUEST: Duration = Duration::from_millis(500);
+
+// Runtime configuration, read from the environment at startup; the default
+// has no feed token, webhooks or bot
+#[derive(Default)]
+pub struct Config {
+    // when set, the journal feed requires ?token=<feed_token>
+    pub(crate) feed_token:     Option<String>,
+    pub(crate) webhooks:       Vec<WebhookTarget>,
+    pub(crate) telegram:       Option<telegram::TelegramConfig>,
+    // bearer credential of the /admin routes, which are off without it
+    pub(crate) admin_token:    Option<String>,
+    // where panics and 5xx responses are reported, besides the output
+    pub(crate) error_sinks:    Vec<Sink>,
+    // requests taking longer are logged and counted, none are when unset
+    pub(crate) slow_request:   Option<Duration>,
+    // the status changes tasks may make
+    pub(crate) transitions:    Transitions,
+    // how daily journals are titled and filled, and whether they are
+    // created every day
+    pub(crate) daily:          DailyConfig,
+    // where the content of attachments is stored
+    pub(crate) blobs:          BlobConfig,
+    // how many journals are kept rendered as HTML, 0 turns that off and
+    // unset keeps the default
+    pub(crate) render_cache:   Option<usize>,
+    // the share of clients each feature flag starts out on for, see flags
+    pub(crate) flags:          HashMap<String, u8>,
+    // where clients reach the server, e.g. "https://example.com/journal",
+    // for absolute links; without it links are paths
+    pub(crate) base_url:       Option<String>,
+    // how much may be stored, nothing is limited by default
+    pub(crate) quotas:         Quotas,
+    // as they were at startup, State::settings has the current ones
+    pub(crate) settings:       Settings,
+    // when set, writes have to be signed with it
+    pub(crate) signing_secret: Option<String>,
+    // where writes are logged and the state is rebuilt from, in memory
+    // only when unset
+    pub(crate) event_log:      Option<EventLogConfig>,
+    // whether bookmarks saved without a title get the one of their page
+    pub(crate) fetch_titles:   bool,
+    // whether those pages may be on loopback, private or link-local
+    // addresses, only public ones are fetched by default
+    pub(crate) fetch_private:  bool,
+    // the primary this server is a read replica of
+    pub(crate) replica:        Option<ReplicaConfig>,
+    // where the WASM plugins are loaded from, there are none without it
+    #[cfg(feature = "plugins")]
+    pub(crate) plugin_dir:     Option<PathBuf>,
+    // what goes wrong on purpose, nothing without it
+    #[cfg(feature = "faults")]
+    pub(crate) faults:         Option<Faults>,
+}
+
+impl Config {
+    pub fn from_env() -> Config {
+        if let Err(err) = reload::load_file() {
+            println!("{}", err);
+        }
+        let webhooks = [
+            WebhookTarget::from_env(Flavor::Slack, "JOURNAL_SLACK"),
+            WebhookTarget::from_env(Flavor::Discord, "JOURNAL_DISCORD"),
+        ];
+        Config {
+            feed_token:     std::env::var("JOURNAL_FEED_TOKEN").ok(),
+            webhooks:       webhooks.into_iter().flatten().collect(),
+            telegram:       telegram::TelegramConfig::from_env(),
+            error_sinks:    Sink::from_env(),
+            admin_token:    std::env::var("JOURNAL_ADMIN_TOKEN").ok().filter(|token| !token.is_empty()),
+            slow_request:   Some(std::env::var("JOURNAL_SLOW_REQUEST_MS").ok()
+                .and_then(|ms| ms.parse().ok())
+                .map_or(DEFAULT_SLOW_REQUEST, Duration::from_millis)),
+            transitions:    std::env::var("JOURNAL_TASK_TRANSITIONS").ok()
+                .and_then(|list| Transitions::parse(&list)
+                    .inspect_err(|err| println!("Ignoring JOURNAL_TASK_TRANSITIONS: {}", err))
+                    .ok())
+                .unwrap_or_default(),
+            daily:          DailyConfig::from_env(),
+            blobs:          BlobConfig::from_env(),
+            render_cache:   std::env::var("JOURNAL_RENDER_CACHE").ok()
+                .and_then(|size| size.parse()
+                    .inspect_err(|_| println!("Ignoring JOURNAL_RENDER_CACHE, {:?} is no number", size))
+                    .ok()),
+            flags:          flags::from_env(),
+            base_url:       std::env::var("JOURNAL_BASE_URL").ok()
+                .map(|url| String::from(url.trim().trim_end_matches('/')))
+                .filter(|url| !url.is_empty()),
+            quotas:         Quotas::from_env(),
+            settings:       Settings::from_env(),
+            signing_secret: std::env::var("JOURNAL_SIGNING_SECRET").ok().filter(|secret| !secret.is_empty()),
+            event_log:      EventLogConfig::from_env(),
+            fetch_titles:   std::env::var("JOURNAL_FETCH_TITLES").is_ok_and(|on| on == "1" || on == "true"),
+            fetch_private:  std::env::var("JOURNAL_FETCH_PRIVATE").is_ok_and(|on| on == "1" || on == "true"),
+            replica:        ReplicaConfig::from_env(),
+            #[cfg(feature = "plugins")]
+            plugin_dir:     std::env::var_os("JOURNAL_PLUGIN_DIR").map(PathBuf::from),
+            #[cfg(feature = "faults")]
+            faults:         Faults::from_env(),
+        }
+    }
+}
+
+// Application state
+pub struct State {
+    pub(crate) journals:    Collection<Journal>,
+    pub(crate) tasks:       Collection<Task>,
+    pub(crate) habits:      Collection<Habit>,
+    pub(crate) notes:       Collection<Note>,
+    pub(crate) bookmarks:   Collection<Bookmark>,
+    pub(crate) schemas:     Schemas,
+    pub(crate) attachments: Attachments,
+    // shared by the attachments of every space
+    pub(crate) blobs:       Arc<dyn BlobStore>,
+    // the HTML of the journals shown most recently
+    pub(crate) rendered:    Rendered,
+    // what all spaces store together, held against the quotas
+    pub(crate) usage:       Arc<Usage>,
+    // of the journals and tasks, searched instead of them
+    #[cfg(feature = "fulltext")]
+    pub(crate) index:       Indexes,
+    // of the journals, tasks and habits, for delta syncs
+    pub(crate) changes:     Arc<ChangeLog>,
+    // the merged edits of offline devices
+    #[cfg(feature = "crdt")]
+    pub(crate) replication: Replication,
+    pub(crate) tokens:      Mutex<HashMap<String, Token>>,
+    pub(crate) config:      Config,
+    pub(crate) workspaces:  Collection<Workspace>,
+    // public links to journals, keyed by their token
+    pub(crate) shares:      Shares,
+    // where every collection sends its events, kept for new workspaces
+    pub(crate) events:      mpsc::UnboundedSender<Event>,
+    // where they are published, for whatever reacts to writes
+    pub(crate) bus:         Bus,
+    // the events published last, for /admin/audit
+    pub(crate) audit:       Arc<Audit>,
+    pub(crate) metrics:     Metrics,
+    // writes are refused while set
+    pub(crate) maintenance: Mutex<Option<Maintenance>>,
+    pub(crate) reporter:    Reporter,
+    // the jobs that run on a timer
+    pub(crate) scheduler:   Scheduler,
+    // the actions clients scheduled, of every space
+    pub(crate) schedules:   Collection<ScheduledAction>,
+    // the requests answered in the background, keyed by their job id
+    pub(crate) operations:  Mutex<HashMap<String, Arc<Mutex<Operation>>>>,
+    // the requests every client made in its current window
+    pub(crate) limiter:     Limiter,
+    // the requests of every client, for /admin/usage
+    pub(crate) analytics:   Analytics,
+    // of the signed writes, so none is replayed
+    pub(crate) nonces:      Nonces,
+    // what embedders registered to run on writes
+    pub(crate) hooks:       Hooks,
+    // where the journals, tasks and habits are stored, in memory only
+    // without it
+    pub(crate) event_log:   Option<Arc<EventLog>>,
+    // how far a replica got in following its primary
+    pub(crate) following:   Mutex<Following>,
+    // the experimental behavior switched on, and for whom
+    pub(crate) flags:       Flags,
+    // the settings that change on a reload
+    pub(crate) settings:    RwLock<Settings>,
+}
+
+pub(crate) trait Readable<T> {
+    fn get_hmap(&self) -> &Collection<T>;
+}
+
+impl Readable<Journal> for State {
+    fn get_hmap(&self) -> &Collection<Journal> {
+        return &self.journals;
+    }
+}
+
+impl Readable<Task> for State {
+    fn get_hmap(&self) -> &Collection<Task> {
+        return &self.tasks;
+    }
+}
+
+impl Readable<Habit> for State {
+    fn get_hmap(&self) -> &Collection<Habit> {
+        return &self.habits;
+    }
+}
+
+impl Readable<Note> for State {
+    fn get_hmap(&self) -> &Collection<Note> {
+        return &self.notes;
+    }
+}
+
+impl Readable<Bookmark> for State {
+    fn get_hmap(&self) -> &Collection<Bookmark> {
+        return &self.bookmarks;
+    }
+}
+
+impl State {
+    // starts the collection writers and the delivery of their events,
+    // so this has to run inside the runtime
+    pub fn new(
+        journals: HashMap<usize, Journal>,
+        tasks: HashMap<usize, Task>,
+        config: Config,
+    ) -> State {
+        return State::build(journals, tasks, HashMap::new(), HashMap::new(), HashMap::new(), config);
+    }
+
+    fn build(
+        journals: HashMap<usize, Journal>,
+        tasks: HashMap<usize, Task>,
+        habits: HashMap<usize, Habit>,
+        notes: HashMap<usize, Note>,
+        bookmarks: HashMap<usize, Bookmark>,
+        config: Config,
+    ) -> State {
+        let (events, queue) = mpsc::unbounded_channel::<Event>();
+        let bus = Bus::start(queue);
+        let webhooks = config.webhooks.clone();
+        let notifier = Notifier::new(config.base_url.clone());
+        bus.consume("webhooks", move |event| notifier.notify(&webhooks, &event));
+        let reporter = Reporter::new(config.error_sinks.clone());
+        let blobs = config.blobs.open();
+        let journals = Collection::new(journals, events.clone());
+        let tasks = Collection::new(tasks, events.clone());
+        let habits = Collection::new(habits, events.clone());
+        let notes = Collection::new(notes, events.clone());
+        let bookmarks = Collection::new(bookmarks, events.clone());
+        let usage = Arc::new(Usage::default());
+        usage.watch(&journals, &tasks, &habits, &notes, &bookmarks);
+        let hooks = Hooks::default();
+        let flags = Flags::new(&config.flags);
+        #[cfg(feature = "plugins")]
+        for plugin in config.plugin_dir.as_deref().map(plugins::load).unwrap_or_default() {
+            hooks.add(plugin);
+        }
+        State {
+            #[cfg(feature = "fulltext")]
+            index:       Indexes::watch(&journals, &tasks),
+            changes:     ChangeLog::watch(&journals, &tasks, &habits, &notes, &bookmarks),
+            attachments: Attachments::watch(&journals, blobs.clone(), usage.clone(), String::from(attachments::SERVER_NAMESPACE)),
+            blobs,
+            usage,
+            rendered:    Rendered::watch(&journals, config.render_cache.unwrap_or(render::DEFAULT_CACHE_SIZE)),
+            shares:      Shares::watch(&journals),
+            #[cfg(feature = "crdt")]
+            replication: Replication::default(),
+            journals,
+            tasks,
+            habits,
+            notes,
+            bookmarks,
+            schemas:     Schemas::default(),
+            tokens:      Mutex::new(HashMap::new()),
+            workspaces:  Collection::new(HashMap::new(), events.clone()),
+            schedules:   Collection::new(HashMap::new(), events.clone()),
+            events,
+            audit:       Audit::watch(&bus),
+            bus,
+            metrics:     Metrics::default(),
+            maintenance: Mutex::new(None),
+            reporter,
+            scheduler:   Scheduler::default(),
+            operations:  Mutex::new(HashMap::new()),
+            limiter:     Limiter::default(),
+            analytics:   Analytics::default(),
+            nonces:      Nonces::default(),
+            hooks,
+            event_log:   None,
+            following:   Mutex::new(Following::default()),
+            flags,
+            settings:    RwLock::new(config.settings.clone()),
+            config,
+        }
+    }
+
+    // what the event log in JOURNAL_EVENT_LOG holds; without one nothing
+    // for a replica, which copies its primary, or the sample data
+    pub fn open(config: Config) -> std::io::Result<State> {
+        let Some(dir) = config.event_log.as_ref().map(|event_log| event_log.dir.clone()) else {
+            if config.replica.is_some() {
+                return Ok(State::new(HashMap::new(), HashMap::new(), config));
+            }
+            return Ok(State::with_sample_data(config));
+        };
+        let (log, replayed) = EventLog::open(&dir)?;
+        let mut state = State::build(
+            replayed.journals.entries,
+            replayed.tasks.entries,
+            replayed.habits.entries,
+            replayed.notes.entries,
+            replayed.bookmarks.entries,
+            config,
+        );
+        state.journals.reserve(replayed.journals.next_id);
+        state.tasks.reserve(replayed.tasks.next_id);
+        state.habits.reserve(replayed.habits.next_id);
+        state.notes.reserve(replayed.notes.next_id);
+        state.bookmarks.reserve(replayed.bookmarks.next_id);
+        log.watch(&state.journals, &state.tasks, &state.habits, &state.notes, &state.bookmarks);
+        // blobs kept in memory are gone, and their attachments with them
+        let mut attachments = replayed.attachments;
+        if matches!(state.config.blobs, BlobConfig::Memory) {
+            attachments.entries.clear();
+        }
+        state.attachments.restore(attachments, log.clone());
+        state.event_log = Some(log);
+        return Ok(state);
+    }
+
+    // ten journals and ten tasks to play with, ids 0 to 9
+    pub fn with_sample_data(config: Config) -> State {
+        let mut journals: HashMap<usize, Journal> = HashMap::new();
+        let mut tasks: HashMap<usize, Task> = HashMap::new();
+        let now = Utc::now();
+        for i in 0..10 {
+            journals.insert(i, Journal{
+                title: format!("Title {}", i),
+                data: String::from("Hello World!"),
+                encrypted: false,
+                tags: Vec::new(),
+                metadata: None,
+                mood: None,
+                energy: None,
+                sleep_hours: None,
+                lat: None,
+                lon: None,
+                place: None,
+                expires_at: None,
+                publish_at: None,
+                day: None,
+                word_count: 2,
+                char_count: 12,
+                etag: String::from("1"),
+                version: 1,
+                created_at: now,
+                updated_at: now,
+            });
+            tasks.insert(i, Task{
+                text: format!("Do the {}", i),
+                done: false,
+                status: Some(Status::Todo),
+                archived: false,
+                tags: Vec::new(),
+                etag: String::from("1"),
+                version: 1,
+                created_at: now,
+                updated_at: now,
+                completed_at: None,
+                time_entries: Vec::new(),
+                metadata: Map::new(),
+                position: i as i64 * POSITION_GAP,
+                due: None,
+                expires_at: None,
+            });
+        }
+        return State::new(journals, tasks, config);
+    }
+}
+
+// The only ways to add or remove resources outside of a change, whichever
+// collection they are in
+impl<T: Send + Sync + 'static> Collection<T> {
+    // runs the delete hooks and emits the deletion event; None when there
+    // was nothing to remove
+    pub(crate) async fn rm_resource(&self, id: usize, hooks: &Hooks) -> Result<Option<T>, Rejection>
+    where T: Resource {
+        let hooks = hooks.clone();
+        return self.change(move |resources| {
+            if resources.get(&id).is_none() {
+                return Ok(None);
+            }
+            hooks.deleted::<T>(id)?;
+            let Some(resource) = resources.remove(&id) else {
+                return Ok(None);
+            };
+            resources.emit(Event::of(Action::Deleted, id, &resource));
+            Ok(Some(resource))
+        }).await.unwrap_or_else(|failed| Err(failed.into()));
+    }
+
+    // removes what expired by now in one change, through the delete hooks
+    // as rm_resource does, emitting the deletion events, and returns how
+    // many; those a hook refuses stay, left out of listings all the same
+    pub(crate) async fn rm_expired(&self, hooks: &Hooks) -> Result<usize, Failed>
+    where T: Resource {
+        let hooks = hooks.clone();
+        return self.change(move |resources| {
+            let now = Utc::now();
+            let mut removed = 0;
+            for id in resources.ids() {
+                if !resources.get(&id).is_some_and(|resource| resource.expired(now)) {
+                    continue;
+                }
+                if let Err(rejection) = hooks.deleted::<T>(id) {
+                    println!("Expired {} {} was kept: {}", T::KIND, id, rejection);
+                    continue;
+                }
+                if let Some(resource) = resources.remove(&id) {
+                    resources.emit(Event::of(Action::Deleted, id, &resource));
+                    removed += 1;
+                }
+            }
+            removed
+        }).await;
+    }
+
+    // emits the creation event
+    pub(crate) async fn add_resource(&self, mut resource: T) -> Result<usize, String>
+    where T: Etagged + Timestamped + Resource + Serialize {
+        let now = Utc::now();
+        resource.set_timestamps(now, now);
+        resource.track_changes(None, now);
+        return self.change(move |resources| {
+            let event = Event::of(Action::Created, 0, &resource);
+            let id = store_resource(resources, resource)?;
+            resources.emit(Event { id, ..event });
+            Ok(id)
+        }).await.unwrap_or_else(|failed| Err(failed.to_string()));
+    }
+
+    // like add_resource, but keeps the timestamps the resources already have
+    // and stores them all in one change, without events; the new ids in the
+    // order of the batch
+    pub(crate) async fn insert_resources(&self, batch: Vec<T>) -> Result<Vec<usize>, String>
+    where T: Etagged + Resource + Serialize {
+        return self.change(move |resources| {
+            batch.into_iter().map(|resource| store_resource(resources, resource)).collect()
+        }).await.unwrap_or_else(|failed| Err(failed.to_string()));
+    }
+}
+
+// inserts under the next index, from within a change
+pub(crate) fn store_resource<T: Etagged + Resource + Serialize>(
+    resources: &Writer<'_, T>,
+    mut resource: T,
+) -> Result<usize, String> {
+    let index = resources.allocate_id();
+    resource.place(index);
+    if etag::refresh(&mut resource).is_err() {
+        return Err(String::from("Error during serialization"));
+    }
+    resources.insert(index, resource);
+    println!("Resource created at index: {}", index);
+    return Ok(index);
+}
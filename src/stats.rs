@@ -0,0 +1,167 @@
+// Trends over the journals per day, week or month of their creation (UTC):
+// the mood, energy and sleep recorded on them, and how much was written
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{Datelike, Days, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::models::Journal;
+use crate::workspace::Space;
+
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+enum Period {
+    Day,
+    #[default]
+    Week,
+    Month,
+}
+
+impl Period {
+    // the first day of the period `date` falls in, weeks start on Monday
+    fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day     => date,
+            Period::Week    => date - Days::new(date.weekday().num_days_from_monday() as u64),
+            Period::Month   => date.with_day(1).unwrap_or(date),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MoodQuery {
+    #[serde(default)]
+    period: Period,
+    // both inclusive
+    from:   Option<NaiveDate>,
+    to:     Option<NaiveDate>,
+}
+
+#[derive(Default)]
+struct Sums {
+    entries:    usize,
+    mood:       (f64, usize),
+    energy:     (f64, usize),
+    sleep:      (f64, usize),
+}
+
+fn add(sum: &mut (f64, usize), value: Option<f64>) {
+    if let Some(value) = value {
+        sum.0 += value;
+        sum.1 += 1;
+    }
+}
+
+// two decimals are plenty for a trend
+fn rounded(value: f64) -> f64 {
+    return (value * 100.0).round() / 100.0;
+}
+
+fn average((total, count): (f64, usize)) -> Option<f64> {
+    if count == 0 {
+        return None;
+    }
+    return Some(rounded(total / count as f64));
+}
+
+#[derive(Serialize)]
+struct Bucket {
+    start:          NaiveDate,
+    // journals with at least one of the fields
+    entries:        usize,
+    mood:           Option<f64>,
+    energy:         Option<f64>,
+    sleep_hours:    Option<f64>,
+}
+
+// every journal the request may see that was created between from and to,
+// both inclusive, with its creation date
+fn each_journal(space: &Space, from: Option<NaiveDate>, to: Option<NaiveDate>, mut visit: impl FnMut(NaiveDate, &Journal)) {
+    for id in space.visible(&space.journals) {
+        let journal = match space.journals.get(&id) {
+            Some(journal)   => journal,
+            None            => continue,
+        };
+        let date = journal.created_at.date_naive();
+        if from.is_some_and(|from| date < from) || to.is_some_and(|to| date > to) {
+            continue;
+        }
+        visit(date, &journal);
+    }
+}
+
+pub(crate) async fn mood(
+    query: web::Query<MoodQuery>,
+    space: Space,
+) -> impl Responder {
+    let mut buckets: BTreeMap<NaiveDate, Sums> = BTreeMap::new();
+    each_journal(&space, query.from, query.to, |date, journal| {
+        if journal.mood.is_none() && journal.energy.is_none() && journal.sleep_hours.is_none() {
+            return;
+        }
+        let sums = buckets.entry(query.period.start(date)).or_default();
+        sums.entries += 1;
+        add(&mut sums.mood, journal.mood.map(f64::from));
+        add(&mut sums.energy, journal.energy.map(f64::from));
+        add(&mut sums.sleep, journal.sleep_hours);
+    });
+    let buckets: Vec<Bucket> = buckets.into_iter()
+        .map(|(start, sums)| Bucket {
+            start,
+            entries:        sums.entries,
+            mood:           average(sums.mood),
+            energy:         average(sums.energy),
+            sleep_hours:    average(sums.sleep),
+        })
+        .collect();
+    return HttpResponse::Ok().json(buckets);
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WritingQuery {
+    #[serde(default)]
+    period: Period,
+    from:   Option<NaiveDate>,
+    to:     Option<NaiveDate>,
+    // words per period to aim for
+    goal:   Option<usize>,
+}
+
+#[derive(Serialize, Default)]
+struct Written {
+    start:          NaiveDate,
+    entries:        usize,
+    words:          usize,
+    characters:     usize,
+    // words / goal, 1.0 once reached
+    #[serde(skip_serializing_if = "Option::is_none")]
+    goal_progress:  Option<f64>,
+}
+
+// how much was written per period, encrypted journals are not counted
+pub(crate) async fn writing(
+    query: web::Query<WritingQuery>,
+    space: Space,
+) -> impl Responder {
+    if query.goal == Some(0) {
+        return HttpResponse::BadRequest().body("The goal has to be at least one word");
+    }
+    let mut buckets: BTreeMap<NaiveDate, Written> = BTreeMap::new();
+    each_journal(&space, query.from, query.to, |date, journal| {
+        if journal.encrypted {
+            return;
+        }
+        let start = query.period.start(date);
+        let written = buckets.entry(start).or_insert_with(|| Written { start, ..Written::default() });
+        written.entries += 1;
+        written.words += journal.word_count;
+        written.characters += journal.char_count;
+    });
+    let buckets: Vec<Written> = buckets.into_values()
+        .map(|written| Written {
+            goal_progress: query.goal.map(|goal| rounded(written.words as f64 / goal as f64).min(1.0)),
+            ..written
+        })
+        .collect();
+    return HttpResponse::Ok().json(buckets);
+}
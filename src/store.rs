@@ -0,0 +1,283 @@
+// Resource collections: reads never wait on the writer task, changes are
+// applied one at a time by a writer task per collection
+use dashmap::mapref::one::{Ref, RefMut};
+use dashmap::DashMap;
+use rand::random;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
+use std::future::Future;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tracing::Instrument;
+
+use crate::notify::Event;
+
+// The entries of one collection. They are sharded, so a long listing or
+// export only ever holds up the writer for the shard it is reading.
+pub struct Entries<T> {
+    map:        DashMap<usize, T>,
+    next_id:    AtomicUsize,
+    // random per process, so versions from before a restart never match
+    epoch:      u32,
+    // bumped after every change that touched an entry
+    version:    AtomicU64,
+    observers:  RwLock<Vec<Arc<dyn Observer<T>>>>,
+}
+
+// Told about the entries every change touched, right after it was applied
+// and before the next one, e.g. to keep an index of them up to date
+pub(crate) trait Observer<T>: Send + Sync {
+    // `ids` may include entries that were removed or left as they were
+    fn changed(&self, entries: &Entries<T>, ids: &BTreeSet<usize>);
+}
+
+impl<T> Entries<T> {
+    // entries outside any collection, e.g. a past state of one, which
+    // nothing changes; its tag stays the version given
+    pub(crate) fn detached(entries: HashMap<usize, T>, version: u64) -> Entries<T> {
+        let next_id = entries.keys().max().map_or(0, |id| id + 1);
+        return Entries {
+            map:        entries.into_iter().collect(),
+            next_id:    AtomicUsize::new(next_id),
+            epoch:      0,
+            version:    AtomicU64::new(version),
+            observers:  RwLock::new(Vec::new()),
+        };
+    }
+
+    pub fn get(&self, id: &usize) -> Option<Ref<'_, usize, T>> {
+        return self.map.get(id);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.map.len();
+    }
+
+    // changes whenever the collection does, usable as the ETag of listings
+    pub fn tag(&self) -> String {
+        return format!("{:08x}-{}", self.epoch, self.version.load(Ordering::SeqCst));
+    }
+
+    // the id allocate_id hands out next
+    pub(crate) fn next_id(&self) -> usize {
+        return self.next_id.load(Ordering::SeqCst);
+    }
+
+    // every id, in ascending order
+    pub fn ids(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.map.iter().map(|entry| *entry.key()).collect();
+        ids.sort();
+        return ids;
+    }
+}
+
+impl<T: Clone> Entries<T> {
+    // a copy of every entry ordered by id, no shard stays locked afterwards
+    pub fn snapshot(&self) -> Vec<(usize, T)> {
+        let mut entries: Vec<(usize, T)> = self.map.iter()
+            .map(|entry| (*entry.key(), entry.value().clone()))
+            .collect();
+        entries.sort_by_key(|(id, _)| *id);
+        return entries;
+    }
+}
+
+// Handed to a change while the writer applies it, the only way to modify
+// the entries. Events emitted here are delivered in the order the changes
+// were applied.
+pub struct Writer<'a, T> {
+    entries:    &'a Entries<T>,
+    events:     Vec<Event>,
+    touched:    RefCell<BTreeSet<usize>>,
+}
+
+impl<T> Writer<'_, T> {
+    pub fn get_mut(&self, id: &usize) -> Option<RefMut<'_, usize, T>> {
+        self.touched.borrow_mut().insert(*id);
+        return self.entries.map.get_mut(id);
+    }
+
+    pub fn insert(&self, id: usize, resource: T) -> Option<T> {
+        self.touched.borrow_mut().insert(id);
+        self.entries.next_id.fetch_max(id + 1, Ordering::SeqCst);
+        return self.entries.map.insert(id, resource);
+    }
+
+    pub fn remove(&self, id: &usize) -> Option<T> {
+        self.touched.borrow_mut().insert(*id);
+        return self.entries.map.remove(id).map(|(_, resource)| resource);
+    }
+
+    // never reuses an id, even after removals
+    pub fn allocate_id(&self) -> usize {
+        return self.entries.next_id.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn emit(&mut self, event: Event) {
+        self.events.push(event);
+    }
+}
+
+impl<T> std::ops::Deref for Writer<'_, T> {
+    type Target = Entries<T>;
+    fn deref(&self) -> &Entries<T> {
+        return self.entries;
+    }
+}
+
+type Change<T> = Box<dyn FnOnce(&mut Writer<'_, T>) + Send>;
+
+// a change that panicked; what it did to the entries before that is kept,
+// its events are dropped
+#[derive(Debug)]
+pub struct Failed;
+
+impl fmt::Display for Failed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str("The change failed");
+    }
+}
+
+pub struct Collection<T> {
+    entries:    Arc<Entries<T>>,
+    changes:    mpsc::UnboundedSender<Change<T>>,
+}
+
+impl<T: Send + Sync + 'static> Collection<T> {
+    // spawns the writer, so this has to run inside the runtime
+    pub fn new(entries: HashMap<usize, T>, events: mpsc::UnboundedSender<Event>) -> Collection<T> {
+        let next_id = entries.keys().max().map_or(0, |id| id + 1);
+        let entries = Arc::new(Entries {
+            map:        entries.into_iter().collect(),
+            next_id:    AtomicUsize::new(next_id),
+            epoch:      random(),
+            version:    AtomicU64::new(0),
+            observers:  RwLock::new(Vec::new()),
+        });
+        let (changes, queue) = mpsc::unbounded_channel();
+        tokio::spawn(write(entries.clone(), queue, events));
+        Collection { entries, changes }
+    }
+
+    // queues a change behind every change sent before it and waits for its
+    // result, a change sees no other change half-applied; Err when it
+    // panicked, the writer goes on with the next one
+    pub async fn change<R, F>(&self, change: F) -> Result<R, Failed>
+    where
+        R: Send + 'static,
+        F: FnOnce(&mut Writer<'_, T>) -> R + Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        // covers queueing and applying, queue_wait_us is the time spent
+        // behind the changes sent before
+        let span = tracing::info_span!(
+            "store.change",
+            collection = std::any::type_name::<T>(),
+            queue_wait_us = tracing::field::Empty,
+        );
+        let queued = Instant::now();
+        let applying = span.clone();
+        let change: Change<T> = Box::new(move |writer| {
+            let waited = queued.elapsed();
+            applying.record("queue_wait_us", waited.as_micros() as u64);
+            let started = Instant::now();
+            let output = applying.in_scope(|| change(writer));
+            let _ = reply.send((output, waited, started.elapsed()));
+        });
+        self.changes.send(change).map_err(|_| Failed)?;
+        // the reply is dropped unsent when the change panics
+        let (output, waited, held) = result.instrument(span).await.map_err(|_| Failed)?;
+        let _ = CHANGE_TIMES.try_with(|times| {
+            let mut total = times.get();
+            total.changes += 1;
+            total.waited += waited;
+            total.held += held;
+            times.set(total);
+        });
+        return Ok(output);
+    }
+}
+
+impl<T> Collection<T> {
+    // the entries, to read after the request is answered, e.g. while streaming
+    pub(crate) fn shared(&self) -> Arc<Entries<T>> {
+        return self.entries.clone();
+    }
+
+    // tells `observer` about every entry now and about every change from
+    // here on; meant for right after new, before the first change
+    pub(crate) fn observe(&self, observer: Arc<dyn Observer<T>>) {
+        observer.changed(&self.entries, &self.entries.ids().into_iter().collect());
+        self.entries.observers.write().unwrap().push(observer);
+    }
+
+    // ids below `next_id` are never handed out, e.g. those of entries that
+    // were removed before a restart
+    pub(crate) fn reserve(&self, next_id: usize) {
+        self.entries.next_id.fetch_max(next_id, Ordering::SeqCst);
+    }
+}
+
+// How long the changes made by one request queued behind others, and how
+// long the writers were busy applying them
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct ChangeTimes {
+    pub(crate) changes: u32,
+    pub(crate) waited:  Duration,
+    pub(crate) held:    Duration,
+}
+
+tokio::task_local! {
+    static CHANGE_TIMES: Cell<ChangeTimes>;
+}
+
+// runs `future` and adds up the changes it made to any collection
+pub(crate) async fn timed<F: Future>(future: F) -> (F::Output, ChangeTimes) {
+    return CHANGE_TIMES.scope(Cell::new(ChangeTimes::default()), async move {
+        let output = future.await;
+        (output, CHANGE_TIMES.with(Cell::get))
+    }).await;
+}
+
+// another handle on the same entries and writer
+impl<T> Clone for Collection<T> {
+    fn clone(&self) -> Collection<T> {
+        Collection { entries: self.entries.clone(), changes: self.changes.clone() }
+    }
+}
+
+impl<T> std::ops::Deref for Collection<T> {
+    type Target = Entries<T>;
+    fn deref(&self) -> &Entries<T> {
+        return &self.entries;
+    }
+}
+
+async fn write<T>(
+    entries:    Arc<Entries<T>>,
+    mut queue:  mpsc::UnboundedReceiver<Change<T>>,
+    events:     mpsc::UnboundedSender<Event>,
+) {
+    while let Some(change) = queue.recv().await {
+        let mut writer = Writer { entries: &entries, events: Vec::new(), touched: RefCell::new(BTreeSet::new()) };
+        if panic::catch_unwind(AssertUnwindSafe(|| change(&mut writer))).is_err() {
+            println!("A change of the {} failed", std::any::type_name::<T>());
+            writer.events.clear();
+        }
+        // only after the change, so a tag never stands for content older than it
+        let touched = writer.touched.into_inner();
+        if !touched.is_empty() {
+            entries.version.fetch_add(1, Ordering::SeqCst);
+            for observer in entries.observers.read().unwrap().iter() {
+                observer.changed(&entries, &touched);
+            }
+        }
+        for event in writer.events {
+            let _ = events.send(event);
+        }
+    }
+}
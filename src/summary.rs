@@ -0,0 +1,53 @@
+// Daily summary: the "what happened today" view
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::{Journal, Task, WithId};
+use crate::workspace::Space;
+
+#[derive(Debug, Deserialize)]
+pub struct SummaryParams {
+    // UTC day, today when omitted
+    date: Option<NaiveDate>,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary<'a> {
+    date:               NaiveDate,
+    journals:           Vec<WithId<'a, Journal>>,
+    // tasks have no completion time yet, so this is "done and last changed that day"
+    completed_tasks:    Vec<WithId<'a, Task>>,
+    // open tasks that already existed at the end of the day
+    open_tasks:         Vec<WithId<'a, Task>>,
+}
+
+pub async fn daily_summary(
+    query: web::Query<SummaryParams>,
+    space: Space,
+) -> impl Responder {
+    let date = query.date.unwrap_or_else(|| Utc::now().date_naive());
+    let journals: Vec<(usize, Journal)> = space.visible(&space.journals).into_iter()
+        .filter_map(|id| Some((id, space.journals.get(&id)?.clone())))
+        .collect();
+    let tasks: Vec<(usize, Task)> = space.visible(&space.tasks).into_iter()
+        .filter_map(|id| Some((id, space.tasks.get(&id)?.clone())))
+        .collect();
+
+    let summary = Summary {
+        date,
+        journals: journals.iter()
+            .filter(|(_, journal)| journal.created_at.date_naive() == date)
+            .map(|(id, resource)| WithId { id: *id, resource })
+            .collect(),
+        completed_tasks: tasks.iter()
+            .filter(|(_, task)| task.done && task.updated_at.date_naive() == date)
+            .map(|(id, resource)| WithId { id: *id, resource })
+            .collect(),
+        open_tasks: tasks.iter()
+            .filter(|(_, task)| !task.done && task.created_at.date_naive() <= date)
+            .map(|(id, resource)| WithId { id: *id, resource })
+            .collect(),
+    };
+    HttpResponse::Ok().json(summary)
+}
@@ -0,0 +1,230 @@
+// Delta sync: what was created, updated and deleted in a space since a sync
+// token, so clients fetch only that instead of every collection again
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use rand::random;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::handlers::Rejection;
+use crate::models::{Bookmark, Etagged, Habit, Journal, Note, Resource, Task};
+use crate::store::{Collection, Entries, Observer};
+use crate::workspace::{Level, Space};
+
+// the last known state of one entry; deleted entries stay as tombstones
+struct Record {
+    // None once deleted
+    etag:       Option<String>,
+    created:    u64,
+    changed:    u64,
+    // when a draft is published, which changes it without a write
+    publish_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct Log {
+    // bumped by every change to any collection of the space
+    sequence:   u64,
+    records:    HashMap<(&'static str, usize), Record>,
+}
+
+impl Log {
+    // Drafts whose time has come are changed and created anew once they
+    // are published: clients that synced while they were drafts never got
+    // them, or were told they were deleted.
+    fn publish(&mut self, now: DateTime<Utc>) {
+        let due: Vec<(&'static str, usize)> = self.records.iter()
+            .filter(|(_, record)| record.publish_at.is_some_and(|publish_at| publish_at <= now))
+            .map(|(key, _)| *key)
+            .collect();
+        if due.is_empty() {
+            return;
+        }
+        self.sequence += 1;
+        let sequence = self.sequence;
+        for key in due {
+            if let Some(record) = self.records.get_mut(&key) {
+                record.publish_at = None;
+                record.created = sequence;
+                record.changed = sequence;
+            }
+        }
+    }
+}
+
+// The changes of the journals, tasks, habits, notes and bookmarks of one
+// space, kept up to date by their writers like the search indexes
+pub(crate) struct ChangeLog {
+    // random per log, so tokens of another space or from before a restart
+    // never match
+    epoch:  u32,
+    log:    Mutex<Log>,
+}
+
+impl ChangeLog {
+    // records what the collections hold and follows their changes
+    pub(crate) fn watch(
+        journals:   &Collection<Journal>,
+        tasks:      &Collection<Task>,
+        habits:     &Collection<Habit>,
+        notes:      &Collection<Note>,
+        bookmarks:  &Collection<Bookmark>,
+    ) -> Arc<ChangeLog> {
+        let log = Arc::new(ChangeLog { epoch: random(), log: Mutex::new(Log::default()) });
+        journals.observe(log.clone());
+        tasks.observe(log.clone());
+        habits.observe(log.clone());
+        notes.observe(log.clone());
+        bookmarks.observe(log.clone());
+        return log;
+    }
+
+    fn token(&self, sequence: u64) -> String {
+        return format!("{:08x}-{}", self.epoch, sequence);
+    }
+
+    // the sequence a token stands for
+    fn since(&self, token: &str, current: u64) -> Result<u64, Rejection> {
+        let parsed = token.split_once('-').and_then(|(epoch, sequence)| {
+            Some((u32::from_str_radix(epoch, 16).ok()?, sequence.parse::<u64>().ok()?))
+        });
+        return match parsed {
+            None => Err(Rejection::new(StatusCode::BAD_REQUEST, "Invalid sync token")),
+            Some((epoch, sequence)) if epoch != self.epoch || sequence > current => {
+                Err(Rejection::new(StatusCode::GONE, "Sync token expired, sync again without since"))
+            }
+            Some((_, sequence)) => Ok(sequence),
+        };
+    }
+}
+
+// entries touched without a new etag are left out, they did not change
+impl<T: Resource + Etagged> Observer<T> for ChangeLog {
+    fn changed(&self, entries: &Entries<T>, ids: &BTreeSet<usize>) {
+        let mut log = self.log.lock().unwrap();
+        log.sequence += 1;
+        let sequence = log.sequence;
+        let now = Utc::now();
+        for id in ids {
+            let entry = entries.get(id);
+            let etag = entry.as_ref().map(|entry| entry.get_etag());
+            let publish_at = entry.and_then(|entry| entry.publish_at()).filter(|publish_at| *publish_at > now);
+            match log.records.entry((T::KIND, *id)) {
+                Entry::Occupied(mut occupied) => {
+                    let record = occupied.get_mut();
+                    if record.etag != etag {
+                        record.etag = etag;
+                        record.changed = sequence;
+                        record.publish_at = publish_at;
+                    }
+                }
+                Entry::Vacant(vacant) => {
+                    if etag.is_some() {
+                        vacant.insert(Record { etag, created: sequence, changed: sequence, publish_at });
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChangesQuery {
+    // everything there is when left out
+    since: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum Action {
+    Created,
+    Updated,
+    Deleted,
+}
+
+#[derive(Serialize)]
+struct Change {
+    kind:       &'static str,
+    id:         usize,
+    action:     Action,
+    // as GET returns it, except for deletions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    resource:   Option<Value>,
+    // its ETag, with the resource
+    #[serde(skip_serializing_if = "Option::is_none")]
+    etag:       Option<String>,
+}
+
+#[derive(Serialize)]
+struct Changes {
+    changes:    Vec<Change>,
+    // the since of the next sync
+    sync_token: String,
+}
+
+// None when the request may not see the entry; drafts and expired entries
+// are left out as listings leave them out, or deleted if the client may
+// have them from before, until drafts are published
+fn change<T: Resource + Etagged + Serialize>(space: &Space, resources: &Entries<T>, id: usize, action: Action) -> Option<Change> {
+    space.allow::<T>(Some(id), Level::Read).ok()?;
+    let now = Utc::now();
+    // deleted after the log was read
+    let (resource, etag) = match action {
+        Action::Deleted => (None, None),
+        _               => resources.get(&id)
+            .filter(|resource| !resource.draft(now) && !resource.expired(now))
+            .and_then(|resource| Some((serde_json::to_value(&*resource).ok()?, resource.get_etag())))
+            .unzip(),
+    };
+    let action = match (resource.is_none(), action) {
+        (true, Action::Created) => return None,
+        (true, _)               => Action::Deleted,
+        (false, action)         => action,
+    };
+    return Some(Change { kind: T::KIND, id, action, resource, etag });
+}
+
+pub(crate) async fn changes(
+    query: web::Query<ChangesQuery>,
+    space: Space,
+) -> impl Responder {
+    let (changed, sync_token) = {
+        let mut log = space.changes.log.lock().unwrap();
+        log.publish(Utc::now());
+        let since = match query.since.as_deref().map(|token| space.changes.since(token, log.sequence)) {
+            Some(Err(rejection))    => return HttpResponse::from(rejection),
+            Some(Ok(since))         => since,
+            None                    => 0,
+        };
+        let mut changed: Vec<(u64, &'static str, usize, Action)> = log.records.iter()
+            .filter(|(_, record)| record.changed > since)
+            .filter_map(|(&(kind, id), record)| {
+                let action = match (record.etag.is_some(), record.created > since) {
+                    (true, true)    => Action::Created,
+                    (true, false)   => Action::Updated,
+                    // never seen by the client
+                    (false, true)   => return None,
+                    (false, false)  => Action::Deleted,
+                };
+                Some((record.changed, kind, id, action))
+            })
+            .collect();
+        changed.sort_by_key(|&(sequence, kind, id, _)| (sequence, kind, id));
+        (changed, space.changes.token(log.sequence))
+    };
+    let changes = changed.into_iter()
+        .filter_map(|(_, kind, id, action)| match kind {
+            Journal::KIND   => change(&space, &space.journals, id, action),
+            Task::KIND      => change(&space, &space.tasks, id, action),
+            Note::KIND      => change(&space, &space.notes, id, action),
+            Bookmark::KIND  => change(&space, &space.bookmarks, id, action),
+            Habit::KIND     => change(&space, &space.habits, id, action),
+            _               => None,
+        })
+        .collect();
+    return HttpResponse::Ok().json(Changes { changes, sync_token });
+}
@@ -0,0 +1,579 @@
+// Full data export and import (takeout) of a space: its journals, tasks,
+// habits, notes and bookmarks, and the attachments without their content
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::{BTreeSet, HashMap};
+
+use crate::attachments::Attachment;
+use crate::auth::response_token;
+use crate::models::{Bookmark, Habit, Journal, Note, Resource, Status, Task, TimeEntry, Timestamped};
+use crate::state::State;
+use crate::store::Collection;
+use crate::workspace::Space;
+use crate::{dryrun, ndjson, operations, quota};
+
+const TAKEOUT_VERSION: u32 = 1;
+pub const IMPORT_LIMIT: usize = 16 * 1024 * 1024;
+
+// the REST models hide timestamps on input, so takeout has its own
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedJournal {
+    id:          usize,
+    title:       String,
+    data:        String,
+    #[serde(default)]
+    encrypted:   bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags:        Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metadata:    Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mood:        Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    energy:      Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sleep_hours: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lat:         Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lon:         Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    place:       Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at:  Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    publish_at:  Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    day:         Option<NaiveDate>,
+    created_at:  DateTime<Utc>,
+    updated_at:  DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedTask {
+    id:           usize,
+    text:         String,
+    done:         bool,
+    // older takeouts have none, done decides then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status:       Option<Status>,
+    #[serde(default)]
+    archived:     bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags:         Vec<String>,
+    created_at:   DateTime<Utc>,
+    updated_at:   DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    completed_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    due:          Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at:   Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    time_entries: Vec<TimeEntry>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    metadata:     Map<String, Value>,
+    // only orders the imported tasks, they get positions of their own
+    #[serde(default)]
+    position:     i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedHabit {
+    id:         usize,
+    name:       String,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    checkins:   BTreeSet<NaiveDate>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    metadata:   Map<String, Value>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedNote {
+    id:         usize,
+    text:       String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags:       Vec<String>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    metadata:   Map<String, Value>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedBookmark {
+    id:          usize,
+    url:         String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title:       Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags:        Vec<String>,
+    // ids of journals in the same takeout, which the imported ones link to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    journals:    Vec<usize>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    metadata:    Map<String, Value>,
+    created_at:  DateTime<Utc>,
+    updated_at:  DateTime<Utc>,
+}
+
+// without the content, which is too large for a takeout; it is attached
+// again on import if the space still has it
+#[derive(Debug, Serialize, Deserialize)]
+struct ExportedAttachment {
+    journal:        usize,
+    name:           String,
+    content_type:   String,
+    sha256:         String,
+    length:         usize,
+    created_at:     DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Takeout {
+    version:        u32,
+    exported_at:    DateTime<Utc>,
+    journals:       Vec<ExportedJournal>,
+    tasks:          Vec<ExportedTask>,
+    // older takeouts have none of these
+    #[serde(default)]
+    habits:         Vec<ExportedHabit>,
+    #[serde(default)]
+    notes:          Vec<ExportedNote>,
+    #[serde(default)]
+    bookmarks:      Vec<ExportedBookmark>,
+    #[serde(default)]
+    attachments:    Vec<ExportedAttachment>,
+}
+
+// one line of an NDJSON takeout: the header first, then every entry
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum TakeoutLine {
+    Header { version: u32, exported_at: DateTime<Utc> },
+    Journal(ExportedJournal),
+    Task(ExportedTask),
+    Habit(ExportedHabit),
+    Note(ExportedNote),
+    Bookmark(ExportedBookmark),
+    Attachment(ExportedAttachment),
+}
+
+#[derive(Debug, Serialize)]
+struct ImportSummary {
+    journals:       usize,
+    tasks:          usize,
+    habits:         usize,
+    notes:          usize,
+    bookmarks:      usize,
+    // those whose content the space has, the others are missing
+    attachments:    usize,
+    // the SHA-256 of the content to upload again for the missing ones
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    missing:        Vec<String>,
+    // only shown on a dry run, which imported nothing
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    dry_run:        bool,
+}
+
+impl From<(&usize, &Journal)> for ExportedJournal {
+    fn from((id, journal): (&usize, &Journal)) -> Self {
+        ExportedJournal {
+            id:          *id,
+            title:       journal.title.clone(),
+            data:        journal.data.clone(),
+            encrypted:   journal.encrypted,
+            tags:        journal.tags.clone(),
+            metadata:    journal.metadata.clone(),
+            mood:        journal.mood,
+            energy:      journal.energy,
+            sleep_hours: journal.sleep_hours,
+            lat:         journal.lat,
+            lon:         journal.lon,
+            place:       journal.place.clone(),
+            expires_at:  journal.expires_at,
+            publish_at:  journal.publish_at,
+            day:         journal.day,
+            created_at:  journal.created_at,
+            updated_at:  journal.updated_at,
+        }
+    }
+}
+
+impl From<ExportedJournal> for Journal {
+    fn from(journal: ExportedJournal) -> Self {
+        let mut imported = Journal {
+            title:       journal.title,
+            data:        journal.data,
+            encrypted:   journal.encrypted,
+            tags:        journal.tags,
+            metadata:    journal.metadata,
+            mood:        journal.mood,
+            energy:      journal.energy,
+            sleep_hours: journal.sleep_hours,
+            lat:         journal.lat,
+            lon:         journal.lon,
+            place:       journal.place,
+            expires_at:  journal.expires_at,
+            publish_at:  journal.publish_at,
+            day:         journal.day,
+            word_count:  0,
+            char_count:  0,
+            etag:        String::new(),
+            version:     0,
+            created_at:  journal.created_at,
+            updated_at:  journal.updated_at,
+        };
+        imported.track_changes(None, imported.updated_at);
+        return imported;
+    }
+}
+
+impl From<(&usize, &Task)> for ExportedTask {
+    fn from((id, task): (&usize, &Task)) -> Self {
+        ExportedTask {
+            id:           *id,
+            text:         task.text.clone(),
+            done:         task.done,
+            status:       task.status,
+            archived:     task.archived,
+            tags:         task.tags.clone(),
+            created_at:   task.created_at,
+            updated_at:   task.updated_at,
+            completed_at: task.completed_at,
+            due:          task.due,
+            expires_at:   task.expires_at,
+            time_entries: task.time_entries.clone(),
+            metadata:     task.metadata.clone(),
+            position:     task.position,
+        }
+    }
+}
+
+impl From<ExportedTask> for Task {
+    fn from(task: ExportedTask) -> Self {
+        let status = task.status.unwrap_or(if task.done { Status::Done } else { Status::Todo });
+        Task {
+            text:         task.text,
+            done:         status == Status::Done,
+            status:       Some(status),
+            archived:     task.archived,
+            tags:         task.tags,
+            etag:         String::new(),
+            version:      0,
+            created_at:   task.created_at,
+            updated_at:   task.updated_at,
+            completed_at: task.completed_at,
+            time_entries: task.time_entries,
+            metadata:     task.metadata,
+            position:     0,
+            due:          task.due,
+            expires_at:   task.expires_at,
+        }
+    }
+}
+
+impl From<(&usize, &Habit)> for ExportedHabit {
+    fn from((id, habit): (&usize, &Habit)) -> Self {
+        ExportedHabit {
+            id:         *id,
+            name:       habit.name.clone(),
+            checkins:   habit.checkins.clone(),
+            metadata:   habit.metadata.clone(),
+            created_at: habit.created_at,
+            updated_at: habit.updated_at,
+        }
+    }
+}
+
+impl From<ExportedHabit> for Habit {
+    fn from(habit: ExportedHabit) -> Self {
+        Habit {
+            name:       habit.name,
+            checkins:   habit.checkins,
+            metadata:   habit.metadata,
+            etag:       String::new(),
+            version:    0,
+            created_at: habit.created_at,
+            updated_at: habit.updated_at,
+        }
+    }
+}
+
+impl From<(&usize, &Note)> for ExportedNote {
+    fn from((id, note): (&usize, &Note)) -> Self {
+        ExportedNote {
+            id:         *id,
+            text:       note.text.clone(),
+            tags:       note.tags.clone(),
+            metadata:   note.metadata.clone(),
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+        }
+    }
+}
+
+impl From<ExportedNote> for Note {
+    fn from(note: ExportedNote) -> Self {
+        Note {
+            text:       note.text,
+            tags:       note.tags,
+            metadata:   note.metadata,
+            etag:       String::new(),
+            version:    0,
+            created_at: note.created_at,
+            updated_at: note.updated_at,
+        }
+    }
+}
+
+impl From<(&usize, &Bookmark)> for ExportedBookmark {
+    fn from((id, bookmark): (&usize, &Bookmark)) -> Self {
+        ExportedBookmark {
+            id:          *id,
+            url:         bookmark.url.clone(),
+            title:       bookmark.title.clone(),
+            description: bookmark.description.clone(),
+            tags:        bookmark.tags.clone(),
+            journals:    bookmark.journals.clone(),
+            metadata:    bookmark.metadata.clone(),
+            created_at:  bookmark.created_at,
+            updated_at:  bookmark.updated_at,
+        }
+    }
+}
+
+impl From<ExportedBookmark> for Bookmark {
+    fn from(bookmark: ExportedBookmark) -> Self {
+        Bookmark {
+            url:         bookmark.url,
+            title:       bookmark.title,
+            description: bookmark.description,
+            tags:        bookmark.tags,
+            journals:    bookmark.journals,
+            metadata:    bookmark.metadata,
+            etag:        String::new(),
+            version:     0,
+            created_at:  bookmark.created_at,
+            updated_at:  bookmark.updated_at,
+        }
+    }
+}
+
+impl From<Attachment> for ExportedAttachment {
+    fn from(attachment: Attachment) -> Self {
+        ExportedAttachment {
+            journal:        attachment.journal,
+            name:           attachment.name,
+            content_type:   attachment.content_type,
+            sha256:         attachment.sha256,
+            length:         attachment.length,
+            created_at:     attachment.created_at,
+        }
+    }
+}
+
+impl From<ExportedAttachment> for Attachment {
+    fn from(attachment: ExportedAttachment) -> Self {
+        Attachment {
+            journal:        attachment.journal,
+            name:           attachment.name,
+            content_type:   attachment.content_type,
+            sha256:         attachment.sha256,
+            length:         attachment.length,
+            created_at:     attachment.created_at,
+        }
+    }
+}
+
+// the lines of one collection, each entry read only once it is streamed
+fn entry_lines<T, E>(resources: &Collection<T>, line: fn(E) -> TakeoutLine) -> impl Iterator<Item = TakeoutLine>
+where T: Send + Sync + 'static, E: for<'a> From<(&'a usize, &'a T)> {
+    let resources = resources.clone();
+    return resources.ids().into_iter().filter_map(move |id| {
+        let resource = resources.get(&id)?;
+        Some(line(E::from((&id, &*resource))))
+    });
+}
+
+// NDJSON takeouts are streamed one entry at a time, they cannot be imported
+fn export_lines(space: &Space) -> HttpResponse {
+    let header = TakeoutLine::Header { version: TAKEOUT_VERSION, exported_at: Utc::now() };
+    let attachments = space.attachments.listed().into_iter()
+        .map(|attachment| TakeoutLine::Attachment(ExportedAttachment::from(attachment)));
+    let lines = std::iter::once(header)
+        .chain(entry_lines(&space.journals, TakeoutLine::Journal))
+        .chain(entry_lines(&space.tasks, TakeoutLine::Task))
+        .chain(entry_lines(&space.habits, TakeoutLine::Habit))
+        .chain(entry_lines(&space.notes, TakeoutLine::Note))
+        .chain(entry_lines(&space.bookmarks, TakeoutLine::Bookmark))
+        .chain(attachments)
+        .map(|line| serde_json::to_string(&line));
+    return ndjson::respond(
+        HttpResponse::Ok().append_header(("Content-Disposition", "attachment; filename=\"takeout.ndjson\"")),
+        lines,
+    );
+}
+
+fn exported<T: Clone + Send + Sync + 'static, E>(resources: &Collection<T>) -> Vec<E>
+where E: for<'a> From<(&'a usize, &'a T)> {
+    return resources.snapshot().iter().map(|(id, resource)| E::from((id, resource))).collect();
+}
+
+// everything in the space, as of now
+pub(crate) fn takeout(space: &Space) -> Takeout {
+    return Takeout {
+        version:        TAKEOUT_VERSION,
+        exported_at:    Utc::now(),
+        journals:       exported(&space.journals),
+        tasks:          exported(&space.tasks),
+        habits:         exported(&space.habits),
+        notes:          exported(&space.notes),
+        bookmarks:      exported(&space.bookmarks),
+        attachments:    space.attachments.listed().into_iter().map(ExportedAttachment::from).collect(),
+    };
+}
+
+pub async fn export(space: Space, request: HttpRequest) -> HttpResponse {
+    if ndjson::wanted(&request) {
+        return export_lines(&space);
+    }
+    return HttpResponse::Ok()
+        .append_header(("Content-Disposition", "attachment; filename=\"takeout.json\""))
+        .json(takeout(&space));
+}
+
+// Runs what creating each entry runs but the quota, which is checked for
+// all of them at once; the hooks not on a dry run
+fn check<T: Resource>(state: &State, space: &Space, resources: &mut [T], dry_run: bool) -> Result<(), HttpResponse> {
+    for resource in resources.iter_mut() {
+        if !dry_run {
+            state.hooks.created(resource).map_err(HttpResponse::from)?;
+        }
+        if let Err(reason) = resource.validate().and_then(|_| space.schemas.check(resource)) {
+            return Err(HttpResponse::BadRequest().body(reason));
+        }
+    }
+    return Ok(());
+}
+
+// imported entries are appended under new ids, and the bookmarks and
+// attachments follow their journals there
+pub async fn import(
+    json: web::Json<Takeout>,
+    state: web::Data<State>,
+    space: Space,
+    request: HttpRequest,
+) -> impl Responder {
+    let dry_run = dryrun::wanted(&request);
+    if !dry_run {
+        if let Err(resp) = response_token(&state, &request) {
+            return resp;
+        }
+    }
+    let takeout = json.into_inner();
+    if takeout.version != TAKEOUT_VERSION {
+        return HttpResponse::BadRequest().body("Unsupported takeout version");
+    }
+
+    let exported_ids: Vec<usize> = takeout.journals.iter().map(|journal| journal.id).collect();
+    let linked = takeout.bookmarks.iter().flat_map(|bookmark| &bookmark.journals)
+        .chain(takeout.attachments.iter().map(|attachment| &attachment.journal));
+    if let Some(missing) = linked.into_iter().find(|id| !exported_ids.contains(id)) {
+        return HttpResponse::BadRequest().body(format!("Journal {} not found", missing));
+    }
+    if takeout.attachments.iter().any(|attachment| attachment.name.trim().is_empty()) {
+        return HttpResponse::BadRequest().body("Attachments need a name");
+    }
+    let mut journals: Vec<Journal> = takeout.journals.into_iter().map(Journal::from).collect();
+    // new ids in the exported order, so the new positions keep it
+    let mut exported_tasks = takeout.tasks;
+    exported_tasks.sort_by_key(|task| task.position);
+    let mut tasks: Vec<Task> = exported_tasks.into_iter().map(Task::from).collect();
+    let mut habits: Vec<Habit> = takeout.habits.into_iter().map(Habit::from).collect();
+    let mut notes: Vec<Note> = takeout.notes.into_iter().map(Note::from).collect();
+    let mut bookmarks: Vec<Bookmark> = takeout.bookmarks.into_iter().map(Bookmark::from).collect();
+    let attachments: Vec<Attachment> = takeout.attachments.into_iter().map(Attachment::from).collect();
+    let checked = check(&state, &space, &mut journals, dry_run)
+        .and_then(|_| check(&state, &space, &mut tasks, dry_run))
+        .and_then(|_| check(&state, &space, &mut habits, dry_run))
+        .and_then(|_| check(&state, &space, &mut notes, dry_run))
+        .and_then(|_| check(&state, &space, &mut bookmarks, dry_run));
+    if let Err(resp) = checked {
+        return resp;
+    }
+    let bytes = journals.iter().map(quota::size).sum::<usize>()
+        + tasks.iter().map(quota::size).sum::<usize>()
+        + habits.iter().map(quota::size).sum::<usize>()
+        + notes.iter().map(quota::size).sum::<usize>()
+        + bookmarks.iter().map(quota::size).sum::<usize>();
+    let admitted = quota::admit(&state, &space.journals, journals.len(), bytes)
+        .and_then(|_| quota::admit(&state, &space.tasks, tasks.len(), bytes))
+        .and_then(|_| quota::admit(&state, &space.habits, habits.len(), bytes))
+        .and_then(|_| quota::admit(&state, &space.notes, notes.len(), bytes))
+        .and_then(|_| quota::admit(&state, &space.bookmarks, bookmarks.len(), bytes));
+    if let Err(rejection) = admitted {
+        return rejection.into();
+    }
+    let mut summary = ImportSummary {
+        journals:       journals.len(),
+        tasks:          tasks.len(),
+        habits:         habits.len(),
+        notes:          notes.len(),
+        bookmarks:      bookmarks.len(),
+        attachments:    attachments.len(),
+        missing:        Vec::new(),
+        dry_run,
+    };
+    if dry_run {
+        summary.missing = space.attachments.import(attachments, true);
+        summary.attachments -= summary.missing.len();
+        return HttpResponse::Ok().json(summary);
+    }
+    let total = summary.journals + summary.tasks + summary.habits + summary.notes + summary.bookmarks;
+    operations::report(&request, 0, total);
+    let new_ids: HashMap<usize, usize> = match space.journals.insert_resources(journals).await {
+        Ok(ids)     => exported_ids.into_iter().zip(ids).collect(),
+        Err(text)   => return HttpResponse::InternalServerError().body(text),
+    };
+    let mut done = summary.journals;
+    operations::report(&request, done, total);
+    if let Err(text) = space.tasks.insert_resources(tasks).await {
+        return HttpResponse::InternalServerError().body(text);
+    }
+    done += summary.tasks;
+    operations::report(&request, done, total);
+    if let Err(text) = space.habits.insert_resources(habits).await {
+        return HttpResponse::InternalServerError().body(text);
+    }
+    done += summary.habits;
+    operations::report(&request, done, total);
+    if let Err(text) = space.notes.insert_resources(notes).await {
+        return HttpResponse::InternalServerError().body(text);
+    }
+    done += summary.notes;
+    operations::report(&request, done, total);
+    for bookmark in &mut bookmarks {
+        bookmark.journals = bookmark.journals.iter().map(|id| new_ids[id]).collect();
+    }
+    // checked as when created, now that the journals are in the space
+    if let Some(reason) = bookmarks.iter().find_map(|bookmark| space.check_links(bookmark).err()) {
+        return HttpResponse::BadRequest().body(reason);
+    }
+    if let Err(text) = space.bookmarks.insert_resources(bookmarks).await {
+        return HttpResponse::InternalServerError().body(text);
+    }
+    let attachments = attachments.into_iter()
+        .map(|attachment| Attachment { journal: new_ids[&attachment.journal], ..attachment })
+        .collect();
+    summary.missing = space.attachments.import(attachments, false);
+    summary.attachments -= summary.missing.len();
+    operations::report(&request, total, total);
+    return HttpResponse::Ok().json(summary);
+}
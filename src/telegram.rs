@@ -0,0 +1,247 @@
+// Telegram bot bridge: long-polls for commands and sends task reminders
+use actix_web::web;
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{json, Map};
+use std::time::Duration;
+
+use crate::notify::{Action, Event};
+use crate::etag;
+use crate::handlers::store_new;
+use crate::models::{Status, Task, Timestamped};
+use crate::state::State;
+use crate::workspace::Space;
+
+const DEFAULT_API_URL: &str = "https://api.telegram.org";
+const POLL_TIMEOUT_SECS: u64 = 30;
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone)]
+pub struct TelegramConfig {
+    // overridable for self-hosted Bot API servers
+    api_url:            String,
+    token:              String,
+    // only these chats may use the bot, and they receive the reminders
+    chat_ids:           Vec<i64>,
+    reminder_interval:  Option<Duration>,
+}
+
+impl TelegramConfig {
+    pub fn from_env() -> Option<TelegramConfig> {
+        let token = std::env::var("JOURNAL_TELEGRAM_TOKEN").ok()?;
+        let api_url = std::env::var("JOURNAL_TELEGRAM_API_URL")
+            .unwrap_or_else(|_| String::from(DEFAULT_API_URL));
+        let chat_ids = std::env::var("JOURNAL_TELEGRAM_CHAT_IDS")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|id| id.trim().parse().ok())
+            .collect();
+        let reminder_interval = std::env::var("JOURNAL_TELEGRAM_REMINDER_MINUTES")
+            .ok()
+            .and_then(|minutes| minutes.parse::<u64>().ok())
+            .filter(|minutes| *minutes > 0)
+            .map(|minutes| Duration::from_secs(minutes * 60));
+        Some(TelegramConfig { api_url, token, chat_ids, reminder_interval })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse<T> {
+    ok:     bool,
+    result: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    update_id:  i64,
+    message:    Option<Message>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Message {
+    chat:   Chat,
+    text:   Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Chat {
+    id: i64,
+}
+
+struct Bot {
+    client: reqwest::Client,
+    config: TelegramConfig,
+}
+
+impl Bot {
+    fn url(&self, method: &str) -> String {
+        format!("{}/bot{}/{}", self.config.api_url, self.config.token, method)
+    }
+
+    async fn updates(&self, offset: i64) -> Result<Vec<Update>, reqwest::Error> {
+        let response: ApiResponse<Vec<Update>> = self.client
+            .get(self.url("getUpdates"))
+            .query(&[("offset", offset), ("timeout", POLL_TIMEOUT_SECS as i64)])
+            .timeout(Duration::from_secs(POLL_TIMEOUT_SECS + 10))
+            .send()
+            .await?
+            .json()
+            .await?;
+        if !response.ok {
+            println!("Telegram getUpdates was rejected");
+        }
+        return Ok(response.result.unwrap_or_default());
+    }
+
+    async fn send(&self, chat_id: i64, text: &str) {
+        let result = self.client
+            .post(self.url("sendMessage"))
+            .json(&json!({ "chat_id": chat_id, "text": text }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(err) = result {
+            println!("Telegram sendMessage failed: {}", err.without_url());
+        }
+    }
+}
+
+fn open_tasks(state: &State) -> String {
+    let space = Space::server(state);
+    let tasks: Vec<(usize, Task)> = space.visible(&space.tasks).into_iter()
+        .filter_map(|id| Some((id, space.tasks.get(&id)?.clone())))
+        .collect();
+    let open: Vec<&(usize, Task)> = tasks.iter().filter(|(_, task)| !task.done).collect();
+    if open.is_empty() {
+        return String::from("No open tasks");
+    }
+    return open.iter()
+        .map(|(id, task)| format!("#{} {}", id, task.text))
+        .collect::<Vec<String>>()
+        .join("\n");
+}
+
+async fn add_task(state: &State, text: &str) -> String {
+    if text.is_empty() {
+        return String::from("Usage: /todo <text>");
+    }
+    let task = Task {
+        text:         String::from(text),
+        done:         false,
+        status:       Some(Status::Todo),
+        archived:     false,
+        tags:         Vec::new(),
+        etag:         String::new(),
+        version:      0,
+        created_at:   Utc::now(),
+        updated_at:   Utc::now(),
+        completed_at: None,
+        time_entries: Vec::new(),
+        metadata:     Map::new(),
+        position:     0,
+        due:          None,
+        expires_at:   None,
+    };
+    // checked like a task posted over REST, quota included
+    match store_new(state, &Space::server(state), task).await {
+        Ok(id)          => format!("Added task #{}", id),
+        Err(rejection)  => rejection.to_string(),
+    }
+}
+
+async fn complete_task(state: &State, argument: &str) -> String {
+    let id: usize = match argument.trim_start_matches('#').parse() {
+        Ok(id)  => id,
+        Err(_)  => return String::from("Usage: /done <id>"),
+    };
+    let transitions = state.config.transitions.clone();
+    return state.tasks.change(move |tasks| {
+        let mut task = match tasks.get_mut(&id) {
+            Some(task)  => task,
+            None        => return format!("No task #{}", id),
+        };
+        let previous = task.clone();
+        let now = Utc::now();
+        task.mark_done(true);
+        if let Err(reason) = transitions.check(Some(&previous), &*task) {
+            *task = previous;
+            return format!("Task #{}: {}", id, reason);
+        }
+        task.updated_at = now;
+        task.track_changes(Some(&previous), now);
+        let _ = etag::refresh(&mut *task);
+        let event = Event::of(Action::Updated, id, &*task);
+        drop(task);
+        tasks.emit(event);
+        format!("Completed task #{}", id)
+    }).await.unwrap_or_else(|failed| failed.to_string());
+}
+
+async fn handle_command(state: &State, text: &str) -> String {
+    let (command, argument) = text.split_once(' ').unwrap_or((text, ""));
+    // commands in groups arrive as "/todo@SomeBot"
+    let command = command.split('@').next().unwrap_or(command);
+    let maintenance = state.maintenance().filter(|_| command == "/todo" || command == "/done");
+    if let Some(maintenance) = maintenance {
+        return maintenance.message();
+    }
+    match command {
+        "/todo"     => add_task(state, argument.trim()).await,
+        "/tasks"    => open_tasks(state),
+        "/done"     => complete_task(state, argument.trim()).await,
+        _           => String::from("Commands: /todo <text>, /tasks, /done <id>"),
+    }
+}
+
+async fn poll(bot: &Bot, state: &State) {
+    let mut offset = 0;
+    loop {
+        let updates = match bot.updates(offset).await {
+            Ok(updates) => updates,
+            Err(err) => {
+                println!("Telegram polling failed: {}", err.without_url());
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+        for update in updates {
+            offset = offset.max(update.update_id + 1);
+            let message = match update.message {
+                Some(message)   => message,
+                None            => continue,
+            };
+            if !bot.config.chat_ids.contains(&message.chat.id) {
+                println!("Ignoring Telegram message from chat {}", message.chat.id);
+                continue;
+            }
+            if let Some(text) = message.text {
+                let reply = handle_command(state, &text).await;
+                bot.send(message.chat.id, &reply).await;
+            }
+        }
+    }
+}
+
+async fn remind(bot: &Bot, state: &State, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    // the first tick completes immediately
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let reminder = format!("Open tasks:\n{}", open_tasks(state));
+        for chat_id in &bot.config.chat_ids {
+            bot.send(*chat_id, &reminder).await;
+        }
+    }
+}
+
+pub async fn run(state: web::Data<State>, config: TelegramConfig) {
+    let bot = Bot { client: reqwest::Client::new(), config };
+    println!("Telegram bot started for chats {:?}", bot.config.chat_ids);
+    match bot.config.reminder_interval {
+        Some(every) => {
+            tokio::join!(poll(&bot, &state), remind(&bot, &state, every));
+        }
+        None => poll(&bot, &state).await,
+    }
+}
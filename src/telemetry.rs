@@ -0,0 +1,129 @@
+// Request and storage spans. They are always created, but only leave the
+// process with the "otel" feature, once init_tracing found an OTLP endpoint
+// in the standard OTEL_* environment variables.
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use tracing::field::Empty;
+use tracing::Instrument;
+
+pub async fn trace(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    // the route pattern, so /tasks/1 and /tasks/2 end up as one operation
+    let route = request.match_pattern().unwrap_or_else(|| String::from(request.path()));
+    let span = tracing::info_span!(
+        "request",
+        otel.name = format!("{} {}", request.method(), route),
+        otel.kind = "server",
+        otel.status_code = Empty,
+        http.request.method = %request.method(),
+        http.route = route,
+        url.path = request.path(),
+        http.response.status_code = Empty,
+    );
+    #[cfg(feature = "otel")]
+    otel::continue_trace(&span, &request);
+
+    let response = next.call(request).instrument(span.clone()).await;
+    match &response {
+        Ok(response) => {
+            span.record("http.response.status_code", response.status().as_u16());
+            if response.status().is_server_error() {
+                span.record("otel.status_code", "ERROR");
+            }
+        }
+        Err(_) => {
+            span.record("otel.status_code", "ERROR");
+        }
+    }
+    return response;
+}
+
+#[cfg(feature = "otel")]
+pub use otel::{init_tracing, Tracing};
+
+#[cfg(feature = "otel")]
+mod otel {
+    use actix_web::dev::ServiceRequest;
+    use actix_web::http::header::HeaderMap;
+    use opentelemetry::propagation::Extractor;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry::global;
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::propagation::TraceContextPropagator;
+    use opentelemetry_sdk::trace::SdkTracerProvider;
+    use opentelemetry_sdk::Resource;
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    // Exports spans until dropped, then flushes what is still buffered
+    pub struct Tracing {
+        provider: SdkTracerProvider,
+    }
+
+    impl Drop for Tracing {
+        fn drop(&mut self) {
+            if let Err(err) = self.provider.shutdown() {
+                eprintln!("Could not flush the remaining spans: {}", err);
+            }
+        }
+    }
+
+    fn env(name: &str) -> Option<String> {
+        return std::env::var(name).ok().filter(|value| !value.trim().is_empty());
+    }
+
+    // Installs the OTLP/HTTP exporter when OTEL_EXPORTER_OTLP_ENDPOINT or
+    // OTEL_EXPORTER_OTLP_TRACES_ENDPOINT is set, unless OTEL_SDK_DISABLED or
+    // OTEL_TRACES_EXPORTER=none say otherwise. Headers, timeouts, the service
+    // name and resource attributes come from their OTEL_* variables as well.
+    pub fn init_tracing() -> Option<Tracing> {
+        if env("OTEL_SDK_DISABLED").is_some_and(|disabled| disabled.eq_ignore_ascii_case("true"))
+            || env("OTEL_TRACES_EXPORTER").is_some_and(|exporter| exporter == "none")
+        {
+            return None;
+        }
+        if env("OTEL_EXPORTER_OTLP_ENDPOINT").is_none() && env("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_none() {
+            return None;
+        }
+        let exporter = match SpanExporter::builder().with_http().build() {
+            Ok(exporter)    => exporter,
+            Err(err)        => {
+                eprintln!("Tracing disabled, could not set up the OTLP exporter: {}", err);
+                return None;
+            }
+        };
+        let mut provider = SdkTracerProvider::builder().with_batch_exporter(exporter);
+        if env("OTEL_SERVICE_NAME").is_none() {
+            provider = provider.with_resource(Resource::builder().with_service_name("rest-journal").build());
+        }
+        let provider = provider.build();
+
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("rest"));
+        if tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer)).is_err() {
+            eprintln!("Tracing disabled, another subscriber is already installed");
+            return None;
+        }
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        return Some(Tracing { provider });
+    }
+
+    struct Headers<'a>(&'a HeaderMap);
+
+    impl Extractor for Headers<'_> {
+        fn get(&self, key: &str) -> Option<&str> {
+            return self.0.get(key).and_then(|value| value.to_str().ok());
+        }
+        fn keys(&self) -> Vec<&str> {
+            return self.0.keys().map(|key| key.as_str()).collect();
+        }
+    }
+
+    // a traceparent header from the caller makes this request part of its trace
+    pub(super) fn continue_trace(span: &tracing::Span, request: &ServiceRequest) {
+        let context = global::get_text_map_propagator(|propagator| propagator.extract(&Headers(request.headers())));
+        let _ = span.set_parent(context);
+    }
+}
@@ -0,0 +1,210 @@
+// Time tracking: a timer per task whose runs are kept as the task's time
+// entries, totals per task and a weekly report over all of them
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::response_token;
+use crate::etag;
+use crate::handlers::{IdPath, Rejection};
+use crate::models::{Task, TimeEntry};
+use crate::notify::{Action, Event};
+use crate::state::State;
+use crate::workspace::{Level, Space};
+
+#[derive(Serialize)]
+struct EntryView {
+    started_at: DateTime<Utc>,
+    stopped_at: Option<DateTime<Utc>>,
+    // up to now while the timer runs
+    seconds:    i64,
+}
+
+impl From<&TimeEntry> for EntryView {
+    fn from(entry: &TimeEntry) -> Self {
+        EntryView {
+            started_at: entry.started_at,
+            stopped_at: entry.stopped_at,
+            seconds:    entry.duration().num_seconds(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct TaskTime {
+    id:             usize,
+    total_seconds:  i64,
+    running:        bool,
+    entries:        Vec<EntryView>,
+}
+
+fn running(task: &Task) -> bool {
+    return task.time_entries.last().is_some_and(|entry| entry.stopped_at.is_none());
+}
+
+fn start_timer(task: &mut Task, now: DateTime<Utc>) -> Result<TimeEntry, Rejection> {
+    if running(task) {
+        return Err(Rejection::new(StatusCode::CONFLICT, "The timer is already running"));
+    }
+    let entry = TimeEntry { started_at: now, stopped_at: None };
+    task.time_entries.push(entry.clone());
+    return Ok(entry);
+}
+
+fn stop_timer(task: &mut Task, now: DateTime<Utc>) -> Result<TimeEntry, Rejection> {
+    if !running(task) {
+        return Err(Rejection::new(StatusCode::CONFLICT, "The timer is not running"));
+    }
+    let entry = task.time_entries.last_mut().unwrap();
+    entry.stopped_at = Some(now);
+    return Ok(entry.clone());
+}
+
+// applies start_timer or stop_timer to the task, for requests with a token
+async fn switch_timer(
+    state: web::Data<State>,
+    space: Space,
+    path: web::Path<IdPath>,
+    request: HttpRequest,
+    switch: fn(&mut Task, DateTime<Utc>) -> Result<TimeEntry, Rejection>,
+    status: StatusCode,
+) -> HttpResponse {
+    let id = path.id;
+    if let Err(rejection) = space.allow::<Task>(Some(id), Level::Write) {
+        return rejection.into();
+    }
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let switched = space.tasks.change(move |tasks| {
+        let mut task = match tasks.get_mut(&id) {
+            Some(task)  => task,
+            None        => return Err(Rejection::new(StatusCode::NOT_FOUND, "Not found")),
+        };
+        let now = Utc::now();
+        let entry = switch(&mut task, now)?;
+        task.updated_at = now;
+        let etag = match etag::refresh(&mut *task) {
+            Ok(etag)    => etag,
+            Err(_)      => return Err(Rejection::new(StatusCode::INTERNAL_SERVER_ERROR, "Json error")),
+        };
+        let event = Event::of(Action::Updated, id, &*task);
+        drop(task);
+        tasks.emit(event);
+        Ok((entry, etag))
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    return match switched {
+        Ok((entry, etag)) => HttpResponse::build(status)
+            .append_header(("ETag", etag))
+            .json(EntryView::from(&entry)),
+        Err(rejection) => rejection.into(),
+    };
+}
+
+pub(crate) async fn start(
+    state: web::Data<State>,
+    space: Space,
+    path: web::Path<IdPath>,
+    request: HttpRequest,
+) -> impl Responder {
+    return switch_timer(state, space, path, request, start_timer, StatusCode::CREATED).await;
+}
+
+pub(crate) async fn stop(
+    state: web::Data<State>,
+    space: Space,
+    path: web::Path<IdPath>,
+    request: HttpRequest,
+) -> impl Responder {
+    return switch_timer(state, space, path, request, stop_timer, StatusCode::OK).await;
+}
+
+pub(crate) async fn show(
+    space: Space,
+    path: web::Path<IdPath>,
+) -> impl Responder {
+    let id = path.id;
+    if let Err(rejection) = space.allow::<Task>(Some(id), Level::Read) {
+        return rejection.into();
+    }
+    let task = match space.tasks.get(&id) {
+        Some(task)  => task,
+        None        => return HttpResponse::NotFound().body("Not found"),
+    };
+    let entries: Vec<EntryView> = task.time_entries.iter().map(EntryView::from).collect();
+    return HttpResponse::Ok().json(TaskTime {
+        id,
+        total_seconds:  entries.iter().map(|entry| entry.seconds).sum(),
+        running:        running(&task),
+        entries,
+    });
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ReportQuery {
+    // an ISO week like 2026-W42, the current one when left out
+    week:   Option<String>,
+}
+
+#[derive(Serialize)]
+struct TaskTotal {
+    id:         usize,
+    text:       String,
+    seconds:    i64,
+}
+
+#[derive(Serialize)]
+struct Report {
+    week:           String,
+    from:           DateTime<Utc>,
+    to:             DateTime<Utc>,
+    total_seconds:  i64,
+    tasks:          Vec<TaskTotal>,
+}
+
+// the Monday the week starts on
+fn parse_week(week: Option<&str>) -> Option<NaiveDate> {
+    let week = match week {
+        Some(week)  => week,
+        None        => {
+            let today = Utc::now().date_naive();
+            return today.checked_sub_days(Days::new(today.weekday().num_days_from_monday() as u64));
+        }
+    };
+    return NaiveDate::parse_from_str(&format!("{}-1", week.trim()), "%G-W%V-%u").ok();
+}
+
+// the time spent on every task during a week (UTC), entries crossing its
+// start or end count with the part inside it
+pub(crate) async fn report(
+    query: web::Query<ReportQuery>,
+    space: Space,
+) -> impl Responder {
+    let monday = match parse_week(query.week.as_deref()) {
+        Some(monday)    => monday,
+        None            => return HttpResponse::BadRequest().body("Expected a week like 2026-W42"),
+    };
+    let from = monday.and_time(NaiveTime::MIN).and_utc();
+    let to = match monday.checked_add_days(Days::new(7)) {
+        Some(next)  => next.and_time(NaiveTime::MIN).and_utc(),
+        None        => return HttpResponse::BadRequest().body("Expected a week like 2026-W42"),
+    };
+
+    let tasks: Vec<TaskTotal> = space.visible(&space.tasks).into_iter()
+        .filter_map(|id| {
+            let task = space.tasks.get(&id)?;
+            let seconds = task.time_entries.iter().map(|entry| entry.within(from, to).num_seconds()).sum();
+            Some(TaskTotal { id, text: task.text.clone(), seconds })
+        })
+        .filter(|total| total.seconds > 0)
+        .collect();
+    let week = monday.iso_week();
+    return HttpResponse::Ok().json(Report {
+        week:           format!("{}-W{:02}", week.year(), week.week()),
+        from,
+        to,
+        total_seconds:  tasks.iter().map(|task| task.seconds).sum(),
+        tasks,
+    });
+}
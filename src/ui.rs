@@ -0,0 +1,145 @@
+// Minimal server-rendered HTML interface under /ui
+use actix_web::{web, HttpResponse, Responder};
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use crate::render::journal_html;
+use crate::handlers::{paginate, PaginationParams, PaginationResponse};
+use crate::models::{Resource, Status};
+use crate::state::State;
+use crate::workspace::Space;
+
+fn layout(title: &str, content: Markup) -> String {
+    let markup = html! {
+        (DOCTYPE)
+        html {
+            head {
+                meta charset="utf-8";
+                title { (title) }
+            }
+            body {
+                nav {
+                    a href="/ui" { "Home" } " | "
+                    a href="/ui/journals" { "Journals" } " | "
+                    a href="/ui/tasks" { "Tasks" }
+                }
+                h1 { (title) }
+                (content)
+            }
+        }
+    };
+    markup.into_string()
+}
+
+fn page(title: &str, content: Markup) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(layout(title, content))
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound()
+        .content_type("text/html; charset=utf-8")
+        .body(layout("Not found", html! {}))
+}
+
+fn pager<T>(base: &str, listing: &PaginationResponse<T>) -> Markup {
+    html! {
+        p {
+            @if listing.page > 1 {
+                a href={ (base) "?page=" (listing.page - 1) } { "Previous" } " "
+            }
+            "Page " (listing.page) " of " (listing.total_pages.max(1))
+            @if listing.page < listing.total_pages {
+                " " a href={ (base) "?page=" (listing.page + 1) } { "Next" }
+            }
+        }
+    }
+}
+
+async fn index() -> impl Responder {
+    page("Journal", html! {
+        ul {
+            li { a href="/ui/journals" { "Journals" } }
+            li { a href="/ui/tasks" { "Tasks" } }
+        }
+    })
+}
+
+async fn journal_list(
+    query: web::Query<PaginationParams>,
+    space: Space,
+) -> impl Responder {
+    let listing = paginate(&space.journals, space.visible(&space.journals), &query);
+    page("Journals", html! {
+        ul {
+            @for (id, journal) in &listing.entries {
+                li {
+                    a href={ "/ui/journals/" (id) } { (journal.title) }
+                    " (" (journal.updated_at.format("%Y-%m-%d %H:%M")) ")"
+                }
+            }
+        }
+        (pager("/ui/journals", &listing))
+    })
+}
+
+async fn journal_detail(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+) -> impl Responder {
+    let id = path.into_inner();
+    let journal = match state.journals.get(&id) {
+        Some(journal)   => journal,
+        None            => return not_found(),
+    };
+    page(&journal.title, html! {
+        p { small { "Updated " (journal.updated_at.format("%Y-%m-%d %H:%M")) } }
+        @if journal.encrypted {
+            p { em { "Encrypted, only the app that wrote it can show it." } }
+        } @else {
+            article { (PreEscaped(journal_html(&state, id, &journal))) }
+        }
+    })
+}
+
+async fn task_list(
+    query: web::Query<PaginationParams>,
+    space: Space,
+) -> impl Responder {
+    let listing = paginate(&space.tasks, space.visible(&space.tasks), &query);
+    page("Tasks", html! {
+        ul {
+            @for (id, task) in &listing.entries {
+                li {
+                    (if task.done { "[x] " } else { "[ ] " })
+                    a href={ "/ui/tasks/" (id) } { (task.text) }
+                }
+            }
+        }
+        (pager("/ui/tasks", &listing))
+    })
+}
+
+async fn task_detail(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+) -> impl Responder {
+    let id = path.into_inner();
+    let task = match state.tasks.get(&id) {
+        Some(task)  => task,
+        None        => return not_found(),
+    };
+    page(&format!("Task {}", id), html! {
+        p { (task.text) }
+        p { "Status: " (task.status().map_or("todo", Status::name)) }
+        p { small { "Updated " (task.updated_at.format("%Y-%m-%d %H:%M")) } }
+    })
+}
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/ui").route(web::get().to(index)))
+        .service(web::resource("/ui/journals").route(web::get().to(journal_list)))
+        .service(web::resource("/ui/journals/{id}").route(web::get().to(journal_detail)))
+        .service(web::resource("/ui/tasks").route(web::get().to(task_list)))
+        .service(web::resource("/ui/tasks/{id}").route(web::get().to(task_detail)));
+}
@@ -0,0 +1,70 @@
+// API versions. /v1/... is the canonical form; the unprefixed routes are
+// deprecated aliases of the current version and can be pinned to a version
+// with the Api-Version header, so they keep working once a /v2 exists.
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{HeaderName, HeaderValue};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpRequest, HttpResponse};
+
+use crate::state::State;
+
+const CURRENT: &str = "1";
+const SUPPORTED: &[&str] = &["1"];
+const HEADER: &str = "Api-Version";
+
+// "1" for "/v1/tasks", None for the unprefixed routes
+pub fn path_version(path: &str) -> Option<&str> {
+    let rest = path.strip_prefix("/v")?;
+    let version = rest.split('/').next()?;
+    if version.is_empty() || !version.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    return Some(version);
+}
+
+// what links start with: the public base URL when one is configured, so
+// they are absolute and right behind a reverse proxy, nothing otherwise
+pub fn base(request: &HttpRequest) -> String {
+    return request.app_data::<web::Data<State>>()
+        .and_then(|state| state.config.base_url.clone())
+        .unwrap_or_default();
+}
+
+// the prefix to build links with, so they stay in the version they were asked in
+pub fn root(request: &HttpRequest) -> String {
+    match path_version(request.path()) {
+        Some(version)   => format!("{}/v{}", base(request), version),
+        None            => base(request),
+    }
+}
+
+// picks the version from the path or the Api-Version header and echoes it
+pub async fn negotiate(
+    request: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let requested = request.headers().get(HEADER)
+        .map(|version| String::from(version.to_str().unwrap_or("").trim()));
+    let version = match (path_version(request.path()), requested.as_deref()) {
+        (Some(path), Some(header)) if path != header => {
+            let response = HttpResponse::BadRequest()
+                .body(format!("{} {} conflicts with the /v{} path", HEADER, header, path));
+            return Ok(request.into_response(response).map_into_right_body());
+        }
+        (Some(path), _)         => String::from(path),
+        (None, Some(header))    => String::from(header),
+        (None, None)            => String::from(CURRENT),
+    };
+    if !SUPPORTED.contains(&version.as_str()) {
+        let response = HttpResponse::BadRequest()
+            .body(format!("Unsupported API version {}, supported: {}", version, SUPPORTED.join(", ")));
+        return Ok(request.into_response(response).map_into_right_body());
+    }
+
+    let mut response = next.call(request).await?;
+    if let Ok(value) = HeaderValue::from_str(&version) {
+        response.headers_mut().insert(HeaderName::from_static("api-version"), value);
+    }
+    return Ok(response.map_into_left_body());
+}
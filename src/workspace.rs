@@ -0,0 +1,655 @@
+// Workspaces: separate journals and tasks for separate projects on one
+// server, reached under /workspaces/{wid} with a member's Workspace-Key
+use actix_web::dev::Payload;
+use actix_web::http::StatusCode;
+use actix_web::{error, web, FromRequest, HttpRequest, HttpResponse, Responder};
+use chrono::Utc;
+use futures_util::future::{ready, Ready};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::attachments::Attachments;
+use crate::auth::{random_key, response_token};
+#[cfg(feature = "crdt")]
+use crate::crdt::Replication;
+#[cfg(feature = "fulltext")]
+use crate::fulltext::Indexes;
+use crate::handlers::{IdPath, Rejection};
+use crate::models::{Bookmark, Habit, Journal, Note, Resource, Task};
+use crate::notify::Event;
+use crate::schema::Schemas;
+use crate::state::{Readable, State};
+use crate::store::{Collection, Entries};
+use crate::sync::ChangeLog;
+use crate::{notify, versioning};
+
+const KEY_HEADER: &str = "Workspace-Key";
+
+pub(crate) struct Member {
+    name:   String,
+    key:    String,
+    // owners manage the members and may delete the workspace
+    owner:  bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, PartialOrd)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Level {
+    #[default]
+    Read,
+    // read, and replace with PUT
+    Write,
+}
+
+// someone who is not a member, with access to single journals only
+pub(crate) struct Collaborator {
+    name:   String,
+    key:    String,
+    grants: HashMap<usize, Level>,
+}
+
+pub(crate) struct Workspace {
+    name:           String,
+    members:        Vec<Member>,
+    collaborators:  Vec<Collaborator>,
+    journals:       Collection<Journal>,
+    tasks:          Collection<Task>,
+    habits:         Collection<Habit>,
+    notes:          Collection<Note>,
+    bookmarks:      Collection<Bookmark>,
+    schemas:        Schemas,
+    attachments:    Attachments,
+    #[cfg(feature = "fulltext")]
+    index:          Indexes,
+    changes:        Arc<ChangeLog>,
+    #[cfg(feature = "crdt")]
+    replication:    Replication,
+}
+
+impl Workspace {
+    fn member(&self, key: Option<&str>) -> Result<&Member, Rejection> {
+        let key = match key {
+            Some(key)   => key,
+            None        => return Err(Rejection::new(StatusCode::UNAUTHORIZED, format!("{} is missing", KEY_HEADER))),
+        };
+        return self.members.iter()
+            .find(|member| member.key == key)
+            .ok_or_else(|| Rejection::new(StatusCode::FORBIDDEN, "Not a member of this workspace"));
+    }
+
+    fn owner(&self, key: Option<&str>) -> Result<&Member, Rejection> {
+        let member = self.member(key)?;
+        if !member.owner {
+            return Err(Rejection::new(StatusCode::FORBIDDEN, "Only owners can do this"));
+        }
+        return Ok(member);
+    }
+}
+
+#[derive(Clone)]
+pub(crate) enum Access {
+    // members, and everyone outside of workspaces
+    Full,
+    // collaborators, on the journals granted to them
+    Journals(HashMap<usize, Level>),
+}
+
+// The collections a request works on: the server's own, or those of the
+// workspace in its path, once the request has shown a member's or a
+// collaborator's key.
+#[derive(Clone)]
+pub(crate) struct Space {
+    pub(crate) journals:   Collection<Journal>,
+    pub(crate) tasks:      Collection<Task>,
+    pub(crate) habits:     Collection<Habit>,
+    pub(crate) notes:      Collection<Note>,
+    pub(crate) bookmarks:  Collection<Bookmark>,
+    pub(crate) schemas:    Schemas,
+    pub(crate) attachments: Attachments,
+    #[cfg(feature = "fulltext")]
+    pub(crate) index:      Indexes,
+    pub(crate) changes:    Arc<ChangeLog>,
+    #[cfg(feature = "crdt")]
+    pub(crate) replication: Replication,
+    workspace:             Option<usize>,
+    access:                Access,
+}
+
+impl Space {
+    // what paths to resources of this space start with, for Location headers
+    pub(crate) fn root(&self, request: &HttpRequest) -> String {
+        match self.workspace {
+            Some(wid)   => format!("{}/workspaces/{}", versioning::root(request), wid),
+            None        => versioning::root(request),
+        }
+    }
+
+    // Rejects requests that may not do `level` on the resource with the id,
+    // or with None, on the collection as a whole (creating, deleting, merging)
+    pub(crate) fn allow<T: Resource>(&self, id: Option<usize>, level: Level) -> Result<(), Rejection> {
+        let grants = match &self.access {
+            Access::Full                => return Ok(()),
+            Access::Journals(grants)    => grants,
+        };
+        let granted = T::KIND == Journal::KIND
+            && id.and_then(|id| grants.get(&id)).is_some_and(|granted| *granted >= level);
+        if !granted {
+            return Err(Rejection::new(StatusCode::FORBIDDEN, "No access to this resource"));
+        }
+        return Ok(());
+    }
+
+    // Err naming the first journal the resource links to that is not in
+    // the space
+    pub(crate) fn check_links<T: Resource>(&self, resource: &T) -> Result<(), String> {
+        return match resource.journals().iter().find(|id| self.journals.get(id).is_none()) {
+            Some(missing)   => Err(format!("Journal {} not found", missing)),
+            None            => Ok(()),
+        };
+    }
+
+    // the ids a listing shows, ascending; drafts are left out, and expired
+    // entries before the sweeper gets to them
+    pub(crate) fn visible<T: Resource>(&self, resources: &Entries<T>) -> Vec<usize> {
+        let now = Utc::now();
+        let mut ids = match &self.access {
+            Access::Full => resources.ids(),
+            Access::Journals(grants) if T::KIND == Journal::KIND => {
+                let mut ids: Vec<usize> = grants.keys()
+                    .copied()
+                    .filter(|id| resources.get(id).is_some())
+                    .collect();
+                ids.sort();
+                ids
+            }
+            Access::Journals(_) => Vec::new(),
+        };
+        ids.retain(|id| resources.get(id).is_some_and(|resource| !resource.expired(now) && !resource.draft(now)));
+        return ids;
+    }
+
+    // the server's own collections
+    pub(crate) fn server(state: &State) -> Space {
+        return Space {
+            journals:   state.journals.clone(),
+            tasks:      state.tasks.clone(),
+            habits:     state.habits.clone(),
+            notes:      state.notes.clone(),
+            bookmarks:  state.bookmarks.clone(),
+            schemas:    state.schemas.clone(),
+            attachments: state.attachments.clone(),
+            #[cfg(feature = "fulltext")]
+            index:      state.index.clone(),
+            changes:    state.changes.clone(),
+            #[cfg(feature = "crdt")]
+            replication: state.replication.clone(),
+            workspace:  None,
+            access:     Access::Full,
+        };
+    }
+
+    fn of(workspace: &Workspace, wid: usize, access: Access) -> Space {
+        return Space {
+            journals:   workspace.journals.clone(),
+            tasks:      workspace.tasks.clone(),
+            habits:     workspace.habits.clone(),
+            notes:      workspace.notes.clone(),
+            bookmarks:  workspace.bookmarks.clone(),
+            schemas:    workspace.schemas.clone(),
+            attachments: workspace.attachments.clone(),
+            #[cfg(feature = "fulltext")]
+            index:      workspace.index.clone(),
+            changes:    workspace.changes.clone(),
+            #[cfg(feature = "crdt")]
+            replication: workspace.replication.clone(),
+            workspace:  Some(wid),
+            access,
+        };
+    }
+
+    // whether the event is of this space and of an entry the request may see
+    pub(crate) fn sees(&self, event: &Event) -> bool {
+        if event.workspace != self.workspace {
+            return false;
+        }
+        return match &self.access {
+            Access::Full                => true,
+            Access::Journals(grants)    => event.kind == Journal::KIND && grants.contains_key(&event.id),
+        };
+    }
+
+    // the workspace's id, None for the server's own collections
+    pub(crate) fn workspace(&self) -> Option<usize> {
+        return self.workspace;
+    }
+
+    // the server's own and every workspace, for work done outside of requests
+    pub(crate) fn all(state: &State) -> Vec<Space> {
+        let mut spaces = vec![Space::server(state)];
+        for wid in state.workspaces.ids() {
+            if let Some(workspace) = state.workspaces.get(&wid) {
+                spaces.push(Space::of(&workspace, wid, Access::Full));
+            }
+        }
+        return spaces;
+    }
+
+    fn resolve(request: &HttpRequest) -> Result<Space, Rejection> {
+        let state = request.app_data::<web::Data<State>>().expect("State is not registered");
+        let wid = match request.match_info().get("wid") {
+            Some(wid)   => wid,
+            None        => return Ok(Space::server(state)),
+        };
+        let not_found = || Rejection::new(StatusCode::NOT_FOUND, "No such workspace");
+        let wid: usize = wid.parse().map_err(|_| not_found())?;
+        let workspace = state.workspaces.get(&wid).ok_or_else(not_found)?;
+        let key = key(request);
+        let collaborator = workspace.collaborators.iter()
+            .find(|collaborator| key.as_deref() == Some(collaborator.key.as_str()));
+        let access = match collaborator {
+            Some(collaborator)  => Access::Journals(collaborator.grants.clone()),
+            None                => workspace.member(key.as_deref()).map(|_| Access::Full)?,
+        };
+        return Ok(Space::of(&workspace, wid, access));
+    }
+}
+
+impl Readable<Journal> for Space {
+    fn get_hmap(&self) -> &Collection<Journal> {
+        return &self.journals;
+    }
+}
+
+impl Readable<Task> for Space {
+    fn get_hmap(&self) -> &Collection<Task> {
+        return &self.tasks;
+    }
+}
+
+impl Readable<Habit> for Space {
+    fn get_hmap(&self) -> &Collection<Habit> {
+        return &self.habits;
+    }
+}
+
+impl Readable<Note> for Space {
+    fn get_hmap(&self) -> &Collection<Note> {
+        return &self.notes;
+    }
+}
+
+impl Readable<Bookmark> for Space {
+    fn get_hmap(&self) -> &Collection<Bookmark> {
+        return &self.bookmarks;
+    }
+}
+
+impl FromRequest for Space {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Space, actix_web::Error>>;
+
+    fn from_request(request: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let space = Space::resolve(request).map_err(|rejection| {
+            error::InternalError::from_response("", HttpResponse::from(rejection)).into()
+        });
+        return ready(space);
+    }
+}
+
+fn key(request: &HttpRequest) -> Option<String> {
+    return request.headers().get(KEY_HEADER)
+        .and_then(|key| key.to_str().ok())
+        .map(String::from);
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NewWorkspace {
+    name:   String,
+    // the name of the first member, who owns the workspace
+    #[serde(default = "default_owner")]
+    owner:  String,
+}
+
+fn default_owner() -> String {
+    return String::from("owner");
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NewMember {
+    name:   String,
+    #[serde(default)]
+    owner:  bool,
+}
+
+// handed out once, the server never shows a key again
+#[derive(Serialize)]
+struct Credentials {
+    id:     usize,
+    name:   String,
+    key:    String,
+}
+
+#[derive(Serialize)]
+struct MemberView<'a> {
+    name:   &'a str,
+    owner:  bool,
+}
+
+#[derive(Serialize)]
+struct WorkspaceView<'a> {
+    id:         usize,
+    name:       &'a str,
+    members:    Vec<MemberView<'a>>,
+    journals:   usize,
+    tasks:      usize,
+    habits:     usize,
+    notes:      usize,
+    bookmarks:  usize,
+}
+
+pub(crate) async fn create(
+    json: web::Json<NewWorkspace>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    if let Err(resp) = response_token(&state, &request) {
+        return resp;
+    }
+    let info = json.into_inner();
+    if info.name.trim().is_empty() || info.owner.trim().is_empty() {
+        return HttpResponse::BadRequest().body("Workspace and owner need a name");
+    }
+    // the id is only known once stored, the events wait for it
+    let (events, queue) = mpsc::unbounded_channel();
+    let journals = Collection::new(HashMap::new(), events.clone());
+    let tasks = Collection::new(HashMap::new(), events.clone());
+    let habits = Collection::new(HashMap::new(), events.clone());
+    let notes = Collection::new(HashMap::new(), events.clone());
+    let bookmarks = Collection::new(HashMap::new(), events);
+    state.usage.watch(&journals, &tasks, &habits, &notes, &bookmarks);
+    let workspace = Workspace {
+        name:       info.name,
+        members:    vec![Member { name: info.owner.clone(), key: random_key(), owner: true }],
+        collaborators: Vec::new(),
+        #[cfg(feature = "fulltext")]
+        index:      Indexes::watch(&journals, &tasks),
+        changes:    ChangeLog::watch(&journals, &tasks, &habits, &notes, &bookmarks),
+        // workspaces do not outlive the server, ids of earlier ones come again
+        attachments: Attachments::watch(&journals, state.blobs.clone(), state.usage.clone(), random_key()),
+        #[cfg(feature = "crdt")]
+        replication: Replication::default(),
+        journals,
+        tasks,
+        habits,
+        notes,
+        bookmarks,
+        schemas:    Schemas::default(),
+    };
+    let key = workspace.members[0].key.clone();
+    let id = state.workspaces.change(move |workspaces| {
+        let id = workspaces.allocate_id();
+        workspaces.insert(id, workspace);
+        id
+    }).await;
+    let id = match id {
+        Ok(id)      => id,
+        Err(failed) => return failed.into(),
+    };
+    tokio::spawn(notify::forward(queue, state.events.clone(), id));
+    return HttpResponse::Created()
+        .append_header(("Location", format!("{}/workspaces/{}", versioning::root(&request), id)))
+        .json(Credentials { id, name: info.owner, key });
+}
+
+pub(crate) async fn get(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    let id = path.into_inner();
+    let workspace = match state.workspaces.get(&id) {
+        Some(workspace) => workspace,
+        None            => return HttpResponse::NotFound().body("No such workspace"),
+    };
+    if let Err(rejection) = workspace.member(key(&request).as_deref()) {
+        return rejection.into();
+    }
+    return HttpResponse::Ok().json(WorkspaceView {
+        id,
+        name:       &workspace.name,
+        members:    workspace.members.iter()
+            .map(|member| MemberView { name: &member.name, owner: member.owner })
+            .collect(),
+        journals:   workspace.journals.len(),
+        tasks:      workspace.tasks.len(),
+        habits:     workspace.habits.len(),
+        notes:      workspace.notes.len(),
+        bookmarks:  workspace.bookmarks.len(),
+    });
+}
+
+// drops the workspace with everything in it
+pub(crate) async fn delete(
+    path: web::Path<usize>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    let id = path.into_inner();
+    let key = key(&request);
+    let deleted = state.workspaces.change(move |workspaces| {
+        match workspaces.get(&id) {
+            Some(workspace) => workspace.owner(key.as_deref())?,
+            None            => return Err(Rejection::new(StatusCode::NOT_FOUND, "No such workspace")),
+        };
+        workspaces.remove(&id);
+        Ok(())
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    match deleted {
+        Ok(())          => return HttpResponse::Ok().body("Removed"),
+        Err(rejection)  => return rejection.into(),
+    }
+}
+
+pub(crate) async fn add_member(
+    json: web::Json<NewMember>,
+    path: web::Path<usize>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    let id = path.into_inner();
+    let info = json.into_inner();
+    if info.name.trim().is_empty() {
+        return HttpResponse::BadRequest().body("Members need a name");
+    }
+    let key = key(&request);
+    let added = state.workspaces.change(move |workspaces| {
+        let mut workspace = match workspaces.get_mut(&id) {
+            Some(workspace) => workspace,
+            None            => return Err(Rejection::new(StatusCode::NOT_FOUND, "No such workspace")),
+        };
+        workspace.owner(key.as_deref())?;
+        if workspace.members.iter().any(|member| member.name == info.name) {
+            return Err(Rejection::new(StatusCode::CONFLICT, format!("{} is already a member", info.name)));
+        }
+        let member = Member { name: info.name, key: random_key(), owner: info.owner };
+        let credentials = Credentials { id, name: member.name.clone(), key: member.key.clone() };
+        workspace.members.push(member);
+        Ok(credentials)
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    match added {
+        Ok(credentials) => return HttpResponse::Created().json(credentials),
+        Err(rejection)  => return rejection.into(),
+    }
+}
+
+// also how members leave, but the last owner cannot
+pub(crate) async fn remove_member(
+    path: web::Path<(usize, String)>,
+    state: web::Data<State>,
+    request: HttpRequest,
+) -> impl Responder {
+    let (id, name) = path.into_inner();
+    let key = key(&request);
+    let removed = state.workspaces.change(move |workspaces| {
+        let mut workspace = match workspaces.get_mut(&id) {
+            Some(workspace) => workspace,
+            None            => return Err(Rejection::new(StatusCode::NOT_FOUND, "No such workspace")),
+        };
+        let caller = workspace.member(key.as_deref())?;
+        if caller.name != name && !caller.owner {
+            return Err(Rejection::new(StatusCode::FORBIDDEN, "Only owners can do this"));
+        }
+        let index = match workspace.members.iter().position(|member| member.name == name) {
+            Some(index) => index,
+            None        => return Err(Rejection::new(StatusCode::NOT_FOUND, format!("{} is not a member", name))),
+        };
+        let owners = workspace.members.iter().filter(|member| member.owner).count();
+        if workspace.members[index].owner && owners == 1 {
+            return Err(Rejection::new(StatusCode::CONFLICT, "The last owner cannot leave"));
+        }
+        workspace.members.remove(index);
+        Ok(())
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    match removed {
+        Ok(())          => return HttpResponse::Ok().body("Removed"),
+        Err(rejection)  => return rejection.into(),
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NewCollaborator {
+    name:   String,
+    #[serde(default)]
+    access: Level,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CollaboratorPath {
+    id:     usize,
+    name:   String,
+}
+
+#[derive(Serialize)]
+struct CollaboratorView<'a> {
+    name:   &'a str,
+    access: Level,
+}
+
+// the workspace a member manages collaborators of
+fn managed(space: &Space) -> Result<usize, Rejection> {
+    let wid = match space.workspace {
+        Some(wid)   => wid,
+        None        => return Err(Rejection::new(StatusCode::BAD_REQUEST, "Collaborators are for journals in a workspace")),
+    };
+    space.allow::<Journal>(None, Level::Write)?;
+    return Ok(wid);
+}
+
+pub(crate) async fn list_collaborators(
+    path: web::Path<IdPath>,
+    space: Space,
+    state: web::Data<State>,
+) -> impl Responder {
+    let wid = match managed(&space) {
+        Ok(wid)         => wid,
+        Err(rejection)  => return rejection.into(),
+    };
+    let workspace = match state.workspaces.get(&wid) {
+        Some(workspace) => workspace,
+        None            => return HttpResponse::NotFound().body("No such workspace"),
+    };
+    let collaborators: Vec<CollaboratorView<'_>> = workspace.collaborators.iter()
+        .filter_map(|collaborator| Some(CollaboratorView {
+            name:   &collaborator.name,
+            access: *collaborator.grants.get(&path.id)?,
+        }))
+        .collect();
+    return HttpResponse::Ok().json(collaborators);
+}
+
+// grants the journal to the collaborator, or changes their access to it;
+// a new collaborator gets a key, used as their Workspace-Key
+pub(crate) async fn add_collaborator(
+    json: web::Json<NewCollaborator>,
+    path: web::Path<IdPath>,
+    space: Space,
+    state: web::Data<State>,
+) -> impl Responder {
+    let wid = match managed(&space) {
+        Ok(wid)         => wid,
+        Err(rejection)  => return rejection.into(),
+    };
+    let info = json.into_inner();
+    if info.name.trim().is_empty() {
+        return HttpResponse::BadRequest().body("Collaborators need a name");
+    }
+    let id = path.id;
+    let added = state.workspaces.change(move |workspaces| {
+        let mut workspace = match workspaces.get_mut(&wid) {
+            Some(workspace) => workspace,
+            None            => return Err(Rejection::new(StatusCode::NOT_FOUND, "No such workspace")),
+        };
+        if workspace.journals.get(&id).is_none() {
+            return Err(Rejection::new(StatusCode::NOT_FOUND, "Not found"));
+        }
+        if workspace.members.iter().any(|member| member.name == info.name) {
+            return Err(Rejection::new(StatusCode::CONFLICT, format!("{} is already a member", info.name)));
+        }
+        let existing = workspace.collaborators.iter().position(|collaborator| collaborator.name == info.name);
+        let created = existing.is_none();
+        let index = existing.unwrap_or_else(|| {
+            workspace.collaborators.push(Collaborator {
+                name:   info.name.clone(),
+                key:    random_key(),
+                grants: HashMap::new(),
+            });
+            workspace.collaborators.len() - 1
+        });
+        let collaborator = &mut workspace.collaborators[index];
+        collaborator.grants.insert(id, info.access);
+        let credentials = Credentials { id: wid, name: collaborator.name.clone(), key: collaborator.key.clone() };
+        Ok((created, credentials))
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    match added {
+        Ok((true, credentials))     => return HttpResponse::Created().json(credentials),
+        Ok((false, credentials))    => return HttpResponse::Ok().json(credentials),
+        Err(rejection)              => return rejection.into(),
+    }
+}
+
+// a collaborator left without journals is removed along with their key
+pub(crate) async fn remove_collaborator(
+    path: web::Path<CollaboratorPath>,
+    space: Space,
+    state: web::Data<State>,
+) -> impl Responder {
+    let wid = match managed(&space) {
+        Ok(wid)         => wid,
+        Err(rejection)  => return rejection.into(),
+    };
+    let CollaboratorPath { id, name } = path.into_inner();
+    let removed = state.workspaces.change(move |workspaces| {
+        let mut workspace = match workspaces.get_mut(&wid) {
+            Some(workspace) => workspace,
+            None            => return Err(Rejection::new(StatusCode::NOT_FOUND, "No such workspace")),
+        };
+        let index = workspace.collaborators.iter()
+            .position(|collaborator| collaborator.name == name && collaborator.grants.contains_key(&id));
+        let index = match index {
+            Some(index) => index,
+            None        => return Err(Rejection::new(StatusCode::NOT_FOUND, format!("{} is not a collaborator", name))),
+        };
+        let collaborator = &mut workspace.collaborators[index];
+        collaborator.grants.remove(&id);
+        if collaborator.grants.is_empty() {
+            workspace.collaborators.remove(index);
+        }
+        Ok(())
+    }).await.unwrap_or_else(|failed| Err(failed.into()));
+    match removed {
+        Ok(())          => return HttpResponse::Ok().body("Removed"),
+        Err(rejection)  => return rejection.into(),
+    }
+}
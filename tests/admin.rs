@@ -0,0 +1,221 @@
+#![allow(clippy::needless_return)]
+// The operator endpoints under /admin
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+
+use rest::{app, create_test_app, Config, State};
+
+mod common;
+use common::{header, token};
+
+const ADMIN: (&str, &str) = ("Authorization", "Bearer secret");
+
+#[actix_web::test]
+async fn admins_revoke_write_tokens() {
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    let leaked = token(&app).await;
+    let other = token(&app).await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/admin/tokens").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request = TestRequest::get().uri("/admin/tokens").insert_header(ADMIN).to_request();
+    let tokens: Value = test::call_and_read_body_json(&app, request).await;
+    let listed: Vec<&str> = tokens.as_array().unwrap().iter().map(|token| token["token"].as_str().unwrap()).collect();
+    assert_eq!(listed.len(), 2);
+    assert!(listed.contains(&leaked.as_str()) && listed.contains(&other.as_str()));
+
+    let request = TestRequest::delete().uri(&format!("/admin/tokens/{}", leaked)).insert_header(ADMIN).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", leaked.as_str()))
+        .set_json(json!({ "text": "Sneaky", "done": false }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+    let request = TestRequest::delete().uri("/admin/tokens").insert_header(ADMIN).to_request();
+    let revoked: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(revoked["revoked"], 1);
+}
+
+#[actix_web::test]
+async fn the_admin_api_is_off_without_a_credential() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::get().uri("/admin/tokens").insert_header(ADMIN).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn maintenance_refuses_writes_only() {
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    #[cfg(feature = "graphql")]
+    let (wid, key) = common::workspace(&app, "Team").await;
+    let request = TestRequest::post().uri("/admin/maintenance")
+        .insert_header(ADMIN)
+        .set_json(json!({ "enabled": true, "retry_after": 60 }))
+        .to_request();
+    let status: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(status["enabled"], true);
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let request = TestRequest::delete().uri("/v1/tasks/1").to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(header(&response, "Retry-After"), "60");
+    let request = TestRequest::post().uri("/admin/seed").insert_header(ADMIN).set_json(json!({ "tasks": 5 })).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::SERVICE_UNAVAILABLE);
+    // admin writes too, but the toggle
+    let request = TestRequest::patch().uri("/admin/flags").insert_header(ADMIN).set_json(json!({ "crdt_sync": false })).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::SERVICE_UNAVAILABLE);
+    // GraphQL only reads, in every space
+    #[cfg(feature = "graphql")]
+    for (uri, key) in [(String::from("/graphql"), None), (format!("/workspaces/{}/graphql", wid), Some(key.as_str()))] {
+        let mut request = TestRequest::post().uri(&uri).set_json(json!({ "query": "{ tasks { totalEntries } }" }));
+        if let Some(key) = key {
+            request = request.insert_header(("Workspace-Key", key));
+        }
+        assert_eq!(test::call_service(&app, request.to_request()).await.status(), StatusCode::OK);
+    }
+
+    // without a body it toggles back
+    let request = TestRequest::post().uri("/admin/maintenance").insert_header(ADMIN).to_request();
+    let status: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(status, json!({ "enabled": false }));
+    let request = TestRequest::delete().uri("/v1/tasks/1").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn admins_see_the_background_jobs() {
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    std::env::set_var("JOURNAL_DAILY_SCHEDULE", "true");
+    let state = web::Data::new(State::with_sample_data(Config::from_env()));
+    rest::spawn_background(&state);
+    let app = test::init_service(app(state)).await;
+    // every job runs once right away
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/admin/jobs").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request = TestRequest::get().uri("/admin/jobs").insert_header(ADMIN).to_request();
+    let jobs: Value = test::call_and_read_body_json(&app, request).await;
+    let names: Vec<&str> = jobs.as_array().unwrap().iter().map(|job| job["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["token_sweep", "expiry_sweep", "schedules", "daily_journal"]);
+    for job in jobs.as_array().unwrap() {
+        assert_eq!(job["runs"], 1);
+        assert_eq!(job["failures"], 0);
+        assert!(job["last_run"].is_string() && job["next_run"].is_string());
+    }
+    assert_eq!(jobs[0]["schedule"], "every 60s");
+    assert_eq!(jobs[3]["schedule"], "daily at 00:00 UTC");
+
+    // the daily journal is there already
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/journals/today").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn admins_see_who_uses_the_api() {
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    let from = |uri: &str| TestRequest::get().uri(uri).peer_addr("10.0.0.5:4000".parse().unwrap());
+    for uri in ["/v1/tasks", "/v1/tasks", "/v1/tasks/999"] {
+        test::call_service(&app, from(uri).to_request()).await;
+    }
+    let request = from("/v1/journals").insert_header(("Workspace-Key", "abcdefghijkl")).to_request();
+    test::call_service(&app, request).await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/admin/usage").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let request = TestRequest::get().uri("/admin/usage?window=minute").insert_header(ADMIN).to_request();
+    let usage: Value = test::call_and_read_body_json(&app, request).await;
+    let clients: Vec<&str> = usage.as_array().unwrap().iter().map(|client| client["client"].as_str().unwrap()).collect();
+    // the refused request of the admin route came from no address
+    assert_eq!(clients, ["address:10.0.0.5", "address:unknown", "key:abcdefgh"]);
+    assert_eq!(usage[0]["requests"], 3);
+    assert_eq!(usage[0]["errors"], 1);
+    assert!((usage[0]["error_rate"].as_f64().unwrap() - 1.0 / 3.0).abs() < 1e-9);
+    assert!(usage[0]["bytes_out"].as_u64().unwrap() > 0);
+
+    // the admin's own request counts by now
+    let request = TestRequest::get().uri("/admin/usage").insert_header(ADMIN).to_request();
+    let usage: Value = test::call_and_read_body_json(&app, request).await;
+    assert!(usage.as_array().unwrap().iter().any(|client| client["client"] == "bearer:secret"));
+}
+
+#[actix_web::test]
+async fn admins_read_the_audit_log() {
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Audited" }))
+        .to_request();
+    let location = header(&test::call_service(&app, request).await, "Location");
+    test::call_service(&app, TestRequest::delete().uri(&location).to_request()).await;
+    test::call_service(&app, TestRequest::delete().uri("/v1/journals/3").to_request()).await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/admin/audit").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    // the log follows the bus in the background
+    let mut audit = Value::Null;
+    for _ in 0..100 {
+        let request = TestRequest::get().uri("/admin/audit?kind=task").insert_header(ADMIN).to_request();
+        audit = test::call_and_read_body_json(&app, request).await;
+        if audit.as_array().unwrap().len() == 2 {
+            break;
+        }
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(10)).await;
+    }
+    let events: Vec<&str> = audit.as_array().unwrap().iter().map(|event| event["event"].as_str().unwrap()).collect();
+    assert_eq!(events, ["task.deleted", "task.created"]);
+    assert_eq!(audit[1]["summary"], "Audited");
+    assert!(audit[1]["at"].is_string());
+
+    let request = TestRequest::get().uri("/admin/audit?limit=1").insert_header(ADMIN).to_request();
+    let audit: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(audit, json!([audit[0].clone()]));
+    assert_eq!(audit[0]["event"], "journal.deleted");
+}
+
+#[actix_web::test]
+async fn admins_seed_made_up_entries() {
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    let seed = json!({ "journals": 30, "tasks": 120, "seed": 7 });
+    let request = TestRequest::post().uri("/admin/seed").set_json(&seed).to_request();
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNAUTHORIZED);
+
+    let request = TestRequest::post().uri("/admin/seed").insert_header(ADMIN).set_json(&seed).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let seeded: Value = test::read_body_json(response).await;
+    assert_eq!(seeded, seed);
+    let tasks: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks").to_request()).await;
+    assert_eq!(tasks["total_entries"], 130);
+    let journals: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals").to_request()).await;
+    assert_eq!(journals["total_entries"], 40);
+
+    // the same seed makes the same entries
+    let other = test::init_service(rest::app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    let request = TestRequest::post().uri("/admin/seed").insert_header(ADMIN).set_json(&seed).to_request();
+    assert_eq!(test::call_service(&other, request).await.status(), StatusCode::CREATED);
+    for (uri, field) in [("/v1/tasks/75", "text"), ("/v1/journals/25", "title"), ("/v1/journals/25", "data")] {
+        let entry: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(uri).to_request()).await;
+        let copy: Value = test::call_and_read_body_json(&other, TestRequest::get().uri(uri).to_request()).await;
+        assert!(entry[field].is_string());
+        assert_eq!(entry[field], copy[field]);
+    }
+    // searchable like any entry
+    let found: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/search?q=coffee").to_request()).await;
+    assert!(found["total"].as_u64().unwrap() > 0);
+
+    let request = TestRequest::post().uri("/admin/seed").insert_header(ADMIN)
+        .set_json(json!({ "tasks": 2_000_000 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,650 @@
+#![allow(clippy::needless_return)]
+// The REST API end to end, against the sample data: ten journals and ten
+// tasks with ids 0 to 9, all with the ETag "1"
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+
+use rest::{app, create_test_app, Config, State};
+
+mod common;
+use common::{header, token};
+
+#[actix_web::test]
+async fn tokens_are_single_use() {
+    let app = test::init_service(create_test_app()).await;
+    let token = token(&app).await;
+    assert_eq!(token.len(), 32);
+
+    let post = || TestRequest::post().uri("/v1/tasks").set_json(json!({ "text": "Water the plants", "done": false }));
+    let response = test::call_service(&app, post().to_request()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = test::call_service(&app, post().insert_header(("Post-Token", token.as_str())).to_request()).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let response = test::call_service(&app, post().insert_header(("Post-Token", token.as_str())).to_request()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn task_lifecycle() {
+    let app = test::init_service(create_test_app()).await;
+
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Water the plants", "done": false }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = header(&response, "Location");
+    assert_eq!(location, "/v1/tasks/10");
+
+    let response = test::call_service(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = header(&response, "ETag");
+    let task: Value = test::read_body_json(response).await;
+    assert_eq!(task["text"], "Water the plants");
+    assert_eq!(task["done"], false);
+
+    let request = TestRequest::put().uri(&location)
+        .insert_header(("If-Match", etag.as_str()))
+        .set_json(json!({ "text": "Water the plants", "done": true }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(header(&response, "ETag"), etag);
+
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(task["done"], true);
+
+    let response = test::call_service(&app, TestRequest::delete().uri(&location).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test::call_service(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn writes_need_the_current_etag() {
+    let app = test::init_service(create_test_app()).await;
+    let put = || TestRequest::put().uri("/v1/journals/0").set_json(json!({ "title": "Title 0", "data": "Edited" }));
+
+    let response = test::call_service(&app, put().to_request()).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let response = test::call_service(&app, put().insert_header(("If-Match", "stale")).to_request()).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+    let response = test::call_service(&app, put().insert_header(("If-Match", "1")).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = header(&response, "ETag");
+
+    // the tag changed with the journal, so the old one no longer matches;
+    // the answer carries the journal as it is now, to merge into
+    let response = test::call_service(&app, put().insert_header(("If-Match", "1")).to_request()).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(header(&response, "ETag"), etag);
+    let conflict: Value = test::read_body_json(response).await;
+    assert_eq!((&conflict["detail"], &conflict["etag"]), (&json!("ETag does not match!"), &json!(etag)));
+    assert_eq!(conflict["current"]["data"], "Edited");
+
+    let patch = || TestRequest::patch().uri("/v1/tasks/0").set_json(json!({ "done": true }));
+    let response = test::call_service(&app, patch().to_request()).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+
+    let response = test::call_service(&app, patch().insert_header(("If-Match", "1")).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = header(&response, "ETag");
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/0").to_request()).await;
+    assert_eq!(header(&response, "ETag"), etag);
+}
+
+#[actix_web::test]
+async fn versions_count_the_writes() {
+    let app = test::init_service(create_test_app()).await;
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/4").to_request()).await;
+    assert_eq!(task["version"], 1);
+
+    // the version stands in for the ETag
+    let patch = |if_match: &str| TestRequest::patch().uri("/v1/tasks/4")
+        .insert_header(("If-Match", if_match))
+        .set_json(json!({ "text": "Do the 4 twice" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, patch("1")).await.status(), StatusCode::OK);
+    let request = TestRequest::put().uri("/v1/tasks/4")
+        .insert_header(("If-Match", "2"))
+        .set_json(json!({ "text": "Do the 4 thrice", "version": 40 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let response = test::call_service(&app, patch("2")).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let conflict: Value = test::read_body_json(response).await;
+    assert_eq!(conflict["current"]["version"], 3);
+
+    let request = TestRequest::post().uri("/v1/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "title": "New", "data": "Fresh" }))
+        .to_request();
+    let location = header(&test::call_service(&app, request).await, "Location");
+    let journal: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(journal["version"], 1);
+}
+
+#[actix_web::test]
+async fn listings_are_paginated() {
+    let app = test::init_service(create_test_app()).await;
+
+    let page: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks").to_request()).await;
+    assert_eq!(page["page"], 1);
+    assert_eq!(page["total_entries"], 10);
+    assert_eq!(page["total_pages"], 2);
+    assert_eq!(page["entries"].as_array().unwrap().len(), 5);
+
+    let request = TestRequest::get().uri("/v1/journals?page=3&per_page=4").to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(page["total_pages"], 3);
+    let titles: Vec<&str> = page["entries"].as_array().unwrap().iter()
+        .map(|journal| journal["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["Title 8", "Title 9"]);
+
+    let request = TestRequest::get().uri("/v1/journals?page=4&per_page=4").to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert!(page["entries"].as_array().unwrap().is_empty());
+}
+
+#[actix_web::test]
+async fn listings_are_not_modified_until_what_they_show_is() {
+    let app = test::init_service(create_test_app()).await;
+    let list = |etag: &str| TestRequest::get().uri("/v1/journals").insert_header(("If-None-Match", etag)).to_request();
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/journals").to_request()).await;
+    let etag = header(&response, "ETag");
+    assert_eq!(header(&response, "Vary"), "Accept");
+    assert_eq!(test::call_service(&app, list(&etag)).await.status(), StatusCode::NOT_MODIFIED);
+    // another format of the same listing is another representation
+    for accept in ["application/msgpack", "application/cbor", "application/vnd.api+json", "application/x-ndjson"] {
+        let request = TestRequest::get().uri("/v1/journals")
+            .insert_header(("Accept", accept))
+            .insert_header(("If-None-Match", etag.as_str()))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_ne!(header(&response, "ETag"), etag);
+    }
+
+    // published without another write, the listing changes all the same
+    let publish_at = chrono::Utc::now() + chrono::Duration::milliseconds(500);
+    let request = TestRequest::put().uri("/v1/journals/4")
+        .insert_header(("If-Match", "1"))
+        .set_json(json!({ "title": "Title 4", "data": "Soon", "publish_at": publish_at }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let response = test::call_service(&app, list(&etag)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = header(&response, "ETag");
+    let page: Value = test::read_body_json(response).await;
+    assert_eq!(page["total_entries"], 9);
+    assert_eq!(test::call_service(&app, list(&etag)).await.status(), StatusCode::NOT_MODIFIED);
+
+    actix_web::rt::time::sleep(std::time::Duration::from_millis(600)).await;
+    let response = test::call_service(&app, list(&etag)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let page: Value = test::read_body_json(response).await;
+    assert_eq!(page["total_entries"], 10);
+}
+
+#[actix_web::test]
+async fn completed_tasks_are_listed_by_completion_time() {
+    let app = test::init_service(create_test_app()).await;
+    let before = chrono::Utc::now();
+    let uri = format!("/v1/tasks?completed_after={}", before.to_rfc3339_opts(chrono::SecondsFormat::Micros, true));
+
+    let complete = |id| TestRequest::patch().uri(&format!("/v1/tasks/{}", id))
+        .insert_header(("If-Match", "1"))
+        .set_json(json!({ "done": true }))
+        .to_request();
+    for id in [2, 5] {
+        assert_eq!(test::call_service(&app, complete(id)).await.status(), StatusCode::OK);
+    }
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/2").to_request()).await;
+    let completed_at = String::from(task["completed_at"].as_str().unwrap());
+
+    let page: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&uri).to_request()).await;
+    assert_eq!(page["total_entries"], 2);
+    assert_eq!(page["entries"][0]["text"], "Do the 2");
+
+    // editing a done task keeps the time, reopening it clears it
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/2").to_request()).await;
+    let request = TestRequest::put().uri("/v1/tasks/2")
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "text": "Do the 2 again", "done": true }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/2").to_request()).await;
+    assert_eq!(task["completed_at"], completed_at.as_str());
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/5").to_request()).await;
+    let request = TestRequest::patch().uri("/v1/tasks/5")
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "done": false }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/5").to_request()).await;
+    assert!(task.get("completed_at").is_none());
+
+    let page: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&uri).to_request()).await;
+    assert_eq!(page["total_entries"], 1);
+    let request = TestRequest::get().uri("/v1/tasks?completed_after=last-week").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn merging_tasks() {
+    let app = test::init_service(create_test_app()).await;
+    let merge = json!({ "ids": [2, 3], "separator": " + " });
+
+    // a preview needs no token and changes nothing
+    let request = TestRequest::post().uri("/v1/task_merger?dry_run=true").set_json(&merge).to_request();
+    let preview: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(preview["text"], "Do the 2 + Do the 3");
+    assert_eq!(preview["deleted"].as_array().unwrap().len(), 2);
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/2").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = TestRequest::post().uri("/v1/task_merger")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(&merge)
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = header(&response, "Location");
+
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(task["text"], "Do the 2 + Do the 3");
+    for source in ["/v1/tasks/2", "/v1/tasks/3"] {
+        let response = test::call_service(&app, TestRequest::get().uri(source).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
+
+#[actix_web::test]
+async fn tasks_are_tagged_in_bulk() {
+    let app = test::init_service(create_test_app()).await;
+    let bulk = |body: Value, token: String| TestRequest::post().uri("/v1/tasks/bulk_tag")
+        .insert_header(("Post-Token", token))
+        .set_json(body)
+        .to_request();
+    let response = test::call_service(&app, bulk(json!({ "ids": [1, 2, 99], "add": ["home", "later"] }), token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let results: Value = test::read_body_json(response).await;
+    assert_eq!(results[0]["tags"], json!(["home", "later"]));
+    assert_eq!(results[0]["changed"], true);
+    assert_eq!(results[2], json!({ "id": 99, "status": 404, "changed": false }));
+
+    let response = test::call_service(&app, bulk(json!({ "ids": [1, 2], "add": ["home"], "remove": ["later"] }), token(&app).await)).await;
+    let results: Value = test::read_body_json(response).await;
+    assert_eq!(results[1]["tags"], json!(["home"]));
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/2").to_request()).await;
+    assert_eq!(header(&response, "ETag"), results[1]["etag"].as_str().unwrap());
+
+    // invalid requests change nothing at all
+    for body in [json!({ "ids": [1, 1], "add": ["x"] }), json!({ "ids": [1], "add": [""] }), json!({ "ids": [1], "add": ["x"], "remove": ["x"] })] {
+        let response = test::call_service(&app, bulk(body, token(&app).await)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    assert_eq!(task["tags"], json!(["home"]));
+}
+
+#[actix_web::test]
+async fn entries_are_cloned() {
+    let app = test::init_service(create_test_app()).await;
+    let clone = |uri: &str, body: Option<Value>, token: String| {
+        let request = TestRequest::post().uri(uri).insert_header(("Post-Token", token));
+        return match body {
+            Some(body)  => request.set_json(body).to_request(),
+            None        => request.to_request(),
+        };
+    };
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Weekly review", "done": true, "due": "2026-01-05T09:00:00Z", "tags": ["work"] }))
+        .to_request();
+    let location = header(&test::call_service(&app, request).await, "Location");
+
+    let body = json!({ "suffix": " (next week)", "clear_done": true, "shift_due_days": 7 });
+    let response = test::call_service(&app, clone(&format!("{}/clone", location), Some(body), token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let copy = header(&response, "Location");
+    assert_ne!(copy, location);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&copy).to_request()).await;
+    assert_eq!((&task["text"], &task["done"], &task["status"]), (&json!("Weekly review (next week)"), &json!(false), &json!("todo")));
+    assert_eq!((&task["due"], &task["tags"]), (&json!("2026-01-12T09:00:00Z"), &json!(["work"])));
+    let original: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(original["done"], true);
+
+    let response = test::call_service(&app, clone("/v1/journals/3/clone", None, token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let journal: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&header(&response, "Location")).to_request()).await;
+    assert_eq!((&journal["title"], &journal["data"]), (&json!("Title 3"), &json!("Hello World!")));
+
+    let response = test::call_service(&app, clone("/v1/journals/42/clone", None, token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body = json!({ "shift_due_days": i64::MAX });
+    let response = test::call_service(&app, clone(&format!("{}/clone", location), Some(body), token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn tasks_are_moved_in_the_manual_order() {
+    let app = test::init_service(create_test_app()).await;
+    let move_task = |id: usize, body: Value, token: String| TestRequest::post().uri(&format!("/v1/tasks/{}/move", id))
+        .insert_header(("Post-Token", token))
+        .set_json(body)
+        .to_request();
+    let order = |uri: &str| TestRequest::get().uri(uri).to_request();
+
+    let response = test::call_service(&app, move_task(9, json!({ "before": 0 }), token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let moved: Value = test::read_body_json(response).await;
+    assert_eq!(moved, json!({ "id": 9, "position": -1024 }));
+    let response = test::call_service(&app, move_task(5, json!({ "after": 9 }), token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let page: Value = test::call_and_read_body_json(&app, order("/v1/tasks?sort=position&per_page=4")).await;
+    let texts: Vec<&str> = page["entries"].as_array().unwrap().iter().map(|task| task["text"].as_str().unwrap()).collect();
+    assert_eq!(texts, ["Do the 9", "Do the 5", "Do the 0", "Do the 1"]);
+
+    // halving the gap between the same two neighbours runs out of room,
+    // then every task is renumbered in the new order
+    for round in 0..12 {
+        let id = if round % 2 == 0 { 1 } else { 2 };
+        let response = test::call_service(&app, move_task(id, json!({ "after": 0 }), token(&app).await)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let page: Value = test::call_and_read_body_json(&app, order("/v1/tasks?sort=position&per_page=6")).await;
+    let entries = page["entries"].as_array().unwrap();
+    let texts: Vec<&str> = entries.iter().map(|task| task["text"].as_str().unwrap()).collect();
+    assert_eq!(texts, ["Do the 9", "Do the 5", "Do the 0", "Do the 2", "Do the 1", "Do the 3"]);
+    assert!(entries.windows(2).all(|pair| pair[1]["position"].as_i64() > pair[0]["position"].as_i64()));
+
+    for (id, body, status) in [
+        (3, json!({ "before": 3 }), StatusCode::BAD_REQUEST),
+        (3, json!({ "before": 4, "after": 5 }), StatusCode::BAD_REQUEST),
+        (3, json!({ "after": 42 }), StatusCode::NOT_FOUND),
+        (42, json!({ "after": 3 }), StatusCode::NOT_FOUND),
+    ] {
+        let response = test::call_service(&app, move_task(id, body, token(&app).await)).await;
+        assert_eq!(response.status(), status);
+    }
+    let response = test::call_service(&app, order("/v1/journals?sort=position")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // tasks put under new ids go last, like posted ones
+    for id in [20, 21] {
+        let request = TestRequest::put().uri(&format!("/v1/tasks/{}", id))
+            .set_json(json!({ "text": format!("Do the {}", id) }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    }
+    let page: Value = test::call_and_read_body_json(&app, order("/v1/tasks?sort=position&page=2&per_page=6")).await;
+    let entries = page["entries"].as_array().unwrap();
+    let texts: Vec<&str> = entries.iter().map(|task| task["text"].as_str().unwrap()).collect();
+    assert_eq!(&texts[texts.len() - 2..], ["Do the 20", "Do the 21"]);
+    assert!(entries.windows(2).all(|pair| pair[1]["position"].as_i64() > pair[0]["position"].as_i64()));
+}
+
+#[actix_web::test]
+async fn daily_journals_are_created_once() {
+    let app = test::init_service(create_test_app()).await;
+    let today = || TestRequest::get().uri("/v1/journals/today?date=2026-10-16").to_request();
+    let response = test::call_service(&app, today()).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert_eq!(header(&response, "Location"), "/v1/journals/10");
+    let journal: Value = test::read_body_json(response).await;
+    assert_eq!((&journal["id"], &journal["title"], &journal["day"]), (&json!(10), &json!("2026-10-16"), &json!("2026-10-16")));
+
+    // edits keep it the day's entry
+    let request = TestRequest::put().uri("/v1/journals/10")
+        .insert_header(("If-Match", header(&test::call_service(&app, today()).await, "ETag")))
+        .set_json(json!({ "title": "A good Friday", "data": "Sunny" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let response = test::call_service(&app, today()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let journal: Value = test::read_body_json(response).await;
+    assert_eq!((&journal["id"], &journal["title"]), (&json!(10), &json!("A good Friday")));
+}
+
+#[actix_web::test]
+async fn rejected_merges_change_nothing() {
+    let app = test::init_service(create_test_app()).await;
+
+    for (ids, status) in [(json!([4, 4]), StatusCode::CONFLICT), (json!([4, 42]), StatusCode::NOT_FOUND)] {
+        let request = TestRequest::post().uri("/v1/task_merger")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "ids": ids }))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), status);
+    }
+
+    let page: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks").to_request()).await;
+    assert_eq!(page["total_entries"], 10);
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/4").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn encrypted_journals_stay_opaque() {
+    let app = test::init_service(create_test_app()).await;
+    let journal = json!({
+        "title": "",
+        "data": "bm90IG1hcmtkb3du",
+        "encrypted": true,
+        "metadata": { "key_id": "k1", "nonce": "AAEC" },
+    });
+    let request = TestRequest::post().uri("/v1/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(&journal)
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = header(&response, "Location");
+    assert_eq!(location, "/v1/journals/10");
+
+    let response = test::call_service(&app, TestRequest::get().uri(&location).to_request()).await;
+    let etag = header(&response, "ETag");
+    let stored: Value = test::read_body_json(response).await;
+    assert_eq!(stored["data"], journal["data"]);
+    assert_eq!(stored["metadata"], journal["metadata"]);
+
+    // neither rendered nor merged
+    let response = test::call_service(&app, TestRequest::get().uri(&format!("{}.md", location)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let request = TestRequest::get().uri("/v1/journals/export.md").to_request();
+    let document = test::call_and_read_body(&app, request).await;
+    assert!(!String::from_utf8(document.to_vec()).unwrap().contains("bm90IG1hcmtkb3du"));
+    let request = TestRequest::post().uri("/v1/journal_merger")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "ids": [0, 10], "etags": ["1", etag] }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+}
+
+#[actix_web::test]
+async fn moods_are_checked_and_averaged() {
+    let app = test::init_service(create_test_app()).await;
+    for rating in [json!({ "mood": 6 }), json!({ "energy": 0 }), json!({ "sleep_hours": 25.5 })] {
+        let mut journal = json!({ "title": "Today", "data": "" });
+        journal.as_object_mut().unwrap().extend(rating.as_object().unwrap().clone());
+        let request = TestRequest::post().uri("/v1/journals")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(journal)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let day = |created_at: &str, mood: u8, sleep: f64| json!({
+        "id": 0, "title": "Day", "data": "", "mood": mood, "sleep_hours": sleep,
+        "created_at": created_at, "updated_at": created_at,
+    });
+    let takeout = json!({
+        "version": 1,
+        "exported_at": "2026-10-20T00:00:00Z",
+        "journals": [
+            day("2026-10-12T21:00:00Z", 2, 6.0),
+            day("2026-10-14T21:00:00Z", 4, 7.5),
+            day("2026-10-19T21:00:00Z", 5, 8.0),
+        ],
+        "tasks": [],
+    });
+    let request = TestRequest::post().uri("/v1/import")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(takeout)
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let request = TestRequest::get().uri("/v1/stats/mood?to=2026-10-31").to_request();
+    let trend: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(trend, json!([
+        { "start": "2026-10-12", "entries": 2, "mood": 3.0, "energy": null, "sleep_hours": 6.75 },
+        { "start": "2026-10-19", "entries": 1, "mood": 5.0, "energy": null, "sleep_hours": 8.0 },
+    ]));
+    let request = TestRequest::get().uri("/v1/stats/mood?period=month&from=2026-10-13&to=2026-10-31").to_request();
+    let trend: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(trend, json!([{ "start": "2026-10-01", "entries": 2, "mood": 4.5, "energy": null, "sleep_hours": 7.75 }]));
+}
+
+#[actix_web::test]
+async fn journals_are_found_near_a_place() {
+    let app = test::init_service(create_test_app()).await;
+    for location in [json!({ "lat": 91.0, "lon": 0.0 }), json!({ "lat": 38.7 }), json!({ "place": " " })] {
+        let mut journal = json!({ "title": "Somewhere", "data": "" });
+        journal.as_object_mut().unwrap().extend(location.as_object().unwrap().clone());
+        let request = TestRequest::post().uri("/v1/journals")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(journal)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let trips = [
+        json!({ "title": "Lisbon", "data": "Trams", "lat": 38.7223, "lon": -9.1393, "place": "Lisbon" }),
+        json!({ "title": "Sintra", "data": "Palaces", "lat": 38.8029, "lon": -9.3817 }),
+        json!({ "title": "Porto", "data": "Bridges", "lat": 41.1579, "lon": -8.6291 }),
+    ];
+    for trip in trips {
+        let request = TestRequest::post().uri("/v1/journals")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(trip)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+
+    let request = TestRequest::get().uri("/v1/journals?near=38.72,-9.14,30").to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    let titles: Vec<&str> = page["entries"].as_array().unwrap().iter()
+        .map(|journal| journal["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["Lisbon", "Sintra"]);
+    let request = TestRequest::get().uri("/v1/journals?near=38.72,-9.14").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+    let markdown = test::call_and_read_body(&app, TestRequest::get().uri("/v1/journals/10.md").to_request()).await;
+    assert_eq!(String::from_utf8(markdown.to_vec()).unwrap(), "# Lisbon\n\n_Lisbon (38.7223, -9.1393)_\n\nTrams\n");
+}
+
+#[actix_web::test]
+async fn writing_is_counted_per_period() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "title": "Long day", "data": "Wrote  three\nwords" }))
+        .to_request();
+    let location = header(&test::call_service(&app, request).await, "Location");
+    let journal: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(journal["word_count"], 3);
+    assert_eq!(journal["char_count"], 18);
+
+    // ten sample journals of two words each, and this one, all written today
+    let request = TestRequest::get().uri("/v1/stats/writing?period=month&goal=50").to_request();
+    let written: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(written.as_array().unwrap().len(), 1);
+    assert_eq!(written[0]["entries"], 11);
+    assert_eq!(written[0]["words"], 23);
+    assert_eq!(written[0]["goal_progress"], 0.46);
+    // no further than reached
+    let request = TestRequest::get().uri("/v1/stats/writing?period=month&goal=10").to_request();
+    let written: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(written[0]["goal_progress"], 1.0);
+
+    let request = TestRequest::get().uri("/v1/stats/writing?goal=0").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn responses_link_to_related_resources_and_pages() {
+    let app = test::init_service(create_test_app()).await;
+    let journal: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals/2").to_request()).await;
+    assert_eq!(journal["_links"], json!({
+        "self":         { "href": "/v1/journals/2" },
+        "collection":   { "href": "/v1/journals" },
+        "attachments":  { "href": "/v1/journals/2/attachments" },
+        "bookmarks":    { "href": "/v1/journals/2/bookmarks" },
+        "related":      { "href": "/v1/journals/2/related" },
+    }));
+
+    let listing: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/tasks?page=1&per_page=5").to_request()).await;
+    assert_eq!(listing["_links"], json!({
+        "self":     { "href": "/tasks?per_page=5&page=1" },
+        "first":    { "href": "/tasks?per_page=5&page=1" },
+        "last":     { "href": "/tasks?per_page=5&page=2" },
+        "next":     { "href": "/tasks?per_page=5&page=2" },
+    }));
+    assert_eq!(listing["entries"][4]["_links"]["self"]["href"], "/tasks/4");
+    assert_eq!(listing["entries"][4]["_links"]["time"]["href"], "/tasks/4/time");
+}
+
+#[actix_web::test]
+async fn links_are_absolute_behind_a_base_url() {
+    std::env::set_var("JOURNAL_BASE_URL", "https://example.com/journal/");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Water the plants" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(header(&response, "Location"), "https://example.com/journal/v1/tasks/10");
+
+    let request = TestRequest::post().uri("/v1/journals/1/share")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert!(header(&response, "Location").starts_with("https://example.com/journal/shared/"));
+
+    let journal: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals/1").to_request()).await;
+    assert_eq!(journal["_links"]["self"]["href"], "https://example.com/journal/v1/journals/1");
+    let feed = test::call_and_read_body(&app, TestRequest::get().uri("/v1/journals/feed.atom").to_request()).await;
+    assert!(String::from_utf8(feed.to_vec()).unwrap().contains("https://example.com/journal/journals/1"));
+}
+
+#[actix_web::test]
+async fn expired_entries_are_not_listed() {
+    let app = test::init_service(create_test_app()).await;
+    for expires_at in ["2000-01-01T00:00:00Z", "2999-01-01T00:00:00Z"] {
+        let request = TestRequest::post().uri("/v1/tasks")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "text": "Scratch", "expires_at": expires_at }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+    let listing: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks?per_page=20").to_request()).await;
+    assert_eq!(listing["total_entries"], 11);
+    let links: Vec<&Value> = listing["entries"].as_array().unwrap().iter().map(|task| &task["_links"]["self"]["href"]).collect();
+    assert!(!links.contains(&&json!("/v1/tasks/10")) && links.contains(&&json!("/v1/tasks/11")));
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/11").to_request()).await;
+    assert_eq!(task["expires_at"], "2999-01-01T00:00:00Z");
+}
@@ -0,0 +1,143 @@
+#![allow(clippy::needless_return)]
+// Attachments of journals, uploaded in chunks
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use rest::{app, create_test_app, Config, State};
+
+mod common;
+use common::{header, token};
+
+// of "hello world"
+const HELLO_SHA256: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+#[actix_web::test]
+async fn uploads_resume_where_they_broke_off() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/journals/1/uploads")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "name": "notes.txt", "content_type": "text/plain", "length": 11 }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let upload = header(&response, "Location");
+    let chunk = |offset: usize, data: &'static str| TestRequest::patch().uri(&upload)
+        .insert_header(("Upload-Offset", offset.to_string()))
+        .set_payload(data)
+        .to_request();
+
+    let response = test::call_service(&app, chunk(0, "hello ")).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(header(&response, "Upload-Offset"), "6");
+    // the chunks wait in a file of their own, not in memory
+    let spooled = std::env::temp_dir()
+        .join(format!("journal-uploads-{}", std::process::id()))
+        .join(upload.rsplit('/').next().unwrap());
+    assert_eq!(std::fs::read(&spooled).unwrap(), b"hello ");
+    // the same chunk again, its answer having been lost
+    let response = test::call_service(&app, chunk(0, "hello ")).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    assert_eq!(header(&response, "Upload-Offset"), "6");
+    let progress: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&upload).to_request()).await;
+    assert_eq!((&progress["offset"], &progress["length"]), (&json!(6), &json!(11)));
+
+    let finalize = || TestRequest::post().uri(&format!("{}/finalize", upload)).to_request();
+    assert_eq!(test::call_service(&app, finalize()).await.status(), StatusCode::CONFLICT);
+    assert_eq!(test::call_service(&app, chunk(6, "world!")).await.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(test::call_service(&app, chunk(6, "world")).await.status(), StatusCode::NO_CONTENT);
+    let response = test::call_service(&app, finalize()).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = header(&response, "Location");
+    assert_eq!(location, "/v1/journals/1/attachments/0");
+    assert_eq!(test::call_service(&app, TestRequest::get().uri(&upload).to_request()).await.status(), StatusCode::NOT_FOUND);
+    assert!(!spooled.exists());
+
+    let response = test::call_service(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(header(&response, "Content-Type"), "text/plain");
+    assert_eq!(test::read_body(response).await, "hello world");
+    let request = TestRequest::get().uri(&location).insert_header(("Range", "bytes=6-")).to_request();
+    assert_eq!(test::call_and_read_body(&app, request).await, "world");
+    let listed: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals/1/attachments").to_request()).await;
+    assert_eq!(listed, json!([{ "id": 0, "name": "notes.txt", "content_type": "text/plain", "length": 11,
+        "sha256": HELLO_SHA256, "created_at": listed[0]["created_at"] }]));
+
+    // the files of a journal go with it
+    assert_eq!(test::call_service(&app, TestRequest::delete().uri("/v1/journals/1").to_request()).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, TestRequest::get().uri(&location).to_request()).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn known_content_is_attached_without_uploading() {
+    let app = test::init_service(create_test_app()).await;
+    let start = |sha256: &str| TestRequest::post().uri("/v1/journals/1/uploads")
+        .set_json(json!({ "name": "notes.txt", "length": 11, "sha256": sha256 }));
+    let upload = header(&test::call_service(&app, start(HELLO_SHA256).insert_header(("Post-Token", token(&app).await)).to_request()).await, "Location");
+    let request = TestRequest::patch().uri(&upload).insert_header(("Upload-Offset", "0")).set_payload("hello world").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NO_CONTENT);
+    let attachment: Value = test::call_and_read_body_json(&app, TestRequest::post().uri(&format!("{}/finalize", upload)).to_request()).await;
+    assert_eq!(attachment["sha256"], HELLO_SHA256);
+
+    // announced, but something else arrived
+    let upload = header(&test::call_service(&app, start(&"0".repeat(64)).insert_header(("Post-Token", token(&app).await)).to_request()).await, "Location");
+    let request = TestRequest::patch().uri(&upload).insert_header(("Upload-Offset", "0")).set_payload("hello world").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NO_CONTENT);
+    let response = test::call_service(&app, TestRequest::post().uri(&format!("{}/finalize", upload)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let attach = |sha256: &str| TestRequest::post().uri("/v1/journals/2/attachments")
+        .set_json(json!({ "name": "copy.txt", "sha256": sha256 }));
+    let response = test::call_service(&app, attach(&"0".repeat(64)).insert_header(("Post-Token", token(&app).await)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = test::call_service(&app, attach(HELLO_SHA256).insert_header(("Post-Token", token(&app).await)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let copy = header(&response, "Location");
+
+    // the content stays while an attachment uses it
+    assert_eq!(test::call_service(&app, TestRequest::delete().uri("/v1/journals/1").to_request()).await.status(), StatusCode::OK);
+    let response = test::call_service(&app, TestRequest::get().uri(&copy).to_request()).await;
+    assert_eq!(header(&response, "ETag"), HELLO_SHA256);
+    assert_eq!(test::read_body(response).await, "hello world");
+    assert_eq!(test::call_service(&app, TestRequest::delete().uri(&copy).to_request()).await.status(), StatusCode::OK);
+    let response = test::call_service(&app, attach(HELLO_SHA256).insert_header(("Post-Token", token(&app).await)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+// every file below `dir`
+fn files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    return entries.flatten()
+        .flat_map(|entry| if entry.path().is_dir() { files(&entry.path()) } else { vec![entry.path()] })
+        .collect();
+}
+
+#[actix_web::test]
+async fn content_is_kept_in_the_blob_directory() {
+    let dir = std::env::temp_dir().join(format!("journal-blobs-{}", std::process::id()));
+    std::env::set_var("JOURNAL_BLOB_DIR", &dir);
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    let request = TestRequest::post().uri("/v1/journals/1/uploads")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "name": "notes.txt", "length": 11 }))
+        .to_request();
+    let upload = header(&test::call_service(&app, request).await, "Location");
+    let request = TestRequest::patch().uri(&upload).insert_header(("Upload-Offset", "0")).set_payload("hello world").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NO_CONTENT);
+    let response = test::call_service(&app, TestRequest::post().uri(&format!("{}/finalize", upload)).to_request()).await;
+    let location = header(&response, "Location");
+
+    let stored = files(&dir);
+    assert_eq!(stored.len(), 1);
+    assert!(stored[0].ends_with(HELLO_SHA256));
+    assert_eq!(std::fs::read(&stored[0]).unwrap(), b"hello world");
+    let request = TestRequest::get().uri(&location).insert_header(("Range", "bytes=0-4")).to_request();
+    assert_eq!(test::call_and_read_body(&app, request).await, "hello");
+
+    assert_eq!(test::call_service(&app, TestRequest::delete().uri(&location).to_request()).await.status(), StatusCode::OK);
+    assert!(files(&dir).is_empty());
+    std::fs::remove_dir_all(&dir).ok();
+}
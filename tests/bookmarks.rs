@@ -0,0 +1,122 @@
+#![allow(clippy::needless_return)]
+// Bookmarks, linked from journals, with their titles fetched from the page
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::{web, HttpResponse, HttpServer};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use rest::{app, create_test_app, spawn_background, Config, State};
+
+mod common;
+use common::{header, token};
+
+#[actix_web::test]
+async fn bookmarks_are_listed_with_the_journals_linking_to_them() {
+    let app = test::init_service(create_test_app()).await;
+    for journals in [json!([2]), json!([2, 5]), json!([])] {
+        let request = TestRequest::post().uri("/v1/bookmarks")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "url": "https://example.com/post", "title": "A post", "journals": journals }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+    let journal: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals/2").to_request()).await;
+    let linked = journal["_links"]["bookmarks"]["href"].as_str().unwrap();
+    let bookmarks: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(linked).to_request()).await;
+    let ids: Vec<&Value> = bookmarks.as_array().unwrap().iter().map(|bookmark| &bookmark["id"]).collect();
+    assert_eq!(ids, [&json!(0), &json!(1)]);
+    assert_eq!(bookmarks[0]["title"], "A post");
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/journals/42/bookmarks").to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    // only journals that are there can link to one
+    let request = TestRequest::post().uri("/v1/bookmarks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "url": "https://example.com/post", "journals": [2, 42] }))
+        .to_request();
+    let problem: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!((&problem["status"], &problem["detail"]), (&json!(400), &json!("Journal 42 not found")));
+    let listing: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/bookmarks").to_request()).await;
+    assert_eq!(listing["total_entries"], 3);
+}
+
+#[actix_web::test]
+async fn bookmarks_need_a_web_url() {
+    let app = test::init_service(create_test_app()).await;
+    for url in ["example.com", "ftp://example.com/file", "mailto:me@example.com", "https://"] {
+        let request = TestRequest::post().uri("/v1/bookmarks")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "url": url }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST, "{}", url);
+    }
+    let request = TestRequest::post().uri("/v1/bookmarks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "url": "http://localhost:8080/read?later=1", "description": " " }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn titles_are_fetched_for_bookmarks_saved_without_one() {
+    static FETCHES: AtomicUsize = AtomicUsize::new(0);
+    let page = HttpServer::new(|| actix_web::App::new().route("/", web::get().to(|| async {
+        FETCHES.fetch_add(1, Ordering::SeqCst);
+        HttpResponse::Ok().content_type("text/html").body("<html><head><TITLE>\n  Fish &amp; chips\n</TITLE></head></html>")
+    })))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let url = format!("http://{}/", page.addrs()[0]);
+    actix_web::rt::spawn(page.run());
+
+    std::env::set_var("JOURNAL_FETCH_TITLES", "true");
+    // the page is on loopback
+    std::env::set_var("JOURNAL_FETCH_PRIVATE", "true");
+    let state = web::Data::new(State::with_sample_data(Config::from_env()));
+    spawn_background(&state);
+    let app = test::init_service(app(state)).await;
+    let request = TestRequest::post().uri("/v1/bookmarks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "url": url }))
+        .to_request();
+    let bookmark = header(&test::call_service(&app, request).await, "Location");
+    let request = TestRequest::post().uri("/v1/bookmarks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "url": url, "title": "Mine" }))
+        .to_request();
+    let titled = header(&test::call_service(&app, request).await, "Location");
+
+    let mut fetched = Value::Null;
+    for _ in 0..50 {
+        fetched = test::call_and_read_body_json(&app, TestRequest::get().uri(&bookmark).to_request()).await;
+        if !fetched["title"].is_null() {
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(fetched["title"], "Fish & chips");
+    assert_eq!(fetched["version"], 2);
+    let kept: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&titled).to_request()).await;
+    assert_eq!((&kept["title"], &kept["version"]), (&json!("Mine"), &json!(1)));
+
+    // only public addresses are fetched from otherwise
+    std::env::remove_var("JOURNAL_FETCH_PRIVATE");
+    let state = web::Data::new(State::with_sample_data(Config::from_env()));
+    spawn_background(&state);
+    let app = test::init_service(rest::app(state)).await;
+    let fetches = FETCHES.load(Ordering::SeqCst);
+    for url in [url.clone(), url.replace("127.0.0.1", "localhost")] {
+        let request = TestRequest::post().uri("/v1/bookmarks")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "url": url }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+    actix_web::rt::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(FETCHES.load(Ordering::SeqCst), fetches);
+    let listing: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/bookmarks").to_request()).await;
+    assert!(listing["entries"].as_array().unwrap().iter().all(|bookmark| bookmark["title"].is_null()));
+}
@@ -0,0 +1,86 @@
+#![allow(clippy::needless_return)]
+// The CalDAV surface of the tasks
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token};
+
+fn vtodo(summary: &str) -> String {
+    return format!("BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nSUMMARY:{}\r\nSTATUS:NEEDS-ACTION\r\nEND:VTODO\r\nEND:VCALENDAR\r\n", summary);
+}
+
+#[actix_web::test]
+async fn caldav_puts_are_checked_like_rest_ones() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::put().uri("/v1/schema/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "type": "object", "required": ["priority"] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    let etag = header(&test::call_service(&app, TestRequest::get().uri("/caldav/tasks/1.ics").to_request()).await, "ETag");
+
+    let request = TestRequest::put().uri("/caldav/tasks/1.ics")
+        .insert_header(("If-Match", etag.as_str()))
+        .set_payload(vtodo("Water the plants"))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    let request = TestRequest::put().uri("/v1/tasks/1")
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "text": "Task 1", "metadata": { "priority": 1 } }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let etag = header(&test::call_service(&app, TestRequest::get().uri("/caldav/tasks/1.ics").to_request()).await, "ETag");
+
+    let request = TestRequest::put().uri("/caldav/tasks/1.ics")
+        .insert_header(("If-Match", etag.as_str()))
+        .set_payload(vtodo("Water the plants"))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let etag = header(&response, "ETag");
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    assert_eq!(format!("\"{}\"", header(&response, "ETag")), etag);
+    let task: Value = test::read_body_json(response).await;
+    assert_eq!(task["text"], "Water the plants");
+}
+
+#[actix_web::test]
+async fn caldav_puts_need_a_precondition() {
+    let app = test::init_service(create_test_app()).await;
+    let put = |uri: &str, header: Option<(&str, &str)>, summary: &str| {
+        let request = TestRequest::put().uri(uri).set_payload(vtodo(summary));
+        match header {
+            Some(header)    => request.insert_header(header).to_request(),
+            None            => request.to_request(),
+        }
+    };
+
+    let response = test::call_service(&app, put("/caldav/tasks/1.ics", None, "Blind")).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+    let response = test::call_service(&app, put("/caldav/tasks/1.ics", Some(("If-Match", "\"stale\"")), "Stale")).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = test::call_service(&app, put("/caldav/tasks/1.ics", Some(("If-None-Match", "*")), "Again")).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    assert_eq!(task["text"], "Do the 1");
+    let response = test::call_service(&app, put("/caldav/tasks/1.ics", Some(("If-Match", "*")), "Any version")).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+    let response = test::call_service(&app, put("/caldav/tasks/42.ics", None, "New")).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+    let response = test::call_service(&app, put("/caldav/tasks/42.ics", Some(("If-Match", "*")), "New")).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = test::call_service(&app, put("/caldav/tasks/42.ics", Some(("If-None-Match", "*")), "New")).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let etag = header(&response, "ETag");
+    let response = test::call_service(&app, TestRequest::get().uri("/caldav/tasks/42.ics").to_request()).await;
+    assert_eq!(header(&response, "ETag"), etag);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/42").to_request()).await;
+    assert_eq!((task["text"].clone(), task["status"].clone()), (json!("New"), json!("todo")));
+}
@@ -0,0 +1,55 @@
+#![cfg(feature = "client")]
+#![allow(clippy::needless_return)]
+// The client against a real server on an ephemeral port, over the sample data
+use actix_web::{web, HttpServer};
+
+use rest::client::JournalClient;
+use rest::{app, Config, State};
+
+fn serve() -> JournalClient {
+    let state = web::Data::new(State::with_sample_data(Config::default()));
+    let server = HttpServer::new(move || app(state.clone()))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let address = server.addrs()[0];
+    actix_web::rt::spawn(server.run());
+    return JournalClient::new(&format!("http://{}", address));
+}
+
+#[actix_web::test]
+async fn tasks_round_trip_their_etag() {
+    let client = serve();
+
+    let mut task = client.create_task("buy milk").await.unwrap();
+    assert_eq!(task.id, 10);
+    assert_eq!(task.resource.text, "buy milk");
+    assert!(!task.resource.done);
+
+    let stale = task.clone();
+    task.resource.done = true;
+    let task = client.update_task(&task).await.unwrap();
+    assert!(task.resource.done);
+    assert_ne!(task.etag, stale.etag);
+    assert!(task.resource.updated_at >= task.resource.created_at);
+
+    let err = client.update_task(&stale).await.unwrap_err();
+    assert!(err.is_stale());
+
+    client.delete_task(task.id).await.unwrap();
+    assert!(client.get_task(task.id).await.is_err());
+}
+
+#[actix_web::test]
+async fn merging_and_journals() {
+    let client = serve();
+
+    let merged = client.merge_tasks(&[1, 2]).await.unwrap();
+    assert_eq!(merged.resource.text, "Do the 1\nDo the 2");
+    assert!(client.get_task(1).await.is_err());
+
+    let journal = client.get_journal(3).await.unwrap();
+    assert_eq!(journal.resource.title, "Title 3");
+    let created = client.create_journal("Today", "It rained").await.unwrap();
+    assert_eq!(client.get_journal(created.id).await.unwrap().resource.data, "It rained");
+}
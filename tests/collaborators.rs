@@ -0,0 +1,132 @@
+#![allow(clippy::needless_return)]
+// Collaborators: read or write access to single journals of a workspace
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token, workspace};
+
+#[actix_web::test]
+async fn collaborators_reach_only_their_journals() {
+    let app = test::init_service(create_test_app()).await;
+    let (id, owner_key) = workspace(&app, "couple").await;
+    let root = format!("/v1/workspaces/{}", id);
+    for title in ["Ours", "Mine"] {
+        let request = TestRequest::post().uri(&format!("{}/journals", root))
+            .insert_header(("Post-Token", token(&app).await))
+            .insert_header(("Workspace-Key", owner_key.as_str()))
+            .set_json(json!({ "title": title, "data": "" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+
+    let request = TestRequest::post().uri(&format!("{}/journals/0/collaborators", root))
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .set_json(json!({ "name": "bob" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let bob: Value = test::read_body_json(response).await;
+    let bob_key = String::from(bob["key"].as_str().unwrap());
+    let as_bob = |request: TestRequest| request.insert_header(("Workspace-Key", bob_key.as_str())).to_request();
+
+    // reading the granted journal only
+    let response = test::call_service(&app, as_bob(TestRequest::get().uri(&format!("{}/journals/0", root)))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = header(&response, "ETag");
+    let response = test::call_service(&app, as_bob(TestRequest::get().uri(&format!("{}/journals/1", root)))).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let page: Value = test::call_and_read_body_json(&app, as_bob(TestRequest::get().uri(&format!("{}/journals", root)))).await;
+    assert_eq!(page["total_entries"], 1);
+    assert_eq!(page["entries"][0]["title"], "Ours");
+    let response = test::call_service(&app, as_bob(TestRequest::get().uri(&format!("{}/tasks", root)))).await;
+    let page: Value = test::read_body_json(response).await;
+    assert_eq!(page["total_entries"], 0);
+
+    let edit = || TestRequest::put().uri(&format!("{}/journals/0", root))
+        .insert_header(("If-Match", etag.as_str()))
+        .set_json(json!({ "title": "Ours", "data": "Edited" }));
+    let response = test::call_service(&app, as_bob(edit())).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // granting write access again keeps the key
+    let request = TestRequest::post().uri(&format!("{}/journals/0/collaborators", root))
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .set_json(json!({ "name": "bob", "access": "write" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let again: Value = test::read_body_json(response).await;
+    assert_eq!(again["key"], bob["key"]);
+    let response = test::call_service(&app, as_bob(edit())).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // but never creating or deleting
+    let request = TestRequest::delete().uri(&format!("{}/journals/0", root));
+    assert_eq!(test::call_service(&app, as_bob(request)).await.status(), StatusCode::FORBIDDEN);
+    let request = TestRequest::post().uri(&format!("{}/journals", root))
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "title": "Theirs", "data": "" }));
+    assert_eq!(test::call_service(&app, as_bob(request)).await.status(), StatusCode::FORBIDDEN);
+
+    let request = TestRequest::get().uri(&format!("{}/journals/0/collaborators", root))
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .to_request();
+    let collaborators: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(collaborators, json!([{ "name": "bob", "access": "write" }]));
+
+    // without journals left, the key opens nothing
+    let request = TestRequest::delete().uri(&format!("{}/journals/0/collaborators/bob", root))
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let response = test::call_service(&app, as_bob(TestRequest::get().uri(&format!("{}/journals/0", root)))).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[actix_web::test]
+async fn only_members_manage_collaborators() {
+    let app = test::init_service(create_test_app()).await;
+    let (id, owner_key) = workspace(&app, "team").await;
+    let collaborators = format!("/v1/workspaces/{}/journals/0/collaborators", id);
+
+    // the journal has to exist
+    let request = TestRequest::post().uri(&collaborators)
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .set_json(json!({ "name": "bob" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+
+    let request = TestRequest::post().uri(&format!("/v1/workspaces/{}/journals", id))
+        .insert_header(("Post-Token", token(&app).await))
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .set_json(json!({ "title": "Plans", "data": "" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+
+    let request = TestRequest::post().uri(&collaborators)
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .set_json(json!({ "name": "alice" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CONFLICT);
+    let request = TestRequest::post().uri(&collaborators)
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .set_json(json!({ "name": "bob", "access": "write" }))
+        .to_request();
+    let bob: Value = test::call_and_read_body_json(&app, request).await;
+
+    let request = TestRequest::post().uri(&collaborators)
+        .insert_header(("Workspace-Key", bob["key"].as_str().unwrap()))
+        .set_json(json!({ "name": "eve" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::FORBIDDEN);
+
+    // the server's own journals have no collaborators
+    let request = TestRequest::post().uri("/v1/journals/0/collaborators")
+        .set_json(json!({ "name": "bob" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
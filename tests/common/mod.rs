@@ -0,0 +1,39 @@
+#![allow(clippy::needless_return, dead_code)]
+// Helpers shared by the integration tests
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+pub async fn token<S, B>(app: &S) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, TestRequest::post().uri("/v1/tokens").to_request()).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = test::read_body(response).await;
+    return String::from_utf8(body.to_vec()).unwrap();
+}
+
+pub fn header<B>(response: &ServiceResponse<B>, name: &str) -> String {
+    return String::from(response.headers().get(name).unwrap().to_str().unwrap());
+}
+
+// creates a workspace and returns its id and the owner's key
+pub async fn workspace<S, B>(app: &S, name: &str) -> (u64, String)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::post().uri("/v1/workspaces")
+        .insert_header(("Post-Token", token(app).await))
+        .set_json(json!({ "name": name, "owner": "alice" }))
+        .to_request();
+    let response = test::call_service(app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let credentials: Value = test::read_body_json(response).await;
+    return (credentials["id"].as_u64().unwrap(), String::from(credentials["key"].as_str().unwrap()));
+}
@@ -0,0 +1,116 @@
+#![cfg(feature = "crdt")]
+#![allow(clippy::needless_return)]
+// Offline edits of several devices merged with POST /sync/merge
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+
+use rest::{app, create_test_app, Config, State};
+
+mod common;
+use common::{header, token};
+
+async fn merge<S, B>(app: &S, edits: Value) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::post().uri("/v1/sync/merge")
+        .insert_header(("Post-Token", token(app).await))
+        .set_json(edits)
+        .to_request();
+    return test::call_and_read_body_json(app, request).await;
+}
+
+#[actix_web::test]
+async fn offline_edits_of_two_devices_are_merged() {
+    let app = test::init_service(create_test_app()).await;
+    let start = merge(&app, json!({ "journals": [{ "id": 0 }] })).await;
+    let replica = &start["journals"][0]["replica"];
+    assert_eq!(replica["clock"], 12);
+    assert_eq!(replica["text"][4], json!({ "id": [5, "server"], "char": "o", "deleted": false }));
+
+    // both insert after "Hello" and rename the journal while offline
+    let phone = json!({ "journals": [{
+        "id": 0,
+        "fields": { "title": { "value": "Phone title", "at": [13, "phone"] } },
+        "text": [{ "insert": { "id": [13, "phone"], "after": [5, "server"], "char": "X" } }],
+    }] });
+    let laptop = json!({ "journals": [{
+        "id": 0,
+        "fields": { "title": { "value": "Laptop title", "at": [14, "laptop"] } },
+        "text": [
+            { "insert": { "id": [13, "laptop"], "after": [5, "server"], "char": "Y" } },
+            { "delete": [12, "server"] },
+        ],
+    }] });
+    assert_eq!(merge(&app, phone.clone()).await["journals"][0]["status"], 200);
+    let merged = merge(&app, laptop).await;
+    assert_eq!(merged["journals"][0]["replica"]["clock"], 14);
+    // sent twice, merged once
+    merge(&app, phone).await;
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/journals/0").to_request()).await;
+    let etag = header(&response, "ETag");
+    let journal: Value = test::read_body_json(response).await;
+    assert_eq!((&journal["title"], &journal["data"]), (&json!("Laptop title"), &json!("HelloXY World")));
+
+    // an edit made outside of syncs is kept next to a later offline one
+    let request = TestRequest::put().uri("/v1/journals/0")
+        .insert_header(("If-Match", etag))
+        .set_json(json!({ "title": "Laptop title", "data": "HelloXY World, again" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    merge(&app, json!({ "journals": [{ "id": 0, "text": [{ "delete": [13, "phone"] }] }] })).await;
+    let journal: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals/0").to_request()).await;
+    assert_eq!(journal["data"], "HelloY World, again");
+}
+
+#[actix_web::test]
+async fn tasks_created_offline_are_created_once() {
+    let app = test::init_service(create_test_app()).await;
+    let created = json!({ "tasks": [{ "key": "phone-1", "fields": {
+        "text": { "value": "Buy bread", "at": [1, "phone"] },
+        "status": { "value": "in_progress", "at": [1, "phone"] },
+    } }] });
+    let merged = merge(&app, created.clone()).await;
+    assert_eq!((&merged["tasks"][0]["id"], &merged["tasks"][0]["status"]), (&json!(10), &json!(201)));
+    let merged = merge(&app, created).await;
+    assert_eq!((&merged["tasks"][0]["id"], &merged["tasks"][0]["status"]), (&json!(10), &json!(200)));
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/10").to_request()).await;
+    assert_eq!((&task["text"], &task["status"]), (&json!("Buy bread"), &json!("in_progress")));
+
+    // a stale write loses against the newer one
+    let stale = json!({ "tasks": [
+        { "id": 10, "fields": { "text": { "value": "Buy rolls", "at": [0, "laptop"] } } },
+        { "id": 3, "fields": { "status": { "value": "paused", "at": [1, "laptop"] } } },
+        { "id": 42 },
+        { "id": 4, "text": [{ "delete": [1, "server"] }] },
+    ] });
+    let merged = merge(&app, stale).await;
+    assert_eq!(merged["tasks"][0]["replica"]["fields"]["text"]["value"], "Buy bread");
+    let statuses: Vec<u64> = merged["tasks"].as_array().unwrap().iter().map(|result| result["status"].as_u64().unwrap()).collect();
+    assert_eq!(statuses, [200, 400, 404, 400]);
+
+    let request = TestRequest::post().uri("/v1/sync/merge").set_json(json!({ "tasks": [] })).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn merging_is_off_for_clients_without_the_flag() {
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    let request = TestRequest::patch().uri("/admin/flags")
+        .insert_header(("Authorization", "Bearer secret"))
+        .set_json(json!({ "crdt_sync": false }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let request = TestRequest::post().uri("/v1/sync/merge")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "tasks": [] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+}
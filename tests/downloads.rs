@@ -0,0 +1,44 @@
+#![allow(clippy::needless_return)]
+// Downloads in byte ranges, against the sample journals ("Hello World!")
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+
+use rest::create_test_app;
+
+mod common;
+use common::header;
+
+#[actix_web::test]
+async fn journal_bodies_are_served_in_ranges() {
+    let app = test::init_service(create_test_app()).await;
+    let get = |range: &str| TestRequest::get().uri("/v1/journals/0/data").insert_header(("Range", range));
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/journals/0/data").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "Accept-Ranges"), "bytes");
+    assert_eq!(test::read_body(response).await, "Hello World!");
+
+    for range in ["bytes=6-", "bytes=-6", "bytes=6-100"] {
+        let response = test::call_service(&app, get(range).to_request()).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(header(&response, "Content-Range"), "bytes 6-11/12");
+        assert_eq!(test::read_body(response).await, "World!");
+    }
+
+    let response = test::call_service(&app, get("bytes=12-").to_request()).await;
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(header(&response, "Content-Range"), "bytes */12");
+
+    // several ranges, or a part of another version, come whole
+    let response = test::call_service(&app, get("bytes=0-1,4-5").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test::call_service(&app, get("bytes=0-4").insert_header(("If-Range", "stale")).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test::call_service(&app, get("bytes=0-4").insert_header(("If-Range", "1")).to_request()).await;
+    assert_eq!(test::read_body(response).await, "Hello");
+
+    let request = TestRequest::get().uri("/v1/journals/0.md").insert_header(("Range", "bytes=0-6")).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(test::read_body(response).await, "# Title");
+}
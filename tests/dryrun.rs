@@ -0,0 +1,90 @@
+#![allow(clippy::needless_return)]
+// Dry runs with X-Dry-Run, which check a write and change nothing
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::header;
+
+const DRY_RUN: (&str, &str) = ("X-Dry-Run", "true");
+
+#[actix_web::test]
+async fn deletes_and_merges_are_only_checked() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::delete().uri("/v1/tasks/3").insert_header(DRY_RUN).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(header(&response, "X-Dry-Run"), "true");
+    let task: Value = test::read_body_json(response).await;
+    assert_eq!((&task["id"], &task["text"]), (&json!(3), &json!("Do the 3")));
+    let request = TestRequest::delete().uri("/v1/tasks/42").insert_header(DRY_RUN).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+
+    // without a token, and with the ETags checked
+    let request = TestRequest::post().uri("/v1/journal_merger")
+        .insert_header(DRY_RUN)
+        .set_json(json!({ "ids": [1, 2], "etags": ["1", "7"] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::PRECONDITION_FAILED);
+    let request = TestRequest::post().uri("/v1/journal_merger")
+        .insert_header(DRY_RUN)
+        .set_json(json!({ "ids": [1, 2], "etags": ["1", "1"] }))
+        .to_request();
+    let preview: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(preview["title"], "Title 1 / Title 2");
+    assert_eq!(preview["deleted"][1]["id"], 2);
+    let request = TestRequest::post().uri("/v1/task_merger")
+        .insert_header(DRY_RUN)
+        .set_json(json!({ "ids": [2, 3], "etags": ["1", "7"] }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(header(&response, "X-Dry-Run"), "true");
+    let request = TestRequest::post().uri("/v1/task_merger")
+        .insert_header(DRY_RUN)
+        .set_json(json!({ "ids": [2, 3], "etags": ["1"] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    let request = TestRequest::post().uri("/v1/task_merger")
+        .insert_header(DRY_RUN)
+        .set_json(json!({ "ids": [2, 3], "etags": ["1", "1"] }))
+        .to_request();
+    let preview: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(preview["text"], "Do the 2\nDo the 3");
+
+    let request = TestRequest::post().uri("/v1/tasks/bulk_tag")
+        .insert_header(DRY_RUN)
+        .set_json(json!({ "ids": [4, 99], "add": ["home"] }))
+        .to_request();
+    let results: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(results[0], json!({ "id": 4, "status": 200, "changed": true, "tags": ["home"] }));
+    assert_eq!(results[1]["status"], 404);
+
+    let request = TestRequest::post().uri("/v1/import/markdown")
+        .insert_header(DRY_RUN)
+        .set_payload("- [ ] Apples\n")
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!((&report["dry_run"], &report["created"][0]["text"]), (&json!(true), &json!("Apples")));
+
+    let journals: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals?per_page=50").to_request()).await;
+    assert_eq!(journals["total_entries"], 10);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/4").to_request()).await;
+    assert_eq!((&task["text"], task.get("tags")), (&json!("Do the 4"), None));
+    let tasks: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks?per_page=50").to_request()).await;
+    assert_eq!(tasks["total_entries"], 10);
+}
+
+#[actix_web::test]
+async fn other_writes_are_refused_as_dry_runs() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::patch().uri("/v1/tasks/3")
+        .insert_header(DRY_RUN)
+        .set_json(json!({ "done": true }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/3").to_request()).await;
+    assert_eq!(task["done"], false);
+}
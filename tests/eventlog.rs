@@ -0,0 +1,131 @@
+#![allow(clippy::needless_return)]
+// Event-sourced storage: the state a server starts with is what its event log holds
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use bytes::Bytes;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::time::Duration;
+
+use rest::{app, spawn_background, Config, State};
+
+mod common;
+use common::{header, token};
+
+// creates the entry and returns where it is
+async fn post<S, B>(app: &S, uri: &str, body: Value) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::post().uri(uri)
+        .insert_header(("Post-Token", token(app).await))
+        .set_json(body)
+        .to_request();
+    let response = test::call_service(app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    return header(&response, "Location");
+}
+
+async fn read<S, B>(app: &S, uri: &str) -> (StatusCode, Option<String>, Bytes)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, TestRequest::get().uri(uri).to_request()).await;
+    let etag = response.headers().get("ETag").map(|etag| String::from(etag.to_str().unwrap()));
+    return (response.status(), etag, test::read_body(response).await);
+}
+
+// uploads the text in one chunk and returns where the attachment is
+async fn attach<S, B>(app: &S, journal: &str, text: &'static str) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::post().uri(&format!("{}/uploads", journal))
+        .insert_header(("Post-Token", token(app).await))
+        .set_json(json!({ "name": "notes.txt", "content_type": "text/plain", "length": text.len() }))
+        .to_request();
+    let upload = header(&test::call_service(app, request).await, "Location");
+    let request = TestRequest::patch().uri(&upload).insert_header(("Upload-Offset", "0")).set_payload(text).to_request();
+    assert_eq!(test::call_service(app, request).await.status(), StatusCode::NO_CONTENT);
+    let response = test::call_service(app, TestRequest::post().uri(&format!("{}/finalize", upload)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    return header(&response, "Location");
+}
+
+#[actix_web::test]
+async fn the_event_log_outlives_the_server() {
+    let dir = std::env::temp_dir().join(format!("journal-events-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::env::set_var("JOURNAL_EVENT_LOG", &dir);
+    std::env::set_var("JOURNAL_BLOB_DIR", dir.join("blobs"));
+    let state = web::Data::new(State::open(Config::from_env()).unwrap());
+    let app = test::init_service(app(state.clone())).await;
+    // no sample data
+    let page: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks").to_request()).await;
+    assert_eq!(page["total_entries"], 0);
+
+    let kept = post(&app, "/v1/tasks", json!({ "text": "Water the plants" })).await;
+    let deleted = post(&app, "/v1/tasks", json!({ "text": "Gone" })).await;
+    let habit = post(&app, "/v1/habits", json!({ "name": "Stretch" })).await;
+    let request = TestRequest::post().uri(&format!("{}/checkins", habit))
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    let packing = post(&app, "/v1/journals", json!({ "title": "Packing list", "data": "Socks" })).await;
+    let snapshotted = attach(&app, &packing, "in the snapshot").await;
+
+    // the snapshot runs at startup, what follows is only in the log
+    spawn_background(&state);
+    while !dir.join("snapshot.json").exists() {
+        actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+    }
+    let journal = post(&app, "/v1/journals", json!({ "title": "Day one", "data": "It rained" })).await;
+    let logged = attach(&app, &journal, "only in the log").await;
+    let response = test::call_service(&app, TestRequest::get().uri(&kept).to_request()).await;
+    let request = TestRequest::patch().uri(&kept)
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "done": true }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, TestRequest::delete().uri(&deleted).to_request()).await.status(), StatusCode::OK);
+
+    let uris = [kept.clone(), journal.clone(), format!("{}/stats", habit), snapshotted.clone(), logged.clone(), format!("{}/attachments", journal)];
+    let mut before = Vec::new();
+    for uri in &uris {
+        before.push(read(&app, uri).await);
+    }
+
+    let restarted = test::init_service(rest::app(web::Data::new(State::open(Config::from_env()).unwrap()))).await;
+    let mut after = Vec::new();
+    for uri in &uris {
+        after.push(read(&restarted, uri).await);
+    }
+    assert_eq!(before, after);
+    assert_eq!(after[4].2, "only in the log");
+    // attachment ids stay used as well
+    let another = post(&restarted, "/v1/journals", json!({ "title": "Day two", "data": "Sun" })).await;
+    assert!(attach(&restarted, &another, "new").await.ends_with("/attachments/2"));
+    assert_eq!(read(&restarted, &deleted).await.0, StatusCode::NOT_FOUND);
+    // the deleted task's id stays used
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&restarted).await))
+        .set_json(json!({ "text": "New" }))
+        .to_request();
+    assert_eq!(header(&test::call_service(&restarted, request).await, "Location"), "/v1/tasks/2");
+
+    // a kind the server does not know is refused rather than replayed as another
+    let mut log = std::fs::OpenOptions::new().append(true).open(dir.join("events.jsonl")).unwrap();
+    writeln!(log, r#"{{"sequence": 1000, "at": "2026-10-16T08:00:00Z", "kind": "widget", "id": 1, "entry": {{}}}}"#).unwrap();
+    assert!(State::open(Config::from_env()).is_err());
+
+    std::env::remove_var("JOURNAL_EVENT_LOG");
+    std::env::remove_var("JOURNAL_BLOB_DIR");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,59 @@
+#![allow(clippy::needless_return)]
+// GET /events, the writes of a space as server-sent events
+use actix_web::body::MessageBody;
+use actix_web::test::{self, TestRequest};
+use futures_util::future::poll_fn;
+use serde_json::{json, Value};
+use std::pin::Pin;
+use std::time::Duration;
+
+use rest::create_test_app;
+
+mod common;
+use common::{token, workspace};
+
+// the next event of the stream, as its name and data
+async fn next_event<B: MessageBody>(body: &mut Pin<Box<B>>) -> (String, Value) {
+    let poll = poll_fn(|cx| body.as_mut().poll_next(cx));
+    let chunk = actix_web::rt::time::timeout(Duration::from_secs(5), poll).await
+        .expect("no event in time")
+        .expect("the stream ended")
+        .unwrap_or_else(|_| panic!("the stream failed"));
+    let message = String::from_utf8(chunk.to_vec()).unwrap();
+    let (name, data) = message.trim_end().split_once('\n').unwrap();
+    return (
+        String::from(name.strip_prefix("event: ").unwrap()),
+        serde_json::from_str(data.strip_prefix("data: ").unwrap()).unwrap(),
+    );
+}
+
+#[actix_web::test]
+async fn writes_are_streamed_to_their_space() {
+    let app = test::init_service(create_test_app()).await;
+    let (wid, key) = workspace(&app, "Elsewhere").await;
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/events").to_request()).await;
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "text/event-stream");
+    let mut events = Box::pin(response.into_body());
+
+    // the workspace's task is not the server's
+    let request = TestRequest::post().uri(&format!("/v1/workspaces/{}/tasks", wid))
+        .insert_header(("Workspace-Key", key.as_str()))
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Elsewhere" }))
+        .to_request();
+    test::call_service(&app, request).await;
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Streamed" }))
+        .to_request();
+    test::call_service(&app, request).await;
+    test::call_service(&app, TestRequest::delete().uri("/v1/journals/2").to_request()).await;
+
+    let (name, task) = next_event(&mut events).await;
+    assert_eq!(name, "task.created");
+    assert_eq!((task["kind"].clone(), task["action"].clone(), task["id"].clone()), (json!("task"), json!("created"), json!(10)));
+    assert_eq!(task["summary"], "Streamed");
+    let (name, journal) = next_event(&mut events).await;
+    assert_eq!(name, "journal.deleted");
+    assert_eq!(journal["id"], 2);
+}
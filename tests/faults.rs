@@ -0,0 +1,52 @@
+#![cfg(feature = "faults")]
+#![allow(clippy::needless_return)]
+// Faults injected on purpose with JOURNAL_FAULTS
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::json;
+use std::time::{Duration, Instant};
+
+use rest::{app, Config, State};
+
+mod common;
+use common::{header, token};
+
+fn faulty(faults: &str) -> web::Data<State> {
+    std::env::set_var("JOURNAL_FAULTS", faults);
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    return web::Data::new(State::with_sample_data(Config::from_env()));
+}
+
+#[actix_web::test]
+async fn faults_hit_their_share_of_requests() {
+    let app = test::init_service(app(faulty("latency=100%:200ms,errors=0%"))).await;
+    let started = Instant::now();
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    assert_eq!(header(&response, "X-Injected-Fault"), "latency");
+
+    // the writers are held before the write queues up behind them
+    let app = test::init_service(rest::app(faulty("contention=100%:150ms"))).await;
+    let token = token(&app).await;
+    let started = Instant::now();
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token))
+        .set_json(json!({ "text": "Water the plants" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    assert!(started.elapsed() >= Duration::from_millis(150));
+    assert_eq!(header(&response, "X-Injected-Fault"), "contention");
+
+    let app = test::init_service(rest::app(faulty("errors=100%,bogus=1%"))).await;
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(header(&response, "X-Injected-Fault"), "error");
+    // the operators' routes are spared
+    let request = TestRequest::get().uri("/admin/jobs").insert_header(("Authorization", "Bearer secret")).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("X-Injected-Fault").is_none());
+}
@@ -0,0 +1,84 @@
+#![allow(clippy::needless_return)]
+// Feature flags, switched with PATCH /admin/flags
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+
+use rest::{app, Config, State};
+
+mod common;
+use common::header;
+
+const ADMIN: (&str, &str) = ("Authorization", "Bearer secret");
+const JSON_API: &str = "application/vnd.api+json";
+
+async fn patch<S, B>(app: &S, flags: Value) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::patch().uri("/admin/flags").insert_header(ADMIN).set_json(flags).to_request();
+    let response = test::call_service(app, request).await;
+    let status = response.status();
+    return (status, serde_json::from_slice(&test::read_body(response).await).unwrap_or(Value::Null));
+}
+
+// whether the client at the address gets JSON:API documents
+async fn json_api<S, B>(app: &S, address: &str) -> bool
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::get().uri("/v1/tasks/1").peer_addr(address.parse().unwrap()).to_request();
+    return header(&test::call_service(app, request).await, "Content-Type") == JSON_API;
+}
+
+#[actix_web::test]
+async fn admins_switch_flags_at_runtime() {
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    let request = TestRequest::get().uri("/admin/flags").insert_header(ADMIN).to_request();
+    let flags: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(flags["json_api"]["percent"], 0);
+    assert!(!json_api(&app, "10.0.0.1:4000").await);
+
+    let (status, flags) = patch(&app, json!({ "json_api": true })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(flags["json_api"]["percent"], 100);
+    assert!(json_api(&app, "10.0.0.1:4000").await);
+
+    // nothing is set when anything is wrong
+    let (status, _) = patch(&app, json!({ "json_api": false, "new_ids": true })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    let (status, _) = patch(&app, json!({ "json_api": 101 })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(json_api(&app, "10.0.0.1:4000").await);
+
+    let (_, flags) = patch(&app, json!({ "json_api": false })).await;
+    assert_eq!(flags["json_api"]["percent"], 0);
+    assert!(!json_api(&app, "10.0.0.1:4000").await);
+}
+
+#[actix_web::test]
+async fn a_rollout_reaches_a_share_of_clients_for_good() {
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    patch(&app, json!({ "json_api": 50 })).await;
+    let addresses: Vec<String> = (0..100).map(|host| format!("10.0.1.{}:4000", host)).collect();
+    let mut half = Vec::new();
+    for address in &addresses {
+        half.push(json_api(&app, address).await);
+    }
+    let on = half.iter().filter(|on| **on).count();
+    assert!((25..=75).contains(&on), "on for {} of 100", on);
+
+    // those it was on for keep it as it grows
+    patch(&app, json!({ "json_api": 80 })).await;
+    for (address, was) in addresses.iter().zip(&half) {
+        assert!(!was || json_api(&app, address).await, "{} lost the flag", address);
+    }
+}
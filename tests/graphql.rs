@@ -0,0 +1,60 @@
+#![cfg(feature = "graphql")]
+#![allow(clippy::needless_return)]
+// GraphQL queries over the journals and tasks of a space, with their tags
+// and what they share a tag with
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{token, workspace};
+
+const QUERY: &str = "{
+    journals(filter: { tag: \"garden\" }) { totalEntries entries { title tags tasks { text } } }
+    tasks { totalEntries entries { text journals { title } } }
+}";
+
+#[actix_web::test]
+async fn queries_follow_tags_within_the_space() {
+    let app = test::init_service(create_test_app()).await;
+    let (work, key) = workspace(&app, "work").await;
+    for (collection, body) in [
+        ("journals", json!({ "title": "Spring", "data": "Planted beans", "tags": ["garden"] })),
+        ("journals", json!({ "title": "Winter", "data": "Snow", "tags": ["weather"] })),
+        ("tasks", json!({ "text": "Water the beans", "tags": ["garden", "daily"] })),
+    ] {
+        let request = TestRequest::post().uri(&format!("/v1/workspaces/{}/{}", work, collection))
+            .insert_header(("Post-Token", token(&app).await))
+            .insert_header(("Workspace-Key", key.as_str()))
+            .set_json(body)
+            .to_request();
+        assert!(test::call_service(&app, request).await.status().is_success());
+    }
+
+    let request = TestRequest::post().uri(&format!("/workspaces/{}/graphql", work))
+        .insert_header(("Workspace-Key", key.as_str()))
+        .set_json(json!({ "query": QUERY }))
+        .to_request();
+    let response: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(response["data"]["journals"], json!({
+        "totalEntries": 1,
+        "entries": [{ "title": "Spring", "tags": ["garden"], "tasks": [{ "text": "Water the beans" }] }],
+    }));
+    assert_eq!(response["data"]["tasks"], json!({
+        "totalEntries": 1,
+        "entries": [{ "text": "Water the beans", "journals": [{ "title": "Spring" }] }],
+    }));
+
+    // the server's own collections do not have them
+    let request = TestRequest::post().uri("/graphql").set_json(json!({ "query": QUERY })).to_request();
+    let response: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(response["data"]["journals"]["totalEntries"], 0);
+    assert_eq!(response["data"]["tasks"]["totalEntries"], 10);
+
+    // nor does the workspace answer without a key
+    let request = TestRequest::post().uri(&format!("/workspaces/{}/graphql", work))
+        .set_json(json!({ "query": QUERY }))
+        .to_request();
+    assert!(test::call_service(&app, request).await.status().is_client_error());
+}
@@ -0,0 +1,51 @@
+#![cfg(feature = "grpc")]
+#![allow(clippy::needless_return)]
+// The gRPC service of a read replica
+use actix_web::web;
+use std::time::Duration;
+use tonic::Code;
+
+use rest::{spawn_background, Config, State};
+
+mod proto {
+    tonic::include_proto!("journal");
+}
+
+use proto::journals_client::JournalsClient;
+
+#[actix_web::test]
+async fn replicas_refuse_grpc_writes() {
+    // the only test of this file, so no other sees the variable; nothing
+    // listens there, the replica keeps retrying
+    std::env::set_var("JOURNAL_PRIMARY_URL", "http://127.0.0.1:9");
+    let state = web::Data::new(State::with_sample_data(Config::from_env()));
+    spawn_background(&state);
+
+    let mut client = None;
+    for _ in 0..50 {
+        if let Ok(connected) = JournalsClient::connect("http://127.0.0.1:50051").await {
+            client = Some(connected);
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    let mut client = client.expect("The gRPC service is not listening");
+
+    let task = client.get_task(proto::IdRequest { id: 1 }).await.unwrap().into_inner();
+    let put = client.put_task(proto::PutTaskRequest {
+        id:         1,
+        if_match:   Some(task.etag),
+        task:       Some(proto::Task { text: String::from("Diverged"), done: false, archived: false }),
+    }).await;
+    assert_eq!(put.unwrap_err().code(), Code::FailedPrecondition);
+    let created = client.create_task(proto::CreateTaskRequest {
+        token:  String::from("any"),
+        task:   Some(proto::Task { text: String::from("Diverged"), done: false, archived: false }),
+    }).await;
+    assert_eq!(created.unwrap_err().code(), Code::FailedPrecondition);
+    let deleted = client.delete_task(proto::IdRequest { id: 1 }).await;
+    assert_eq!(deleted.unwrap_err().code(), Code::FailedPrecondition);
+
+    let task = client.get_task(proto::IdRequest { id: 1 }).await.unwrap().into_inner();
+    assert_eq!(task.task.unwrap().text, "Do the 1");
+}
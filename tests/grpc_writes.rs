@@ -0,0 +1,77 @@
+#![cfg(feature = "grpc")]
+#![allow(clippy::needless_return)]
+// gRPC puts, which carry fewer fields than the entries they replace
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tonic::Code;
+
+use rest::{app, spawn_background, Config, State};
+
+mod common;
+use common::{header, token};
+
+mod proto {
+    tonic::include_proto!("journal");
+}
+
+use proto::journals_client::JournalsClient;
+
+#[actix_web::test]
+async fn grpc_puts_keep_the_fields_they_have_none_for() {
+    // the only test of this file, so no other binds the service
+    let state = web::Data::new(State::with_sample_data(Config::from_env()));
+    spawn_background(&state);
+    let app = test::init_service(app(state)).await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    let request = TestRequest::put().uri("/v1/tasks/1")
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "text": "Tagged", "tags": ["home"], "status": "in_progress" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let request = TestRequest::post().uri("/v1/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "title": "Secret", "data": "c2VjcmV0", "encrypted": true }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let journal: u64 = header(&response, "Location").rsplit('/').next().unwrap().parse().unwrap();
+
+    let mut client = None;
+    for _ in 0..50 {
+        if let Ok(connected) = JournalsClient::connect("http://127.0.0.1:50051").await {
+            client = Some(connected);
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    let mut client = client.expect("The gRPC service is not listening");
+
+    // the tags and the status the message has no field for are kept
+    let task = client.get_task(proto::IdRequest { id: 1 }).await.unwrap().into_inner();
+    client.put_task(proto::PutTaskRequest {
+        id:         1,
+        if_match:   Some(task.etag),
+        task:       Some(proto::Task { text: String::from("Renamed"), done: false, archived: false }),
+    }).await.unwrap();
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    assert_eq!(task["text"], "Renamed");
+    assert_eq!(task["tags"], json!(["home"]));
+    assert_eq!(task["status"], "in_progress");
+
+    // an encrypted journal is not stored as plaintext
+    let entry = client.get_journal(proto::IdRequest { id: journal }).await.unwrap().into_inner();
+    let put = client.put_journal(proto::PutJournalRequest {
+        id:         journal,
+        if_match:   Some(entry.etag),
+        journal:    Some(proto::Journal { title: String::from("Secret"), data: String::from("plain") }),
+    }).await;
+    assert_eq!(put.unwrap_err().code(), Code::FailedPrecondition);
+    let request = TestRequest::get().uri(&format!("/v1/journals/{}", journal)).to_request();
+    let stored: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(stored["encrypted"], true);
+    assert_eq!(stored["data"], "c2VjcmV0");
+}
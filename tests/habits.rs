@@ -0,0 +1,92 @@
+#![allow(clippy::needless_return)]
+// Habits, their daily check-ins and streaks
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use chrono::{Days, Utc};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token};
+
+#[actix_web::test]
+async fn checkins_add_up_to_streaks() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/habits")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "name": "Stretch" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let habit = header(&response, "Location");
+
+    let today = Utc::now().date_naive();
+    let days_ago = |days| today.checked_sub_days(Days::new(days)).unwrap().to_string();
+    for days in [4, 2, 1] {
+        let request = TestRequest::post().uri(&format!("{}/checkins", habit))
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "date": days_ago(days) }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+    let stats: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&format!("{}/stats", habit)).to_request()).await;
+    assert_eq!(stats, json!({ "checkins": 3, "current_streak": 2, "longest_streak": 2, "last_checkin": days_ago(1) }));
+
+    // today by default, and only once
+    for status in [StatusCode::CREATED, StatusCode::OK] {
+        let request = TestRequest::post().uri(&format!("{}/checkins", habit))
+            .insert_header(("Post-Token", token(&app).await))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), status);
+        let stats: Value = test::read_body_json(response).await;
+        assert_eq!(stats["current_streak"], 3);
+    }
+
+    // replacing the habit keeps its check-ins
+    let response = test::call_service(&app, TestRequest::get().uri(&habit).to_request()).await;
+    let request = TestRequest::put().uri(&habit)
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "name": "Stretch twice", "checkins": [] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let stored: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&habit).to_request()).await;
+    assert_eq!(stored["name"], "Stretch twice");
+    assert_eq!(stored["checkins"].as_array().unwrap().len(), 4);
+
+    let request = TestRequest::delete().uri(&format!("{}/checkins/{}", habit, days_ago(2))).to_request();
+    let stats: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(stats["current_streak"], 2);
+    assert_eq!(stats["longest_streak"], 2);
+    let request = TestRequest::delete().uri(&format!("{}/checkins/{}", habit, days_ago(2))).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn checkins_need_a_token_and_a_past_day() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/habits")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "name": "Read" }))
+        .to_request();
+    let habit = header(&test::call_service(&app, request).await, "Location");
+
+    let request = TestRequest::post().uri(&format!("{}/checkins", habit)).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+    let next_week = Utc::now().date_naive().checked_add_days(Days::new(7)).unwrap().to_string();
+    let request = TestRequest::post().uri(&format!("{}/checkins", habit))
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "date": next_week }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+    let request = TestRequest::post().uri("/v1/habits/42/checkins")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NOT_FOUND);
+
+    let stats: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&format!("{}/stats", habit)).to_request()).await;
+    assert_eq!(stats, json!({ "checkins": 0, "current_streak": 0, "longest_streak": 0, "last_checkin": null }));
+}
@@ -0,0 +1,68 @@
+#![allow(clippy::needless_return)]
+// ?as_of=, the entries as they were at a time, replayed from the event log
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use chrono::{SecondsFormat, Utc};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use rest::{app, Config, State};
+
+mod common;
+use common::{header, token, workspace};
+
+#[actix_web::test]
+async fn entries_are_read_as_they_were() {
+    let dir = std::env::temp_dir().join(format!("journal-history-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::env::set_var("JOURNAL_EVENT_LOG", &dir);
+    let app = test::init_service(app(web::Data::new(State::open(Config::from_env()).unwrap()))).await;
+    let before = Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true);
+    actix_web::rt::time::sleep(Duration::from_millis(5)).await;
+
+    for text in ["Water the plants", "Call the bank"] {
+        let request = TestRequest::post().uri("/v1/tasks")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "text": text }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+    actix_web::rt::time::sleep(Duration::from_millis(5)).await;
+    let then = Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true);
+    actix_web::rt::time::sleep(Duration::from_millis(5)).await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/0").to_request()).await;
+    let request = TestRequest::patch().uri("/v1/tasks/0")
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "done": true }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    test::call_service(&app, TestRequest::delete().uri("/v1/tasks/1").to_request()).await;
+
+    let get = |uri: String| TestRequest::get().uri(&uri).to_request();
+    let task: Value = test::call_and_read_body_json(&app, get(format!("/v1/tasks/0?as_of={}", then))).await;
+    assert_eq!((task["text"].clone(), task["done"].clone()), (json!("Water the plants"), json!(false)));
+    let task: Value = test::call_and_read_body_json(&app, get(String::from("/v1/tasks/0"))).await;
+    assert_eq!(task["done"], true);
+
+    let page: Value = test::call_and_read_body_json(&app, get(format!("/v1/tasks?as_of={}", then))).await;
+    assert_eq!(page["total_entries"], 2);
+    let page: Value = test::call_and_read_body_json(&app, get(format!("/v1/tasks?as_of={}", before))).await;
+    assert_eq!(page["total_entries"], 0);
+    let page: Value = test::call_and_read_body_json(&app, get(String::from("/v1/tasks"))).await;
+    assert_eq!(page["total_entries"], 1);
+    let response = test::call_service(&app, get(format!("/v1/tasks/1?as_of={}", before))).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = test::call_service(&app, get(String::from("/v1/tasks?as_of=yesterday"))).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    // only the server's own entries are logged
+    let (wid, key) = workspace(&app, "Elsewhere").await;
+    let request = TestRequest::get().uri(&format!("/v1/workspaces/{}/tasks?as_of={}", wid, then))
+        .insert_header(("Workspace-Key", key.as_str()))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
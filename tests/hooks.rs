@@ -0,0 +1,272 @@
+#![allow(clippy::needless_return)]
+// Hooks registered by embedders, run on the writes of the REST API, CalDAV and CRDT merges
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+use rest::{app, spawn_background, Config, Entry, Hook, State};
+
+mod common;
+use common::{header, token};
+
+// tags new tasks, refuses secrets and keeps task 0, and notes every call
+struct Rules {
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl Hook for Rules {
+    fn on_create(&self, entry: &mut Entry<'_>) -> Result<(), String> {
+        self.calls.lock().unwrap().push(format!("create {}", entry.kind()));
+        if let Entry::Task(task) = entry {
+            if task.text.contains("secret") {
+                return Err(String::from("No secrets in tasks"));
+            }
+            task.tags.push(String::from("hooked"));
+        }
+        return Ok(());
+    }
+
+    fn on_update(&self, id: usize, entry: &mut Entry<'_>) -> Result<(), String> {
+        self.calls.lock().unwrap().push(format!("update {} {}", entry.kind(), id));
+        return Ok(());
+    }
+
+    fn on_delete(&self, kind: &str, id: usize) -> Result<(), String> {
+        self.calls.lock().unwrap().push(format!("delete {} {}", kind, id));
+        if id == 0 {
+            return Err(format!("The first {} stays", kind));
+        }
+        return Ok(());
+    }
+
+    fn on_merge(&self, ids: &[usize], merged: &mut Entry<'_>) -> Result<(), String> {
+        self.calls.lock().unwrap().push(format!("merge {} {:?}", merged.kind(), ids));
+        return Ok(());
+    }
+}
+
+#[actix_web::test]
+async fn hooks_change_and_refuse_writes() {
+    let state = web::Data::new(State::with_sample_data(Config::default()));
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    state.register_hook(Rules { calls: calls.clone() });
+    let app = test::init_service(app(state)).await;
+
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Water the plants" }))
+        .to_request();
+    let location = header(&test::call_service(&app, request).await, "Location");
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(task["tags"], json!(["hooked"]));
+
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "The secret plan" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let problem: Value = test::read_body_json(response).await;
+    assert_eq!(problem["detail"], "No secrets in tasks");
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    let request = TestRequest::patch().uri("/v1/tasks/1")
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "done": true }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let response = test::call_service(&app, TestRequest::delete().uri("/v1/tasks/0").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/0").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = TestRequest::post().uri("/v1/task_merger")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "ids": [2, 3] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+
+    assert_eq!(*calls.lock().unwrap(), [
+        "create task", "create task", "update task 1", "delete task 0", "merge task [2, 3]",
+    ]);
+}
+
+// tags every task it sees updated
+struct Toucher;
+
+impl Hook for Toucher {
+    fn on_update(&self, _id: usize, entry: &mut Entry<'_>) -> Result<(), String> {
+        if let Entry::Task(task) = entry {
+            task.tags.push(String::from("touched"));
+        }
+        return Ok(());
+    }
+}
+
+#[actix_web::test]
+async fn hooks_leave_refused_patches_alone() {
+    let state = web::Data::new(State::with_sample_data(Config::default()));
+    state.register_hook(Toucher);
+    let app = test::init_service(app(state)).await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    let etag = header(&response, "ETag");
+    let before: Value = test::read_body_json(response).await;
+    let request = TestRequest::patch().uri("/v1/tasks/1")
+        .insert_header(("If-Match", etag.as_str()))
+        .set_json(json!({ "priority": 3 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    assert_eq!(header(&response, "ETag"), etag);
+    let after: Value = test::read_body_json(response).await;
+    assert_eq!(after, before);
+}
+
+#[actix_web::test]
+async fn hooks_never_see_puts_refused_by_their_preconditions() {
+    let state = web::Data::new(State::with_sample_data(Config::default()));
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    state.register_hook(Rules { calls: calls.clone() });
+    let app = test::init_service(app(state)).await;
+
+    let put = |etag: Option<&str>, body: Value| {
+        let request = TestRequest::put().uri("/v1/tasks/1").set_json(body);
+        match etag {
+            Some(etag)  => request.insert_header(("If-Match", etag)).to_request(),
+            None        => request.to_request(),
+        }
+    };
+    let response = test::call_service(&app, put(None, json!({ "text": "Task 1" }))).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_REQUIRED);
+    let response = test::call_service(&app, put(Some("stale"), json!({ "text": "Task 1" }))).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let etag = header(&test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await, "ETag");
+    let response = test::call_service(&app, put(Some(&etag), json!({ "text": "Task 1", "status": "todo" }))).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(*calls.lock().unwrap(), ["update task 1"]);
+}
+
+#[actix_web::test]
+async fn hooks_see_caldav_deletes() {
+    let state = web::Data::new(State::with_sample_data(Config::default()));
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    state.register_hook(Rules { calls: calls.clone() });
+    let app = test::init_service(app(state)).await;
+
+    let response = test::call_service(&app, TestRequest::delete().uri("/caldav/tasks/0.ics").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = test::call_service(&app, TestRequest::delete().uri("/caldav/tasks/1.ics").to_request()).await;
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(*calls.lock().unwrap(), ["delete task 0", "delete task 1"]);
+}
+
+#[actix_web::test]
+async fn hooks_see_expired_entries_swept() {
+    let state = web::Data::new(State::with_sample_data(Config::default()));
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    state.register_hook(Rules { calls: calls.clone() });
+    let app = test::init_service(app(state.clone())).await;
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Scratch", "expires_at": "2000-01-01T00:00:00Z" }))
+        .to_request();
+    let location = header(&test::call_service(&app, request).await, "Location");
+
+    // the sweeper runs once at startup
+    spawn_background(&state);
+    for _ in 0..50 {
+        if test::call_service(&app, TestRequest::get().uri(&location).to_request()).await.status() == StatusCode::NOT_FOUND {
+            break;
+        }
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(*calls.lock().unwrap(), ["create task", "delete task 10"]);
+}
+
+#[actix_web::test]
+async fn hooks_see_the_parts_and_the_end_of_split_tasks() {
+    let state = web::Data::new(State::with_sample_data(Config::default()));
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    state.register_hook(Rules { calls: calls.clone() });
+    let app = test::init_service(app(state)).await;
+
+    for (id, expected) in [(0, StatusCode::UNPROCESSABLE_ENTITY), (1, StatusCode::CREATED)] {
+        let response = test::call_service(&app, TestRequest::get().uri(&format!("/v1/tasks/{}", id)).to_request()).await;
+        let request = TestRequest::post().uri(&format!("/v1/tasks/{}/split", id))
+            .insert_header(("Post-Token", token(&app).await))
+            .insert_header(("If-Match", header(&response, "ETag")))
+            .set_json(json!({ "parts": ["Buy", "Cook"] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), expected);
+    }
+    // the refused split left the first task alone and stored no part
+    assert_eq!(test::call_service(&app, TestRequest::get().uri("/v1/tasks/0").to_request()).await.status(), StatusCode::OK);
+    let listing: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks").to_request()).await;
+    assert_eq!(listing["total_entries"], 11);
+    let part: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/10").to_request()).await;
+    assert_eq!((&part["text"], &part["tags"]), (&json!("Buy"), &json!(["hooked"])));
+    assert_eq!(*calls.lock().unwrap(), ["create task", "create task", "delete task 0", "create task", "create task", "delete task 1"]);
+}
+
+// refuses every merge
+struct NoMerges;
+
+impl Hook for NoMerges {
+    fn on_merge(&self, _ids: &[usize], _merged: &mut Entry<'_>) -> Result<(), String> {
+        return Err(String::from("No merges"));
+    }
+}
+
+#[actix_web::test]
+async fn refused_merges_leave_the_tasks_alone() {
+    let state = web::Data::new(State::with_sample_data(Config::default()));
+    state.register_hook(NoMerges);
+    let app = test::init_service(app(state)).await;
+
+    let request = TestRequest::post().uri("/v1/task_merger")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "ids": [2, 3], "sources": "archive" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    for id in [2, 3] {
+        let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&format!("/v1/tasks/{}", id)).to_request()).await;
+        assert_eq!(task["archived"], false);
+    }
+    let listing: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks").to_request()).await;
+    assert_eq!(listing["total_entries"], 10);
+}
+
+#[cfg(feature = "crdt")]
+#[actix_web::test]
+async fn hooks_see_crdt_merges() {
+    let state = web::Data::new(State::with_sample_data(Config::default()));
+    let calls = Arc::new(Mutex::new(Vec::new()));
+    state.register_hook(Rules { calls: calls.clone() });
+    let app = test::init_service(app(state)).await;
+
+    let request = TestRequest::post().uri("/v1/sync/merge")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "tasks": [
+            { "key": "phone-1", "fields": { "text": { "value": "Offline", "at": [1, "phone"] } } },
+            { "key": "phone-2", "fields": { "text": { "value": "The secret plan", "at": [1, "phone"] } } },
+            { "id": 1, "fields": { "text": { "value": "Renamed", "at": [1, "phone"] } } },
+            { "id": 2 },
+        ] }))
+        .to_request();
+    let merged: Value = test::call_and_read_body_json(&app, request).await;
+    let statuses: Vec<u64> = merged["tasks"].as_array().unwrap().iter().map(|result| result["status"].as_u64().unwrap()).collect();
+    assert_eq!(statuses, [201, 422, 200, 200]);
+    assert_eq!(merged["tasks"][1]["error"], "No secrets in tasks");
+    // what the hook changed is in the replica handed back
+    assert_eq!(merged["tasks"][0]["replica"]["fields"]["tags"]["value"], json!(["hooked"]));
+    let id = merged["tasks"][0]["id"].as_u64().unwrap();
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&format!("/v1/tasks/{}", id)).to_request()).await;
+    assert_eq!(task["tags"], json!(["hooked"]));
+    // the edit that changes nothing is not shown to them
+    assert_eq!(*calls.lock().unwrap(), ["create task", "create task", "update task 1"]);
+}
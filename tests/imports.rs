@@ -0,0 +1,165 @@
+#![allow(clippy::needless_return)]
+// Tasks imported from other tools
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+use std::io::{Cursor, Write};
+use zip::write::SimpleFileOptions;
+use zip::{DateTime, ZipWriter};
+
+use rest::create_test_app;
+
+mod common;
+use common::token;
+
+const CALENDAR: &str = "BEGIN:VCALENDAR\r\n\
+VERSION:2.0\r\n\
+BEGIN:VTODO\r\n\
+SUMMARY:File the taxes\r\n\
+DUE:20261031T170000Z\r\n\
+CATEGORIES:home,paperwork\r\n\
+END:VTODO\r\n\
+BEGIN:VTODO\r\n\
+SUMMARY:Renew the passport\r\n\
+STATUS:COMPLETED\r\n\
+END:VTODO\r\n\
+BEGIN:VTODO\r\n\
+SUMMARY:File the taxes\r\n\
+DUE:20261031T170000Z\r\n\
+END:VTODO\r\n\
+BEGIN:VTODO\r\n\
+SUMMARY:Do the 0\r\n\
+END:VTODO\r\n\
+BEGIN:VTODO\r\n\
+DUE:20261101\r\n\
+END:VTODO\r\n\
+BEGIN:VEVENT\r\n\
+SUMMARY:Dentist\r\n\
+DTSTART;VALUE=DATE:20261105\r\n\
+BEGIN:VALARM\r\n\
+TRIGGER:-PT1H\r\n\
+END:VALARM\r\n\
+END:VEVENT\r\n\
+END:VCALENDAR\r\n";
+
+#[actix_web::test]
+async fn calendars_are_imported_as_tasks() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/import/ics?dry_run=true").set_payload(CALENDAR).to_request();
+    let report: Value = test::call_and_read_body_json(&app, request).await;
+    let created: Vec<&str> = report["created"].as_array().unwrap().iter().map(|task| task["text"].as_str().unwrap()).collect();
+    assert_eq!(created, ["File the taxes", "Renew the passport", "Dentist"]);
+    let reasons: Vec<&str> = report["skipped"].as_array().unwrap().iter().map(|task| task["reason"].as_str().unwrap()).collect();
+    assert_eq!(reasons, ["no text", "duplicate", "duplicate"]);
+    assert!(report["created"][0]["id"].is_null());
+    let page: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks").to_request()).await;
+    assert_eq!(page["total_entries"], 10);
+
+    let import = |token: String| TestRequest::post().uri("/v1/import/ics")
+        .insert_header(("Post-Token", token))
+        .set_payload(CALENDAR)
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, import(token(&app).await)).await;
+    assert_eq!(report["created"][2], json!({ "id": 12, "text": "Dentist", "status": "todo", "due": "2026-11-05T00:00:00Z" }));
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/10").to_request()).await;
+    assert_eq!((&task["due"], &task["tags"]), (&json!("2026-10-31T17:00:00Z"), &json!(["home", "paperwork"])));
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/11").to_request()).await;
+    assert_eq!((&task["status"], &task["done"]), (&json!("done"), &json!(true)));
+
+    // nothing is new the second time
+    let report: Value = test::call_and_read_body_json(&app, import(token(&app).await)).await;
+    assert!(report["created"].as_array().unwrap().is_empty());
+
+    let request = TestRequest::post().uri("/v1/import/ics")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_payload("SUMMARY:Not a calendar")
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn todoist_exports_are_imported() {
+    let app = test::init_service(create_test_app()).await;
+    let csv = "TYPE,CONTENT,DESCRIPTION,PRIORITY,INDENT,AUTHOR,RESPONSIBLE,DATE,DATE_LANG,TIMEZONE\r\n\
+section,Errands,,,,,,,,\r\n\
+task,\"Buy milk, eggs\",,4,1,,,2026-10-20,en,UTC\r\n\
+task,\"Call \"\"the\"\" bank\",,4,1,,,every monday,en,UTC\r\n\
+note,Remember the card,,,,,,,,\r\n";
+    let request = TestRequest::post().uri("/v1/import/todoist")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_payload(csv)
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(report["created"], json!([
+        { "id": 10, "text": "Buy milk, eggs", "status": "todo", "due": "2026-10-20T00:00:00Z" },
+        { "id": 11, "text": "Call \"the\" bank", "status": "todo" },
+    ]));
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/10").to_request()).await;
+    assert_eq!(task["tags"], json!(["Errands"]));
+
+    let tasks = json!([
+        { "content": "Water the plants", "labels": ["home"], "due": { "date": "2026-10-21", "datetime": "2026-10-21T08:00:00Z" } },
+        { "content": "Buy milk, eggs", "due": { "date": "2026-10-20" } },
+    ]);
+    let request = TestRequest::post().uri("/v1/import/todoist?dry_run=true").set_json(&tasks).to_request();
+    let report: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(report["created"][0]["due"], "2026-10-21T08:00:00Z");
+    assert_eq!(report["skipped"][0]["reason"], "duplicate");
+
+    let request = TestRequest::post().uri("/v1/import/todoist?dry_run=true").set_payload("CONTENT\nOnly content").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn markdown_checklists_are_imported() {
+    let app = test::init_service(create_test_app()).await;
+    let checklist = "# Groceries\n- [ ] Apples\n  * [x] Pears\n- not a task\n\n## Garden\n1. [ ] Rake the leaves\n+ [ ]\n";
+    let request = TestRequest::post().uri("/v1/import/markdown")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_payload(checklist)
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, request).await;
+    let created: Vec<(&str, &str)> = report["created"].as_array().unwrap().iter()
+        .map(|task| (task["text"].as_str().unwrap(), task["status"].as_str().unwrap()))
+        .collect();
+    assert_eq!(created, [("Apples", "todo"), ("Pears", "done"), ("Rake the leaves", "todo")]);
+    assert_eq!(report["skipped"], json!([{ "text": "", "reason": "no text" }]));
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/12").to_request()).await;
+    assert_eq!(task["tags"], json!(["Garden"]));
+}
+
+#[actix_web::test]
+async fn markdown_files_are_imported_as_journals() {
+    let app = test::init_service(create_test_app()).await;
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    // modified on the first of the month, in 2026
+    let files: [(&str, &[u8], u8); 5] = [
+        ("2026/october.md", b"# Autumn\n\nLeaves everywhere.", 10),
+        ("notes/plain-notes.md", b"No heading here.", 9),
+        ("notes/.hidden.md", b"# Hidden", 8),
+        ("photo.png", b"not text", 7),
+        ("broken.md", &[0xff, 0xfe, 0x00], 6),
+    ];
+    for (name, content, month) in files {
+        let time = DateTime::from_date_and_time(2026, month, 1, 12, 0, 0).unwrap();
+        zip.start_file(name, SimpleFileOptions::default().last_modified_time(time)).unwrap();
+        zip.write_all(content).unwrap();
+    }
+    let archive = zip.finish().unwrap().into_inner();
+
+    let request = TestRequest::post().uri("/v1/import/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_payload(archive)
+        .to_request();
+    let report: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(report["created"], json!([
+        { "id": 10, "file": "notes/plain-notes.md", "title": "plain-notes", "created_at": "2026-09-01T12:00:00Z" },
+        { "id": 11, "file": "2026/october.md", "title": "Autumn", "created_at": "2026-10-01T12:00:00Z" },
+    ]));
+    assert_eq!(report["skipped"], json!([{ "file": "broken.md", "reason": "not UTF-8 text" }]));
+    let journal: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals/11").to_request()).await;
+    assert_eq!((&journal["data"], &journal["updated_at"]), (&json!("Leaves everywhere."), &json!("2026-10-01T12:00:00Z")));
+
+    let request = TestRequest::post().uri("/v1/import/journals?dry_run=true").set_payload("not a zip").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,66 @@
+#![allow(clippy::needless_return)]
+// JSON:API documents, asked for with the Accept header
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::header;
+
+const JSON_API: &str = "application/vnd.api+json";
+
+#[actix_web::test]
+async fn resources_and_listings_are_documents() {
+    let app = test::init_service(create_test_app()).await;
+    let get = |uri: &str| TestRequest::get().uri(uri).insert_header(("Accept", JSON_API)).to_request();
+
+    let response = test::call_service(&app, get("/v1/journals/3")).await;
+    assert_eq!(header(&response, "Content-Type"), JSON_API);
+    let document: Value = test::read_body_json(response).await;
+    let journal = &document["data"];
+    assert_eq!((&journal["type"], &journal["id"]), (&json!("journals"), &json!("3")));
+    assert_eq!(journal["attributes"]["title"], "Title 3");
+    assert_eq!(journal["links"]["self"], "/v1/journals/3");
+    assert_eq!(journal["relationships"]["attachments"]["links"]["related"], "/v1/journals/3/attachments");
+
+    let document: Value = test::call_and_read_body_json(&app, get("/v1/tasks?per_page=4&page=2")).await;
+    let ids: Vec<&Value> = document["data"].as_array().unwrap().iter().map(|task| &task["id"]).collect();
+    assert_eq!(ids, [&json!("4"), &json!("5"), &json!("6"), &json!("7")]);
+    assert_eq!(document["links"], json!({
+        "self":     "/v1/tasks?per_page=4&page=2",
+        "first":    "/v1/tasks?per_page=4&page=1",
+        "last":     "/v1/tasks?per_page=4&page=3",
+        "prev":     "/v1/tasks?per_page=4&page=1",
+        "next":     "/v1/tasks?per_page=4&page=3",
+    }));
+    assert_eq!(document["meta"]["total_entries"], 10);
+
+    // plain JSON for everyone else
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/4").to_request()).await;
+    assert_eq!(task["text"], "Do the 4");
+}
+
+#[actix_web::test]
+async fn errors_are_error_objects() {
+    let app = test::init_service(create_test_app()).await;
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/99").insert_header(("Accept", JSON_API)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(header(&response, "Content-Type"), JSON_API);
+    let document: Value = test::read_body_json(response).await;
+    assert_eq!(document, json!({ "errors": [{ "status": "404", "title": "Not Found", "detail": "Not found" }] }));
+
+    // the entry a conflict carries stays with it
+    let request = TestRequest::put().uri("/v1/tasks/1")
+        .insert_header(("Accept", JSON_API))
+        .insert_header(("If-Match", "stale"))
+        .set_json(json!({ "text": "Edited" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let document: Value = test::read_body_json(response).await;
+    let error = &document["errors"][0];
+    assert_eq!(error["status"], "412");
+    assert_eq!(error["meta"]["current"]["text"], "Do the 1");
+}
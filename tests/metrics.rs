@@ -0,0 +1,49 @@
+#![allow(clippy::needless_return)]
+// The Prometheus metrics at /metrics
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::json;
+
+use rest::{app, create_test_app, Config, State};
+
+#[actix_web::test]
+async fn slow_requests_are_counted_per_route() {
+    // every request counts as slow
+    std::env::set_var("JOURNAL_SLOW_REQUEST_MS", "0");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+
+    for id in [1, 2] {
+        let request = TestRequest::put().uri(&format!("/v1/journals/{}", id))
+            .insert_header(("If-Match", "1"))
+            .set_json(json!({ "title": "Edited", "data": "" }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    }
+
+    let metrics = test::call_and_read_body(&app, TestRequest::get().uri("/metrics").to_request()).await;
+    let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+    assert!(metrics.contains("journal_slow_requests_total{method=\"PUT\",route=\"/v1/journals/{id}\"} 2"), "{}", metrics);
+}
+
+#[actix_web::test]
+async fn latencies_and_requests_in_flight_per_route() {
+    let app = test::init_service(create_test_app()).await;
+    for id in [1, 2] {
+        let response = test::call_service(&app, TestRequest::get().uri(&format!("/v1/tasks/{}", id)).to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    let metrics = test::call_and_read_body(&app, TestRequest::get().uri("/metrics").to_request()).await;
+    let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+    let tasks = "method=\"GET\",route=\"/v1/tasks/{id}\"";
+    for line in [
+        format!("journal_request_duration_seconds_bucket{{{},le=\"+Inf\"}} 2", tasks),
+        format!("journal_request_duration_seconds_count{{{}}} 2", tasks),
+        format!("journal_requests_in_flight{{{}}} 0", tasks),
+        // the request reading them
+        String::from("journal_requests_in_flight{method=\"GET\",route=\"/metrics\"} 1"),
+    ] {
+        assert!(metrics.lines().any(|metric| metric == line), "{} missing from\n{}", line, metrics);
+    }
+}
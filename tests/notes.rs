@@ -0,0 +1,74 @@
+#![allow(clippy::needless_return)]
+// Notes, quick captures with the same surface as journals and tasks
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token};
+
+#[actix_web::test]
+async fn notes_are_created_replaced_and_deleted() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/notes")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Call the plumber", "tags": ["home"] }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let note = header(&response, "Location");
+    assert_eq!(note, "/v1/notes/0");
+
+    let response = test::call_service(&app, TestRequest::get().uri(&note).to_request()).await;
+    let etag = header(&response, "ETag");
+    let stored: Value = test::read_body_json(response).await;
+    assert_eq!((&stored["text"], &stored["tags"]), (&json!("Call the plumber"), &json!(["home"])));
+    assert_eq!(stored["_links"]["collection"]["href"], "/v1/notes");
+
+    let put = || TestRequest::put().uri(&note).set_json(json!({ "text": "Plumber on Tuesday" }));
+    let response = test::call_service(&app, put().insert_header(("If-Match", "stale")).to_request()).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    let response = test::call_service(&app, put().insert_header(("If-Match", etag.as_str())).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(header(&response, "ETag"), etag);
+    let stored: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&note).to_request()).await;
+    assert_eq!((&stored["text"], stored.get("tags")), (&json!("Plumber on Tuesday"), None));
+
+    let response = test::call_service(&app, TestRequest::delete().uri(&note).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test::call_service(&app, TestRequest::get().uri(&note).to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn notes_are_listed_in_pages() {
+    let app = test::init_service(create_test_app()).await;
+    for i in 0..5 {
+        let request = TestRequest::post().uri("/v1/notes")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "text": format!("Idea {}", i) }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+    let page: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/notes?page=2&per_page=2").to_request()).await;
+    assert_eq!((&page["total_entries"], &page["total_pages"]), (&json!(5), &json!(3)));
+    assert_eq!(page["entries"][0]["text"], "Idea 2");
+    let tasks: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks?per_page=50").to_request()).await;
+    assert_eq!(tasks["total_entries"], 10);
+}
+
+#[actix_web::test]
+async fn notes_are_short_and_not_blank() {
+    let app = test::init_service(create_test_app()).await;
+    for note in [json!({ "text": " " }), json!({ "text": "x".repeat(2001) }), json!({ "text": "Tagged", "tags": ["a", "a"] })] {
+        let request = TestRequest::post().uri("/v1/notes")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(note)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    }
+    let notes: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/notes").to_request()).await;
+    assert_eq!(notes["total_entries"], 0);
+}
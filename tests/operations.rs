@@ -0,0 +1,101 @@
+#![allow(clippy::needless_return)]
+// Exports and imports answered in the background, followed at /jobs/{id}
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token};
+
+// the job once it is no longer running
+async fn finished<S, B>(app: &S, location: &str) -> Value
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    for _ in 0..100 {
+        let job: Value = test::call_and_read_body_json(app, TestRequest::get().uri(location).to_request()).await;
+        if job["status"] != "running" {
+            return job;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("job {} never finished", location);
+}
+
+#[actix_web::test]
+async fn exports_can_be_answered_in_the_background() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::get().uri("/v1/export")
+        .insert_header(("Prefer", "respond-async"))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(header(&response, "Preference-Applied"), "respond-async");
+    let location = header(&response, "Location");
+    assert!(location.starts_with("/v1/jobs/"));
+    let job: Value = test::read_body_json(response).await;
+    assert_eq!(job["kind"], "GET /v1/export");
+
+    let job = finished(&app, &location).await;
+    assert_eq!(job["status"], "succeeded");
+    assert_eq!(job["result"], format!("{}/result", location));
+    let response = test::call_service(&app, TestRequest::get().uri(&format!("{}/result", location)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "Content-Disposition"), "attachment; filename=\"takeout.json\"");
+    let takeout: Value = test::read_body_json(response).await;
+    assert_eq!(takeout["journals"].as_array().unwrap().len(), 10);
+
+    // without the preference nothing changes
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/export").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn imports_report_progress_and_failures() {
+    let app = test::init_service(create_test_app()).await;
+    let takeout = json!({
+        "version": 1,
+        "exported_at": "2026-10-16T00:00:00Z",
+        "journals": [{
+            "id": 0, "title": "Imported", "data": "",
+            "created_at": "2026-10-15T08:00:00Z", "updated_at": "2026-10-15T08:00:00Z",
+        }],
+        "tasks": [],
+    });
+    let request = TestRequest::post().uri("/v1/import")
+        .insert_header(("Post-Token", token(&app).await))
+        .insert_header(("Prefer", "respond-async, wait=0"))
+        .set_json(&takeout)
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let job = finished(&app, &header(&response, "Location")).await;
+    assert_eq!(job["status"], "succeeded");
+    assert_eq!(job["progress"], json!({ "done": 1, "total": 1 }));
+    let summary: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(job["result"].as_str().unwrap()).to_request()).await;
+    assert_eq!(summary, json!({ "journals": 1, "tasks": 0, "habits": 0, "notes": 0, "bookmarks": 0, "attachments": 0 }));
+
+    let mut unsupported = takeout.clone();
+    unsupported["version"] = json!(99);
+    let request = TestRequest::post().uri("/v1/import")
+        .insert_header(("Post-Token", token(&app).await))
+        .insert_header(("Prefer", "respond-async"))
+        .set_json(&unsupported)
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let location = header(&response, "Location");
+    let job = finished(&app, &location).await;
+    assert_eq!(job["status"], "failed");
+    assert_eq!(job["error"], json!({ "status": 400, "detail": "Unsupported takeout version" }));
+    assert_eq!(job["result"], Value::Null);
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/jobs/unknown").to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
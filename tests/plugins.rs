@@ -0,0 +1,77 @@
+#![cfg(feature = "plugins")]
+#![allow(clippy::needless_return)]
+// WASM plugins loaded from JOURNAL_PLUGIN_DIR, run as hooks
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+
+use rest::{app, Config, State};
+
+mod common;
+use common::{header, token};
+
+// a data segment with the JSON at the offset, and what returns it
+fn answer(at: i64, json: &Value) -> (String, i64) {
+    let json = json.to_string();
+    let data = format!("(data (i32.const {}) \"{}\")", at, json.replace('"', "\\\""));
+    return (data, (at << 32) | json.len() as i64);
+}
+
+// tags new entries, refuses deletes and never finishes updates
+fn rules() -> Vec<u8> {
+    let (tags, tagged) = answer(0, &json!({ "entry": { "tags": ["plugin"] } }));
+    let (error, refused) = answer(256, &json!({ "error": "Entries stay" }));
+    let module = format!(r#"
+        (module
+            (memory (export "memory") 1)
+            {tags}
+            {error}
+            (func (export "alloc") (param i32) (result i32) (i32.const 1024))
+            (func (export "on_create") (param i32 i32) (result i64) (i64.const {tagged}))
+            (func (export "on_update") (param i32 i32) (result i64) (loop $spin (br $spin)) (i64.const 0))
+            (func (export "on_delete") (param i32 i32) (result i64) (i64.const {refused})))
+    "#);
+    return wat::parse_str(module).unwrap();
+}
+
+#[actix_web::test]
+async fn plugins_change_and_refuse_writes() {
+    let dir = std::env::temp_dir().join(format!("journal-plugins-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("rules.wasm"), rules()).unwrap();
+    // neither stops the others from loading
+    std::fs::write(dir.join("broken.wasm"), b"not wasm").unwrap();
+    std::fs::write(dir.join("imports.wasm"), wat::parse_str(r#"(module (import "env" "now" (func)))"#).unwrap()).unwrap();
+    std::env::set_var("JOURNAL_PLUGIN_DIR", &dir);
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Water the plants" }))
+        .to_request();
+    let location = header(&test::call_service(&app, request).await, "Location");
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(task["text"], "Water the plants");
+    assert_eq!(task["tags"], json!(["plugin"]));
+
+    // out of fuel, the patch is undone
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    let request = TestRequest::patch().uri("/v1/tasks/1")
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "done": true }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let problem: Value = test::read_body_json(response).await;
+    assert!(problem["detail"].as_str().unwrap().starts_with("Plugin rules failed"));
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    assert_eq!(task["done"], false);
+
+    let response = test::call_service(&app, TestRequest::delete().uri("/v1/journals/2").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let problem: Value = test::read_body_json(response).await;
+    assert_eq!(problem["detail"], "Entries stay");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,53 @@
+#![allow(clippy::needless_return)]
+// Errors as RFC 7807 problem details
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token};
+
+#[actix_web::test]
+async fn errors_are_problem_details() {
+    let app = test::init_service(create_test_app()).await;
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/99").to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(header(&response, "Content-Type"), "application/problem+json");
+    let problem: Value = test::read_body_json(response).await;
+    assert_eq!(problem, json!({
+        "type":     "about:blank",
+        "title":    "Not Found",
+        "status":   404,
+        "detail":   "Not found",
+        "instance": "/v1/tasks/99",
+    }));
+
+    // validation errors
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Tagged", "tags": [" padded "] }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let problem: Value = test::read_body_json(response).await;
+    assert_eq!(problem["status"], 400);
+    assert!(problem["detail"].as_str().is_some_and(|detail| !detail.is_empty()));
+
+    // failed preconditions carry the entry as it is now
+    let request = TestRequest::put().uri("/v1/tasks/2")
+        .insert_header(("If-Match", "stale"))
+        .set_json(json!({ "text": "Edited" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+    assert_eq!(header(&response, "Content-Type"), "application/problem+json");
+    let problem: Value = test::read_body_json(response).await;
+    assert_eq!((&problem["title"], &problem["instance"]), (&json!("Precondition Failed"), &json!("/v1/tasks/2")));
+    assert_eq!(problem["current"]["text"], "Do the 2");
+
+    // successes are untouched
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/2").to_request()).await;
+    assert_eq!(header(&response, "Content-Type"), "application/json");
+}
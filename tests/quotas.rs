@@ -0,0 +1,122 @@
+#![allow(clippy::needless_return)]
+// Storage quotas and GET /quota
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+
+use rest::{app, Config, State};
+
+mod common;
+use common::{header, token, workspace};
+
+#[actix_web::test]
+async fn writes_over_a_quota_are_refused() {
+    // the only test of this file, so no other sees the variables
+    std::env::set_var("JOURNAL_MAX_ENTRIES", "11");
+    std::env::set_var("JOURNAL_MAX_ATTACHMENT_BYTES", "10");
+    let state = web::Data::new(State::with_sample_data(Config::from_env()));
+    let app = test::init_service(app(state)).await;
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/quota").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let quota: Value = test::read_body_json(response).await;
+    assert_eq!(quota["tasks"], json!({ "used": 10, "limit": 11 }));
+    assert_eq!(quota["habits"], json!({ "used": 0, "limit": 11 }));
+    assert_eq!(quota["bytes"]["limit"], Value::Null);
+    assert!(quota["bytes"]["used"].as_u64().is_some_and(|used| used > 0));
+
+    // the eleventh task fits, the twelfth does not
+    for expected in [StatusCode::CREATED, StatusCode::INSUFFICIENT_STORAGE] {
+        let request = TestRequest::post().uri("/v1/tasks")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "text": "One more" }))
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), expected);
+        if expected == StatusCode::INSUFFICIENT_STORAGE {
+            let problem: Value = test::read_body_json(response).await;
+            assert_eq!(problem["detail"], "Quota exceeded, there are at most 11 tasks");
+        }
+    }
+    // nor one a schedule creates
+    let request = TestRequest::post().uri("/v1/schedules")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "cron": "0 9 * * Mon", "action": { "type": "create_task", "template": { "text": "Weekly" } } }))
+        .to_request();
+    let schedule = header(&test::call_service(&app, request).await, "Location");
+    let request = TestRequest::post().uri(&format!("{}/run", schedule))
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    let problem: Value = test::read_body_json(response).await;
+    assert_eq!(problem["detail"], "Quota exceeded, there are at most 11 tasks");
+    // nor is an entry created offline
+    #[cfg(feature = "crdt")]
+    {
+        let request = TestRequest::post().uri("/v1/sync/merge")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "tasks": [{ "key": "phone-1", "fields": { "text": { "value": "Offline", "at": [1, "phone"] } } }] }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::INSUFFICIENT_STORAGE);
+    }
+    // replacing a task adds no entry
+    let request = TestRequest::put().uri("/v1/tasks/99")
+        .set_json(json!({ "text": "New id" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::INSUFFICIENT_STORAGE);
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/0").to_request()).await;
+    let request = TestRequest::put().uri("/v1/tasks/0")
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "text": "Same id" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    // a split adds all parts but the one taking the task's place
+    for (parts, expected) in [(json!(["Buy", "Cook"]), StatusCode::INSUFFICIENT_STORAGE), (json!(["Buy"]), StatusCode::CREATED)] {
+        let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+        let request = TestRequest::post().uri("/v1/tasks/1/split")
+            .insert_header(("Post-Token", token(&app).await))
+            .insert_header(("If-Match", header(&response, "ETag")))
+            .set_json(json!({ "parts": parts }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), expected);
+    }
+
+    // workspaces have entries of their own
+    let (id, key) = workspace(&app, "Team").await;
+    let request = TestRequest::get().uri(&format!("/v1/workspaces/{}/quota", id))
+        .insert_header(("Workspace-Key", key.as_str()))
+        .to_request();
+    let quota: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(quota["tasks"]["used"], 0);
+
+    // attachments are counted by their content
+    let request = TestRequest::post().uri("/v1/journals/1/uploads")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "name": "big.txt", "length": 11 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::INSUFFICIENT_STORAGE);
+    let request = TestRequest::post().uri("/v1/journals/1/uploads")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "name": "small.txt", "length": 6 }))
+        .to_request();
+    let upload: Value = test::call_and_read_body_json(&app, request).await;
+    let uri = format!("/v1/journals/1/uploads/{}", upload["upload"].as_str().unwrap());
+    let request = TestRequest::patch().uri(&uri)
+        .insert_header(("Upload-Offset", "0"))
+        .set_payload("hello!")
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NO_CONTENT);
+    let response = test::call_service(&app, TestRequest::post().uri(&format!("{}/finalize", uri)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let quota: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/quota").to_request()).await;
+    assert_eq!(quota["attachment_bytes"], json!({ "used": 6, "limit": 10 }));
+
+    // other content no longer fits
+    let request = TestRequest::post().uri("/v1/journals/2/uploads")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "name": "other.txt", "length": 6 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::INSUFFICIENT_STORAGE);
+}
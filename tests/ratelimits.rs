@@ -0,0 +1,42 @@
+#![allow(clippy::needless_return)]
+// Rate limits and their X-RateLimit-* headers
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+
+use rest::{app, Config, State};
+
+mod common;
+use common::header;
+
+#[actix_web::test]
+async fn clients_are_told_their_rate_limit() {
+    // the only test of this file, so no other sees the variables
+    std::env::set_var("JOURNAL_RATE_LIMIT", "2");
+    std::env::set_var("JOURNAL_RATE_LIMIT_WINDOW", "3600");
+    let state = web::Data::new(State::with_sample_data(Config::from_env()));
+    let app = test::init_service(app(state)).await;
+    let get = |address: &str| TestRequest::get().uri("/v1/tasks").peer_addr(address.parse().unwrap()).to_request();
+
+    let response = test::call_service(&app, get("10.0.0.1:4000")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "X-RateLimit-Limit"), "2");
+    assert_eq!(header(&response, "X-RateLimit-Remaining"), "1");
+    let reset: i64 = header(&response, "X-RateLimit-Reset").parse().unwrap();
+    assert!((reset - chrono::Utc::now().timestamp() - 3600).abs() <= 1);
+
+    // another port is the same client
+    let response = test::call_service(&app, get("10.0.0.1:4001")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "X-RateLimit-Remaining"), "0");
+    let response = test::call_service(&app, get("10.0.0.1:4000")).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header(&response, "X-RateLimit-Remaining"), "0");
+    assert_eq!(header(&response, "X-RateLimit-Reset"), reset.to_string());
+    assert!(header(&response, "Retry-After").parse::<u64>().unwrap() > 3500);
+
+    // others have limits of their own
+    let response = test::call_service(&app, get("10.0.0.2:4000")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header(&response, "X-RateLimit-Remaining"), "1");
+}
@@ -0,0 +1,52 @@
+#![allow(clippy::needless_return)]
+// Settings read again with POST /admin/reload, keeping what is in memory
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use rest::{app, Config, State};
+
+mod common;
+use common::token;
+
+const ADMIN: (&str, &str) = ("Authorization", "Bearer secret");
+
+#[actix_web::test]
+async fn reloads_change_settings_without_losing_state() {
+    let file = std::env::temp_dir().join(format!("journal-reload-{}.env", std::process::id()));
+    std::fs::write(&file, "# nothing yet\n").unwrap();
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    std::env::set_var("JOURNAL_CONFIG_FILE", &file);
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    let old = token(&app).await;
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Kept" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+
+    std::fs::write(&file, "JOURNAL_TOKEN_TTL=1\nJOURNAL_RATE_LIMIT = 3\nJOURNAL_LOG_LEVEL=warn\n").unwrap();
+    let request = TestRequest::post().uri("/admin/reload").insert_header(ADMIN).to_request();
+    let settings: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(settings, json!({ "token_ttl": 1, "rate_limit": 3, "rate_limit_window": 60, "log_level": "warn" }));
+
+    // what stays when the file is gone
+    std::env::set_var("JOURNAL_CONFIG_FILE", file.with_extension("gone"));
+    let request = TestRequest::post().uri("/admin/reload").insert_header(ADMIN).to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    // the token outlived the new lifetime, the task created before is there
+    actix_web::rt::time::sleep(Duration::from_millis(1100)).await;
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", old))
+        .set_json(json!({ "text": "Late" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/10").to_request()).await;
+    assert_eq!(task["text"], "Kept");
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/1").to_request()).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let _ = std::fs::remove_file(&file);
+}
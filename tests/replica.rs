@@ -0,0 +1,100 @@
+#![allow(clippy::needless_return)]
+// A read replica following a primary on an ephemeral port
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::{web, HttpServer};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+use rest::{app, spawn_background, Config, State};
+
+mod common;
+use common::{header, token};
+
+const ADMIN: (&str, &str) = ("Authorization", "Bearer secret");
+
+// the status, ETag and body of a GET
+async fn read<S, B>(app: &S, uri: &str) -> (StatusCode, Option<String>, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, TestRequest::get().uri(uri).to_request()).await;
+    let status = response.status();
+    let etag = response.headers().get("ETag").map(|etag| String::from(etag.to_str().unwrap()));
+    let body = serde_json::from_slice(&test::read_body(response).await).unwrap_or(Value::Null);
+    return (status, etag, body);
+}
+
+// waits for the replica to answer the GET as `expected` does
+async fn caught_up<S, B>(replica: &S, uri: &str, expected: (StatusCode, Option<String>, Value))
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let mut read_back = None;
+    for _ in 0..50 {
+        let answer = read(replica, uri).await;
+        if answer == expected {
+            return;
+        }
+        read_back = Some(answer);
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("The replica answered {:?} for {}, not {:?}", read_back, uri, expected);
+}
+
+#[actix_web::test]
+async fn replicas_follow_the_primary_and_redirect_writes() {
+    let primary = web::Data::new(State::with_sample_data(Config::default()));
+    let shared = primary.clone();
+    let server = HttpServer::new(move || app(shared.clone()))
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let url = format!("http://{}", server.addrs()[0]);
+    actix_web::rt::spawn(server.run());
+    let primary = test::init_service(app(primary)).await;
+
+    std::env::set_var("JOURNAL_PRIMARY_URL", &url);
+    std::env::set_var("JOURNAL_ADMIN_TOKEN", "secret");
+    let state = web::Data::new(State::open(Config::from_env()).unwrap());
+    spawn_background(&state);
+    let replica = test::init_service(app(state)).await;
+
+    // the copy keeps the primary's ETags
+    caught_up(&replica, "/v1/tasks/1", read(&primary, "/v1/tasks/1").await).await;
+    caught_up(&replica, "/v1/journals/2", read(&primary, "/v1/journals/2").await).await;
+
+    let (_, etag, _) = read(&primary, "/v1/tasks/1").await;
+    let request = TestRequest::patch().uri("/v1/tasks/1")
+        .insert_header(("If-Match", etag.unwrap()))
+        .set_json(json!({ "done": true }))
+        .to_request();
+    assert_eq!(test::call_service(&primary, request).await.status(), StatusCode::OK);
+    let request = TestRequest::delete().uri("/v1/tasks/2").to_request();
+    assert_eq!(test::call_service(&primary, request).await.status(), StatusCode::OK);
+    caught_up(&replica, "/v1/tasks/1", read(&primary, "/v1/tasks/1").await).await;
+    caught_up(&replica, "/v1/tasks/2", read(&primary, "/v1/tasks/2").await).await;
+
+    let request = TestRequest::post().uri("/v1/tasks?dry=1")
+        .insert_header(("Post-Token", token(&primary).await))
+        .set_json(json!({ "text": "Water the plants" }))
+        .to_request();
+    let response = test::call_service(&replica, request).await;
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(header(&response, "Location"), format!("{}/v1/tasks?dry=1", url));
+    // as is a read that would create the day's journal
+    let response = test::call_service(&replica, TestRequest::get().uri("/v1/journals/today?date=2031-05-04").to_request()).await;
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(header(&response, "Location"), format!("{}/v1/journals/today?date=2031-05-04", url));
+
+    let request = TestRequest::get().uri("/admin/replication").insert_header(ADMIN).to_request();
+    let status: Value = test::call_and_read_body_json(&replica, request).await;
+    assert_eq!(status["primary"], url);
+    assert!(status["changes"].as_u64().unwrap() > 0);
+    assert!(status["sync_token"].is_string());
+}
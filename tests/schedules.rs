@@ -0,0 +1,138 @@
+#![allow(clippy::needless_return)]
+// Actions scheduled with cron expressions
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web::{self, Bytes};
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use std::sync::{Arc, Mutex};
+
+use rest::{app, create_test_app, Config, State};
+
+mod common;
+use common::{header, token};
+
+#[actix_web::test]
+async fn schedules_are_managed_and_run() {
+    let app = test::init_service(create_test_app()).await;
+
+    let request = TestRequest::post().uri("/v1/schedules")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "cron": "every day", "action": { "type": "export" } }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    // webhooks only go to public addresses
+    for url in ["http://127.0.0.1:8080/hook", "http://169.254.169.254/latest/meta-data", "http://localhost/hook", "ftp://example.com"] {
+        let request = TestRequest::post().uri("/v1/schedules")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "cron": "0 0 * * *", "action": { "type": "webhook", "url": url } }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    }
+
+    let request = TestRequest::post().uri("/v1/schedules")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({
+            "cron":     "0 9 * * Mon",
+            "action":   { "type": "create_task", "template": { "text": "Review of {date}", "tags": ["weekly"] } },
+        }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = header(&response, "Location");
+    let schedule: Value = test::read_body_json(response).await;
+    assert_eq!(location, format!("/v1/schedules/{}", schedule["id"]));
+    assert_eq!(schedule["runs"], 0);
+    assert!(schedule["next_run"].as_str().is_some_and(|next_run| next_run.ends_with("T09:00:00Z")));
+
+    // paused, it has no next run but can still be run by hand
+    let request = TestRequest::patch().uri(&location).set_json(json!({ "paused": true })).to_request();
+    let schedule: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(schedule["paused"], true);
+    assert_eq!(schedule["next_run"], Value::Null);
+    let request = TestRequest::post().uri(&format!("{}/run", location))
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let schedule: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(schedule["runs"], 1);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/10").to_request()).await;
+    assert!(task["text"].as_str().unwrap().starts_with("Review of 20"));
+    assert_eq!(task["tags"], json!(["weekly"]));
+
+    // exports land in the blob store
+    let request = TestRequest::post().uri("/v1/schedules")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "cron": "0 0 3 * * *", "action": { "type": "export" } }))
+        .to_request();
+    let export = header(&test::call_service(&app, request).await, "Location");
+    let response = test::call_service(&app, TestRequest::get().uri(&format!("{}/export", export)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let request = TestRequest::post().uri(&format!("{}/run", export))
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let takeout: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&format!("{}/export", export)).to_request()).await;
+    assert_eq!(takeout["tasks"].as_array().unwrap().len(), 11);
+
+    let schedules: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/schedules").to_request()).await;
+    assert_eq!(schedules.as_array().unwrap().len(), 2);
+    assert_eq!(test::call_service(&app, TestRequest::delete().uri(&location).to_request()).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, TestRequest::get().uri(&location).to_request()).await.status(), StatusCode::NOT_FOUND);
+}
+
+// what a webhook receiver got: the signature header and the body
+type Received = Arc<Mutex<Vec<(Option<String>, Bytes)>>>;
+
+fn receiver() -> (String, Received) {
+    let received = Received::default();
+    let shared = received.clone();
+    let server = HttpServer::new(move || {
+        let received = shared.clone();
+        App::new().default_service(web::to(move |request: HttpRequest, body: Bytes| {
+            let signature = request.headers().get("X-Journal-Signature").map(|value| String::from(value.to_str().unwrap()));
+            received.lock().unwrap().push((signature, body));
+            async { HttpResponse::Ok().finish() }
+        }))
+    })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+    let url = format!("http://{}/hook", server.addrs()[0]);
+    actix_web::rt::spawn(server.run());
+    return (url, received);
+}
+
+#[actix_web::test]
+async fn webhook_deliveries_are_signed() {
+    // the only test of this file reading the environment; the receiver is on loopback
+    std::env::set_var("JOURNAL_FETCH_PRIVATE", "true");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    let (url, received) = receiver();
+
+    let request = TestRequest::post().uri("/v1/schedules")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({
+            "cron":     "0 0 * * *",
+            "action":   { "type": "webhook", "url": url, "body": { "hello": "world" }, "secret": "hush" },
+        }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    let location = header(&response, "Location");
+    let schedule: Value = test::read_body_json(response).await;
+    // the secret is never shown again
+    assert_eq!(schedule["action"], json!({ "type": "webhook", "url": url, "body": { "hello": "world" } }));
+
+    let request = TestRequest::post().uri(&format!("{}/run", location))
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let schedule: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(schedule["failures"], 0);
+    let (signature, body) = received.lock().unwrap().pop().unwrap();
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap(), json!({ "hello": "world" }));
+    let mut mac = Hmac::<Sha256>::new_from_slice(b"hush").unwrap();
+    mac.update(&body);
+    let expected: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    assert_eq!(signature, Some(format!("sha256={}", expected)));
+}
@@ -0,0 +1,82 @@
+#![allow(clippy::needless_return)]
+// Custom metadata and the schemas it is checked against
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token};
+
+#[actix_web::test]
+async fn metadata_follows_the_schema() {
+    let app = test::init_service(create_test_app()).await;
+    let schema = json!({
+        "type": "object",
+        "properties": {
+            "priority": { "type": "integer", "minimum": 1, "maximum": 5 },
+            "client":   { "type": "string" },
+        },
+        "required": ["priority"],
+    });
+    let request = TestRequest::put().uri("/v1/schema/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(&schema)
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    let stored: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/schema/tasks").to_request()).await;
+    assert_eq!(stored, schema);
+
+    let post = |metadata: Value, token: String| TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token))
+        .set_json(json!({ "text": "Invoice", "done": false, "metadata": metadata }))
+        .to_request();
+    for metadata in [json!({}), json!({ "priority": 7 }), json!({ "priority": 2, "client": 42 })] {
+        let response = test::call_service(&app, post(metadata, token(&app).await)).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+    let response = test::call_service(&app, post(json!({ "priority": 2, "client": "ACME" }), token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&header(&response, "Location")).to_request()).await;
+    assert_eq!(task["metadata"]["client"], "ACME");
+
+    // replacing is checked as well, the other kinds are not
+    let request = TestRequest::put().uri("/v1/tasks/0")
+        .insert_header(("If-Match", "1"))
+        .set_json(json!({ "text": "Do the 0", "done": true }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let reason = test::read_body(response).await;
+    assert!(String::from_utf8(reason.to_vec()).unwrap().contains("priority"));
+    let request = TestRequest::post().uri("/v1/habits")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "name": "Walk", "metadata": { "steps": 10000 } }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+
+    let request = TestRequest::delete().uri("/v1/schema/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let response = test::call_service(&app, post(json!({}), token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn broken_schemas_are_refused() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::put().uri("/v1/schema/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "type": "integer-ish" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+
+    let request = TestRequest::put().uri("/v1/schema/journals")
+        .set_json(json!({ "type": "object" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/schema/journals").to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
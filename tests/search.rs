@@ -0,0 +1,277 @@
+#![allow(clippy::needless_return)]
+// Ranked search over journals and tasks
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token, workspace};
+
+#[actix_web::test]
+async fn title_matches_and_recent_entries_rank_first() {
+    let app = test::init_service(create_test_app()).await;
+    let journal = |title: &str, data: &str, updated_at: &str| json!({
+        "id": 0,
+        "title": title,
+        "data": data,
+        "encrypted": false,
+        "created_at": "2026-01-01T00:00:00Z",
+        "updated_at": updated_at,
+    });
+    let takeout = json!({
+        "version": 1,
+        "exported_at": "2026-10-16T00:00:00Z",
+        "journals": [
+            journal("Monday", "Weeded the garden & planted <tulips>.", "2025-01-01T00:00:00Z"),
+            journal("Tuesday", "The garden needs rain.", "2026-10-15T00:00:00Z"),
+            journal("Garden plans", "Nothing yet", "2024-01-01T00:00:00Z"),
+        ],
+        "tasks": [],
+    });
+    let request = TestRequest::post().uri("/v1/import")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(takeout)
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let request = TestRequest::get().uri("/v1/search?q=Garden").to_request();
+    let found: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(found["total"], 3);
+    let titles: Vec<&str> = found["results"].as_array().unwrap().iter()
+        .map(|hit| hit["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["Garden plans", "Tuesday", "Monday"]);
+
+    let title_hit = &found["results"][0];
+    assert_eq!(title_hit["field"], "title");
+    assert_eq!(title_hit["highlights"], json!([[0, 6]]));
+    let body_hit = &found["results"][2];
+    assert_eq!(body_hit["field"], "data");
+    assert_eq!(body_hit["snippet"], "Weeded the garden & planted <tulips>.");
+    assert_eq!(body_hit["highlights"], json!([[11, 17]]));
+    assert_eq!(body_hit["highlighted"], "Weeded the <mark>garden</mark> &amp; planted &lt;tulips&gt;.");
+}
+
+#[actix_web::test]
+async fn every_word_of_the_query_has_to_match() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "title": "Secret", "data": "c2VjcmV0IGdhcmRlbg==", "encrypted": true }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+
+    // the sample tasks are "Do the 0" to "Do the 9"
+    let request = TestRequest::get().uri("/v1/search?q=do+7").to_request();
+    let found: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(found["total"], 1);
+    assert_eq!(found["results"][0]["kind"], "task");
+    assert_eq!(found["results"][0]["title"], "Do the 7");
+
+    let request = TestRequest::get().uri("/v1/search?q=world&kind=tasks").to_request();
+    let found: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(found["total"], 0);
+    let request = TestRequest::get().uri("/v1/search?q=world&limit=2").to_request();
+    let found: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(found["total"], 10);
+    assert_eq!(found["results"].as_array().unwrap().len(), 2);
+
+    // encrypted data is never searched, only the title
+    let request = TestRequest::get().uri("/v1/search?q=c2VjcmV0IGdhcmRlbg").to_request();
+    let found: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(found["total"], 0);
+
+    for uri in ["/v1/search?q=%20", "/v1/search?q=x&kind=habits"] {
+        let response = test::call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[actix_web::test]
+async fn fuzzy_searches_forgive_typos() {
+    let app = test::init_service(create_test_app()).await;
+    for text in ["Buy groceries", "Buy milk", "Buy silk"] {
+        let request = TestRequest::post().uri("/v1/tasks")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(json!({ "text": text, "done": false }))
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+    let search = |query: &str| TestRequest::get().uri(&format!("/v1/search?kind=tasks&q={}", query)).to_request();
+
+    let found: Value = test::call_and_read_body_json(&app, search("groserys")).await;
+    assert_eq!(found["total"], 0);
+    let found: Value = test::call_and_read_body_json(&app, search("groserys&fuzzy=true")).await;
+    assert_eq!(found["total"], 1);
+    assert_eq!(found["results"][0]["title"], "Buy groceries");
+    assert_eq!(found["results"][0]["highlights"], json!([[4, 13]]));
+
+    // exact matches rank above near misses, short words have to be exact
+    let found: Value = test::call_and_read_body_json(&app, search("milk&fuzzy=true")).await;
+    let titles: Vec<&str> = found["results"].as_array().unwrap().iter()
+        .map(|hit| hit["title"].as_str().unwrap())
+        .collect();
+    assert_eq!(titles, ["Buy milk", "Buy silk"]);
+    let found: Value = test::call_and_read_body_json(&app, search("bye&fuzzy=true")).await;
+    assert_eq!(found["total"], 0);
+}
+
+#[actix_web::test]
+async fn searches_follow_every_write() {
+    let app = test::init_service(create_test_app()).await;
+    let (wid, key) = workspace(&app, "Home").await;
+    let search = |query: &str| TestRequest::get()
+        .uri(&format!("/v1/workspaces/{}/search?q={}", wid, query))
+        .insert_header(("Workspace-Key", key.as_str()))
+        .to_request();
+
+    let request = TestRequest::post().uri(&format!("/v1/workspaces/{}/tasks", wid))
+        .insert_header(("Post-Token", token(&app).await))
+        .insert_header(("Workspace-Key", key.as_str()))
+        .set_json(json!({ "text": "Paint the fence", "done": false }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = header(&response, "Location");
+    let found: Value = test::call_and_read_body_json(&app, search("fence")).await;
+    assert_eq!(found["total"], 1);
+    // the server's own tasks are not in the workspace
+    let found: Value = test::call_and_read_body_json(&app, search("do")).await;
+    assert_eq!(found["total"], 0);
+
+    let etag = header(&test::call_service(&app, TestRequest::get().uri(&location)
+        .insert_header(("Workspace-Key", key.as_str()))
+        .to_request()).await, "ETag");
+    let request = TestRequest::put().uri(&location)
+        .insert_header(("If-Match", etag))
+        .insert_header(("Workspace-Key", key.as_str()))
+        .set_json(json!({ "text": "Paint the shed", "done": false }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let found: Value = test::call_and_read_body_json(&app, search("fence")).await;
+    assert_eq!(found["total"], 0);
+    let found: Value = test::call_and_read_body_json(&app, search("shed")).await;
+    assert_eq!(found["total"], 1);
+
+    let request = TestRequest::delete().uri(&location)
+        .insert_header(("Workspace-Key", key.as_str()))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let found: Value = test::call_and_read_body_json(&app, search("shed")).await;
+    assert_eq!(found["total"], 0);
+}
+
+#[actix_web::test]
+async fn tags_and_titles_are_completed() {
+    let app = test::init_service(create_test_app()).await;
+    let tagged = [
+        ("/v1/journals", json!({ "title": "Roadmap", "data": "Q4", "tags": ["project", "work"] })),
+        ("/v1/tasks", json!({ "text": "Prototype", "done": false, "tags": ["Project", "prototype"] })),
+        ("/v1/tasks", json!({ "text": "Profile page", "done": false, "tags": ["project"] })),
+    ];
+    for (uri, body) in tagged {
+        let request = TestRequest::post().uri(uri)
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(body)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+
+    let request = TestRequest::get().uri("/v1/autocomplete?q=pro&type=tags").to_request();
+    let tags: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(tags, json!([
+        { "value": "project", "count": 2 },
+        { "value": "Project", "count": 1 },
+        { "value": "prototype", "count": 1 },
+    ]));
+    let request = TestRequest::get().uri("/v1/autocomplete?q=PRO&type=titles&limit=1").to_request();
+    let titles: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(titles, json!([{ "value": "Profile page", "count": 1 }]));
+
+    // tags are trimmed and given once, also when patched in
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Untidy", "done": false, "tags": ["a", "a"] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/0").to_request()).await;
+    let request = TestRequest::patch().uri("/v1/tasks/0")
+        .insert_header(("If-Match", header(&response, "ETag")))
+        .set_json(json!({ "done": true, "tags": [" spaced "] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/0").to_request()).await;
+    assert_eq!(task["done"], false);
+    assert!(task.get("tags").is_none());
+}
+
+#[actix_web::test]
+async fn related_journals_share_tags_or_words() {
+    let app = test::init_service(create_test_app()).await;
+    let journals = [
+        json!({ "title": "Sourdough starter", "data": "Fed the sourdough starter with rye flour.", "tags": ["baking"] }),
+        json!({ "title": "Bread", "data": "Baked a sourdough loaf with rye." }),
+        json!({ "title": "Cycling", "data": "Rode around the lake.", "tags": ["baking"] }),
+        json!({ "title": "Taxes", "data": "Filed them, finally." }),
+    ];
+    let mut locations = Vec::new();
+    for journal in journals {
+        let request = TestRequest::post().uri("/v1/journals")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(journal)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        locations.push(header(&response, "Location"));
+    }
+
+    let request = TestRequest::get().uri(&format!("{}/related", locations[0])).to_request();
+    let related: Value = test::call_and_read_body_json(&app, request).await;
+    let related = related.as_array().unwrap();
+    assert_eq!(related.len(), 2);
+    assert_eq!(related[0]["title"], "Cycling");
+    assert_eq!(related[0]["shared_tags"], json!(["baking"]));
+    assert_eq!(related[1]["title"], "Bread");
+    let words: Vec<&str> = related[1]["shared_words"].as_array().unwrap().iter().map(|word| word.as_str().unwrap()).collect();
+    assert!(words.contains(&"sourdough") && words.contains(&"rye"));
+
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/journals/99/related").to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn near_duplicate_journals_are_clustered() {
+    let app = test::init_service(create_test_app()).await;
+    let text = "Walked the dog along the river this morning, met the neighbours and talked about the new bakery \
+        that opened on the corner of the market square next to the old library";
+    let journals = [
+        json!({ "title": "Morning", "data": text }),
+        json!({ "title": "Morning", "data": text.replace("dog", "dogs") }),
+        json!({ "title": "Evening", "data": "Read a book about the history of bread until late at night" }),
+    ];
+    for journal in journals {
+        let request = TestRequest::post().uri("/v1/journals")
+            .insert_header(("Post-Token", token(&app).await))
+            .set_json(journal)
+            .to_request();
+        assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    }
+
+    // the ten sample journals are all "Hello World!"
+    let clusters: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals/duplicates").to_request()).await;
+    let clusters = clusters.as_array().unwrap();
+    assert_eq!(clusters.len(), 2);
+    assert_eq!(clusters[0]["journals"].as_array().unwrap().len(), 10);
+    assert_eq!(clusters[0]["similarity"], 1.0);
+    assert_eq!(clusters[1]["journals"], json!([{ "id": 10, "title": "Morning" }, { "id": 11, "title": "Morning" }]));
+    let similarity = clusters[1]["similarity"].as_f64().unwrap();
+    assert!(similarity > 0.8 && similarity < 1.0);
+
+    let request = TestRequest::get().uri("/v1/journals/duplicates?threshold=0.95").to_request();
+    let clusters: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(clusters.as_array().unwrap().len(), 1);
+    let request = TestRequest::get().uri("/v1/journals/duplicates?threshold=0.2").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
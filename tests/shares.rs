@@ -0,0 +1,193 @@
+#![allow(clippy::needless_return)]
+// Public share links to single journals
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token};
+
+#[actix_web::test]
+async fn shared_journals_are_readable_until_revoked() {
+    let app = test::init_service(create_test_app()).await;
+
+    let response = test::call_service(&app, TestRequest::post().uri("/v1/journals/4/share").to_request()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let request = TestRequest::post().uri("/v1/journals/4/share")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let url = header(&response, "Location");
+    let share: Value = test::read_body_json(response).await;
+    assert_eq!(share["url"], url.as_str());
+
+    let response = test::call_service(&app, TestRequest::get().uri(&url).to_request()).await;
+    assert_eq!(header(&response, "Cache-Control"), "no-store");
+    let journal: Value = test::read_body_json(response).await;
+    assert_eq!(journal["title"], "Title 4");
+
+    let request = TestRequest::get().uri(&url).insert_header(("Accept", "text/html")).to_request();
+    let page = test::call_and_read_body(&app, request).await;
+    let page = String::from_utf8(page.to_vec()).unwrap();
+    assert!(page.contains("<h1>Title 4</h1>"));
+    assert!(page.contains("<p>Hello World!</p>"));
+
+    // the tokens are the secret, so listing them takes a write token
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/journals/4/share").to_request()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let request = TestRequest::get().uri("/v1/journals/4/share")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let shares: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(shares.as_array().unwrap().len(), 1);
+
+    let request = TestRequest::delete().uri(&format!("/v1/journals/4/share/{}", share["token"].as_str().unwrap()))
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test::call_service(&app, TestRequest::get().uri(&url).to_request()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn revoking_every_share_of_a_journal() {
+    let app = test::init_service(create_test_app()).await;
+    let mut urls = Vec::new();
+    for journal in [1, 1, 2] {
+        let request = TestRequest::post().uri(&format!("/v1/journals/{}/share", journal))
+            .insert_header(("Post-Token", token(&app).await))
+            .to_request();
+        urls.push(header(&test::call_service(&app, request).await, "Location"));
+    }
+
+    let request = TestRequest::delete().uri("/v1/journals/1/share")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut statuses = Vec::new();
+    for url in &urls {
+        statuses.push(test::call_service(&app, TestRequest::get().uri(url).to_request()).await.status());
+    }
+    assert_eq!(statuses, [StatusCode::NOT_FOUND, StatusCode::NOT_FOUND, StatusCode::OK]);
+
+    let request = TestRequest::post().uri("/v1/journals/42/share")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn shares_go_away_with_their_journal() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/journals/4/share")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let url = header(&test::call_service(&app, request).await, "Location");
+
+    assert_eq!(test::call_service(&app, TestRequest::delete().uri("/v1/journals/4").to_request()).await.status(), StatusCode::OK);
+    let request = TestRequest::put().uri("/v1/journals/4")
+        .set_json(json!({ "title": "Someone else's", "data": "Private" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, TestRequest::get().uri(&url).to_request()).await.status(), StatusCode::NOT_FOUND);
+    let request = TestRequest::get().uri("/v1/journals/4/share")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let shares: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(shares, json!([]));
+}
+
+#[actix_web::test]
+async fn shared_pages_only_link_to_safe_schemes() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/journals/4/share")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let url = header(&test::call_service(&app, request).await, "Location");
+    let data = "[a](javascript:alert(1)) [b](JavaScript:alert(2)) <javascript:alert(3)> ![c](data:text/html,x) [d](https://example.com) [e](/v1/journals/1) [f](#top)";
+    let request = TestRequest::put().uri("/v1/journals/4")
+        .insert_header(("If-Match", "1"))
+        .set_json(json!({ "title": "Title 4", "data": data }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let request = TestRequest::get().uri(&url).insert_header(("Accept", "text/html")).to_request();
+    let page = String::from_utf8(test::call_and_read_body(&app, request).await.to_vec()).unwrap();
+    assert!(!page.to_lowercase().contains("href=\"javascript:"));
+    assert!(!page.contains("src=\"data:"));
+    assert!(page.contains("<a href=\"\">a</a>"));
+    assert!(page.contains("href=\"https://example.com\""));
+    assert!(page.contains("href=\"/v1/journals/1\""));
+    assert!(page.contains("href=\"#top\""));
+}
+
+#[actix_web::test]
+async fn shared_pages_are_rendered_again_after_a_write() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/journals/4/share")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let url = header(&test::call_service(&app, request).await, "Location");
+    let page = || TestRequest::get().uri(&url).insert_header(("Accept", "text/html")).to_request();
+    let metric = |metrics: &str, name: &str| metrics.lines()
+        .find_map(|line| line.strip_prefix(&format!("{} ", name)).map(String::from))
+        .unwrap();
+
+    for _ in 0..2 {
+        let body = test::call_and_read_body(&app, page()).await;
+        assert!(String::from_utf8(body.to_vec()).unwrap().contains("<p>Hello World!</p>"));
+    }
+    let request = TestRequest::put().uri("/v1/journals/4")
+        .insert_header(("If-Match", "1"))
+        .set_json(serde_json::json!({ "title": "Title 4", "data": "*Edited*" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let body = test::call_and_read_body(&app, page()).await;
+    assert!(String::from_utf8(body.to_vec()).unwrap().contains("<p><em>Edited</em></p>"));
+
+    let metrics = test::call_and_read_body(&app, TestRequest::get().uri("/metrics").to_request()).await;
+    let metrics = String::from_utf8(metrics.to_vec()).unwrap();
+    assert_eq!(metric(&metrics, "journal_render_cache_hits_total"), "1");
+    assert_eq!(metric(&metrics, "journal_render_cache_misses_total"), "2");
+}
+
+#[actix_web::test]
+async fn scheduled_journals_stay_drafts_until_published() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/journals/4/share")
+        .insert_header(("Post-Token", token(&app).await))
+        .to_request();
+    let url = header(&test::call_service(&app, request).await, "Location");
+
+    let schedule = |etag: &str, publish_at: &str| TestRequest::put().uri("/v1/journals/4")
+        .insert_header(("If-Match", etag))
+        .set_json(json!({ "title": "Title 4", "data": "Soon", "publish_at": publish_at }))
+        .to_request();
+    let response = test::call_service(&app, schedule("1", "2999-01-01T00:00:00Z")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = header(&response, "ETag");
+    let listing: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals").to_request()).await;
+    assert_eq!(listing["total_entries"], 9);
+    let feed = test::call_and_read_body(&app, TestRequest::get().uri("/v1/journals/feed.atom").to_request()).await;
+    assert!(!String::from_utf8(feed.to_vec()).unwrap().contains("/journals/4<"));
+    assert_eq!(test::call_service(&app, TestRequest::get().uri(&url).to_request()).await.status(), StatusCode::NOT_FOUND);
+    // the author still reaches it
+    let journal: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals/4").to_request()).await;
+    assert_eq!(journal["publish_at"], "2999-01-01T00:00:00Z");
+
+    let response = test::call_service(&app, schedule(&etag, "2000-01-01T00:00:00Z")).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let listing: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/journals").to_request()).await;
+    assert_eq!(listing["total_entries"], 10);
+    let feed = test::call_and_read_body(&app, TestRequest::get().uri("/v1/journals/feed.atom").to_request()).await;
+    assert!(String::from_utf8(feed.to_vec()).unwrap().contains("/journals/4<"));
+    assert_eq!(test::call_service(&app, TestRequest::get().uri(&url).to_request()).await.status(), StatusCode::OK);
+}
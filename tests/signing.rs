@@ -0,0 +1,93 @@
+#![allow(clippy::needless_return)]
+// Writes signed with a nonce and timestamp, against replays
+use actix_web::http::{Method, StatusCode};
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+
+use rest::{app, Config, State};
+
+#[cfg(feature = "grpc")]
+mod proto {
+    tonic::include_proto!("journal");
+}
+
+const SECRET: &str = "s3cret";
+
+// the request with the headers of a signature made at `timestamp`
+fn signed(method: Method, uri: &str, body: &str, timestamp: i64, nonce: &str) -> TestRequest {
+    let message = format!("{}\n{}\n{}\n{}\n{}", timestamp, nonce, method, uri, sha256::digest(body));
+    let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+    mac.update(message.as_bytes());
+    let signature: String = mac.finalize().into_bytes().iter().map(|byte| format!("{:02x}", byte)).collect();
+    return TestRequest::default().method(method).uri(uri)
+        .insert_header(("X-Journal-Timestamp", timestamp.to_string()))
+        .insert_header(("X-Journal-Nonce", nonce))
+        .insert_header(("X-Journal-Signature", signature))
+        .insert_header(("Content-Type", "application/json"))
+        .set_payload(String::from(body));
+}
+
+#[actix_web::test]
+async fn writes_have_to_be_signed_once() {
+    // the only test of this file, so no other sees the variable
+    std::env::set_var("JOURNAL_SIGNING_SECRET", SECRET);
+    let state = web::Data::new(State::with_sample_data(Config::from_env()));
+    let app = test::init_service(app(state.clone())).await;
+    let now = chrono::Utc::now().timestamp();
+
+    // reads need no signature
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks").to_request()).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = test::call_service(&app, TestRequest::post().uri("/v1/tokens").to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::read_body(response).await, "Writes have to be signed");
+
+    let token = test::call_and_read_body(&app, signed(Method::POST, "/v1/tokens", "", now, "n1").to_request()).await;
+    let token = String::from_utf8(token.to_vec()).unwrap();
+    let body = json!({ "text": "Signed" }).to_string();
+    let request = signed(Method::POST, "/v1/tasks?fields=text", &body, now, "n2")
+        .insert_header(("Post-Token", token.as_str()))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+
+    // a replay within the skew has a used nonce, a later one an old timestamp
+    let request = signed(Method::POST, "/v1/tasks?fields=text", &body, now, "n2").to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(test::read_body(response).await, "The nonce was used already");
+    let request = signed(Method::POST, "/v1/tasks", &body, now - 600, "n3").to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(test::read_body(response).await, "The signature is too old");
+
+    // the signature covers the body
+    let request = signed(Method::POST, "/v1/tasks", &body, now, "n4")
+        .set_payload(json!({ "text": "Changed" }).to_string())
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(test::read_body(response).await, "Bad signature");
+
+    // the gRPC service cannot check signatures, so it takes no writes at all
+    #[cfg(feature = "grpc")]
+    {
+        use proto::journals_client::JournalsClient;
+        rest::spawn_background(&state);
+        let mut client = None;
+        for _ in 0..50 {
+            if let Ok(connected) = JournalsClient::connect("http://127.0.0.1:50051").await {
+                client = Some(connected);
+                break;
+            }
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        let mut client = client.expect("The gRPC service is not listening");
+        let created = client.create_task(proto::CreateTaskRequest {
+            token:  token.clone(),
+            task:   Some(proto::Task { text: String::from("Unsigned"), done: false, archived: false }),
+        }).await;
+        assert_eq!(created.unwrap_err().code(), tonic::Code::Unauthenticated);
+        assert!(client.get_task(proto::IdRequest { id: 1 }).await.is_ok());
+    }
+}
@@ -0,0 +1,127 @@
+#![allow(clippy::needless_return)]
+// Task statuses, and done as computed from them
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use serde_json::{json, Value};
+
+use rest::{app, create_test_app, Config, State};
+
+mod common;
+use common::{header, token};
+
+#[actix_web::test]
+async fn done_follows_the_status() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Paint the fence", "status": "in_progress" }))
+        .to_request();
+    let location = header(&test::call_service(&app, request).await, "Location");
+    let response = test::call_service(&app, TestRequest::get().uri(&location).to_request()).await;
+    let etag = header(&response, "ETag");
+    let task: Value = test::read_body_json(response).await;
+    assert_eq!((&task["status"], &task["done"]), (&json!("in_progress"), &json!(false)));
+
+    // done alone closes the task
+    let request = TestRequest::patch().uri(&location)
+        .insert_header(("If-Match", etag.as_str()))
+        .set_json(json!({ "done": true }))
+        .to_request();
+    let etag = header(&test::call_service(&app, request).await, "ETag");
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(task["status"], "done");
+    assert!(task["completed_at"].is_string());
+
+    // a changed status wins over the done sent back with it
+    let request = TestRequest::put().uri(&location)
+        .insert_header(("If-Match", etag.as_str()))
+        .set_json(json!({ "text": "Paint the fence", "status": "blocked", "done": true }))
+        .to_request();
+    let etag = header(&test::call_service(&app, request).await, "ETag");
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!((&task["status"], &task["done"]), (&json!("blocked"), &json!(false)));
+    assert!(task["completed_at"].is_null());
+
+    // reopening without a status keeps it while it agrees
+    let request = TestRequest::put().uri(&location)
+        .insert_header(("If-Match", etag.as_str()))
+        .set_json(json!({ "text": "Paint the fence again", "done": false }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(task["status"], "blocked");
+
+    let request = TestRequest::patch().uri("/v1/tasks/0")
+        .insert_header(("If-Match", "1"))
+        .set_json(json!({ "status": "paused" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn only_configured_transitions_are_allowed() {
+    std::env::set_var("JOURNAL_TASK_TRANSITIONS", "todo>in_progress, in_progress>done, in_progress>blocked");
+    let app = test::init_service(app(web::Data::new(State::with_sample_data(Config::from_env())))).await;
+    let mut etag = String::from("1");
+    for (patch, status) in [
+        (json!({ "status": "done" }), StatusCode::CONFLICT),
+        (json!({ "status": "in_progress" }), StatusCode::OK),
+        (json!({ "status": "in_progress", "text": "Do the 0 now" }), StatusCode::OK),
+        (json!({ "done": true }), StatusCode::OK),
+        (json!({ "done": false }), StatusCode::CONFLICT),
+    ] {
+        let request = TestRequest::patch().uri("/v1/tasks/0")
+            .insert_header(("If-Match", etag.as_str()))
+            .set_json(&patch)
+            .to_request();
+        let response = test::call_service(&app, request).await;
+        assert_eq!(response.status(), status, "{}", patch);
+        if status == StatusCode::OK {
+            etag = header(&response, "ETag");
+        }
+    }
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/0").to_request()).await;
+    assert_eq!((&task["status"], &task["done"]), (&json!("done"), &json!(true)));
+
+    let request = TestRequest::put().uri("/v1/tasks/1")
+        .insert_header(("If-Match", "1"))
+        .set_json(json!({ "text": "Do the 1", "status": "cancelled" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let conflict: Value = test::read_body_json(response).await;
+    assert_eq!((&conflict["etag"], &conflict["current"]["status"]), (&json!("1"), &json!("todo")));
+}
+
+#[actix_web::test]
+async fn tasks_are_shown_on_a_board() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::patch().uri("/v1/tasks/3")
+        .insert_header(("If-Match", "1"))
+        .set_json(json!({ "status": "in_progress" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let request = TestRequest::post().uri("/v1/tasks/9/move")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "before": 0 }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let board: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/board?limit=3").to_request()).await;
+    let columns = board["columns"].as_array().unwrap();
+    let statuses: Vec<&str> = columns.iter().map(|column| column["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["todo", "in_progress", "blocked", "done", "cancelled"]);
+    let ids = |column: &Value| -> Vec<u64> {
+        return column["tasks"].as_array().unwrap().iter().map(|task| task["id"].as_u64().unwrap()).collect();
+    };
+    assert_eq!(columns[0]["total"], 9);
+    assert_eq!(ids(&columns[0]), [9, 0, 1]);
+    assert_eq!(ids(&columns[1]), [3]);
+
+    let request = TestRequest::get().uri("/v1/board?order=updated&limits=todo:1").to_request();
+    let board: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(ids(&board["columns"][0]), [9]);
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/board?limits=todo").to_request()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,225 @@
+#![allow(clippy::needless_return)]
+// Invariants of the storage over random sequences of writes, in memory and
+// with the event log
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::rt::System;
+use actix_web::test::{self, TestRequest};
+use actix_web::web;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::Index;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use rest::{app, Config, State};
+
+mod common;
+use common::{header, token};
+
+#[derive(Debug, Clone)]
+enum Op {
+    CreateTask(String),
+    CreateJournal(String),
+    // of the live entries, the one at the index
+    PatchTask(Index, Option<bool>, Option<String>),
+    Delete(Index),
+    // a new server over the same storage
+    Restart,
+}
+
+fn text() -> impl Strategy<Value = String> {
+    return "[a-z]{1,12}( [a-z]{1,12}){0,3}";
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    return prop_oneof![
+        3 => text().prop_map(Op::CreateTask),
+        2 => text().prop_map(Op::CreateJournal),
+        3 => (any::<Index>(), proptest::option::of(any::<bool>()), proptest::option::of(text()))
+            .prop_map(|(index, done, text)| Op::PatchTask(index, done, text)),
+        2 => any::<Index>().prop_map(Op::Delete),
+        1 => Just(Op::Restart),
+    ];
+}
+
+#[derive(Clone, Copy)]
+enum Backend {
+    Memory,
+    EventLog,
+}
+
+// what a live entry has to look like
+struct Expected {
+    // "text" of tasks, "title" of journals
+    field:  &'static str,
+    value:  String,
+    done:   Option<bool>,
+    // as last read, without its links
+    body:   Value,
+    etag:   String,
+}
+
+async fn read<S, B>(app: &S, uri: &str) -> (StatusCode, Option<String>, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let response = test::call_service(app, TestRequest::get().uri(uri).to_request()).await;
+    let status = response.status();
+    let etag = response.headers().get("ETag").map(|etag| String::from(etag.to_str().unwrap()));
+    let mut body: Value = serde_json::from_slice(&test::read_body(response).await).unwrap_or(Value::Null);
+    if let Some(body) = body.as_object_mut() {
+        body.remove("_links");
+    }
+    return (status, etag, body);
+}
+
+// every live entry is as expected, with an ETag that changed exactly when
+// its content did, and every deleted one is gone
+async fn check<S, B>(app: &S, live: &mut BTreeMap<String, Expected>, deleted: &BTreeSet<String>)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    for (uri, expected) in live.iter_mut() {
+        let (status, etag, body) = read(app, uri).await;
+        assert_eq!(status, StatusCode::OK, "{}", uri);
+        let etag = etag.unwrap();
+        assert_eq!(body[expected.field], expected.value.as_str(), "{}", uri);
+        if let Some(done) = expected.done {
+            assert_eq!(body["done"], done, "{}", uri);
+        }
+        assert_eq!(body == expected.body, etag == expected.etag, "{} has ETag {} for {}", uri, etag, body);
+        (expected.body, expected.etag) = (body, etag);
+    }
+    for uri in deleted {
+        assert_eq!(read(app, uri).await.0, StatusCode::NOT_FOUND, "{}", uri);
+    }
+}
+
+// creates the entry and returns where it is
+async fn create<S, B>(app: &S, uri: &str, body: Value) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::post().uri(uri)
+        .insert_header(("Post-Token", token(app).await))
+        .set_json(body)
+        .to_request();
+    let response = test::call_service(app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    return header(&response, "Location");
+}
+
+static LOGS: AtomicUsize = AtomicUsize::new(0);
+
+fn state(backend: Backend, log: &std::path::Path) -> State {
+    return match backend {
+        Backend::Memory     => State::with_sample_data(Config::default()),
+        Backend::EventLog   => {
+            std::env::set_var("JOURNAL_EVENT_LOG", log);
+            State::open(Config::from_env()).unwrap()
+        }
+    };
+}
+
+async fn run(backend: Backend, ops: Vec<Op>) {
+    let log = std::env::temp_dir().join(format!("journal-storage-{}-{}", std::process::id(), LOGS.fetch_add(1, Ordering::SeqCst)));
+    let mut app = test::init_service(app(web::Data::new(state(backend, &log)))).await;
+    let mut live: BTreeMap<String, Expected> = BTreeMap::new();
+    let mut deleted: BTreeSet<String> = BTreeSet::new();
+    // every id handed out, with those of the sample data, which the event
+    // log starts without
+    let sample = match backend {
+        Backend::Memory     => 0..10,
+        Backend::EventLog   => 0..0,
+    };
+    let mut ids: BTreeSet<String> = sample.flat_map(|id| [format!("/v1/tasks/{}", id), format!("/v1/journals/{}", id)]).collect();
+
+    for op in ops {
+        match op {
+            Op::CreateTask(text) => {
+                let location = create(&app, "/v1/tasks", json!({ "text": text })).await;
+                assert!(ids.insert(location.clone()), "{} was handed out before", location);
+                live.insert(location, Expected { field: "text", value: text, done: Some(false), body: Value::Null, etag: String::new() });
+            }
+            Op::CreateJournal(title) => {
+                let location = create(&app, "/v1/journals", json!({ "title": title, "data": "Hello" })).await;
+                assert!(ids.insert(location.clone()), "{} was handed out before", location);
+                live.insert(location, Expected { field: "title", value: title, done: None, body: Value::Null, etag: String::new() });
+            }
+            Op::PatchTask(index, done, text) => {
+                let tasks: Vec<&String> = live.keys().filter(|uri| uri.starts_with("/v1/tasks/")).collect();
+                if tasks.is_empty() {
+                    continue;
+                }
+                let uri = tasks[index.index(tasks.len())].clone();
+                let expected = live.get_mut(&uri).unwrap();
+                let changes = done.is_some() || text.is_some();
+                let mut patch = json!({});
+                if let Some(done) = done {
+                    patch["done"] = json!(done);
+                    expected.done = Some(done);
+                }
+                if let Some(text) = text {
+                    patch["text"] = json!(text);
+                    expected.value = text;
+                }
+                let request = TestRequest::patch().uri(&uri)
+                    .insert_header(("If-Match", expected.etag.as_str()))
+                    .set_json(patch)
+                    .to_request();
+                let response = test::call_service(&app, request).await;
+                if !changes {
+                    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+                    continue;
+                }
+                assert_eq!(response.status(), StatusCode::OK);
+                // the tag of the new content, which the next check reads
+                assert_ne!(header(&response, "ETag"), expected.etag);
+            }
+            Op::Delete(index) => {
+                if live.is_empty() {
+                    continue;
+                }
+                let uri = live.keys().nth(index.index(live.len())).unwrap().clone();
+                let response = test::call_service(&app, TestRequest::delete().uri(&uri).to_request()).await;
+                assert_eq!(response.status(), StatusCode::OK);
+                live.remove(&uri);
+                deleted.insert(uri);
+            }
+            Op::Restart => {
+                if let Backend::EventLog = backend {
+                    app = test::init_service(rest::app(web::Data::new(state(backend, &log)))).await;
+                }
+            }
+        }
+        check(&app, &mut live, &deleted).await;
+    }
+    let _ = std::fs::remove_dir_all(&log);
+}
+
+// fewer than proptest's default, each case is a whole server
+fn cases() -> u32 {
+    return std::env::var("PROPTEST_CASES").ok().and_then(|cases| cases.parse().ok()).unwrap_or(32);
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(cases()))]
+
+    #[test]
+    fn storage_in_memory_keeps_its_invariants(ops in vec(op(), 1..30)) {
+        System::new().block_on(run(Backend::Memory, ops));
+    }
+
+    // a restart restores every entry with its ETag
+    #[test]
+    fn storage_in_the_event_log_keeps_its_invariants(ops in vec(op(), 1..30)) {
+        System::new().block_on(run(Backend::EventLog, ops));
+    }
+}
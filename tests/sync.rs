@@ -0,0 +1,116 @@
+#![allow(clippy::needless_return)]
+// Delta syncs with GET /changes
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token, workspace};
+
+#[actix_web::test]
+async fn changes_since_a_token_are_listed() {
+    let app = test::init_service(create_test_app()).await;
+    let everything: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/changes").to_request()).await;
+    let changes = everything["changes"].as_array().unwrap();
+    assert_eq!(changes.len(), 20);
+    assert!(changes.iter().all(|change| change["action"] == "created"));
+    assert_eq!(changes[0]["resource"]["title"], "Title 0");
+    let since = String::from(everything["sync_token"].as_str().unwrap());
+
+    let request = TestRequest::patch().uri("/v1/tasks/3")
+        .insert_header(("If-Match", "1"))
+        .set_json(json!({ "text": "Do the 3 now" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let request = TestRequest::delete().uri("/v1/journals/2").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    // created and deleted in between, so never seen
+    let request = TestRequest::post().uri("/v1/tasks")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "text": "Short-lived" }))
+        .to_request();
+    let location = header(&test::call_service(&app, request).await, "Location");
+    assert_eq!(test::call_service(&app, TestRequest::delete().uri(&location).to_request()).await.status(), StatusCode::OK);
+    let request = TestRequest::post().uri("/v1/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "title": "New", "data": "Fresh" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+
+    let uri = format!("/v1/changes?since={}", since);
+    let delta: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&uri).to_request()).await;
+    let changes: Vec<(&str, u64, &str)> = delta["changes"].as_array().unwrap().iter()
+        .map(|change| (change["kind"].as_str().unwrap(), change["id"].as_u64().unwrap(), change["action"].as_str().unwrap()))
+        .collect();
+    assert_eq!(changes, [("task", 3, "updated"), ("journal", 2, "deleted"), ("journal", 10, "created")]);
+    assert_eq!(delta["changes"][0]["resource"]["text"], "Do the 3 now");
+    assert!(delta["changes"][1].get("resource").is_none());
+
+    // nothing new since the last one
+    let uri = format!("/v1/changes?since={}", delta["sync_token"].as_str().unwrap());
+    let delta: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&uri).to_request()).await;
+    assert!(delta["changes"].as_array().unwrap().is_empty());
+
+    // tokens are only good for the space they came from
+    let (wid, key) = workspace(&app, "Elsewhere").await;
+    let request = TestRequest::get().uri(&format!("/v1/workspaces/{}/changes?since={}", wid, since))
+        .insert_header(("Workspace-Key", key))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::GONE);
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/changes?since=yesterday").to_request()).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn drafts_are_left_out_of_changes() {
+    let app = test::init_service(create_test_app()).await;
+    let everything: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/changes").to_request()).await;
+    let since = String::from(everything["sync_token"].as_str().unwrap());
+
+    let request = TestRequest::post().uri("/v1/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "title": "Soon", "data": "Not yet", "publish_at": "2999-01-01T00:00:00Z" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    // scheduled after the client got it, so it has to go
+    let request = TestRequest::put().uri("/v1/journals/4")
+        .insert_header(("If-Match", "1"))
+        .set_json(json!({ "title": "Title 4", "data": "Later", "publish_at": "2999-01-01T00:00:00Z" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let uri = format!("/v1/changes?since={}", since);
+    let delta: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&uri).to_request()).await;
+    assert_eq!(delta["changes"], json!([{ "kind": "journal", "id": 4, "action": "deleted" }]));
+    let everything: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/changes").to_request()).await;
+    assert_eq!(everything["changes"].as_array().unwrap().len(), 19);
+}
+
+#[actix_web::test]
+async fn published_drafts_come_with_the_next_sync() {
+    let app = test::init_service(create_test_app()).await;
+    let publish_at = chrono::Utc::now() + chrono::Duration::seconds(1);
+    let request = TestRequest::post().uri("/v1/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "title": "Soon", "data": "Not yet", "publish_at": publish_at }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    let everything: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/changes").to_request()).await;
+    assert_eq!(everything["changes"].as_array().unwrap().len(), 20);
+
+    // published without a write of its own, after the client synced
+    actix_web::rt::time::sleep(std::time::Duration::from_millis(1100)).await;
+    let uri = format!("/v1/changes?since={}", everything["sync_token"].as_str().unwrap());
+    let delta: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&uri).to_request()).await;
+    let changes = delta["changes"].as_array().unwrap();
+    assert_eq!(changes.len(), 1);
+    assert_eq!((&changes[0]["id"], &changes[0]["action"]), (&json!(10), &json!("created")));
+    assert_eq!(changes[0]["resource"]["title"], "Soon");
+
+    // and only once
+    let uri = format!("/v1/changes?since={}", delta["sync_token"].as_str().unwrap());
+    let delta: Value = test::call_and_read_body_json(&app, TestRequest::get().uri(&uri).to_request()).await;
+    assert!(delta["changes"].as_array().unwrap().is_empty());
+}
@@ -0,0 +1,130 @@
+#![allow(clippy::needless_return)]
+// Takeouts of a whole space, exported and imported again
+use actix_http::Request;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token};
+
+// of "hello world"
+const HELLO_SHA256: &str = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+
+async fn post<S, B>(app: &S, uri: &str, body: Value) -> String
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::post().uri(uri).insert_header(("Post-Token", token(app).await)).set_json(body).to_request();
+    let response = test::call_service(app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    return header(&response, "Location");
+}
+
+async fn import<S, B>(app: &S, takeout: &Value) -> (StatusCode, Value)
+where
+    S: Service<Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    B: MessageBody,
+{
+    let request = TestRequest::post().uri("/v1/import").insert_header(("Post-Token", token(app).await)).set_json(takeout).to_request();
+    let response = test::call_service(app, request).await;
+    return (response.status(), test::read_body_json(response).await);
+}
+
+// the exported entries of a kind that match
+fn matching<'a>(takeout: &'a Value, kind: &str, field: &str, value: &str) -> Vec<&'a Value> {
+    return takeout[kind].as_array().unwrap().iter().filter(|entry| entry[field] == value).collect();
+}
+
+#[actix_web::test]
+async fn takeouts_carry_every_kind_of_entry() {
+    let app = test::init_service(create_test_app()).await;
+    let trip = post(&app, "/v1/journals", json!({ "title": "Trip", "data": "Rain",
+        "expires_at": "2036-01-01T00:00:00Z", "publish_at": "2026-01-01T00:00:00Z" })).await;
+    let trip_id: u64 = trip.rsplit('/').next().unwrap().parse().unwrap();
+    let habit = post(&app, "/v1/habits", json!({ "name": "Stretch" })).await;
+    let request = TestRequest::post().uri(&format!("{}/checkins", habit))
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "date": "2026-10-15" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    post(&app, "/v1/notes", json!({ "text": "Buy stamps" })).await;
+    post(&app, "/v1/bookmarks", json!({ "url": "https://example.com/", "journals": [trip_id] })).await;
+    let upload = post(&app, &format!("{}/uploads", trip), json!({ "name": "notes.txt", "length": 11 })).await;
+    let request = TestRequest::patch().uri(&upload).insert_header(("Upload-Offset", "0")).set_payload("hello world").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::NO_CONTENT);
+    let response = test::call_service(&app, TestRequest::post().uri(&format!("{}/finalize", upload)).to_request()).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let takeout: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/export").to_request()).await;
+    let count = |kind: &str| takeout[kind].as_array().unwrap().len();
+    let (status, summary) = import(&app, &takeout).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(summary, json!({ "journals": count("journals"), "tasks": count("tasks"), "habits": count("habits"),
+        "notes": count("notes"), "bookmarks": count("bookmarks"), "attachments": count("attachments") }));
+
+    // the copies link to the copy of the journal, which is the last one imported
+    let copy_id = trip_id + count("journals") as u64;
+    let again: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/export").to_request()).await;
+    let trips = matching(&again, "journals", "title", "Trip");
+    assert_eq!(trips.len(), 2);
+    assert_eq!((&trips[1]["id"], &trips[1]["expires_at"], &trips[1]["publish_at"]),
+        (&json!(copy_id), &json!("2036-01-01T00:00:00Z"), &json!("2026-01-01T00:00:00Z")));
+    let habits = matching(&again, "habits", "name", "Stretch");
+    assert_eq!(habits.iter().map(|habit| &habit["checkins"]).collect::<Vec<_>>(), [&json!(["2026-10-15"]); 2]);
+    assert_eq!(matching(&again, "notes", "text", "Buy stamps").len(), 2);
+    let bookmarks = matching(&again, "bookmarks", "url", "https://example.com/");
+    assert_eq!(bookmarks.iter().map(|bookmark| &bookmark["journals"]).collect::<Vec<_>>(), [&json!([trip_id]), &json!([copy_id])]);
+    let attachments = matching(&again, "attachments", "sha256", HELLO_SHA256);
+    assert_eq!(attachments.iter().map(|attachment| &attachment["journal"]).collect::<Vec<_>>(), [&json!(trip_id), &json!(copy_id)]);
+    let response = test::call_service(&app, TestRequest::get().uri(&format!("/v1/journals/{}/attachments/1", copy_id)).to_request()).await;
+    assert_eq!(test::read_body(response).await, "hello world");
+}
+
+#[actix_web::test]
+async fn imports_are_checked_as_created_entries() {
+    let app = test::init_service(create_test_app()).await;
+    let takeout = |extra: Value| {
+        let mut takeout = json!({
+            "version": 1,
+            "exported_at": "2026-10-16T00:00:00Z",
+            "journals": [{ "id": 7, "title": "Trip", "data": "",
+                "created_at": "2026-10-15T08:00:00Z", "updated_at": "2026-10-15T08:00:00Z" }],
+            "tasks": [],
+        });
+        takeout.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        return takeout;
+    };
+    let stamped = |mut entry: Value| {
+        entry["created_at"] = json!("2026-10-15T08:00:00Z");
+        entry["updated_at"] = json!("2026-10-15T08:00:00Z");
+        return entry;
+    };
+
+    // bookmarks and attachments only link to journals of the same takeout
+    let linked = takeout(json!({ "bookmarks": [stamped(json!({ "id": 0, "url": "https://example.com/", "journals": [8] }))] }));
+    let (status, problem) = import(&app, &linked).await;
+    assert_eq!((status, &problem["detail"]), (StatusCode::BAD_REQUEST, &json!("Journal 8 not found")));
+
+    let request = TestRequest::put().uri("/v1/schema/notes")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "type": "object", "required": ["priority"] }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+    let notes = takeout(json!({ "notes": [stamped(json!({ "id": 0, "text": "Buy stamps" }))] }));
+    assert_eq!(import(&app, &notes).await.0, StatusCode::BAD_REQUEST);
+
+    // content the space does not have is left for the client to upload
+    let unknown = "0".repeat(64);
+    let attached = takeout(json!({ "attachments": [{ "journal": 7, "name": "notes.txt", "content_type": "text/plain",
+        "sha256": unknown, "length": 11, "created_at": "2026-10-15T08:00:00Z" }] }));
+    let request = TestRequest::post().uri("/v1/import").insert_header(("X-Dry-Run", "true")).set_json(&attached).to_request();
+    let summary: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(summary, json!({ "journals": 1, "tasks": 0, "habits": 0, "notes": 0, "bookmarks": 0,
+        "attachments": 0, "missing": [unknown], "dry_run": true }));
+}
@@ -0,0 +1,89 @@
+#![allow(clippy::needless_return)]
+// Time tracking on tasks and the weekly report
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token};
+
+#[actix_web::test]
+async fn timers_start_and_stop_once() {
+    let app = test::init_service(create_test_app()).await;
+    let switch = |action: &str, token: String| TestRequest::post()
+        .uri(&format!("/v1/tasks/3/timer/{}", action))
+        .insert_header(("Post-Token", token))
+        .to_request();
+
+    let response = test::call_service(&app, switch("stop", token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = test::call_service(&app, switch("start", token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let response = test::call_service(&app, switch("start", token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    let time: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/3/time").to_request()).await;
+    assert_eq!(time["running"], true);
+    assert_eq!(time["entries"].as_array().unwrap().len(), 1);
+
+    let response = test::call_service(&app, switch("stop", token(&app).await)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let entry: Value = test::read_body_json(response).await;
+    assert!(entry["stopped_at"].is_string());
+
+    // the entries survive replacing the task
+    let response = test::call_service(&app, TestRequest::get().uri("/v1/tasks/3").to_request()).await;
+    let etag = header(&response, "ETag");
+    let task: Value = test::read_body_json(response).await;
+    assert_eq!(task["time_entries"].as_array().unwrap().len(), 1);
+    let request = TestRequest::put().uri("/v1/tasks/3")
+        .insert_header(("If-Match", etag))
+        .set_json(json!({ "text": "Bill this", "done": false }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+    let time: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/3/time").to_request()).await;
+    assert_eq!(time["running"], false);
+    assert_eq!(time["entries"].as_array().unwrap().len(), 1);
+}
+
+#[actix_web::test]
+async fn weekly_reports_count_the_time_inside_the_week() {
+    let app = test::init_service(create_test_app()).await;
+    // 2026-W42 runs from Monday, October 12th to Sunday, October 18th
+    let takeout = json!({
+        "version": 1,
+        "exported_at": "2026-10-20T00:00:00Z",
+        "journals": [],
+        "tasks": [{
+            "id": 0,
+            "text": "Client work",
+            "done": false,
+            "created_at": "2026-10-01T00:00:00Z",
+            "updated_at": "2026-10-01T00:00:00Z",
+            "time_entries": [
+                { "started_at": "2026-10-11T23:00:00Z", "stopped_at": "2026-10-12T01:00:00Z" },
+                { "started_at": "2026-10-14T09:00:00Z", "stopped_at": "2026-10-14T09:30:00Z" },
+                { "started_at": "2026-10-20T09:00:00Z", "stopped_at": "2026-10-20T10:00:00Z" },
+            ],
+        }],
+    });
+    let request = TestRequest::post().uri("/v1/import")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(takeout)
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::OK);
+
+    let request = TestRequest::get().uri("/v1/time/report?week=2026-W42").to_request();
+    let report: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(report["from"], "2026-10-12T00:00:00Z");
+    assert_eq!(report["total_seconds"], 5400);
+    assert_eq!(report["tasks"], json!([{ "id": 10, "text": "Client work", "seconds": 5400 }]));
+
+    let time: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/10/time").to_request()).await;
+    assert_eq!(time["total_seconds"], 7200 + 1800 + 3600);
+
+    let request = TestRequest::get().uri("/v1/time/report?week=last").to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::BAD_REQUEST);
+}
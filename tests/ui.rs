@@ -0,0 +1,26 @@
+#![cfg(feature = "ui")]
+#![allow(clippy::needless_return)]
+// The HTML interface under /ui lists what the API lists
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::json;
+
+use rest::create_test_app;
+
+mod common;
+use common::token;
+
+#[actix_web::test]
+async fn drafts_are_left_out_of_pages() {
+    let app = test::init_service(create_test_app()).await;
+    let request = TestRequest::post().uri("/v1/journals")
+        .insert_header(("Post-Token", token(&app).await))
+        .set_json(json!({ "title": "Soon", "data": "Not yet", "publish_at": "2999-01-01T00:00:00Z" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, request).await.status(), StatusCode::CREATED);
+
+    let page = test::call_and_read_body(&app, TestRequest::get().uri("/ui/journals?per_page=20").to_request()).await;
+    let page = String::from_utf8(page.to_vec()).unwrap();
+    assert!(page.contains("Title 9"));
+    assert!(!page.contains("Soon"));
+}
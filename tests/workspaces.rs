@@ -0,0 +1,104 @@
+#![allow(clippy::needless_return)]
+// Workspaces: isolated journals and tasks, reachable only with a member's key
+use actix_web::http::StatusCode;
+use actix_web::test::{self, TestRequest};
+use serde_json::{json, Value};
+
+use rest::create_test_app;
+
+mod common;
+use common::{header, token, workspace};
+
+#[actix_web::test]
+async fn workspaces_are_isolated() {
+    let app = test::init_service(create_test_app()).await;
+    let (work, work_key) = workspace(&app, "work").await;
+    let (home, home_key) = workspace(&app, "home").await;
+
+    let request = TestRequest::post().uri(&format!("/v1/workspaces/{}/tasks", work))
+        .insert_header(("Post-Token", token(&app).await))
+        .insert_header(("Workspace-Key", work_key.as_str()))
+        .set_json(json!({ "text": "Write the report", "done": false }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let location = header(&response, "Location");
+    assert_eq!(location, format!("/v1/workspaces/{}/tasks/0", work));
+
+    let request = TestRequest::get().uri(&location).insert_header(("Workspace-Key", work_key.as_str())).to_request();
+    let task: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(task["text"], "Write the report");
+
+    // the server's own tasks and the other workspace do not see it
+    let task: Value = test::call_and_read_body_json(&app, TestRequest::get().uri("/v1/tasks/0").to_request()).await;
+    assert_eq!(task["text"], "Do the 0");
+    let request = TestRequest::get().uri(&format!("/v1/workspaces/{}/tasks", home))
+        .insert_header(("Workspace-Key", home_key.as_str()))
+        .to_request();
+    let page: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(page["total_entries"], 0);
+
+    // nor does the key of another workspace open it
+    let request = TestRequest::get().uri(&location).insert_header(("Workspace-Key", home_key.as_str())).to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = test::call_service(&app, TestRequest::get().uri(&location).to_request()).await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn owners_manage_members() {
+    let app = test::init_service(create_test_app()).await;
+    let (id, owner_key) = workspace(&app, "family").await;
+    let members = format!("/v1/workspaces/{}/members", id);
+
+    let request = TestRequest::post().uri(&members)
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .set_json(json!({ "name": "bob" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let bob: Value = test::read_body_json(response).await;
+    let bob_key = bob["key"].as_str().unwrap();
+
+    // bob is a member, but not an owner
+    let request = TestRequest::get().uri(&format!("/v1/workspaces/{}", id))
+        .insert_header(("Workspace-Key", bob_key))
+        .to_request();
+    let workspace: Value = test::call_and_read_body_json(&app, request).await;
+    assert_eq!(workspace["members"], json!([{ "name": "alice", "owner": true }, { "name": "bob", "owner": false }]));
+    let request = TestRequest::post().uri(&members)
+        .insert_header(("Workspace-Key", bob_key))
+        .set_json(json!({ "name": "eve" }))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // the last owner cannot leave, bob can
+    let request = TestRequest::delete().uri(&format!("{}/alice", members))
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let request = TestRequest::delete().uri(&format!("{}/bob", members))
+        .insert_header(("Workspace-Key", bob_key))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let request = TestRequest::get().uri(&format!("/v1/workspaces/{}/journals", id))
+        .insert_header(("Workspace-Key", bob_key))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let request = TestRequest::delete().uri(&format!("/v1/workspaces/{}", id))
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let request = TestRequest::get().uri(&format!("/v1/workspaces/{}", id))
+        .insert_header(("Workspace-Key", owner_key.as_str()))
+        .to_request();
+    let response = test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}